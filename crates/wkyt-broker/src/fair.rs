@@ -0,0 +1,282 @@
+//! Fair multi-connector bus: one long-lived transport that many connectors
+//! publish into and one consumer drains.
+//!
+//! Each connector id gets its own bounded queue (`per_connector_capacity`
+//! batches), created on first publish. The subscriber serves connectors
+//! with queued batches in round-robin order, one batch per turn, so a
+//! calendar backfill with thousands of batches queued gets every other
+//! slot at most while a file import is also publishing — it cannot starve
+//! it. Backpressure is per connector as well: a full queue blocks only
+//! that connector's publisher.
+//!
+//! Ordering: batches of one connector are delivered in publish order (each
+//! queue is FIFO and only its head is ever taken), which is what cursor
+//! monotonicity needs. There is no ordering across connectors, and none is
+//! needed — their cursors are independent.
+//!
+//! Fairness is per batch, not per delta; connectors already bound their
+//! batch sizes (D11), which keeps turns comparable.
+
+use crate::{deliver, AckHook, BusError, BusPublisher, BusSubscriber, Counters, Delivery, Envelope};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+use wkyt_core::DeltaBatch;
+use wkyt_metrics::Metrics;
+
+/// Create a fair bus with `per_connector_capacity` batches of headroom per
+/// connector. The publisher is `Clone`; the bus closes once every clone is
+/// dropped and the queues have drained.
+pub fn fair(per_connector_capacity: usize) -> (FairPublisher, FairSubscriber) {
+    build(per_connector_capacity, None)
+}
+
+/// [`fair`], reporting published/acked counts and total queue depth into
+/// `metrics`.
+pub fn fair_with_metrics(
+    per_connector_capacity: usize,
+    metrics: Metrics,
+) -> (FairPublisher, FairSubscriber) {
+    build(per_connector_capacity, Some(metrics))
+}
+
+fn build(capacity: usize, metrics: Option<Metrics>) -> (FairPublisher, FairSubscriber) {
+    assert!(capacity > 0, "a zero-capacity bus cannot move anything");
+    let shared = Arc::new(Shared {
+        capacity,
+        state: Mutex::new(State { publishers: 1, subscriber_alive: true, ..State::default() }),
+        item_ready: Notify::new(),
+        space_ready: Notify::new(),
+        counters: Arc::new(Counters::new(metrics)),
+    });
+    (FairPublisher { shared: Arc::clone(&shared) }, FairSubscriber { shared })
+}
+
+struct Shared {
+    capacity: usize,
+    state: Mutex<State>,
+    /// Wakes the subscriber: a batch was queued or the last publisher left.
+    item_ready: Notify,
+    /// Wakes blocked publishers: a slot freed or the subscriber left.
+    space_ready: Notify,
+    counters: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct State {
+    queues: BTreeMap<String, VecDeque<Envelope>>,
+    /// Connectors with at least one queued batch, in serving order. A
+    /// connector is in the ring iff its queue is non-empty.
+    ring: VecDeque<String>,
+    publishers: usize,
+    subscriber_alive: bool,
+}
+
+pub struct FairPublisher {
+    shared: Arc<Shared>,
+}
+
+impl FairPublisher {
+    /// Batches accepted by the bus so far, across all connectors.
+    pub fn published(&self) -> u64 {
+        self.shared.counters.published.load(Ordering::SeqCst)
+    }
+
+    /// Deliveries the consumer has acked so far, across all connectors.
+    pub fn acked(&self) -> u64 {
+        self.shared.counters.acked.load(Ordering::SeqCst)
+    }
+
+    async fn send(&self, envelope: Envelope) -> Result<(), BusError> {
+        let mut envelope = Some(envelope);
+        loop {
+            let space = self.shared.space_ready.notified();
+            tokio::pin!(space);
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if !state.subscriber_alive {
+                    return Err(BusError::Closed);
+                }
+                let connector_id = envelope.as_ref().expect("sent at most once").batch.connector_id.clone();
+                let queue = state.queues.entry(connector_id.clone()).or_default();
+                if queue.len() < self.shared.capacity {
+                    queue.push_back(envelope.take().expect("sent at most once"));
+                    if queue.len() == 1 {
+                        state.ring.push_back(connector_id.clone());
+                    }
+                    drop(state);
+                    let metrics_id = self.shared.counters.metrics.as_ref().map(|_| connector_id);
+                    self.shared.counters.record_published(metrics_id.as_deref());
+                    self.shared.item_ready.notify_one();
+                    return Ok(());
+                }
+                // Full: register for a wakeup before releasing the lock so
+                // a slot freed in between is not missed.
+                space.as_mut().enable();
+            }
+            space.await;
+        }
+    }
+}
+
+impl Clone for FairPublisher {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().unwrap().publishers += 1;
+        Self { shared: Arc::clone(&self.shared) }
+    }
+}
+
+impl Drop for FairPublisher {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().publishers -= 1;
+        self.shared.item_ready.notify_one();
+    }
+}
+
+#[async_trait::async_trait]
+impl BusPublisher for FairPublisher {
+    async fn publish(&self, batch: DeltaBatch) -> Result<(), BusError> {
+        self.send(Envelope { batch, on_ack: None }).await
+    }
+
+    async fn publish_with_ack_hook(&self, batch: DeltaBatch, on_ack: AckHook) -> Result<(), BusError> {
+        self.send(Envelope { batch, on_ack: Some(on_ack) }).await
+    }
+}
+
+pub struct FairSubscriber {
+    shared: Arc<Shared>,
+}
+
+#[async_trait::async_trait]
+impl BusSubscriber for FairSubscriber {
+    async fn next(&mut self) -> Option<Delivery> {
+        loop {
+            let ready = self.shared.item_ready.notified();
+            tokio::pin!(ready);
+            {
+                let mut state = self.shared.state.lock().unwrap();
+                if let Some(connector_id) = state.ring.pop_front() {
+                    let queue = state.queues.get_mut(&connector_id).expect("ring entries have queues");
+                    let envelope = queue.pop_front().expect("ring entries are non-empty");
+                    if queue.is_empty() {
+                        state.queues.remove(&connector_id);
+                    } else {
+                        // Back of the line: everyone else queued goes first.
+                        state.ring.push_back(connector_id);
+                    }
+                    drop(state);
+                    self.shared.space_ready.notify_waiters();
+                    return Some(deliver(&self.shared.counters, envelope));
+                }
+                if state.publishers == 0 {
+                    return None;
+                }
+                ready.as_mut().enable();
+            }
+            ready.await;
+        }
+    }
+}
+
+impl Drop for FairSubscriber {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().subscriber_alive = false;
+        self.shared.space_ready.notify_waiters();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use wkyt_core::SyncToken;
+
+    fn batch(connector_id: &str, n: u32) -> DeltaBatch {
+        DeltaBatch {
+            connector_id: connector_id.into(),
+            deltas: vec![],
+            cursor: Some(SyncToken(format!("{connector_id}-{n}"))),
+        }
+    }
+
+    async fn drain(subscriber: &mut FairSubscriber, n: usize) -> Vec<String> {
+        let mut order = Vec::new();
+        for _ in 0..n {
+            let d = subscriber.next().await.unwrap();
+            order.push(d.batch().cursor.clone().unwrap().0);
+            d.into_parts().1.ack();
+        }
+        order
+    }
+
+    #[tokio::test]
+    async fn round_robin_across_connectors_fifo_within_each() {
+        let (publisher, mut subscriber) = fair(8);
+        for n in 1..=5 {
+            publisher.publish(batch("google", n)).await.unwrap();
+        }
+        for n in 1..=2 {
+            publisher.publish(batch("file", n)).await.unwrap();
+        }
+
+        let order = drain(&mut subscriber, 7).await;
+        assert_eq!(
+            order,
+            ["google-1", "file-1", "google-2", "file-2", "google-3", "google-4", "google-5"]
+        );
+        assert_eq!((publisher.published(), publisher.acked()), (7, 7));
+    }
+
+    #[tokio::test]
+    async fn a_full_queue_blocks_only_its_own_connector() {
+        let (publisher, mut subscriber) = fair(1);
+        publisher.publish(batch("google", 1)).await.unwrap();
+
+        let blocked = publisher.publish(batch("google", 2));
+        tokio::pin!(blocked);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), &mut blocked).await.is_err(),
+            "google's queue is full: its publish must wait"
+        );
+        // Another connector still has headroom.
+        tokio::time::timeout(Duration::from_millis(50), publisher.publish(batch("file", 1)))
+            .await
+            .expect("file must not be blocked by google's backlog")
+            .unwrap();
+
+        assert_eq!(drain(&mut subscriber, 1).await, ["google-1"]);
+        tokio::time::timeout(Duration::from_millis(200), blocked)
+            .await
+            .expect("publish completes once google's slot frees")
+            .unwrap();
+        assert_eq!(drain(&mut subscriber, 2).await, ["file-1", "google-2"]);
+    }
+
+    #[tokio::test]
+    async fn closes_when_every_clone_is_gone_and_reports_closed_to_publishers() {
+        let (publisher, mut subscriber) = fair(4);
+        let second = publisher.clone();
+        second.publish(batch("file", 1)).await.unwrap();
+        drop(publisher);
+        drop(second);
+        assert!(subscriber.next().await.is_some(), "queued batches still drain");
+        assert!(subscriber.next().await.is_none());
+
+        let (publisher, subscriber) = fair(4);
+        drop(subscriber);
+        assert!(matches!(publisher.publish(batch("file", 1)).await, Err(BusError::Closed)));
+    }
+
+    #[tokio::test]
+    async fn a_publisher_blocked_on_a_full_queue_sees_the_subscriber_leave() {
+        let (publisher, subscriber) = fair(1);
+        publisher.publish(batch("google", 1)).await.unwrap();
+        let blocked = tokio::spawn(async move { publisher.publish(batch("google", 2)).await });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(subscriber);
+        let result = tokio::time::timeout(Duration::from_millis(200), blocked).await.unwrap().unwrap();
+        assert!(matches!(result, Err(BusError::Closed)));
+    }
+}
@@ -15,16 +15,34 @@
 //!   bus; the un-advanced cursor redelivers it on the next sync instead.
 //! - Ack bookkeeping (`published()` / `acked()`) lets tests assert the
 //!   ack-after-commit ordering.
+//! - [`BusPublisher::publish_with_ack_hook`] lets a producer attach
+//!   work that must happen only once its batch has committed (e.g. the
+//!   host deleting a drained spool file). The hook runs inside
+//!   [`Ack::ack`]; a delivery dropped un-acked drops its hook unrun.
+//! - [`in_process_with_metrics`] additionally reports the same counters,
+//!   per connector, plus the in-flight depth, to a `wkyt_metrics` registry.
+//!
+//! [`fair()`] is the multi-connector variant for a long-lived shared bus:
+//! one bounded queue per connector, drained round-robin, so one source's
+//! backfill cannot starve another's (see the [`fair`](mod@fair) module).
 
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use wkyt_core::DeltaBatch;
+use wkyt_metrics::Metrics;
+
+pub mod fair;
+pub use fair::{fair, fair_with_metrics, FairPublisher, FairSubscriber};
 
 #[derive(Debug, thiserror::Error)]
 pub enum BusError {
     #[error("bus is closed (subscriber dropped)")]
     Closed,
+    /// The publisher's backing transport failed (e.g. the host's on-disk
+    /// spool could not be written).
+    #[error("transport failed: {0}")]
+    Transport(#[source] Box<dyn std::error::Error + Send + Sync>),
 }
 
 /// Producer half: connectors (via the orchestrator pump) publish batches.
@@ -33,6 +51,14 @@ pub trait BusPublisher: Send + Sync {
     /// Awaits when the transport is at capacity (backpressure), errors when
     /// the consumer is gone.
     async fn publish(&self, batch: DeltaBatch) -> Result<(), BusError>;
+
+    /// [`publish`](Self::publish), plus `on_ack` run when the consumer
+    /// acks this batch. A transport without a consumer to ack (the host's
+    /// spool) drops the hook unrun, which is the default.
+    async fn publish_with_ack_hook(&self, batch: DeltaBatch, on_ack: AckHook) -> Result<(), BusError> {
+        drop(on_ack);
+        self.publish(batch).await
+    }
 }
 
 /// Consumer half: the vault-side orchestrator pulls deliveries.
@@ -60,6 +86,10 @@ impl Delivery {
     }
 }
 
+/// Producer-side work run when the consumer acks a delivery — i.e. only
+/// after the batch's vault transaction has committed.
+pub type AckHook = Box<dyn FnOnce() + Send>;
+
 /// Acknowledgement handle. Consuming `self` is deliberate: a delivery can
 /// be acked at most once, and dropping it un-acked is a visible decision.
 pub struct Ack(Box<dyn FnOnce() + Send>);
@@ -73,23 +103,87 @@ impl Ack {
 /// Create a bounded in-process bus. `capacity` is the maximum number of
 /// batches in flight before `publish` blocks the producer.
 pub fn in_process(capacity: usize) -> (InProcessPublisher, InProcessSubscriber) {
+    build(capacity, None)
+}
+
+/// [`in_process`], recording published/acked counts and queue depth into
+/// `metrics` as well as the local diagnostic counters.
+pub fn in_process_with_metrics(
+    capacity: usize,
+    metrics: Metrics,
+) -> (InProcessPublisher, InProcessSubscriber) {
+    build(capacity, Some(metrics))
+}
+
+fn build(capacity: usize, metrics: Option<Metrics>) -> (InProcessPublisher, InProcessSubscriber) {
     assert!(capacity > 0, "a zero-capacity bus cannot move anything");
     let (tx, rx) = mpsc::channel(capacity);
-    let counters = Arc::new(Counters::default());
+    let counters = Arc::new(Counters::new(metrics));
     (
         InProcessPublisher { tx, counters: Arc::clone(&counters) },
         InProcessSubscriber { rx, counters },
     )
 }
 
-#[derive(Default)]
 struct Counters {
     published: AtomicU64,
+    /// Handed to the subscriber; `published - received` is the depth.
+    received: AtomicU64,
     acked: AtomicU64,
+    metrics: Option<Metrics>,
+    /// This bus's depth slot in `metrics`; buses sharing a registry each
+    /// report their own depth and the gauge sums them.
+    bus: u64,
+}
+
+impl Counters {
+    fn new(metrics: Option<Metrics>) -> Self {
+        let bus = metrics.as_ref().map_or(0, Metrics::register_bus);
+        Self {
+            published: AtomicU64::new(0),
+            received: AtomicU64::new(0),
+            acked: AtomicU64::new(0),
+            metrics,
+            bus,
+        }
+    }
+
+    /// `connector_id` is only needed (and only cloned by callers) when a
+    /// metrics registry is attached.
+    fn record_published(&self, connector_id: Option<&str>) {
+        self.published.fetch_add(1, Ordering::SeqCst);
+        if let (Some(m), Some(id)) = (&self.metrics, connector_id) {
+            m.bus_published(id);
+        }
+        self.report_depth();
+    }
+
+    fn report_depth(&self) {
+        if let Some(m) = &self.metrics {
+            let published = self.published.load(Ordering::SeqCst);
+            let received = self.received.load(Ordering::SeqCst);
+            m.set_bus_queue_depth(self.bus, published.saturating_sub(received));
+        }
+    }
+}
+
+impl Drop for Counters {
+    fn drop(&mut self) {
+        if let Some(m) = &self.metrics {
+            m.unregister_bus(self.bus);
+        }
+    }
+}
+
+/// What actually travels through the channel: the batch plus the
+/// producer's optional ack hook.
+struct Envelope {
+    batch: DeltaBatch,
+    on_ack: Option<AckHook>,
 }
 
 pub struct InProcessPublisher {
-    tx: mpsc::Sender<DeltaBatch>,
+    tx: mpsc::Sender<Envelope>,
     counters: Arc<Counters>,
 }
 
@@ -103,33 +197,57 @@ impl InProcessPublisher {
     pub fn acked(&self) -> u64 {
         self.counters.acked.load(Ordering::SeqCst)
     }
+
+    async fn send(&self, envelope: Envelope) -> Result<(), BusError> {
+        let connector_id = self.counters.metrics.as_ref().map(|_| envelope.batch.connector_id.clone());
+        self.tx.send(envelope).await.map_err(|_| BusError::Closed)?;
+        self.counters.record_published(connector_id.as_deref());
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl BusPublisher for InProcessPublisher {
     async fn publish(&self, batch: DeltaBatch) -> Result<(), BusError> {
-        self.tx.send(batch).await.map_err(|_| BusError::Closed)?;
-        self.counters.published.fetch_add(1, Ordering::SeqCst);
-        Ok(())
+        self.send(Envelope { batch, on_ack: None }).await
+    }
+
+    async fn publish_with_ack_hook(&self, batch: DeltaBatch, on_ack: AckHook) -> Result<(), BusError> {
+        self.send(Envelope { batch, on_ack: Some(on_ack) }).await
     }
 }
 
 pub struct InProcessSubscriber {
-    rx: mpsc::Receiver<DeltaBatch>,
+    rx: mpsc::Receiver<Envelope>,
     counters: Arc<Counters>,
 }
 
 #[async_trait::async_trait]
 impl BusSubscriber for InProcessSubscriber {
     async fn next(&mut self) -> Option<Delivery> {
-        let batch = self.rx.recv().await?;
-        let counters = Arc::clone(&self.counters);
-        Some(Delivery {
-            batch,
-            ack: Ack(Box::new(move || {
-                counters.acked.fetch_add(1, Ordering::SeqCst);
-            })),
-        })
+        let envelope = self.rx.recv().await?;
+        Some(deliver(&self.counters, envelope))
+    }
+}
+
+/// Turn a dequeued envelope into a delivery whose ack bumps the counters,
+/// reports to metrics, then runs the producer's hook.
+fn deliver(counters: &Arc<Counters>, Envelope { batch, on_ack }: Envelope) -> Delivery {
+    counters.received.fetch_add(1, Ordering::SeqCst);
+    counters.report_depth();
+    let counters = Arc::clone(counters);
+    let connector_id = batch.connector_id.clone();
+    Delivery {
+        batch,
+        ack: Ack(Box::new(move || {
+            counters.acked.fetch_add(1, Ordering::SeqCst);
+            if let Some(m) = &counters.metrics {
+                m.bus_acked(&connector_id);
+            }
+            if let Some(hook) = on_ack {
+                hook();
+            }
+        })),
     }
 }
 
@@ -190,6 +308,58 @@ mod tests {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn ack_hook_runs_on_ack_and_never_on_drop() {
+        let (publisher, mut subscriber) = in_process(4);
+        let fired = Arc::new(AtomicU64::new(0));
+        for n in 1..=2 {
+            let f = Arc::clone(&fired);
+            publisher
+                .publish_with_ack_hook(
+                    batch(n),
+                    Box::new(move || {
+                        f.fetch_add(1, Ordering::SeqCst);
+                    }),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Dropped un-acked: the hook must not run (nothing committed).
+        drop(subscriber.next().await.unwrap());
+        assert_eq!(fired.load(Ordering::SeqCst), 0);
+
+        subscriber.next().await.unwrap().into_parts().1.ack();
+        assert_eq!(fired.load(Ordering::SeqCst), 1);
+        assert_eq!(publisher.acked(), 1);
+    }
+
+    #[tokio::test]
+    async fn metrics_variant_reports_per_connector_counts_and_depth() {
+        let metrics = Metrics::new();
+        let (publisher, mut subscriber) = in_process_with_metrics(4, metrics.clone());
+        publisher.publish(batch(1)).await.unwrap();
+        publisher.publish(batch(2)).await.unwrap();
+        assert!(metrics.render().contains("wkyt_bus_queue_depth 2\n"));
+
+        subscriber.next().await.unwrap().into_parts().1.ack();
+        let text = metrics.render();
+        assert!(text.contains("wkyt_bus_published_total{connector=\"test\"} 2\n"));
+        assert!(text.contains("wkyt_bus_acked_total{connector=\"test\"} 1\n"));
+        assert!(text.contains("wkyt_bus_queue_depth 1\n"));
+        // The diagnostic counters are unaffected by the registry.
+        assert_eq!((publisher.published(), publisher.acked()), (2, 1));
+
+        // A second bus on the same registry adds to the gauge instead of
+        // overwriting it, and a dropped bus stops counting.
+        let (other, other_subscriber) = in_process_with_metrics(4, metrics.clone());
+        other.publish(batch(3)).await.unwrap();
+        assert!(metrics.render().contains("wkyt_bus_queue_depth 2\n"));
+        drop((publisher, subscriber));
+        assert!(metrics.render().contains("wkyt_bus_queue_depth 1\n"));
+        drop((other, other_subscriber));
+    }
+
     #[tokio::test]
     async fn closed_bus_reports_closed_to_publishers_and_drains_for_consumers() {
         let (publisher, mut subscriber) = in_process(4);
@@ -0,0 +1,61 @@
+//! Domain allow/deny lists: which sites' history may be ingested at all.
+//!
+//! A listed domain covers itself and every subdomain (`example.com`
+//! covers `mail.example.com`, not `badexample.com`). The deny list always
+//! wins; a non-empty allow list admits only what it covers. Entries are
+//! folded to lower case, and a leading `*.` or `.` is ignored, so
+//! `*.bank.example` and `bank.example` mean the same.
+
+/// The compiled lists.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Domains {
+    allow: Vec<String>,
+    deny: Vec<String>,
+}
+
+impl Domains {
+    pub(crate) fn allow(&mut self, domains: impl IntoIterator<Item = impl AsRef<str>>) {
+        self.allow.extend(domains.into_iter().filter_map(|d| normalize(d.as_ref())));
+    }
+
+    pub(crate) fn deny(&mut self, domains: impl IntoIterator<Item = impl AsRef<str>>) {
+        self.deny.extend(domains.into_iter().filter_map(|d| normalize(d.as_ref())));
+    }
+
+    /// Whether a page on `host` may be ingested.
+    pub(crate) fn admits(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let covers = |domain: &String| {
+            host == *domain || host.strip_suffix(domain.as_str()).is_some_and(|sub| sub.ends_with('.'))
+        };
+        !self.deny.iter().any(covers) && (self.allow.is_empty() || self.allow.iter().any(covers))
+    }
+}
+
+fn normalize(domain: &str) -> Option<String> {
+    let domain = domain.trim().trim_start_matches("*.").trim_start_matches('.').trim_end_matches('.');
+    (!domain.is_empty()).then(|| domain.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_cover_subdomains_and_deny_wins() {
+        let mut domains = Domains::default();
+        assert!(domains.admits("anything.example"), "no lists: everything");
+
+        domains.deny(["*.Bank.example", " health.example ", ""]);
+        assert!(!domains.admits("bank.example"));
+        assert!(!domains.admits("online.BANK.example."));
+        assert!(domains.admits("notbank.example"), "a suffix is not a subdomain");
+        assert!(!domains.admits("portal.health.example"));
+
+        domains.allow(["example", "news.site"]);
+        assert!(domains.admits("docs.example"));
+        assert!(!domains.admits("online.bank.example"), "deny wins over allow");
+        assert!(!domains.admits("other.site"));
+        assert!(domains.admits("news.site"));
+    }
+}
@@ -0,0 +1,362 @@
+//! Browser-history connector: reads a Firefox `places.sqlite` or a
+//! Chromium-based browser's `History` database — no browser extension
+//! involved — into one [`ItemKind::Event`] per visit and one `web_page`
+//! item per page visited, with its URL, title and visit count.
+//!
+//! The browser's files are only ever copied: each pass reads a private
+//! copy, opened query-only (see [`profile`]), so a running browser neither
+//! blocks the import nor sees it.
+//!
+//! A visit's source id is `visit:{row id}`; its properties are the page's
+//! `url`, `title` and `domain`, the `browser`, the `transition` that led
+//! to it (`link`, `typed`, `reload`, ...), `from_visit` (the referring
+//! visit's item id) and `page` (the page's item id), plus `duration_ms`
+//! where Chromium records one. A page is `page:{url}` and is upserted
+//! with each batch holding one of its visits, so its `title`,
+//! `visit_count` and `last_visit` follow the browser's. Only http and
+//! https pages are read; `file:`, internal and extension URLs are skipped,
+//! as are resources a page embedded (Firefox `embed`, Chromium
+//! `auto_subframe` visits).
+//!
+//! Sites on the deny list — or off a non-empty allow list — are never
+//! ingested: their visits are skipped before any item is built (see
+//! [`domains`]). Changing the lists does not retract what was ingested
+//! before, and does not bring back visits skipped before.
+//!
+//! Cursor design: the highest visit id and visit time seen, in the
+//! browser's units (`{"visit_id": 81234, "visit_time": 13345678901234567}`).
+//! A pass reads the visits above that id *or* after that time, in id
+//! order: row ids grow as visits are added, but clearing recent history
+//! lets the browser hand out the cleared ids again, and the time catches
+//! those. Every batch carries the cursor (it is a few bytes), so an
+//! interrupted pass resumes after its last committed batch. Visits
+//! deleted in the browser are not tombstoned; a reused id overwrites the
+//! deleted visit's item.
+
+mod domains;
+mod profile;
+
+pub use profile::Browser;
+
+use domains::Domains;
+use futures_util::stream;
+use profile::{Copy, Visit};
+use rusqlite::ErrorCode;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use wkyt_core::batches::{self, Step};
+use wkyt_core::files::retryable;
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+
+pub struct BrowserHistory {
+    id: String,
+    browser: Browser,
+    db: PathBuf,
+    batch_size: usize,
+    domains: Domains,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+struct HistoryCursor {
+    visit_id: i64,
+    visit_time: i64,
+}
+
+impl BrowserHistory {
+    /// `db` is the history database itself: `places.sqlite` in a Firefox
+    /// profile, `History` in a Chromium one.
+    pub fn new(id: impl Into<String>, browser: Browser, db: PathBuf) -> Self {
+        Self { id: id.into(), browser, db, batch_size: DEFAULT_BATCH_SIZE, domains: Domains::default() }
+    }
+
+    /// Visits per batch (default 100).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Ingest only these domains and their subdomains. May be called more
+    /// than once; the lists add up.
+    pub fn with_allowed_domains(mut self, domains: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.domains.allow(domains);
+        self
+    }
+
+    /// Never ingest these domains or their subdomains, whatever the allow
+    /// list says.
+    pub fn with_denied_domains(mut self, domains: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.domains.deny(domains);
+        self
+    }
+
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(Copy, HistoryCursor), SyncError> {
+        let cursor = match cursor {
+            None => HistoryCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        if !self.db.is_file() {
+            return Err(SyncError::Fatal { source: format!("history database {:?} does not exist", self.db).into() });
+        }
+        Ok((profile::copy(&self.db)?, cursor))
+    }
+
+    /// The items for `visit`, if it is one to ingest: the page, then the
+    /// visit.
+    fn items(&self, visit: Visit) -> Option<(Item, Item)> {
+        if profile::is_embedded(visit.transition) {
+            return None;
+        }
+        let url = url::Url::parse(&visit.url).ok().filter(|u| matches!(u.scheme(), "http" | "https"))?;
+        let domain = url.host_str()?.to_ascii_lowercase();
+        if !self.domains.admits(&domain) {
+            return None;
+        }
+        let at = self.browser.instant(visit.time)?;
+        let last_visit = visit.last_visit.and_then(|t| self.browser.instant(t));
+        let browser = self.browser.name();
+
+        let page_properties = json!({
+            "url": visit.url,
+            "title": visit.title,
+            "domain": domain,
+            "browser": browser,
+            "visit_count": visit.visit_count,
+            "last_visit": last_visit.map(|t| t.to_rfc3339()),
+        });
+        let page_source_id = format!("page:{}", visit.url);
+        let kind = ItemKind::Other("web_page".into());
+        let page = Item::new(page_source_id, &self.id, kind, last_visit.unwrap_or(at), page_properties);
+
+        let visit_properties = json!({
+            "url": visit.url,
+            "title": visit.title,
+            "domain": domain,
+            "browser": browser,
+            "transition": visit.transition,
+            "from_visit": visit.from_visit.map(|v| Item::deterministic_id(&self.id, &format!("visit:{v}")).to_string()),
+            "page": page.id,
+            "duration_ms": visit.duration_us.map(|us| us / 1000),
+        });
+        let visit = Item::new(format!("visit:{}", visit.id), &self.id, ItemKind::Event, at, visit_properties);
+        Some((page, visit))
+    }
+}
+
+/// The sync stream's batches, built lazily as it is polled.
+struct Batches<'a> {
+    importer: &'a BrowserHistory,
+    /// `None` once the pass is over.
+    copy: Option<Copy>,
+    /// What the pass reads from.
+    since: HistoryCursor,
+    /// What the next batch will carry.
+    cursor: HistoryCursor,
+    /// The last visit id read.
+    after: i64,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        let Some(copy) = &self.copy else { return Ok(None) };
+        let visits = importer
+            .browser
+            .visits(&copy.conn, self.since.visit_id, self.since.visit_time, self.after, importer.batch_size)
+            .map_err(sqlite_error)?;
+        if visits.is_empty() {
+            self.copy = None;
+            return Ok(None);
+        }
+
+        let mut pages = BTreeMap::new();
+        let mut deltas = Vec::new();
+        for visit in visits {
+            self.after = visit.id;
+            self.cursor.visit_id = self.cursor.visit_id.max(visit.id);
+            self.cursor.visit_time = self.cursor.visit_time.max(visit.time);
+            if let Some((page, visit)) = importer.items(visit) {
+                // One upsert per page per batch, however many visits.
+                pages.insert(page.source_id.clone(), page);
+                deltas.push(Delta::Upsert(visit));
+            }
+        }
+        deltas.splice(0..0, pages.into_values().map(Delta::Upsert));
+        let cursor = serde_json::to_string(&self.cursor).expect("cursor serialization is infallible");
+        Ok(Some(DeltaBatch { connector_id: importer.id.clone(), deltas, cursor: Some(SyncToken(cursor)) }))
+    }
+}
+
+/// A locked or torn copy is worth another try; anything else (not a
+/// database, not this browser's schema) needs the configuration fixed.
+fn sqlite_error(e: rusqlite::Error) -> SyncError {
+    match e.sqlite_error_code() {
+        Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked | ErrorCode::DatabaseCorrupt) => {
+            SyncError::Retryable { source: Box::new(e), retry_after: None }
+        }
+        _ => SyncError::Fatal { source: Box::new(e) },
+    }
+}
+
+#[async_trait::async_trait]
+impl Connector for BrowserHistory {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((copy, since)) => batches::stream(Batches {
+                importer: self,
+                copy: Some(copy),
+                since,
+                cursor: since,
+                after: i64::MIN,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, upserts_of};
+    use futures_util::StreamExt;
+    use rusqlite::{params, Connection};
+    use serde_json::Value;
+    use std::path::Path;
+
+    fn visit_ids(batches: &[DeltaBatch]) -> Vec<&str> {
+        upserts_of(batches, ItemKind::Event).iter().map(|i| i.source_id.as_str()).collect()
+    }
+
+    /// A Firefox history in WAL mode whose writes stay in the log while
+    /// the returned connection (the "running browser") is open.
+    fn firefox(path: &Path) -> Connection {
+        let conn = Connection::open(path).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        conn.pragma_update(None, "wal_autocheckpoint", 0).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE moz_places (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR,
+                                      visit_count INTEGER DEFAULT 0, last_visit_date INTEGER);
+             CREATE TABLE moz_historyvisits (id INTEGER PRIMARY KEY, from_visit INTEGER, place_id INTEGER,
+                                             visit_date INTEGER, visit_type INTEGER);
+             INSERT INTO moz_places VALUES
+               (1, 'https://example.com/a', 'Page A', 2, 1704103200000000),
+               (2, 'https://online.bank.example/login', 'Bank', 1, 1704103300000000),
+               (3, 'file:///home/ana/notes.txt', NULL, 1, 1704103400000000),
+               (4, 'https://ads.example/frame', NULL, 1, 1704103500000000);
+             INSERT INTO moz_historyvisits VALUES
+               (1, 0, 1, 1704103100000000, 2),
+               (2, 1, 2, 1704103300000000, 1),
+               (3, 0, 3, 1704103400000000, 1),
+               (4, 0, 4, 1704103500000000, 4),
+               (5, 1, 1, 1704103200000000, 9);",
+        )
+        .unwrap();
+        conn
+    }
+
+    #[tokio::test]
+    async fn firefox_visits_are_read_from_a_copy_and_filtered_by_domain() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("places.sqlite");
+        let browser = firefox(&db);
+        assert!(dir.path().join("places.sqlite-wal").exists(), "the rows live in the log");
+        let c = BrowserHistory::new("firefox", Browser::Firefox, db.clone())
+            .with_batch_size(2)
+            .with_denied_domains(["bank.example"]);
+
+        let batches = drain(&c, None).await;
+        assert_eq!(visit_ids(&batches), ["visit:1", "visit:5"], "no bank, file: or embedded visits");
+        assert_eq!(batches.len(), 3);
+        assert!(batches.iter().all(|b| b.cursor.is_some()));
+        let pages = upserts_of(&batches, ItemKind::Other("web_page".into()));
+        assert_eq!(pages.len(), 2, "once per batch it is visited in");
+        assert_eq!(pages[0].source_id, "page:https://example.com/a");
+        assert_eq!(pages[0].properties["visit_count"], 2);
+        assert_eq!(pages[0].properties["domain"], "example.com");
+        assert_eq!(pages[0].timestamp.to_rfc3339(), "2024-01-01T10:00:00+00:00");
+
+        let visits = upserts_of(&batches, ItemKind::Event);
+        assert_eq!(visits[0].properties["transition"], "typed");
+        assert_eq!(visits[0].properties["page"], pages[0].id.as_str());
+        assert_eq!(visits[0].timestamp.to_rfc3339(), "2024-01-01T09:58:20+00:00");
+        assert_eq!(visits[1].properties["transition"], "reload");
+        assert_eq!(visits[1].properties["from_visit"], visits[0].id.as_str());
+        assert_eq!(visits[1].properties["duration_ms"], Value::Null);
+
+        let cursor = last_cursor(&batches);
+        let position: Value = serde_json::from_str(&cursor.as_ref().unwrap().0).unwrap();
+        assert_eq!(position, json!({ "visit_id": 5, "visit_time": 1704103500000000_i64 }));
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+
+        // New visits, and one reusing a cleared id, are all the next pass
+        // reads.
+        browser
+            .execute_batch(
+                "DELETE FROM moz_historyvisits WHERE id = 5;
+                 INSERT INTO moz_historyvisits VALUES (5, 0, 1, 1704200000000000, 1), (6, 0, 1, 1704200001000000, 1);",
+            )
+            .unwrap();
+        let batches = drain(&c, cursor).await;
+        assert_eq!(visit_ids(&batches), ["visit:5", "visit:6"]);
+        drop(browser);
+    }
+
+    #[tokio::test]
+    async fn chromium_times_transitions_and_allow_lists() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = dir.path().join("History");
+        let conn = Connection::open(&db).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE urls (id INTEGER PRIMARY KEY, url LONGVARCHAR, title LONGVARCHAR,
+                                visit_count INTEGER, last_visit_time INTEGER);
+             CREATE TABLE visits (id INTEGER PRIMARY KEY, url INTEGER, visit_time INTEGER, from_visit INTEGER,
+                                  transition INTEGER, visit_duration INTEGER);",
+        )
+        .unwrap();
+        // 2024-01-01T10:00:00Z in microseconds since 1601.
+        let t = 13_348_576_800_000_000_i64;
+        conn.execute("INSERT INTO urls VALUES (1, 'https://docs.rs/serde', 'serde - Rust', 3, ?1)", [t]).unwrap();
+        conn.execute("INSERT INTO urls VALUES (2, 'https://news.example/', 'News', 1, ?1)", [t]).unwrap();
+        conn.execute("INSERT INTO urls VALUES (3, 'chrome://settings/', 'Settings', 1, ?1)", [t]).unwrap();
+        for (id, url, transition) in [(1, 1, 0x3000_0001_i64), (2, 2, 0), (3, 3, 1), (4, 1, 3)] {
+            conn.execute("INSERT INTO visits VALUES (?1, ?2, ?3, 0, ?4, 42000000)", params![id, url, t, transition])
+                .unwrap();
+        }
+        drop(conn);
+
+        let c = BrowserHistory::new("chrome", Browser::Chromium, db.clone()).with_allowed_domains(["*.rs"]);
+        let batches = drain(&c, None).await;
+        assert_eq!(visit_ids(&batches), ["visit:1"], "allow list, internal URL and subframe");
+        let visit = upserts_of(&batches, ItemKind::Event)[0];
+        assert_eq!(visit.timestamp.to_rfc3339(), "2024-01-01T10:00:00+00:00");
+        assert_eq!(visit.properties["transition"], "typed", "qualifier bits masked off");
+        assert_eq!(visit.properties["duration_ms"], 42000);
+        assert_eq!(visit.properties["browser"], "chromium");
+
+        // The wrong schema is a configuration error, not a retry.
+        let wrong = BrowserHistory::new("x", Browser::Firefox, db);
+        let results: Vec<_> = wrong.sync(None).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::Fatal { .. })]));
+    }
+
+    #[tokio::test]
+    async fn missing_databases_and_malformed_cursors_fail_the_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = BrowserHistory::new("firefox", Browser::Firefox, dir.path().join("places.sqlite"));
+        let results: Vec<_> = c.sync(None).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::Fatal { .. })]));
+        let results: Vec<_> = c.sync(Some(SyncToken("not json".into()))).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::ResyncRequired)]));
+    }
+}
@@ -0,0 +1,180 @@
+//! The two history schemas, and the private copy each pass reads.
+//!
+//! Firefox keeps pages in `moz_places` and visits in `moz_historyvisits`,
+//! times in microseconds since the Unix epoch. Chromium-based browsers
+//! (Chrome, Chromium, Edge, Brave, ...) keep `urls` and `visits`, times in
+//! microseconds since 1601-01-01 (the Windows epoch), and a transition
+//! whose low byte is the visit's core type. Both key visits by an integer
+//! row id that grows as visits are added.
+//!
+//! A running browser holds its database open and locked, and may have
+//! recent writes only in its write-ahead log or journal. The pass
+//! therefore copies the database with its `-wal` or `-journal` file into a
+//! private temporary directory and opens only the copy; the profile's
+//! files are never opened by SQLite. A copy taken mid-write can be torn,
+//! which surfaces as a retryable error and is retaken on the next pass.
+
+use crate::{retryable, sqlite_error};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags};
+use std::path::Path;
+use tempfile::TempDir;
+use wkyt_core::SyncError;
+
+/// Microseconds from 1601-01-01 to 1970-01-01.
+const WINDOWS_EPOCH_OFFSET_US: i64 = 11_644_473_600_000_000;
+
+/// Which schema a history database has.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Browser {
+    /// `places.sqlite` from a Firefox (or Firefox-based) profile.
+    Firefox,
+    /// `History` from a Chromium-based browser's profile.
+    Chromium,
+}
+
+/// A visit row joined with its page.
+pub(crate) struct Visit {
+    pub(crate) id: i64,
+    /// In the browser's own units; see [`Browser::instant`].
+    pub(crate) time: i64,
+    pub(crate) transition: &'static str,
+    /// The visit this one was reached from.
+    pub(crate) from_visit: Option<i64>,
+    /// In microseconds; Chromium only.
+    pub(crate) duration_us: Option<i64>,
+    pub(crate) url: String,
+    pub(crate) title: Option<String>,
+    pub(crate) visit_count: i64,
+    pub(crate) last_visit: Option<i64>,
+}
+
+impl Browser {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Browser::Firefox => "firefox",
+            Browser::Chromium => "chromium",
+        }
+    }
+
+    /// A visit time in this browser's units as an instant.
+    pub(crate) fn instant(self, time: i64) -> Option<DateTime<Utc>> {
+        match self {
+            Browser::Firefox => DateTime::from_timestamp_micros(time),
+            Browser::Chromium => DateTime::from_timestamp_micros(time.checked_sub(WINDOWS_EPOCH_OFFSET_US)?),
+        }
+    }
+
+    /// The visits with an id above `after` that are new since the cursor
+    /// (`id` above `since_id`, or later than `since_time`), in id order,
+    /// at most `limit` of them.
+    pub(crate) fn visits(
+        self,
+        conn: &Connection,
+        since_id: i64,
+        since_time: i64,
+        after: i64,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<Visit>> {
+        let sql = match self {
+            Browser::Firefox => {
+                "SELECT v.id, v.visit_date, v.visit_type, v.from_visit, NULL,
+                        p.url, p.title, p.visit_count, p.last_visit_date
+                 FROM moz_historyvisits v JOIN moz_places p ON p.id = v.place_id
+                 WHERE (v.id > ?1 OR v.visit_date > ?2) AND v.id > ?3
+                 ORDER BY v.id LIMIT ?4"
+            }
+            Browser::Chromium => {
+                "SELECT v.id, v.visit_time, v.transition, v.from_visit, v.visit_duration,
+                        u.url, u.title, u.visit_count, u.last_visit_time
+                 FROM visits v JOIN urls u ON u.id = v.url
+                 WHERE (v.id > ?1 OR v.visit_time > ?2) AND v.id > ?3
+                 ORDER BY v.id LIMIT ?4"
+            }
+        };
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params![since_id, since_time, after, limit as i64], |row| {
+            Ok(Visit {
+                id: row.get(0)?,
+                time: row.get::<_, Option<i64>>(1)?.unwrap_or_default(),
+                transition: self.transition(row.get::<_, Option<i64>>(2)?.unwrap_or_default()),
+                from_visit: row.get::<_, Option<i64>>(3)?.filter(|&v| v > 0),
+                duration_us: row.get::<_, Option<i64>>(4)?.filter(|&d| d > 0),
+                url: row.get(5)?,
+                title: row.get::<_, Option<String>>(6)?.filter(|t| !t.is_empty()),
+                visit_count: row.get::<_, Option<i64>>(7)?.unwrap_or_default(),
+                last_visit: row.get::<_, Option<i64>>(8)?.filter(|&t| t > 0),
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// The name of a visit's transition (Firefox `visit_type`, Chromium
+    /// core transition).
+    fn transition(self, code: i64) -> &'static str {
+        match (self, code) {
+            (Browser::Firefox, 1) => "link",
+            (Browser::Firefox, 2) => "typed",
+            (Browser::Firefox, 3) => "bookmark",
+            (Browser::Firefox, 4) => "embed",
+            (Browser::Firefox, 5) => "redirect_permanent",
+            (Browser::Firefox, 6) => "redirect_temporary",
+            (Browser::Firefox, 7) => "download",
+            (Browser::Firefox, 8) => "framed_link",
+            (Browser::Firefox, 9) => "reload",
+            (Browser::Chromium, code) => match code & 0xff {
+                0 => "link",
+                1 => "typed",
+                2 => "auto_bookmark",
+                3 => "auto_subframe",
+                4 => "manual_subframe",
+                5 => "generated",
+                6 => "auto_toplevel",
+                7 => "form_submit",
+                8 => "reload",
+                9 => "keyword",
+                10 => "keyword_generated",
+                _ => "other",
+            },
+            (Browser::Firefox, _) => "other",
+        }
+    }
+}
+
+/// Transitions that are not page views: resources a page embedded.
+pub(crate) fn is_embedded(transition: &str) -> bool {
+    matches!(transition, "embed" | "auto_subframe")
+}
+
+/// A private read-only copy of a history database.
+pub(crate) struct Copy {
+    pub(crate) conn: Connection,
+    // Dropped after `conn`, removing the copy.
+    _dir: TempDir,
+}
+
+/// Copy `db` (and its `-wal` / `-journal`, when present) and open the copy.
+pub(crate) fn copy(db: &Path) -> Result<Copy, SyncError> {
+    let dir = tempfile::tempdir().map_err(retryable)?;
+    let name = db.file_name().ok_or_else(|| SyncError::Fatal { source: format!("{db:?} is not a file").into() })?;
+    let copy = dir.path().join(name);
+    std::fs::copy(db, &copy).map_err(retryable)?;
+    for suffix in ["-wal", "-journal"] {
+        let mut log = db.as_os_str().to_owned();
+        log.push(suffix);
+        let mut target = copy.as_os_str().to_owned();
+        target.push(suffix);
+        match std::fs::copy(&log, &target) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            result => {
+                result.map_err(retryable)?;
+            }
+        }
+    }
+    // Read-write so SQLite can replay the copied log into the copy;
+    // query_only keeps this connection from changing anything else.
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let conn = Connection::open_with_flags(&copy, flags).map_err(sqlite_error)?;
+    conn.pragma_update(None, "query_only", true).map_err(sqlite_error)?;
+    Ok(Copy { conn, _dir: dir })
+}
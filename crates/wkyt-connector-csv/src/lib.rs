@@ -0,0 +1,471 @@
+//! CSV statement connector: imports bank and card statements exported as
+//! CSV, under one local directory tree, as [`ItemKind::Transaction`]s.
+//! How a statement's columns map onto a transaction is a [`Profile`] —
+//! configuration, one per account, reused for every statement it
+//! exports; each profile's `files` patterns pick its statements. CSV
+//! files no profile selects are ignored, as are hidden files and
+//! directories; symlinks are not followed.
+//!
+//! Identity is the row's content, not its file (see [`statement`]):
+//! importing overlapping statements, or the same statement twice, upserts
+//! each transaction over itself. Each payee becomes an
+//! [`ItemKind::Organization`] (`payee:{name}`, case and spacing folded)
+//! linked from its transactions by a `counterparty` relationship.
+//!
+//! Transaction properties: `account`, `date` (`YYYY-MM-DD`, or with a time
+//! when the statement has one), `amount` (a signed decimal string, money
+//! out negative, as written: `-12.50`), `currency`, `payee`
+//! (`{id, name}`), `memo`, `profile`, and the row's origin, `statement`
+//! and `line`. `raw_payload` is the row, by header. The timestamp is the
+//! date, taken as UTC.
+//!
+//! Cursor: per statement, the mtime, size and a content hash last read
+//! (`{"files": {"chase/2024-01.csv": [1720000000000, 5120, "9f86d081884c7d65"]}}`).
+//! A statement is read again when it changed; a touched file with the
+//! same content is not. Like the mail importer this is an import, not a
+//! mirror: a statement deleted from the tree tombstones nothing, since
+//! its transactions are facts that happened, and usually also appear in
+//! the statements either side of it.
+//!
+//! A statement its profile does not fit (see [`statement`]) is skipped,
+//! not fatal: one odd export must not stop the others importing. The
+//! cursor records it under `skipped`, with why and the file and profile
+//! it was tried with, and it is tried again once either changes. Rows
+//! read before the misfit showed stay imported.
+
+mod profile;
+mod statement;
+
+pub use profile::{Column, Decimal, Profile, ProfileError};
+
+use futures_util::stream;
+use profile::Patterns;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use statement::{ReadError, Row, Statement};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use wkyt_core::batches::{self, Step};
+use wkyt_core::hash::{hex, sha256_hex};
+use wkyt_core::files::{retryable, Found, Walk};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 256;
+/// Hex digits of each statement's SHA-256 kept in the cursor.
+const CURSOR_HASH_HEX: usize = 16;
+/// Hex digits of a row hash in its source id (128 bits).
+const ROW_ID_HEX: usize = 32;
+
+pub struct StatementImporter {
+    id: String,
+    dir: PathBuf,
+    batch_size: usize,
+    profiles: Vec<Profile>,
+}
+
+/// `{"files": {path: [mtime_ms, size, hash]}, "skipped": {path: Skipped}}`.
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct StatementCursor {
+    files: BTreeMap<String, (i64, u64, String)>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    skipped: BTreeMap<String, Skipped>,
+}
+
+/// A statement its profile did not fit.
+#[derive(Serialize, Deserialize, Clone)]
+struct Skipped {
+    /// `[mtime_ms, size, hash]` of the file tried.
+    seen: (i64, u64, String),
+    /// [`fingerprint`] of the profile it was tried with.
+    profile: String,
+    error: String,
+}
+
+/// A statement with something new to read, and the profile it is read
+/// with (an index into `profiles`).
+struct Planned {
+    path: String,
+    profile: usize,
+    seen: (i64, u64, String),
+}
+
+impl StatementImporter {
+    pub fn new(id: impl Into<String>, dir: PathBuf) -> Self {
+        Self { id: id.into(), dir, batch_size: DEFAULT_BATCH_SIZE, profiles: Vec::new() }
+    }
+
+    /// Read the statements `profile` selects with it (repeatable). A file
+    /// two profiles select is read with the first.
+    pub fn with_profile(mut self, profile: Profile) -> Self {
+        self.profiles.push(profile);
+        self
+    }
+
+    /// Transactions per batch (default 256).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn patterns(&self) -> Result<Vec<Patterns>, SyncError> {
+        let fatal = |e: ProfileError| SyncError::Fatal { source: Box::new(e) };
+        self.profiles.iter().map(|p| p.check().and_then(|()| p.patterns()).map_err(fatal)).collect()
+    }
+
+    /// Scan the tree for statements whose content changed since `cursor`.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(Vec<Planned>, StatementCursor, bool), SyncError> {
+        let mut prev: StatementCursor = match cursor {
+            None => StatementCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        if !self.dir.is_dir() {
+            return Err(SyncError::Fatal {
+                source: format!("statement directory {:?} does not exist", self.dir).into(),
+            });
+        }
+        let patterns = self.patterns()?;
+        let found = Walk::new()
+            .files(&self.dir, |name| name.to_ascii_lowercase().ends_with(".csv"))
+            .map_err(retryable)?;
+
+        let mut present = HashSet::new();
+        let mut planned = Vec::new();
+        let mut unsaved = false;
+        let fingerprints: Vec<String> = self.profiles.iter().map(fingerprint).collect();
+        for Found { path, mtime_ms, size } in found {
+            let Some(profile) = patterns.iter().position(|p| p.matches(&path)) else { continue };
+            present.insert(path.clone());
+            // Still skipped while neither the file nor its profile changed.
+            let skipped = prev.skipped.get_mut(&path).filter(|s| s.profile == fingerprints[profile]);
+            let known = skipped.as_ref().map(|s| &s.seen).or(prev.files.get(&path));
+            if known.is_some_and(|(m, s, _)| (*m, *s) == (mtime_ms, size)) {
+                continue;
+            }
+            let hash = hash_file(&self.dir.join(&path)).map_err(retryable)?[..CURSOR_HASH_HEX].to_string();
+            if let Some(skipped) = skipped.filter(|s| s.seen.2 == hash) {
+                skipped.seen = (mtime_ms, size, hash);
+                unsaved = true;
+                continue;
+            }
+            match prev.files.get_mut(&path) {
+                // Touched, not changed.
+                Some(known) if known.2 == hash => {
+                    *known = (mtime_ms, size, hash);
+                    unsaved = true;
+                }
+                _ => planned.push(Planned { path, profile, seen: (mtime_ms, size, hash) }),
+            }
+        }
+        let before = prev.files.len() + prev.skipped.len();
+        prev.files.retain(|path, _| present.contains(path));
+        prev.skipped.retain(|path, _| present.contains(path));
+        unsaved |= prev.files.len() + prev.skipped.len() != before;
+        Ok((planned, prev, unsaved))
+    }
+
+    /// A row's transaction, and its payee with the link between them.
+    fn items(&self, profile: &Profile, statement: &str, row: Row) -> Vec<Item> {
+        let source_id = format!("txn:{}", &row.hash[..ROW_ID_HEX]);
+        let timestamp = row.at.and_utc();
+        let date = row.at.format(if row.has_time { "%Y-%m-%dT%H:%M:%S" } else { "%Y-%m-%d" }).to_string();
+
+        let mut payee_items = Vec::new();
+        let payee = row.payee.as_ref().map(|name| {
+            let payee_source_id = format!("payee:{}", name.to_lowercase());
+            let payee_id = Item::deterministic_id(&self.id, &payee_source_id).to_string();
+            payee_items.push(Item::new(
+                format!("counterparty:{source_id}->{payee_source_id}"),
+                &self.id,
+                ItemKind::Relationship,
+                timestamp,
+                json!({
+                    "source": Item::deterministic_id(&self.id, &source_id).to_string(),
+                    "target": payee_id,
+                    "relation": "counterparty",
+                }),
+            ));
+            let payee = json!({ "name": name });
+            payee_items.push(Item::new(payee_source_id, &self.id, ItemKind::Organization, timestamp, payee));
+            json!({ "id": payee_id, "name": name })
+        });
+
+        let properties = json!({
+            "account": profile.account(),
+            "date": date,
+            "amount": row.amount,
+            "currency": row.currency,
+            "payee": payee,
+            "memo": row.memo,
+            "profile": profile.name,
+            "statement": statement,
+            "line": row.line,
+        });
+        let mut transaction = Item::new(source_id, &self.id, ItemKind::Transaction, timestamp, properties);
+        transaction.raw_payload = Some(row.raw);
+        let mut items = vec![transaction];
+        items.extend(payee_items.into_iter().rev());
+        items
+    }
+}
+
+/// The sync stream's batches, built lazily as it is polled: one statement
+/// open at a time, read a batch of rows at a time.
+struct Batches<'a> {
+    importer: &'a StatementImporter,
+    planned: std::vec::IntoIter<Planned>,
+    reading: Option<(Planned, Statement<'a>)>,
+    cursor: StatementCursor,
+    unsaved: bool,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        let mut deltas = Vec::new();
+        let mut payees = HashSet::new();
+        let mut rows = 0;
+        while rows < importer.batch_size {
+            let Some((planned, statement)) = &mut self.reading else {
+                let Some(planned) = self.planned.next() else { break };
+                let profile = &importer.profiles[planned.profile];
+                match Statement::open(profile, &importer.dir.join(&planned.path), &planned.path) {
+                    Ok(statement) => self.reading = Some((planned, statement)),
+                    Err(ReadError::Misfit(error)) => self.skip(planned, error),
+                    Err(ReadError::Sync(e)) => return Err(e),
+                }
+                continue;
+            };
+            match statement.next_row() {
+                Err(ReadError::Sync(e)) => return Err(e),
+                Err(ReadError::Misfit(error)) => {
+                    let (planned, _) = self.reading.take().expect("matched above");
+                    self.skip(planned, error);
+                }
+                Ok(Some(row)) => {
+                    let profile = &importer.profiles[planned.profile];
+                    for item in importer.items(profile, &planned.path, row) {
+                        // One upsert per payee per batch.
+                        if item.kind == ItemKind::Organization && !payees.insert(item.source_id.clone()) {
+                            continue;
+                        }
+                        deltas.push(Delta::Upsert(item));
+                    }
+                    rows += 1;
+                }
+                // Recorded once the whole statement is read: an
+                // interrupted one is read again, over itself.
+                Ok(None) => {
+                    let (planned, _) = self.reading.take().expect("matched above");
+                    self.cursor.skipped.remove(&planned.path);
+                    self.cursor.files.insert(planned.path, planned.seen);
+                    self.unsaved = true;
+                }
+            }
+        }
+
+        let checkpoint = std::mem::take(&mut self.unsaved);
+        if deltas.is_empty() && !checkpoint {
+            return Ok(None);
+        }
+        let cursor = checkpoint
+            .then(|| SyncToken(serde_json::to_string(&self.cursor).expect("cursor serialization is infallible")));
+        Ok(Some(DeltaBatch { connector_id: importer.id.clone(), deltas, cursor }))
+    }
+}
+
+impl Batches<'_> {
+    /// Record that `planned`'s profile does not fit it, and move on.
+    fn skip(&mut self, planned: Planned, error: String) {
+        let profile = fingerprint(&self.importer.profiles[planned.profile]);
+        self.cursor.files.remove(&planned.path);
+        self.cursor.skipped.insert(planned.path, Skipped { seen: planned.seen, profile, error });
+        self.unsaved = true;
+    }
+}
+
+/// SHA-256 of a file's content in hex, read in bounded chunks.
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
+}
+
+/// Identifies a profile's settings, so a statement skipped under one is
+/// tried again once it is edited.
+fn fingerprint(profile: &Profile) -> String {
+    let settings = serde_json::to_string(profile).expect("profile serialization is infallible");
+    sha256_hex(settings.as_bytes())[..CURSOR_HASH_HEX].to_string()
+}
+
+#[async_trait::async_trait]
+impl Connector for StatementImporter {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        self.patterns()?;
+        std::fs::create_dir_all(&self.dir).map_err(retryable)?;
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((planned, cursor, unsaved)) => batches::stream(Batches {
+                importer: self,
+                planned: planned.into_iter(),
+                reading: None,
+                cursor,
+                unsaved,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, upserts_of};
+    use futures_util::StreamExt;
+    use std::fs;
+
+    const CHECKING: &str = r#"{"name": "checking", "files": ["bank/*.csv"], "date": "Date",
+        "date_format": "%m/%d/%Y", "amount": "Amount", "payee": "Description", "memo": "Memo",
+        "currency": "USD"}"#;
+
+    fn importer(dir: &Path) -> StatementImporter {
+        StatementImporter::new("statements", dir.to_path_buf()).with_profile(Profile::from_json(CHECKING).unwrap())
+    }
+
+    #[tokio::test]
+    async fn overlapping_statements_import_each_transaction_once() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("bank")).unwrap();
+        let header = "Date,Description,Amount,Memo\n";
+        let jan = "01/30/2024,Corner  Cafe,-4.50,\n01/31/2024,CORNER CAFE,-4.50,\n01/31/2024,Corner Cafe,-4.50,\n";
+        let feb = "01/31/2024,Corner Cafe,-4.50,\n01/31/2024,Corner Cafe,-4.50,\n\
+                   02/01/2024,Employer Inc,\"2,000.00\",Salary\n";
+        fs::write(dir.path().join("bank/2024-01.csv"), format!("\u{feff}{header}{jan}Total,,-13.50,\n")).unwrap();
+        let c = importer(dir.path());
+
+        let batches = drain(&c, None).await;
+        let january: Vec<&Item> = upserts_of(&batches, ItemKind::Transaction);
+        assert_eq!(january.len(), 3);
+        assert_eq!(january[0].properties["date"], "2024-01-30");
+        assert_eq!(january[0].properties["amount"], "-4.50");
+        assert_eq!(january[0].properties["currency"], "USD");
+        assert_eq!(january[0].properties["payee"]["name"], "Corner Cafe");
+        assert_eq!(january[0].properties["line"], 2);
+        assert_eq!(january[0].raw_payload.as_ref().unwrap()["Description"], "Corner  Cafe");
+        // Two identical coffees on the 31st, once spelled differently.
+        assert_ne!(january[1].source_id, january[2].source_id);
+        let payees = upserts_of(&batches, ItemKind::Organization);
+        assert_eq!(payees.len(), 1, "one payee per batch, whatever the case");
+        assert_eq!(payees[0].source_id, "payee:corner cafe");
+        let link = upserts_of(&batches, ItemKind::Relationship)[0];
+        assert_eq!(link.properties["source"], january[0].id);
+        assert_eq!(link.properties["target"], payees[0].id);
+
+        // February's statement repeats the 31st: the same two transactions.
+        let cursor = batches.last().unwrap().cursor.clone();
+        fs::write(dir.path().join("bank/2024-02.csv"), format!("{header}{feb}")).unwrap();
+        let batches = drain(&c, cursor.clone()).await;
+        let february: Vec<&str> =
+            upserts_of(&batches, ItemKind::Transaction).iter().map(|t| t.source_id.as_str()).collect();
+        assert_eq!(february[..2], [january[1].source_id.as_str(), january[2].source_id.as_str()]);
+        assert_eq!(upserts_of(&batches, ItemKind::Transaction)[2].properties["amount"], "2000.00");
+
+        // Unchanged statements are not read again; removed ones tombstone nothing.
+        let cursor = batches.last().unwrap().cursor.clone();
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+        fs::remove_file(dir.path().join("bank/2024-01.csv")).unwrap();
+        let batches = drain(&c, cursor).await;
+        assert!(batches.iter().all(|b| b.deltas.is_empty()));
+        let cursor = batches.last().unwrap().cursor.as_ref().unwrap();
+        let cursor: serde_json::Value = serde_json::from_str(&cursor.0).unwrap();
+        assert_eq!(cursor["files"].as_object().unwrap().keys().collect::<Vec<_>>(), ["bank/2024-02.csv"]);
+    }
+
+    #[tokio::test]
+    async fn split_columns_and_headerless_files_map_by_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let card = Profile::from_json(
+            r#"{"name": "card", "account": "Visa 1234", "files": ["visa-*.csv"], "date": 0,
+                "date_format": "%Y-%m-%d %H:%M", "debit": 2, "credit": 3, "payee": 1,
+                "currency_column": 4, "decimal": "comma", "delimiter": ";", "skip_rows": 1, "header": false}"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("visa-2024.csv"),
+            "Exported 2024-02-01\n2024-01-05 09:30;Bakery;3,20;;eur\n2024-01-06 12:00;Refund Shop;;1.000,00;EUR\n",
+        )
+        .unwrap();
+        let c = StatementImporter::new("statements", dir.path().to_path_buf()).with_profile(card);
+        let batches = drain(&c, None).await;
+        let txns = upserts_of(&batches, ItemKind::Transaction);
+        assert_eq!(txns.len(), 2);
+        assert_eq!(txns[0].properties["amount"], "-3.20");
+        assert_eq!(txns[0].properties["date"], "2024-01-05T09:30:00");
+        assert_eq!(txns[0].properties["currency"], "EUR");
+        assert_eq!(txns[0].properties["account"], "Visa 1234");
+        assert_eq!(txns[1].properties["amount"], "1000.00");
+        assert_eq!(txns[1].timestamp.to_rfc3339(), "2024-01-06T12:00:00+00:00");
+    }
+
+    #[tokio::test]
+    async fn statements_a_profile_does_not_fit_are_skipped_until_they_change() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("bank")).unwrap();
+        let c = importer(dir.path());
+        let skipped = |batches: &[DeltaBatch]| -> serde_json::Value {
+            let cursor = batches.last().unwrap().cursor.as_ref().unwrap();
+            serde_json::from_str::<serde_json::Value>(&cursor.0).unwrap()["skipped"].clone()
+        };
+
+        fs::write(dir.path().join("bank/good.csv"), "Date,Description,Amount,Memo\n01/02/2024,X,1,\n").unwrap();
+        for (misfit, reason) in [
+            ("Date,Payee,Amount\n01/02/2024,X,1\n", "no column \"Description\""),
+            ("Date,Description,Amount,Memo\n2024-01-02,X,1,\n", "no row has a date"),
+            ("Date,Description,Amount,Memo\n01/03/2024,Y,2,\n01/04/2024,Z,lots,\n", "\"lots\" is not a number"),
+        ] {
+            fs::write(dir.path().join("bank/bad.csv"), misfit).unwrap();
+            let batches = drain(&c, None).await;
+            let txns = upserts_of(&batches, ItemKind::Transaction);
+            assert!(txns.iter().any(|t| t.properties["statement"] == "bank/good.csv"));
+            let error = skipped(&batches)["bank/bad.csv"]["error"].as_str().unwrap().to_string();
+            assert!(error.starts_with("bank/bad.csv") && error.contains(reason), "{error}");
+        }
+
+        // Not tried again while unchanged; read once it is fixed.
+        let cursor = drain(&c, None).await.last().unwrap().cursor.clone();
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+        fs::write(dir.path().join("bank/bad.csv"), "Date,Description,Amount,Memo\n01/03/2024,Y,2,\n").unwrap();
+        let batches = drain(&c, cursor.clone()).await;
+        assert_eq!(upserts_of(&batches, ItemKind::Transaction).len(), 1);
+        assert!(skipped(&batches).is_null());
+
+        // Or once its profile is.
+        fs::write(dir.path().join("bank/bad.csv"), "Date,Payee,Amount,Memo\n01/03/2024,Y,2,\n").unwrap();
+        let cursor = drain(&c, cursor).await.last().unwrap().cursor.clone();
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+        let fixed = CHECKING.replace(r#""payee": "Description""#, r#""payee": "Payee""#);
+        let c = StatementImporter::new("statements", dir.path().to_path_buf())
+            .with_profile(Profile::from_json(&fixed).unwrap());
+        let batches = drain(&c, cursor).await;
+        assert_eq!(upserts_of(&batches, ItemKind::Transaction).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn broken_profiles_and_cursors_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let headerless = CHECKING.replace(r#""currency": "USD""#, r#""currency": "USD", "header": false"#);
+        let error = Profile::from_json(&headerless).unwrap_err();
+        assert!(matches!(error, ProfileError::Invalid { .. }), "names columns but reads no header: {error}");
+
+        let results: Vec<_> = importer(dir.path()).sync(Some(SyncToken("not json".into()))).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::ResyncRequired)]));
+    }
+}
@@ -0,0 +1,303 @@
+//! Mapping profiles: how one bank's (or card's) CSV statements map onto
+//! transactions. A profile is plain configuration, kept as JSON and reused
+//! for every statement that account exports:
+//!
+//! ```json
+//! { "name": "chase-checking", "files": ["chase/*.csv"],
+//!   "date": "Posting Date", "date_format": "%m/%d/%Y",
+//!   "amount": "Amount", "payee": "Description", "memo": 4,
+//!   "currency": "USD", "decimal": "point" }
+//! ```
+//!
+//! Columns are named by header (matched trimmed and case-insensitively)
+//! or given as 0-based indexes, for files without a header row. The
+//! amount is either one signed column (`amount`) or a `debit` / `credit`
+//! pair; `negate` flips the sign for card statements that list charges as
+//! positive. The currency is a column (`currency_column`) or a fixed code
+//! (`currency`). `files` patterns without a `/` match a file's name at any
+//! depth; with one, its path below the importer's root (case-insensitive,
+//! `*` stops at `/`).
+
+use chrono::{NaiveDate, NaiveDateTime};
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("reading profile {path:?}: {source}")]
+    Io { path: String, source: std::io::Error },
+    #[error("profile is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("profile {profile:?}: {problem}")]
+    Invalid { profile: String, problem: String },
+    #[error("profile {profile:?}: bad file pattern: {source}")]
+    Pattern { profile: String, source: globset::Error },
+}
+
+/// A column, by header name or 0-based index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+/// How amounts write their decimal point.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decimal {
+    /// `1,234.56`
+    #[default]
+    Point,
+    /// `1.234,56` (also `1 234,56`)
+    Comma,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Identifies the profile; also the account, unless `account` is set.
+    pub name: String,
+    /// Which statements under the root this profile reads.
+    pub files: Vec<String>,
+    /// The account the statements are for. Part of every row's identity,
+    /// so identical rows in two accounts stay two transactions.
+    #[serde(default)]
+    pub account: Option<String>,
+    pub date: Column,
+    /// chrono `strftime` format; with a time (`%H`...) or a date only.
+    pub date_format: String,
+    #[serde(default)]
+    pub amount: Option<Column>,
+    #[serde(default)]
+    pub debit: Option<Column>,
+    #[serde(default)]
+    pub credit: Option<Column>,
+    #[serde(default)]
+    pub negate: bool,
+    #[serde(default)]
+    pub currency_column: Option<Column>,
+    /// ISO 4217 code for rows without a currency column (or an empty one).
+    #[serde(default)]
+    pub currency: Option<String>,
+    pub payee: Column,
+    #[serde(default)]
+    pub memo: Option<Column>,
+    #[serde(default)]
+    pub decimal: Decimal,
+    #[serde(default = "default_delimiter")]
+    pub delimiter: char,
+    /// Lines of preamble before the header (or the first row).
+    #[serde(default)]
+    pub skip_rows: usize,
+    #[serde(default = "default_header")]
+    pub header: bool,
+}
+
+fn default_delimiter() -> char {
+    ','
+}
+
+fn default_header() -> bool {
+    true
+}
+
+impl Profile {
+    /// Parse and check a profile.
+    pub fn from_json(json: &str) -> Result<Self, ProfileError> {
+        let profile: Profile = serde_json::from_str(json)?;
+        profile.check()?;
+        Ok(profile)
+    }
+
+    /// Read a profile kept as a JSON file.
+    pub fn load(path: &Path) -> Result<Self, ProfileError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|source| ProfileError::Io { path: path.display().to_string(), source })?;
+        Self::from_json(&json)
+    }
+
+    /// The account rows are attributed to.
+    pub fn account(&self) -> &str {
+        self.account.as_deref().unwrap_or(&self.name)
+    }
+
+    pub(crate) fn check(&self) -> Result<(), ProfileError> {
+        let invalid = |problem: &str| ProfileError::Invalid { profile: self.name.clone(), problem: problem.into() };
+        if self.name.trim().is_empty() {
+            return Err(invalid("name is empty"));
+        }
+        if self.files.is_empty() {
+            return Err(invalid("no file patterns"));
+        }
+        match (&self.amount, &self.debit, &self.credit) {
+            (Some(_), None, None) | (None, Some(_), Some(_)) => {}
+            _ => return Err(invalid("needs either `amount` or both `debit` and `credit`")),
+        }
+        if !self.delimiter.is_ascii() {
+            return Err(invalid("delimiter must be a single ASCII character"));
+        }
+        if self.date_format.trim().is_empty() {
+            return Err(invalid("date_format is empty"));
+        }
+        let columns = [Some(&self.date), self.amount.as_ref(), self.debit.as_ref(), self.credit.as_ref()];
+        let columns = columns.into_iter().chain([self.currency_column.as_ref(), Some(&self.payee), self.memo.as_ref()]);
+        if !self.header && columns.flatten().any(|c| matches!(c, Column::Name(_))) {
+            return Err(invalid("names a column but reads no header"));
+        }
+        if let Some(code) = &self.currency {
+            if code.len() != 3 || !code.chars().all(|c| c.is_ascii_alphabetic()) {
+                return Err(invalid("currency must be a three-letter code"));
+            }
+        }
+        self.patterns()?;
+        Ok(())
+    }
+
+    pub(crate) fn patterns(&self) -> Result<Patterns, ProfileError> {
+        let pattern_error = |source| ProfileError::Pattern { profile: self.name.clone(), source };
+        let mut by_name = GlobSetBuilder::new();
+        let mut by_path = GlobSetBuilder::new();
+        for pattern in &self.files {
+            let glob = GlobBuilder::new(pattern)
+                .case_insensitive(true)
+                .literal_separator(true)
+                .build()
+                .map_err(pattern_error)?;
+            if pattern.contains('/') {
+                by_path.add(glob);
+            } else {
+                by_name.add(glob);
+            }
+        }
+        Ok(Patterns {
+            by_name: by_name.build().map_err(pattern_error)?,
+            by_path: by_path.build().map_err(pattern_error)?,
+        })
+    }
+
+    /// A date cell as a date and time, and whether the format had a time
+    /// at all (if not, midnight).
+    pub(crate) fn parse_date(&self, cell: &str) -> Option<(NaiveDateTime, bool)> {
+        let cell = cell.trim();
+        match NaiveDateTime::parse_from_str(cell, &self.date_format) {
+            Ok(at) => Some((at, true)),
+            Err(_) => {
+                let date = NaiveDate::parse_from_str(cell, &self.date_format).ok()?;
+                Some((date.and_time(Default::default()), false))
+            }
+        }
+    }
+
+    /// An amount cell as a canonical signed decimal (`-1234.50`), written
+    /// decimals kept; `None` when empty, `Err` when not a number.
+    pub(crate) fn parse_amount(&self, cell: &str) -> Result<Option<String>, ()> {
+        let cell = cell.trim();
+        if cell.is_empty() {
+            return Ok(None);
+        }
+        let parenthesized = cell.starts_with('(') && cell.ends_with(')');
+        let negative = parenthesized || cell.starts_with('-') || cell.ends_with('-');
+        // Currency symbols and codes (upper case: `1e5` is not an
+        // amount), signs, and grouping are dropped.
+        let (group, point): (&[char], char) = match self.decimal {
+            Decimal::Point => (&[',', '\'', ' ', '\u{a0}'], '.'),
+            Decimal::Comma => (&['.', '\'', ' ', '\u{a0}'], ','),
+        };
+        let mut digits = String::new();
+        for c in cell.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else if c == point {
+                digits.push('.');
+            } else if !(group.contains(&c)
+                || matches!(c, '-' | '+' | '(' | ')')
+                || c.is_ascii_uppercase()
+                || is_currency_symbol(c))
+            {
+                return Err(());
+            }
+        }
+        let (whole, fraction) = digits.split_once('.').unwrap_or((&digits, ""));
+        if whole.is_empty() && fraction.is_empty() || fraction.contains('.') {
+            return Err(());
+        }
+        let whole = whole.trim_start_matches('0');
+        let whole = if whole.is_empty() { "0" } else { whole };
+        let zero = whole == "0" && fraction.chars().all(|c| c == '0');
+        let sign = if negative != self.negate && !zero { "-" } else { "" };
+        Ok(Some(match fraction {
+            "" => format!("{sign}{whole}"),
+            fraction => format!("{sign}{whole}.{fraction}"),
+        }))
+    }
+}
+
+fn is_currency_symbol(c: char) -> bool {
+    matches!(c, '$' | '\u{a3}' | '\u{a5}' | '\u{20ac}' | '\u{20b9}' | '\u{20a9}' | '\u{20bd}' | '\u{20ba}')
+}
+
+/// A profile's file patterns, compiled.
+pub(crate) struct Patterns {
+    by_name: GlobSet,
+    by_path: GlobSet,
+}
+
+impl Patterns {
+    pub(crate) fn matches(&self, rel: &str) -> bool {
+        let name = rel.rsplit('/').next().unwrap_or(rel);
+        self.by_name.is_match(name) || self.by_path.is_match(rel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(extra: &str) -> Result<Profile, ProfileError> {
+        Profile::from_json(&format!(
+            r#"{{"name": "bank", "files": ["bank/*.csv"], "date": "Date", "date_format": "%d.%m.%Y",
+                "payee": 2{extra}}}"#
+        ))
+    }
+
+    #[test]
+    fn profiles_need_a_complete_amount_mapping() {
+        assert!(profile(r#", "amount": "Amount""#).is_ok());
+        assert!(profile(r#", "debit": "Out", "credit": "In""#).is_ok());
+        for bad in ["", r#", "debit": "Out""#, r#", "amount": 1, "credit": 2, "debit": 3"#] {
+            assert!(matches!(profile(bad), Err(ProfileError::Invalid { .. })), "{bad}");
+        }
+        assert!(matches!(profile(r#", "amount": 1, "currency": "dollars""#), Err(ProfileError::Invalid { .. })));
+        assert!(matches!(profile(r#", "amount": 1, "colour": "red""#), Err(ProfileError::Json(_))));
+
+        let p = profile(r#", "amount": "Amount""#).unwrap();
+        assert_eq!((p.date.clone(), p.payee.clone()), (Column::Name("Date".into()), Column::Index(2)));
+        assert!(p.patterns().unwrap().matches("bank/2024-01.CSV"));
+        assert!(!p.patterns().unwrap().matches("other/bank/2024-01.csv"));
+        let (date, has_time) = p.parse_date("31.01.2024").unwrap();
+        assert_eq!((date.to_string().as_str(), has_time), ("2024-01-31 00:00:00", false));
+        assert_eq!(p.parse_date("Total"), None);
+    }
+
+    #[test]
+    fn amounts_follow_the_decimal_convention() {
+        let mut p = profile(r#", "amount": "Amount""#).unwrap();
+        let amount = |p: &Profile, cell| p.parse_amount(cell).unwrap().unwrap();
+        assert_eq!(amount(&p, "1,234.50"), "1234.50");
+        assert_eq!(amount(&p, "-$4.5"), "-4.5");
+        assert_eq!(amount(&p, "(12.00)"), "-12.00");
+        assert_eq!(amount(&p, "USD 7"), "7");
+        assert_eq!(amount(&p, "-0.00"), "0.00");
+        assert_eq!(p.parse_amount("  "), Ok(None));
+        assert_eq!(p.parse_amount("1.2.3"), Err(()));
+        assert_eq!(p.parse_amount("n/a"), Err(()));
+
+        p.decimal = Decimal::Comma;
+        assert_eq!(amount(&p, "1.234,56"), "1234.56");
+        assert_eq!(amount(&p, "-1 234,56 \u{20ac}"), "-1234.56");
+        p.negate = true;
+        assert_eq!(amount(&p, "12,00"), "-12.00");
+    }
+}
@@ -0,0 +1,252 @@
+//! Reading one statement file through its profile: rows come out one at
+//! a time, each with the stable identity that makes re-imports
+//! idempotent.
+//!
+//! A row's source id is `txn:` and 128 bits of the SHA-256 of what it
+//! says — account, date, amount, currency, payee and memo, whitespace
+//! collapsed and case folded — plus how many identical rows came before it in the file
+//! (two coffees at the same place on the same day are two transactions).
+//! Two statements that overlap say the same things about the shared days,
+//! in the same multiplicity, so their rows get the same ids; file names,
+//! row positions and columns the profile does not map play no part.
+//!
+//! Rows whose date cell does not parse with the profile's format are not
+//! transactions — repeated headers, totals, "opening balance" lines — and
+//! are skipped. A file where *every* row is skipped, a mapped column that
+//! is missing, a dated row whose amount is not a number, or a file that is
+//! not CSV at all means the profile does not fit the file:
+//! [`ReadError::Misfit`], naming the file. The importer skips that file
+//! rather than failing the pass (see the crate docs).
+
+use crate::profile::{Column, Profile};
+use crate::retryable;
+use chrono::NaiveDateTime;
+use csv::ByteRecord;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::Path;
+use wkyt_core::SyncError;
+
+/// Why a statement could not be read further.
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum ReadError {
+    /// The profile does not fit the file.
+    #[error("{0}")]
+    Misfit(String),
+    #[error(transparent)]
+    Sync(#[from] SyncError),
+}
+
+/// One transaction row, mapped.
+pub(crate) struct Row {
+    /// 1-based line of the row in its file.
+    pub(crate) line: u64,
+    pub(crate) at: NaiveDateTime,
+    /// Whether the date cell carried a time of day.
+    pub(crate) has_time: bool,
+    pub(crate) amount: String,
+    pub(crate) currency: Option<String>,
+    pub(crate) payee: Option<String>,
+    pub(crate) memo: Option<String>,
+    /// The row as read: by header when the file has one.
+    pub(crate) raw: Value,
+    /// Hex row hash (see the module docs).
+    pub(crate) hash: String,
+}
+
+/// Resolved column positions.
+struct Columns {
+    date: usize,
+    amount: Option<usize>,
+    debit: Option<usize>,
+    credit: Option<usize>,
+    currency: Option<usize>,
+    payee: usize,
+    memo: Option<usize>,
+}
+
+pub(crate) struct Statement<'p> {
+    profile: &'p Profile,
+    path: String,
+    reader: csv::Reader<File>,
+    headers: Option<Vec<String>>,
+    columns: Columns,
+    record: ByteRecord,
+    /// Rows with the same content so far, by content hash.
+    seen: HashMap<String, u32>,
+    rows: usize,
+    skipped: usize,
+}
+
+impl<'p> Statement<'p> {
+    /// Open `file` (shown as `path` in errors), skip its preamble and
+    /// resolve the profile's columns against its header.
+    pub(crate) fn open(profile: &'p Profile, file: &Path, path: &str) -> Result<Self, ReadError> {
+        let reader = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .flexible(true)
+            .delimiter(profile.delimiter as u8)
+            .from_reader(File::open(file).map_err(retryable)?);
+        let mut statement = Statement {
+            profile,
+            path: path.to_string(),
+            reader,
+            headers: None,
+            columns: Columns { date: 0, amount: None, debit: None, credit: None, currency: None, payee: 0, memo: None },
+            record: ByteRecord::new(),
+            seen: HashMap::new(),
+            rows: 0,
+            skipped: 0,
+        };
+        for _ in 0..profile.skip_rows {
+            if !statement.read()? {
+                break;
+            }
+        }
+        if profile.header && statement.read()? {
+            statement.headers = Some(statement.cells());
+        }
+        statement.columns = statement.resolve()?;
+        Ok(statement)
+    }
+
+    /// Read the next record into `record`; `false` at the end.
+    fn read(&mut self) -> Result<bool, ReadError> {
+        let first = self.reader.position().byte() == 0;
+        let more = self.reader.read_byte_record(&mut self.record).map_err(|e| match e.into_kind() {
+            csv::ErrorKind::Io(e) => retryable(e).into(),
+            kind => ReadError::Misfit(format!("{}: {kind:?}", self.path)),
+        })?;
+        if first && more && self.record.get(0).is_some_and(|f| f.starts_with(b"\xef\xbb\xbf")) {
+            let position = self.record.position().cloned();
+            let fields: Vec<Vec<u8>> = self.record.iter().map(<[u8]>::to_vec).collect();
+            self.record = fields.iter().enumerate().map(|(i, f)| if i == 0 { &f[3..] } else { &f[..] }).collect();
+            self.record.set_position(position);
+        }
+        Ok(more)
+    }
+
+    fn cells(&self) -> Vec<String> {
+        self.record.iter().map(|f| String::from_utf8_lossy(f).trim().to_string()).collect()
+    }
+
+    fn resolve(&self) -> Result<Columns, ReadError> {
+        let profile = &self.profile.name;
+        let find = |column: &Column| -> Result<usize, ReadError> {
+            match (column, &self.headers) {
+                (Column::Index(i), _) => Ok(*i),
+                (Column::Name(name), Some(headers)) => headers
+                    .iter()
+                    .position(|h| h.eq_ignore_ascii_case(name.trim()))
+                    .ok_or_else(|| {
+                        ReadError::Misfit(format!("{}: no column {name:?} (profile {profile:?})", self.path))
+                    }),
+                (Column::Name(_), None) => unreachable!("checked: a profile naming columns reads a header"),
+            }
+        };
+        let p = self.profile;
+        Ok(Columns {
+            date: find(&p.date)?,
+            amount: p.amount.as_ref().map(find).transpose()?,
+            debit: p.debit.as_ref().map(find).transpose()?,
+            credit: p.credit.as_ref().map(find).transpose()?,
+            currency: p.currency_column.as_ref().map(find).transpose()?,
+            payee: find(&p.payee)?,
+            memo: p.memo.as_ref().map(find).transpose()?,
+        })
+    }
+
+    /// The next transaction row, or `None` at the end of the file.
+    pub(crate) fn next_row(&mut self) -> Result<Option<Row>, ReadError> {
+        loop {
+            if !self.read()? {
+                if self.rows == 0 && self.skipped > 0 {
+                    return Err(ReadError::Misfit(format!(
+                        "{}: no row has a date in profile {:?}'s format {:?}",
+                        self.path, self.profile.name, self.profile.date_format
+                    )));
+                }
+                return Ok(None);
+            }
+            let line = self.record.position().map_or(0, |p| p.line());
+            let cells = self.cells();
+            if cells.iter().all(String::is_empty) {
+                continue;
+            }
+            let cell = |i: usize| cells.get(i).map(String::as_str).unwrap_or("");
+            let optional = |i: Option<usize>| i.map(cell).map(collapse).filter(|s| !s.is_empty());
+            let Some((at, has_time)) = self.profile.parse_date(cell(self.columns.date)) else {
+                self.skipped += 1;
+                continue;
+            };
+
+            let bad_amount =
+                |text: &str| ReadError::Misfit(format!("{}:{line}: amount {text:?} is not a number", self.path));
+            let parse = |i: usize| self.profile.parse_amount(cell(i)).map_err(|()| bad_amount(cell(i)));
+            let amount = match self.columns.amount {
+                Some(i) => parse(i)?,
+                // Split columns, whatever sign each is written with:
+                // money out is negative, money in positive.
+                None => {
+                    let (debit, credit) = (self.columns.debit.expect("checked"), self.columns.credit.expect("checked"));
+                    let (out, into) = (parse(debit)?, parse(credit)?);
+                    let unsigned = |a: &String| a.trim_start_matches('-').to_string();
+                    match (out.as_ref().filter(|a| nonzero(a)), into.as_ref().filter(|a| nonzero(a))) {
+                        (Some(out), _) => Some(format!("-{}", unsigned(out))),
+                        (None, Some(into)) => Some(unsigned(into)),
+                        (None, None) => out.or(into),
+                    }
+                }
+            };
+            let Some(amount) = amount else {
+                // Dated, but no amount at all: a balance or note line.
+                self.skipped += 1;
+                continue;
+            };
+            let currency = optional(self.columns.currency)
+                .or_else(|| self.profile.currency.clone())
+                .map(|c| c.to_ascii_uppercase());
+            let payee = optional(Some(self.columns.payee));
+            let memo = optional(self.columns.memo);
+
+            let date = at.format(if has_time { "%Y-%m-%dT%H:%M:%S" } else { "%Y-%m-%d" }).to_string();
+            let content = [
+                self.profile.account(),
+                &date,
+                &amount,
+                currency.as_deref().unwrap_or(""),
+                &payee.as_deref().unwrap_or("").to_lowercase(),
+                &memo.as_deref().unwrap_or("").to_lowercase(),
+            ]
+            .join("\u{1f}");
+            let content = crate::sha256_hex(content.as_bytes());
+            let n = self.seen.entry(content.clone()).or_default();
+            let hash = crate::sha256_hex(format!("{content}\u{1f}{n}").as_bytes());
+            *n += 1;
+
+            let raw = match &self.headers {
+                Some(headers) => {
+                    let mut row = Map::new();
+                    for (i, value) in cells.iter().enumerate() {
+                        let key = headers.get(i).filter(|h| !h.is_empty()).cloned().unwrap_or_else(|| i.to_string());
+                        row.insert(key, Value::String(value.clone()));
+                    }
+                    Value::Object(row)
+                }
+                None => Value::from(cells.clone()),
+            };
+            self.rows += 1;
+            return Ok(Some(Row { line, at, has_time, amount, currency, payee, memo, raw, hash }));
+        }
+    }
+}
+
+fn nonzero(amount: &str) -> bool {
+    amount.chars().any(|c| c.is_ascii_digit() && c != '0')
+}
+
+/// Trimmed, inner runs of whitespace as one space.
+fn collapse(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
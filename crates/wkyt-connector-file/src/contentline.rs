@@ -0,0 +1,187 @@
+//! The content-line syntax iCalendar (RFC 5545 §3.1) and vCard (RFC 6350
+//! §3.3) share: folded lines of `group.NAME;PARAM=VALUE:value`, grouped
+//! into `BEGIN:X` / `END:X` components, with backslash-escaped TEXT.
+//!
+//! Groups (`item1.EMAIL`, as Apple exports them) are dropped: only the
+//! property name is kept. Parameters without `=` (vCard 2.1's bare
+//! `TEL;WORK`) are ignored.
+
+/// One content line: `NAME;PARAM=VALUE:value`.
+#[derive(Debug)]
+pub(crate) struct Property {
+    pub(crate) name: String,
+    pub(crate) params: Vec<(String, String)>,
+    pub(crate) value: String,
+}
+
+impl Property {
+    pub(crate) fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Every value of a parameter that may repeat or list values
+    /// (`TYPE=work,voice;TYPE=pref`), lowercased.
+    pub(crate) fn param_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = String> + 'a {
+        self.params
+            .iter()
+            .filter(move |(k, _)| k == name)
+            .flat_map(|(_, v)| v.split(','))
+            .map(|v| v.trim().to_ascii_lowercase())
+            .filter(|v| !v.is_empty())
+    }
+}
+
+/// The properties of one component (nested components excluded) and its
+/// unfolded source text.
+#[derive(Debug)]
+pub(crate) struct Component {
+    pub(crate) props: Vec<Property>,
+    pub(crate) raw: String,
+}
+
+impl Component {
+    pub(crate) fn get(&self, name: &str) -> Option<&Property> {
+        self.props.iter().find(|p| p.name == name)
+    }
+
+    pub(crate) fn all<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> + 'a {
+        self.props.iter().filter(move |p| p.name == name)
+    }
+
+    pub(crate) fn text(&self, name: &str) -> Option<String> {
+        self.get(name).map(|p| unescape(&p.value))
+    }
+}
+
+/// The `kind` components of `text` (e.g. `VEVENT`), wherever they sit.
+/// Components nested inside one (a VEVENT's VALARM) are skipped.
+pub(crate) fn components(text: &str, kind: &str) -> Vec<Component> {
+    let mut found = Vec::new();
+    let mut current: Option<Component> = None;
+    // Depth of components nested inside the current one.
+    let mut nested = 0usize;
+    for line in unfold(text) {
+        let Some(prop) = parse_line(&line) else { continue };
+        if let Some(component) = current.as_mut() {
+            component.raw.push_str(&line);
+            component.raw.push('\n');
+        }
+        match (prop.name.as_str(), current.is_some()) {
+            ("BEGIN", false) if prop.value.eq_ignore_ascii_case(kind) => {
+                current = Some(Component { props: Vec::new(), raw: format!("{line}\n") });
+            }
+            ("BEGIN", true) => nested += 1,
+            ("END", true) if nested > 0 => nested -= 1,
+            ("END", true) => {
+                if let Some(component) = current.take() {
+                    found.push(component);
+                }
+            }
+            (_, true) if nested == 0 => current.as_mut().expect("inside a component").props.push(prop),
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Join folded continuation lines (those starting with a space or tab).
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(prev)) => prev.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+/// Split one unfolded line into name, parameters and value. Colons and
+/// semicolons inside double-quoted parameter values do not delimit.
+fn parse_line(line: &str) -> Option<Property> {
+    let mut in_quotes = false;
+    let mut value_at = None;
+    let mut cuts = Vec::new();
+    for (i, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => cuts.push(i),
+            ':' if !in_quotes => {
+                value_at = Some(i);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let value_at = value_at?;
+    let head = &line[..value_at];
+    let mut bounds = cuts.into_iter().chain([value_at]);
+    let name_end = bounds.next()?;
+    let name = head[..name_end].trim();
+    let name = name.rsplit_once('.').map_or(name, |(_group, name)| name).to_ascii_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+    let mut params = Vec::new();
+    let mut start = name_end + 1;
+    for end in bounds {
+        if let Some((k, v)) = line[start..end].split_once('=') {
+            params.push((k.trim().to_ascii_uppercase(), v.trim_matches('"').to_string()));
+        }
+        start = end + 1;
+    }
+    Some(Property { name, params, value: line[value_at + 1..].to_string() })
+}
+
+/// Undo TEXT escaping: `\n`, `\,`, `\;`, `\\`.
+pub(crate) fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split a structured value (`N:Lee;Ana;;;`) at unescaped `sep`, then
+/// unescape each part.
+pub(crate) fn split_unescaped(value: &str, sep: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in value.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            c if c == sep => {
+                parts.push(unescape(&value[start..i]));
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(unescape(&value[start..]));
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn groups_repeated_params_and_structured_values() {
+        let cards = components("BEGIN:VCARD\nitem1.EMAIL;TYPE=work,INTERNET;type=pref:a@b.c\nEND:VCARD\n", "VCARD");
+        let email = cards[0].get("EMAIL").unwrap();
+        assert_eq!(email.param_values("TYPE").collect::<Vec<_>>(), ["work", "internet", "pref"]);
+
+        assert_eq!(split_unescaped(r"Lee;Ana\; Jo;;Dr.", ';'), ["Lee", "Ana; Jo", "", "Dr."]);
+    }
+}
@@ -0,0 +1,177 @@
+//! The file importer's cursor: the JSON document inside its `SyncToken`.
+//!
+//! `known` maps every selected path to what was last seen of it — mtime,
+//! size and a content hash — written grouped by directory, so a tree of
+//! many files costs each directory's path once rather than once per file:
+//!
+//! ```json
+//! { "last_mtime_ms": 1720000000000, "scanned_at_ms": 1720000005000,
+//!   "known": { "": { "a.json": [1720000000000, 12, "9f86d081884c7d65"] },
+//!              "2024/06": { "b.ics": [1719000000000, 840, "60303ae22b998861"] } } }
+//! ```
+//!
+//! The hash is the first 64 bits of the content's SHA-256 in hex: enough
+//! to tell one version of a file from the next, or to pair a vanished path
+//! with the same content at a new one, at 16 bytes a file.
+//!
+//! Older cursors hold `known` as a flat array of names, or as directories
+//! of name arrays. They still parse; their entries carry no per-file
+//! state (`None`) and are judged by the old `last_mtime_ms` watermark
+//! until next read.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// File -> source ids of the records parsed out of it.
+pub(crate) type RecordIndex = BTreeMap<String, BTreeSet<String>>;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct FileCursor {
+    /// Newest mtime read so far; only legacy entries are judged by it.
+    pub(crate) last_mtime_ms: i64,
+    /// Wall clock when the pass that wrote this cursor scanned the tree.
+    #[serde(default)]
+    pub(crate) scanned_at_ms: i64,
+    #[serde(with = "grouped")]
+    pub(crate) known: BTreeMap<String, Option<Seen>>,
+    /// Event source ids per calendar file, as of its last read. Absent
+    /// from cursors written before calendars were parsed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) events: RecordIndex,
+    /// Person, organization and membership source ids per contacts file.
+    /// Unlike events these may be shared between files.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) contacts: RecordIndex,
+    /// Link, tag and date records per Markdown note; tags are shared.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) notes: RecordIndex,
+    /// Record source ids per record file (see `FileImporter::with_records`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) records: RecordIndex,
+    /// Paths delivered in shred mode (`FileImporter::with_shred_after_import`):
+    /// to be shredded once a committed batch delivers them, and forgotten,
+    /// not tombstoned, once gone.
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub(crate) consumed: BTreeSet<String>,
+}
+
+impl FileCursor {
+    /// Drop every trace of `path` without tombstoning anything: a consumed
+    /// file, shredded, whose items the vault keeps.
+    pub(crate) fn forget(&mut self, path: &str) {
+        self.known.remove(path);
+        self.consumed.remove(path);
+        for index in [&mut self.events, &mut self.contacts, &mut self.notes, &mut self.records] {
+            index.remove(path);
+        }
+    }
+}
+
+/// What was last read of one path.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Seen {
+    pub(crate) mtime_ms: i64,
+    pub(crate) size: u64,
+    pub(crate) hash: String,
+}
+
+/// `known` as `{directory: {name: [mtime, size, hash]}}`; `""` is the root.
+mod grouped {
+    use super::*;
+
+    type Entry = (i64, u64, String);
+    /// Directory -> name -> entry, borrowing from the cursor.
+    type Tree<'a> = BTreeMap<&'a str, BTreeMap<&'a str, Option<(i64, u64, &'a str)>>>;
+
+    pub(super) fn serialize<S: Serializer>(known: &BTreeMap<String, Option<Seen>>, s: S) -> Result<S::Ok, S::Error> {
+        let mut tree = Tree::new();
+        for (path, state) in known {
+            let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+            let entry = state.as_ref().map(|s| (s.mtime_ms, s.size, s.hash.as_str()));
+            tree.entry(dir).or_default().insert(name, entry);
+        }
+        tree.serialize(s)
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Names {
+        States(BTreeMap<String, Option<Entry>>),
+        Names(BTreeSet<String>),
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Known {
+        Grouped(BTreeMap<String, Names>),
+        Flat(BTreeSet<String>),
+    }
+
+    fn join(dir: &str, name: String) -> String {
+        match dir {
+            "" => name,
+            dir => format!("{dir}/{name}"),
+        }
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<String, Option<Seen>>, D::Error> {
+        let legacy = |path| (path, None);
+        Ok(match Known::deserialize(d)? {
+            Known::Flat(names) => names.into_iter().map(legacy).collect(),
+            Known::Grouped(tree) => {
+                let mut known = BTreeMap::new();
+                for (dir, names) in tree {
+                    match names {
+                        Names::Names(names) => known.extend(names.into_iter().map(|n| legacy(join(&dir, n)))),
+                        Names::States(states) => known.extend(states.into_iter().map(|(n, entry)| {
+                            let seen = entry.map(|(mtime_ms, size, hash)| Seen { mtime_ms, size, hash });
+                            (join(&dir, n), seen)
+                        })),
+                    }
+                }
+                known
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_round_trips_grouped_and_reads_older_cursors() {
+        let seen = |mtime_ms, hash: &str| Some(Seen { mtime_ms, size: 2, hash: hash.into() });
+        let cursor = FileCursor {
+            last_mtime_ms: 7,
+            scanned_at_ms: 9,
+            known: [
+                ("a.json".to_string(), seen(1, "aa")),
+                ("x/b.ics".to_string(), seen(2, "bb")),
+                ("x/y/d.json".to_string(), None),
+            ]
+            .into(),
+            events: RecordIndex::new(),
+            contacts: RecordIndex::new(),
+            notes: RecordIndex::new(),
+            records: RecordIndex::new(),
+            consumed: BTreeSet::new(),
+        };
+        let text = serde_json::to_string(&cursor).unwrap();
+        assert_eq!(
+            text,
+            r#"{"last_mtime_ms":7,"scanned_at_ms":9,"known":{"":{"a.json":[1,2,"aa"]},"x":{"b.ics":[2,2,"bb"]},"x/y":{"d.json":null}}}"#
+        );
+        let back: FileCursor = serde_json::from_str(&text).unwrap();
+        assert_eq!(back.known, cursor.known);
+
+        for old in [
+            r#"{"last_mtime_ms": 1, "known": ["a.json", "b.ics"]}"#,
+            r#"{"last_mtime_ms": 1, "known": {"": ["a.json", "b.ics"]}}"#,
+        ] {
+            let old: FileCursor = serde_json::from_str(old).unwrap();
+            assert_eq!(old.known.keys().collect::<Vec<_>>(), ["a.json", "b.ics"]);
+            assert!(old.known.values().all(Option::is_none));
+        }
+    }
+}
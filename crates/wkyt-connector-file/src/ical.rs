@@ -0,0 +1,267 @@
+//! Minimal iCalendar (RFC 5545) reader: enough of VCALENDAR to turn each
+//! VEVENT of an exported `.ics` file into one [`ItemKind::Event`].
+//!
+//! Handled: the shared content-line syntax (see [`contentline`](crate::contentline)),
+//! DATE / DATE-TIME values in UTC, floating or TZID form, and nested
+//! components inside a VEVENT (VALARM), which are skipped. Not
+//! handled: VTIMEZONE definitions — a TZID is resolved against the IANA
+//! database by name, and a name it does not know (e.g. a Windows zone) is
+//! kept as written with the time treated as UTC for the item timestamp.
+//!
+//! Event properties follow the shape the Google Calendar connector uses
+//! (`summary`, `start.dateTime`, `attendees[].responseStatus`, ...), so an
+//! event reads the same whichever source it came from. Attendee and
+//! organizer emails are normalized as contacts' are, so the two match.
+//! An overridden occurrence carries its RECURRENCE-ID as
+//! `originalStartTime`, which is what Google derives an instance's id from.
+
+use crate::contentline::{components, Component, Property};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Offset as _, TimeZone as _, Utc};
+use serde_json::{json, Value};
+use wkyt_core::identity::normalize_email;
+use wkyt_core::{Item, ItemKind};
+
+/// The `source_id` of the event `uid` (and, for an overridden occurrence
+/// of a recurring event, `recurrence_id`) in calendar file `file`.
+pub(crate) fn event_source_id(file: &str, uid: &str, recurrence_id: Option<&str>) -> String {
+    match recurrence_id {
+        None => format!("{file}#{uid}"),
+        Some(rid) => format!("{file}#{uid}#{rid}"),
+    }
+}
+
+/// One Event item per VEVENT in `text`. Cancelled events are left out, so
+/// they are tombstoned like events removed from the file. A VEVENT without
+/// a UID (invalid, but seen in hand-written files) is keyed by its position
+/// in the file instead.
+pub fn events(connector_id: &str, file: &str, text: &str, fallback: DateTime<Utc>) -> Vec<Item> {
+    components(text, "VEVENT")
+        .into_iter()
+        .enumerate()
+        .filter(|(_, ev)| !ev.get("STATUS").is_some_and(|s| s.value.eq_ignore_ascii_case("CANCELLED")))
+        .map(|(n, ev)| event_item(connector_id, file, n, ev, fallback))
+        .collect()
+}
+
+fn event_item(connector_id: &str, file: &str, n: usize, ev: Component, fallback: DateTime<Utc>) -> Item {
+    let uid = ev.get("UID").map(|p| p.value.clone()).unwrap_or_else(|| format!("vevent-{n}"));
+    let recurrence_id = ev.get("RECURRENCE-ID").map(|p| p.value.as_str());
+    let source_id = event_source_id(file, &uid, recurrence_id);
+    let start = ev.get("DTSTART").and_then(time);
+
+    let properties = json!({
+        "summary": ev.text("SUMMARY"),
+        "description": ev.text("DESCRIPTION"),
+        "location": ev.text("LOCATION"),
+        "status": ev.get("STATUS").map(|p| p.value.to_ascii_lowercase()),
+        "start": start.as_ref().map(|t| t.json.clone()),
+        "end": ev.get("DTEND").and_then(time).map(|t| t.json),
+        "organizer": ev.get("ORGANIZER").map(|p| json!({
+            "email": normalize_email(&p.value),
+            "displayName": p.param("CN"),
+        })),
+        "attendees": ev.all("ATTENDEE").map(|p| json!({
+            "email": normalize_email(&p.value),
+            "displayName": p.param("CN"),
+            "responseStatus": p.param("PARTSTAT").map(response_status),
+            "role": p.param("ROLE"),
+        })).collect::<Vec<_>>(),
+        "recurrence": {
+            "rrule": ev.get("RRULE").map(|p| p.value.clone()),
+            "exdate": ev
+                .all("EXDATE")
+                .flat_map(|p| p.value.split(',').filter_map(|v| time_value(v, p)))
+                .map(|t| t.json)
+                .collect::<Vec<_>>(),
+        },
+        "recurringEventId": recurrence_id.map(|_| event_source_id(file, &uid, None)),
+        "originalStartTime": ev.get("RECURRENCE-ID").and_then(time).map(|t| t.json),
+        "created": ev.get("CREATED").and_then(time).and_then(|t| t.utc).map(|t| t.to_rfc3339()),
+        "updated": ev.get("LAST-MODIFIED").and_then(time).and_then(|t| t.utc).map(|t| t.to_rfc3339()),
+        "uid": uid,
+        "calendar_file": file,
+        "sha256": crate::sha256_hex(ev.raw.as_bytes()),
+    });
+
+    let timestamp = start.and_then(|t| t.utc).unwrap_or(fallback);
+    let mut item = Item::new(source_id, connector_id, ItemKind::Event, timestamp, properties);
+    item.raw_payload = Some(Value::String(ev.raw));
+    item
+}
+
+/// A DATE or DATE-TIME value: its JSON form and, when it names an instant,
+/// that instant in UTC.
+struct Time {
+    json: Value,
+    utc: Option<DateTime<Utc>>,
+}
+
+fn time(prop: &Property) -> Option<Time> {
+    time_value(&prop.value, prop)
+}
+
+/// Parse `value` with the VALUE/TZID parameters of `prop`.
+fn time_value(value: &str, prop: &Property) -> Option<Time> {
+    let value = value.trim();
+    let date_only = prop.param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE")) || value.len() == 8;
+    if date_only {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(Time {
+            json: json!({ "date": date.format("%Y-%m-%d").to_string() }),
+            utc: date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc()),
+        });
+    }
+    if let Some(utc) = value.strip_suffix('Z') {
+        let dt = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").ok()?.and_utc();
+        return Some(Time { json: json!({ "dateTime": dt.to_rfc3339() }), utc: Some(dt) });
+    }
+    let local = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?;
+    let tzid = prop.param("TZID");
+    let zoned = tzid.and_then(|name| name.parse::<chrono_tz::Tz>().ok()).map(|tz| in_zone(tz, local));
+    Some(Time {
+        json: json!({
+            "dateTime": zoned.map_or_else(|| local.format("%Y-%m-%dT%H:%M:%S").to_string(), |dt| dt.to_rfc3339()),
+            "timeZone": tzid,
+        }),
+        utc: Some(zoned.map_or_else(|| local.and_utc(), |dt| dt.with_timezone(&Utc))),
+    })
+}
+
+/// `local` in `tz`. A time repeated when clocks go back is its first
+/// occurrence; a time skipped when they go forward is read with the offset
+/// in effect before the gap, as RFC 5545 has it, which moves it forward by
+/// the gap's length (02:30 on a spring-forward night is 03:30).
+fn in_zone(tz: chrono_tz::Tz, local: NaiveDateTime) -> DateTime<chrono_tz::Tz> {
+    if let Some(dt) = tz.from_local_datetime(&local).earliest() {
+        return dt;
+    }
+    // No transition is closer than a few hours to another, so three hours
+    // earlier is before the gap.
+    let before = tz.offset_from_utc_datetime(&(local - chrono::Duration::hours(3))).fix();
+    (local - before).and_utc().with_timezone(&tz)
+}
+
+/// PARTSTAT in the Google Calendar vocabulary.
+fn response_status(partstat: &str) -> String {
+    match partstat.to_ascii_uppercase().as_str() {
+        "ACCEPTED" => "accepted".into(),
+        "DECLINED" => "declined".into(),
+        "TENTATIVE" => "tentative".into(),
+        "NEEDS-ACTION" => "needsAction".into(),
+        other => other.to_ascii_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAL: &str = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VEVENT\r\n\
+UID:weekly-1@example.com\r\n\
+SUMMARY:Team sync\\, weekly\r\n\
+DESCRIPTION:Agenda:\\n1. status\r\n\
+LOCATION:Room 4\\; floor 2\r\n\
+DTSTART;TZID=Europe/Berlin:20240902T090000\r\n\
+DTEND;TZID=Europe/Berlin:20240902T093000\r\n\
+RRULE:FREQ=WEEKLY;BYDAY=MO\r\n\
+EXDATE;TZID=Europe/Berlin:20240909T090000,20240916T090000\r\n\
+ORGANIZER;CN=\"Lee, Ana\":mailto:ana@example.com\r\n\
+ATTENDEE;CN=Bob;PARTSTAT=ACCEPTED;ROLE=REQ-PARTICIPANT:MAILTO:bob@exa\r\n mple.com\r\n\
+ATTENDEE;PARTSTAT=NEEDS-ACTION:mailto:cy@example.com\r\n\
+BEGIN:VALARM\r\n\
+ACTION:DISPLAY\r\n\
+SUMMARY:not the event summary\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:weekly-1@example.com\r\n\
+RECURRENCE-ID;TZID=Europe/Berlin:20240923T090000\r\n\
+SUMMARY:Team sync (moved)\r\n\
+DTSTART:20240923T120000Z\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:holiday\r\n\
+SUMMARY:Holiday\r\n\
+DTSTART;VALUE=DATE:20241003\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:gone\r\n\
+STATUS:CANCELLED\r\n\
+DTSTART:20241004T100000Z\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+    fn fallback() -> DateTime<Utc> {
+        DateTime::from_timestamp(0, 0).unwrap()
+    }
+
+    #[test]
+    fn vevents_become_events_with_zoned_times_people_and_recurrence() {
+        let items = events("file-import", "cal.ics", CAL, fallback());
+        let ids: Vec<_> = items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "cal.ics#weekly-1@example.com",
+                "cal.ics#weekly-1@example.com#20240923T090000",
+                "cal.ics#holiday",
+            ],
+            "cancelled events are left out"
+        );
+
+        let weekly = &items[0];
+        assert_eq!(weekly.kind, ItemKind::Event);
+        let p = &weekly.properties;
+        assert_eq!(p["summary"], "Team sync, weekly", "not the VALARM's");
+        assert_eq!(p["description"], "Agenda:\n1. status");
+        assert_eq!(p["location"], "Room 4; floor 2");
+        assert_eq!(p["start"]["dateTime"], "2024-09-02T09:00:00+02:00");
+        assert_eq!(p["start"]["timeZone"], "Europe/Berlin");
+        assert_eq!(p["end"]["dateTime"], "2024-09-02T09:30:00+02:00");
+        assert_eq!(weekly.timestamp.to_rfc3339(), "2024-09-02T07:00:00+00:00");
+        assert_eq!(p["organizer"]["email"], "ana@example.com");
+        assert_eq!(p["organizer"]["displayName"], "Lee, Ana");
+        assert_eq!(p["attendees"][0]["email"], "bob@example.com", "unfolded");
+        assert_eq!(p["attendees"][0]["responseStatus"], "accepted");
+        assert_eq!(p["attendees"][0]["role"], "REQ-PARTICIPANT");
+        assert_eq!(p["attendees"][1]["responseStatus"], "needsAction");
+        assert_eq!(p["recurrence"]["rrule"], "FREQ=WEEKLY;BYDAY=MO");
+        assert_eq!(p["recurrence"]["exdate"][1]["dateTime"], "2024-09-16T09:00:00+02:00");
+        assert!(weekly.raw_payload.as_ref().unwrap().as_str().unwrap().starts_with("BEGIN:VEVENT\n"));
+
+        let moved = &items[1].properties;
+        assert_eq!(moved["recurringEventId"], "cal.ics#weekly-1@example.com");
+        assert_eq!(moved["start"]["dateTime"], "2024-09-23T12:00:00+00:00");
+        assert_eq!(moved["originalStartTime"]["dateTime"], "2024-09-23T09:00:00+02:00");
+        assert_eq!(p["originalStartTime"], Value::Null);
+
+        let holiday = &items[2];
+        assert_eq!(holiday.properties["start"]["date"], "2024-10-03");
+        assert_eq!(holiday.timestamp.to_rfc3339(), "2024-10-03T00:00:00+00:00");
+    }
+
+    #[test]
+    fn unknown_zones_and_missing_uids_degrade_instead_of_failing() {
+        let text = "BEGIN:VEVENT\nDTSTART;TZID=W. Europe Standard Time:20240902T090000\nEND:VEVENT\n\
+                    BEGIN:VEVENT\nSUMMARY:no start\nEND:VEVENT\n";
+        let items = events("file-import", "x.ics", text, fallback());
+        assert_eq!(items[0].source_id, "x.ics#vevent-0");
+        assert_eq!(items[0].properties["start"]["dateTime"], "2024-09-02T09:00:00");
+        assert_eq!(items[0].properties["start"]["timeZone"], "W. Europe Standard Time");
+        assert_eq!(items[0].timestamp.to_rfc3339(), "2024-09-02T09:00:00+00:00");
+        assert_eq!(items[1].timestamp, fallback(), "no DTSTART: the file's mtime");
+        assert!(events("file-import", "x.ics", "not a calendar", fallback()).is_empty());
+    }
+
+    #[test]
+    fn times_in_a_dst_gap_move_forward_and_repeated_times_take_the_first() {
+        let text = "BEGIN:VEVENT\nUID:gap\nDTSTART;TZID=America/New_York:20240310T023000\nEND:VEVENT\n\
+                    BEGIN:VEVENT\nUID:overlap\nDTSTART;TZID=America/New_York:20241103T013000\nEND:VEVENT\n";
+        let items = events("file-import", "x.ics", text, fallback());
+        assert_eq!(items[0].properties["start"]["dateTime"], "2024-03-10T03:30:00-04:00");
+        assert_eq!(items[0].timestamp.to_rfc3339(), "2024-03-10T07:30:00+00:00");
+        assert_eq!(items[1].properties["start"]["dateTime"], "2024-11-03T01:30:00-04:00");
+    }
+}
@@ -1,72 +1,308 @@
-//! File-importer connector (M4): watches one local directory,
-//! non-recursively, for `.json` and `.ics` files.
+//! File-importer connector (M4): watches one local directory tree for
+//! `.json`, `.ics` and `.vcf` files, or whatever [`FileImporter::with_include`]
+//! selects. Source ids are paths relative to the root (see [`walk`] for
+//! patterns, depth and symlinks).
 //!
-//! Cursor design — why mtime alone is not enough: a file *copied into* the
-//! watch dir keeps its original (possibly old) modification time, and a
-//! *deleted* file has no mtime at all. The cursor is therefore a JSON
-//! document inside the opaque `SyncToken`:
+//! Cursor design — why a single mtime watermark is not enough: a file
+//! *copied into* the watch dir keeps its original (possibly old)
+//! modification time, a *deleted* file has no mtime at all, and a file
+//! rewritten within one mtime tick keeps the same one. The cursor is
+//! therefore a JSON document inside the opaque `SyncToken` recording, per
+//! known path, the mtime, size and a content hash last read (layout in
+//! [`cursor`]).
 //!
-//! ```json
-//! { "last_mtime_ms": 1720000000000, "known": ["a.json", "b.ics"] }
-//! ```
-//!
-//! A file is selected when its mtime is newer than `last_mtime_ms` OR its
-//! name is not in `known` (catches old-mtime copies). A name in `known`
-//! that is missing on disk becomes a [`Delta::Tombstone`]. A cursor that
-//! fails to parse yields `SyncError::ResyncRequired` — the orchestrator
-//! discards it and full-syncs, exactly the taxonomy's purpose.
+//! A file is read when its path is not in `known` (catches old-mtime
+//! copies), when its mtime or size differ from the recorded ones, or when
+//! it was last read within `RACY_WINDOW_MS` of its mtime (it may have
+//! changed again in the same tick). Reading decides: same hash, no delta
+//! — a touch costs a read, not an upsert; a new hash, an upsert. A path
+//! in `known` that is missing on disk becomes a [`Delta::Tombstone`] —
+//! unless its content turns up at a new path in the same pass, which is a
+//! rename: the old path is tombstoned, the new one upserted with
+//! `renamed_from`, and a `renamed_to` relationship links the two records,
+//! so the history under the old id stays reachable. A cursor that fails
+//! to parse yields `SyncError::ResyncRequired` — the orchestrator discards
+//! it and full-syncs, exactly the taxonomy's purpose.
 //!
 //! Batches are planned up front from *metadata only* (cheap), then file
 //! contents are read lazily one batch at a time as the stream is polled —
-//! memory stays O(batch), not O(directory). Each batch carries a cursor
-//! that is a valid resume point after that batch commits.
+//! memory stays O(batch) in inline contents, O(tree) only in paths. The cursor
+//! is checkpointed rather than attached to every batch: it is as large as
+//! the tree, so writing it per batch would make a first sync of a big tree
+//! quadratic. A checkpoint is taken once the files since the last one
+//! reach an eighth of `known` (and at least a batch), which keeps the
+//! total written linear; the last batch always carries one. Every cursor
+//! is a valid resume point after its batch commits; a pass interrupted
+//! between checkpoints re-delivers at most that stretch.
+//!
+//! `.ics` files are also read as calendars: besides the file row, each
+//! VEVENT becomes an [`ItemKind::Event`] with source id `{file}#{UID}`
+//! (see [`ical`] for what is parsed). Which events a file yielded is only
+//! known once it is read, so the cursor also records them per file
+//! (`"events": {"b.ics": ["b.ics#uid-1"]}`); an event missing from a
+//! re-exported file, or every event of a deleted one, is tombstoned.
+//!
+//! `.vcf` files are read as address books the same way: each VCARD
+//! becomes an [`ItemKind::Person`], keyed by its UID rather than the file,
+//! with its organizations as shared [`ItemKind::Organization`]s (see
+//! [`vcard`]). The cursor records them per file under `"contacts"`; a
+//! record is tombstoned once no file yields it any more. Both readers are
+//! public, for importers that find calendars and address books inside
+//! something else (a Takeout archive, say).
+//!
+//! Markdown notes (`.md`, selected by [`FileImporter::notes`] or an
+//! include pattern) are read as a notes vault: the file's item gains the
+//! note's title, front matter, headings, links, tags and dates, and its
+//! wiki links, tags and inline dates become relationships (see
+//! [`markdown`]). Link targets resolve against the files of the same pass
+//! — a note is re-read only when it changes, so a link keeps pointing
+//! where it resolved until then. The cursor records each note's records
+//! under `"notes"`, retired like contacts.
+//!
+//! Exports that are lists of records — a JSON array, or NDJSON — can be
+//! read as one item per record instead ([`FileImporter::with_records`]):
+//! each record becomes an item of the configured kind, keyed by the file
+//! and the value at a JSON pointer (see [`records`]). The cursor records
+//! them per file under `"records"`; a record missing from a re-export, or
+//! every record of a deleted file, is tombstoned. A record file that does
+//! not parse keeps its previous records (its File item says why under
+//! `records_error`), and one too large to inline is stored as chunks
+//! without being split.
+//!
+//! Photos (`.jpg`, `.heic`, `.png`, ... selected by [`FileImporter::photos`]
+//! or an include pattern) are read for their EXIF metadata: capture time —
+//! which becomes the item's timestamp in place of the mtime — camera
+//! fields and GPS coordinates (see [`photo`]). A photos importer keeps the
+//! image bytes out of the vault; [`FileImporter::with_image_content`]
+//! chunks them like any binary file, which is what other importers do.
+//!
+//! Content: every file is hashed (`sha256`) and sniffed (`mime_type`, see
+//! [`mime`]). Text up to [`MAX_FILE_BYTES`] travels inline, as before —
+//! `raw_payload`, parsed JSON under `content`. Anything else, binary or
+//! large, is streamed into the vault as content-addressed
+//! [`Delta::Chunk`]s, one chunk per batch, ahead of the batch carrying the
+//! item, which lists them in order under `chunks`; memory stays O(chunk)
+//! however large the file. A large file is read twice when it changed
+//! (once to hash, once to chunk); one rewritten in between is left for the
+//! next pass.
+//!
+//! Import-then-shred ([`FileImporter::with_shred_after_import`]) is for
+//! drop folders whose plaintext must not outlive its import: once the
+//! batch carrying a file's item has committed and been acked (see
+//! `Connector::on_commit`), the file is overwritten and unlinked, and its
+//! cursor entry is marked consumed under `"consumed"`. A consumed path
+//! that is gone is forgotten, not tombstoned — the vault copy is the
+//! authoritative one now — and one dropped again is read as new. The pass
+//! that finds it gone records that instead: an [`ItemKind::Event`]
+//! `shred:{path}` (`"action": "shredded"`, the File's id under `file`),
+//! which is what retracts whatever was derived from the file being in the
+//! folder (see `wkyt_host::EvidenceClaims`). Every
+//! batch that delivers a file then carries the cursor, so the shred
+//! follows its own commit. A batch quarantined instead shreds nothing:
+//! its dead letter is no copy to rely on. A consumed file still there on
+//! a later pass — its shred lost to a crash or a spooled pass, or its
+//! batch quarantined — is delivered again, and shredded once that
+//! delivery commits. Overwriting
+//! is best effort: copy-on-write filesystems, SSD wear levelling and
+//! backups may still hold the old blocks.
+//!
+//! Besides polling, the importer implements [`ChangeNotifier`] (see
+//! [`watch`]): filesystem notifications wake the host when the tree
+//! changes, and the pass that follows is the ordinary cursor-driven sync.
 //!
-//! Known limits, deliberate for M4: same-millisecond re-modification of a
-//! known file is missed until its next touch (mtime granularity); reads
-//! are synchronous std::fs (local files, bounded size); files larger than
-//! [`MAX_FILE_BYTES`] are indexed by metadata but their content is not
-//! ingested.
+//! Known limits, deliberate for M4: an edit that keeps both the size and
+//! the exact mtime of a file read outside its racy window is missed until
+//! its next touch; reads are synchronous std::fs (local files); chunks
+//! no longer referenced by any item stay in the vault.
+
+mod contentline;
+mod cursor;
+pub mod ical;
+mod markdown;
+mod mime;
+mod photo;
+mod records;
+pub mod vcard;
+mod walk;
+mod watch;
 
 use chrono::{DateTime, Utc};
-use futures_util::{stream, StreamExt as _};
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
-use std::path::PathBuf;
-use std::time::UNIX_EPOCH;
-use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
-
-/// Content above this size is not ingested (metadata still is).
+use cursor::{FileCursor, RecordIndex, Seen};
+use records::RecordSets;
+use futures_util::stream;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use walk::{Filter, Found, Patterns, DEFAULT_INCLUDE};
+pub use records::Records;
+use wkyt_core::batches::{self, Step};
+use wkyt_core::hash::{hex, sha256_hex};
+use wkyt_core::files::retryable;
+use wkyt_core::{
+    CommitHook, Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, Settled, SyncError, SyncToken,
+    MAX_CHUNK_BYTES,
+};
+
+/// Text up to this size travels inline in its item; larger (or binary)
+/// content goes to the vault as chunks.
 pub const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+/// Content per chunk: the most a chunk may hold.
+const CHUNK_BYTES: usize = MAX_CHUNK_BYTES;
 const DEFAULT_BATCH_SIZE: usize = 64;
-const EXTENSIONS: [&str; 2] = ["json", "ics"];
+/// A checkpoint is due once the files since the last one reach
+/// `known.len() / CHECKPOINT_FRACTION`.
+const CHECKPOINT_FRACTION: usize = 8;
+/// A file last read with an mtime this close to (or after) that pass's
+/// scan may have been rewritten within the same mtime tick, unseen; it is
+/// read again next pass. Two seconds covers the coarsest common
+/// filesystems (FAT).
+const RACY_WINDOW_MS: i64 = 2_000;
+/// Hex digits of each file's SHA-256 kept in the cursor.
+const CURSOR_HASH_HEX: usize = 16;
 
 pub struct FileImporter {
     id: String,
     dir: PathBuf,
     batch_size: usize,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    max_depth: Option<usize>,
+    records: Vec<Records>,
+    shred: bool,
+    image_content: bool,
 }
 
-#[derive(Serialize, Deserialize, Default, Clone)]
-struct FileCursor {
-    last_mtime_ms: i64,
-    known: BTreeSet<String>,
-}
-
-/// One pre-planned batch: names + mtimes only; contents read lazily.
+/// One pre-planned batch: paths + metadata only; contents read lazily.
+#[derive(Default)]
 struct PlannedBatch {
+    /// Consumed paths found gone, to record as shredded.
+    shredded: Vec<String>,
     tombstones: Vec<String>,
-    files: Vec<(String, i64)>, // (file name, mtime ms)
+    files: Vec<Found>,
+    last: bool,
+}
+
+/// The cursor as of the batches built so far in one pass.
+struct Running {
     cursor: FileCursor,
+    /// Files read or tombstoned since the last checkpoint.
+    since_checkpoint: usize,
+    /// Whether `cursor` differs from the last one handed out.
+    unsaved: bool,
+    /// Vanished paths that may have moved, by their last (size, hash):
+    /// resolved into a rename when a new path turns up with that content,
+    /// and tombstoned by the last batch otherwise.
+    moved: HashMap<(u64, String), Vec<String>>,
+    /// Every selected file of this pass, for resolving note links.
+    names: markdown::Names,
+    /// The configured record sets, compiled.
+    records: RecordSets,
 }
 
 impl FileImporter {
     pub fn new(id: impl Into<String>, dir: PathBuf) -> Self {
-        Self { id: id.into(), dir, batch_size: DEFAULT_BATCH_SIZE }
+        Self {
+            id: id.into(),
+            dir,
+            batch_size: DEFAULT_BATCH_SIZE,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_depth: None,
+            records: Vec::new(),
+            shred: false,
+            image_content: true,
+        }
+    }
+
+    /// An importer for a Markdown notes vault (Obsidian and the like):
+    /// `*.md` under `dir`, without the editor's own `.obsidian` and
+    /// `.trash` folders.
+    pub fn notes(id: impl Into<String>, dir: PathBuf) -> Self {
+        Self::new(id, dir).with_include("*.md").with_exclude(".obsidian").with_exclude(".trash")
+    }
+
+    /// An importer for photo directories: JPEG, HEIF/HEIC, PNG, TIFF and
+    /// WebP images under `dir`, read for their EXIF metadata only — the
+    /// image bytes stay out of the vault unless [`Self::with_image_content`]
+    /// asks for them.
+    pub fn photos(id: impl Into<String>, dir: PathBuf) -> Self {
+        let mut importer = Self::new(id, dir);
+        importer.include.extend(photo::PATTERNS.map(String::from));
+        importer.image_content = false;
+        importer
+    }
+
+    /// Deliver photos' image bytes as chunks, like any other binary file.
+    /// Only [`Self::photos`] leaves them out by default.
+    pub fn with_image_content(mut self) -> Self {
+        self.image_content = true;
+        self
+    }
+
+    /// Select files matching `pattern` (repeatable). Replaces the default
+    /// `*.json` / `*.ics` / `*.vcf` selection. An invalid pattern fails
+    /// `init` and every sync with `SyncError::Fatal`.
+    pub fn with_include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    /// Skip files and whole directories matching `pattern` (repeatable).
+    pub fn with_exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Read the files `records` matches as lists of records, one item per
+    /// record, rather than as one File item (repeatable; the first set
+    /// matching a file applies). See [`Records`].
+    pub fn with_records(mut self, records: Records) -> Self {
+        self.records.push(records);
+        self
+    }
+
+    /// Overwrite and unlink each file once the batch carrying its item has
+    /// committed, and let it go without a tombstone; see the module docs.
+    pub fn with_shred_after_import(mut self) -> Self {
+        self.shred = true;
+        self
+    }
+
+    /// Descend at most `depth` directory levels below the root; `0` reads
+    /// the root only. Unlimited by default.
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    fn filter(&self) -> Result<Filter, SyncError> {
+        let fatal = |e: globset::Error| SyncError::Fatal { source: Box::new(e) };
+        let mut include: Vec<&str> = if self.include.is_empty() {
+            DEFAULT_INCLUDE.to_vec()
+        } else {
+            self.include.iter().map(String::as_str).collect()
+        };
+        include.extend(self.records.iter().map(Records::pattern));
+        let include = Patterns::new(&include);
+        Ok(Filter {
+            include: include.map_err(fatal)?,
+            exclude: Patterns::new(&self.exclude).map_err(fatal)?,
+            max_depth: self.max_depth,
+        })
+    }
+
+    fn record_sets(&self) -> Result<RecordSets, SyncError> {
+        RecordSets::new(&self.records).map_err(|e| SyncError::Fatal { source: e.into() })
     }
 
     /// Metadata-only scan and batch planning. No file contents touched.
-    fn plan(&self, cursor: Option<SyncToken>) -> Result<Vec<PlannedBatch>, SyncError> {
-        let prev: FileCursor = match cursor {
+    /// Also returns the starting state for `build`: the previous cursor
+    /// minus deleted paths, and the vanished paths held back as possible
+    /// renames.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(Vec<PlannedBatch>, Running), SyncError> {
+        let mut prev: FileCursor = match cursor {
             None => FileCursor::default(),
             // Unintelligible cursor => the resume position is meaningless:
             // signal a full resync rather than guessing.
@@ -79,150 +315,445 @@ impl FileImporter {
             });
         }
 
-        // Current state of the directory (names + mtimes).
-        let mut current: Vec<(String, i64)> = Vec::new();
-        let entries = std::fs::read_dir(&self.dir).map_err(retryable)?;
-        for entry in entries {
-            let entry = entry.map_err(retryable)?;
-            let path = entry.path();
-            let Some(ext) = path.extension().and_then(|e| e.to_str()) else { continue };
-            if !EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()) || !path.is_file() {
+        // Current state of the tree (paths + mtimes + sizes).
+        let scanned_at_ms = Utc::now().timestamp_millis();
+        let records = self.record_sets()?;
+        let current = walk::scan(&self.dir, &self.filter()?).map_err(retryable)?;
+
+        let current_paths: HashSet<&str> = current.iter().map(|f| f.path.as_str()).collect();
+        // Consumed files that are gone were shredded (or taken away): the
+        // vault keeps their items, the cursor lets them go.
+        let shredded: Vec<String> =
+            prev.consumed.iter().filter(|p| !current_paths.contains(p.as_str())).cloned().collect();
+        for path in &shredded {
+            prev.forget(path);
+        }
+        let racy_floor = prev.scanned_at_ms - RACY_WINDOW_MS;
+        let mut changed: Vec<Found> = current
+            .iter()
+            .filter(|f| match prev.known.get(&f.path) {
+                // Never seen (catches copied-in files that kept an old mtime).
+                None => true,
+                // Listed by an older cursor, without per-file state.
+                Some(None) => f.mtime_ms > prev.last_mtime_ms,
+                // Touched, resized, or possibly rewritten within the mtime
+                // tick it was last read in; reading decides which.
+                Some(Some(seen)) => {
+                    seen.mtime_ms != f.mtime_ms || seen.size != f.size || seen.mtime_ms >= racy_floor
+                        // Consumed but still here: delivered again, so a
+                        // commit carrying it can shred it (see `read`).
+                        || (self.shred && prev.consumed.contains(&f.path))
+                }
+            })
+            .cloned()
+            .collect();
+        changed.sort_by(|a, b| a.mtime_ms.cmp(&b.mtime_ms).then_with(|| a.path.cmp(&b.path)));
+
+        // A vanished path whose content may have reappeared under a new
+        // one (same size) is held back; the rest are deleted outright.
+        let new_sizes: HashSet<u64> =
+            changed.iter().filter(|f| !prev.known.contains_key(&f.path)).map(|f| f.size).collect();
+        let mut deleted = Vec::new();
+        let mut moved: HashMap<(u64, String), Vec<String>> = HashMap::new();
+        for (path, seen) in &prev.known {
+            if current_paths.contains(path.as_str()) {
                 continue;
             }
-            let Some(name) = path.file_name().and_then(|n| n.to_str()) else { continue };
-            let meta = entry.metadata().map_err(retryable)?;
-            let mtime_ms = meta
-                .modified()
-                .map_err(retryable)?
-                .duration_since(UNIX_EPOCH)
-                .map(|d| d.as_millis() as i64)
-                .unwrap_or(0);
-            current.push((name.to_string(), mtime_ms));
+            match seen {
+                Some(seen) if new_sizes.contains(&seen.size) => {
+                    moved.entry((seen.size, seen.hash.clone())).or_default().push(path.clone());
+                }
+                _ => deleted.push(path.clone()),
+            }
         }
 
-        let current_names: BTreeSet<String> = current.iter().map(|(n, _)| n.clone()).collect();
-        let deleted: Vec<String> =
-            prev.known.iter().filter(|n| !current_names.contains(*n)).cloned().collect();
+        let mut start = prev;
+        let held: HashSet<&String> = moved.values().flatten().collect();
+        start.known.retain(|p, _| current_paths.contains(p.as_str()) || held.contains(p));
+        start.scanned_at_ms = scanned_at_ms;
 
-        // New or modified: newer mtime, or a name we have never seen
-        // (catches copied-in files that kept an old mtime).
-        let mut changed: Vec<(String, i64)> = current
-            .into_iter()
-            .filter(|(name, mtime)| *mtime > prev.last_mtime_ms || !prev.known.contains(name))
+        let mut plans: Vec<PlannedBatch> = shredded
+            .chunks(self.batch_size)
+            .map(|chunk| PlannedBatch { shredded: chunk.to_vec(), ..Default::default() })
             .collect();
-        changed.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
-
-        let mut plans = Vec::new();
-        // Running cursor state, advanced batch by batch so every batch's
-        // cursor is a correct resume point once that batch commits.
-        let mut running = FileCursor {
-            last_mtime_ms: prev.last_mtime_ms,
-            known: prev.known.iter().filter(|n| current_names.contains(*n)).cloned().collect(),
+        let (deleted_chunks, changed_chunks) = (deleted.chunks(self.batch_size), changed.chunks(self.batch_size));
+        plans.extend(deleted_chunks.map(|chunk| PlannedBatch { tombstones: chunk.to_vec(), ..Default::default() }));
+        plans.extend(changed_chunks.map(|chunk| PlannedBatch { files: chunk.to_vec(), ..Default::default() }));
+        if let Some(last) = plans.last_mut() {
+            last.last = true;
+        }
+        let unsaved = !deleted.is_empty() || !shredded.is_empty();
+        let names = markdown::Names::new(current.iter().map(|f| f.path.as_str()));
+        Ok((plans, Running { cursor: start, since_checkpoint: 0, unsaved, moved, names, records }))
+    }
+
+    /// Read one planned file. Unchanged content updates the cursor only;
+    /// text that fits inline is delivered into `deltas` at once; anything
+    /// else comes back as a [`Chunking`] for the stream to hand out.
+    fn read(&self, found: Found, running: &mut Running, deltas: &mut Vec<Delta>) -> Result<Option<Chunking>, SyncError> {
+        let name = found.path;
+        let path = self.dir.join(&name);
+        let size = std::fs::metadata(&path).map_err(retryable)?.len();
+        let mtime_ms = found.mtime_ms;
+        let timestamp = DateTime::<Utc>::from_timestamp_millis(mtime_ms)
+            .unwrap_or_else(Utc::now);
+
+        // Small files are read whole. Larger ones are hashed streaming and
+        // sniffed by their head; their content is read again to chunk it.
+        let (bytes, size, sha256, sniffed) = if size <= MAX_FILE_BYTES {
+            let bytes = std::fs::read(&path).map_err(retryable)?;
+            let sniffed = mime::sniff(&name, &bytes, true);
+            let (size, sha256) = (bytes.len() as u64, sha256_hex(&bytes));
+            (Some(bytes), size, sha256, sniffed)
+        } else {
+            let mut head = Vec::with_capacity(mime::SNIFF_BYTES);
+            let file = File::open(&path).map_err(retryable)?;
+            file.take(mime::SNIFF_BYTES as u64).read_to_end(&mut head).map_err(retryable)?;
+            (None, size, hash_file(&path).map_err(retryable)?, mime::sniff(&name, &head, false))
         };
+        let seen = Seen { mtime_ms, size, hash: sha256[..CURSOR_HASH_HEX].to_string() };
 
-        if !deleted.is_empty() {
-            plans.push(PlannedBatch {
-                tombstones: deleted,
-                files: Vec::new(),
-                cursor: running.clone(),
-            });
+        let previous = running.cursor.known.get(&name).cloned();
+        running.cursor.last_mtime_ms = running.cursor.last_mtime_ms.max(mtime_ms);
+        let before = previous.clone().flatten();
+        let unchanged = before.as_ref().is_some_and(|b| b.size == seen.size && b.hash == seen.hash);
+        if unchanged && !self.shred {
+            // Same content: nothing to deliver, but remember the new mtime
+            // so the file is not re-read every pass.
+            if before.is_some_and(|b| b.mtime_ms != seen.mtime_ms) {
+                running.cursor.known.insert(name, Some(seen));
+                running.unsaved = true;
+            }
+            return Ok(None);
         }
+        // In shred mode a file still here never had a committed delivery
+        // shred it: its hook was lost, its batch was quarantined, or shred
+        // mode is new. It is delivered again, and shredded after that.
+        // New content at a consumed path, though, is a new drop: what the
+        // shredded one yielded stays in the vault, untouched by this
+        // one's records.
+        if !unchanged && running.cursor.consumed.remove(&name) {
+            let known = running.cursor.known.remove(&name);
+            running.cursor.forget(&name);
+            running.cursor.known.extend(known.map(|seen| (name.clone(), seen)));
+        }
+
+        // A new path carrying a vanished path's content is that file,
+        // moved: retire the old path and link it to the new one.
+        let moved_from = match previous {
+            Some(_) => None,
+            None => running.moved.get_mut(&(seen.size, seen.hash.clone())).and_then(Vec::pop),
+        };
 
-        for chunk in changed.chunks(self.batch_size) {
-            for (name, mtime) in chunk {
-                running.known.insert(name.clone());
-                running.last_mtime_ms = running.last_mtime_ms.max(*mtime);
+        let properties = serde_json::json!({
+            "filename": path.file_name().and_then(|n| n.to_str()).unwrap_or(&name),
+            "path": name,
+            "extension": path.extension().and_then(|e| e.to_str()).unwrap_or(""),
+            "size_bytes": size,
+            "modified_ms": mtime_ms,
+            "mime_type": sniffed.mime,
+            "sha256": sha256,
+        });
+        let mut item = Item::new(&name, &self.id, ItemKind::File, timestamp, properties);
+        if photo::is_photo(&name) {
+            photo::describe(&path, &mut item);
+            if !self.image_content {
+                self.deliver(ReadFile { name, seen, item, moved_from }, running, deltas);
+                return Ok(None);
             }
-            plans.push(PlannedBatch {
-                tombstones: Vec::new(),
-                files: chunk.to_vec(),
-                cursor: running.clone(),
-            });
         }
-        Ok(plans)
-    }
-
-    /// Read contents and materialize one planned batch. Called lazily as
-    /// the stream is polled.
-    fn build(&self, plan: PlannedBatch) -> Result<DeltaBatch, SyncError> {
-        let mut deltas: Vec<Delta> =
-            plan.tombstones.into_iter().map(|source_id| Delta::Tombstone { source_id }).collect();
-
-        for (name, mtime_ms) in plan.files {
-            let path = self.dir.join(&name);
-            let meta = std::fs::metadata(&path).map_err(retryable)?;
-            let timestamp = DateTime::<Utc>::from_timestamp_millis(mtime_ms)
-                .unwrap_or_else(Utc::now);
-
-            let mut properties = serde_json::json!({
-                "filename": name,
-                "extension": path.extension().and_then(|e| e.to_str()).unwrap_or(""),
-                "size_bytes": meta.len(),
-                "modified_ms": mtime_ms,
-            });
-            let mut raw_payload = None;
-
-            if meta.len() <= MAX_FILE_BYTES {
-                let content = std::fs::read_to_string(&path).map_err(retryable)?;
-                // .json contents become structured properties when they
-                // parse; anything else rides along as the raw string.
-                if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&content) {
-                    properties["content"] = parsed;
-                }
-                raw_payload = Some(serde_json::Value::String(content));
-            } else {
-                properties["content_skipped"] = serde_json::json!("exceeds MAX_FILE_BYTES");
-            }
 
-            let mut item = Item::new(&name, &self.id, ItemKind::File, timestamp, properties);
-            item.raw_payload = raw_payload;
-            let item_id = item.id.clone();
-            deltas.push(Delta::Upsert(item));
+        let bytes = match bytes {
+            Some(bytes) if sniffed.text => bytes,
+            // Binary, or too large to inline: chunks, then the item.
+            bytes => {
+                let reader: Box<dyn Read + Send> = match bytes {
+                    Some(bytes) => Box::new(std::io::Cursor::new(bytes)),
+                    None => Box::new(File::open(&path).map_err(retryable)?),
+                };
+                let file = ReadFile { name, seen, item, moved_from };
+                return Ok(Some(Chunking { file, reader, hasher: Sha256::new(), chunks: Vec::new() }));
+            }
+        };
+        let content = String::from_utf8(bytes).expect("sniffed whole as text, so UTF-8");
+        let lower = name.to_ascii_lowercase();
+        if let Some(set) = running.records.find(&name) {
+            // Record files: the records are the content. One that does
+            // not parse keeps the records it had until it does.
+            match records::items(&self.id, &name, &content, set, timestamp) {
+                Ok(records) => deliver_records(&name, records, &mut running.cursor.records, deltas),
+                Err(e) => item.properties["records_error"] = serde_json::json!(e),
+            }
+        } else if let Ok(parsed) = serde_json::from_str::<serde_json::Value>(&content) {
+            // .json contents become structured properties when they parse;
+            // anything else rides along as the raw string.
+            item.properties["content"] = parsed;
+        }
+        if lower.ends_with(".ics") {
+            let events = ical::events(&self.id, &name, &content, timestamp);
+            deliver_records(&name, events, &mut running.cursor.events, deltas);
+        } else if lower.ends_with(".vcf") || lower.ends_with(".vcard") {
+            let people = vcard::people(&self.id, &name, &content, timestamp);
+            deliver_records(&name, people, &mut running.cursor.contacts, deltas);
+        } else if lower.ends_with(".md") || lower.ends_with(".markdown") {
+            let note = markdown::parse(&content);
+            let (note, records) = markdown::records(&self.id, &name, &note, &running.names, timestamp);
+            if let serde_json::Value::Object(properties) = &mut item.properties {
+                properties.extend(note);
+            }
+            deliver_records(&name, records, &mut running.cursor.notes, deltas);
+        }
+        item.raw_payload = Some(serde_json::Value::String(content));
+        self.deliver(ReadFile { name, seen, item, moved_from }, running, deltas);
+        Ok(None)
+    }
 
-            // Derive a claim from this file
-            let claim_source_id = format!("{}-claim", name);
-            let claim = Item::new(
-                &claim_source_id,
-                &self.id,
-                ItemKind::Claim,
-                timestamp,
-                serde_json::json!({
-                    "assertion": format!("File '{}' exists in the watched directory", name),
-                    "source": "file_importer",
-                    "epistemic_type": wkyt_core::EpistemicType::Observation,
-                })
-            );
-            let claim_id = claim.id.clone();
+    /// A chunked file's content has all been handed out: attach the chunk
+    /// list and deliver its item. A file rewritten since it was hashed is
+    /// left out (and out of the cursor), for the next pass to read anew.
+    fn finish_chunking(&self, chunking: Chunking, running: &mut Running, deltas: &mut Vec<Delta>) {
+        let Chunking { mut file, hasher, chunks, .. } = chunking;
+        if file.item.properties["sha256"] != hex(&hasher.finalize()) {
+            return;
+        }
+        file.item.properties["chunks"] = serde_json::json!(chunks);
+        self.deliver(file, running, deltas);
+    }
 
-            // Create a relationship (evidence linkage)
-            let rel_source_id = format!("{}-rel", name);
-            let rel = Item::new(
-                &rel_source_id,
+    /// Deliver a read file's item (for a moved file, after retiring the
+    /// old path and linking it to the new one) and record it as known.
+    fn deliver(&self, file: ReadFile, running: &mut Running, deltas: &mut Vec<Delta>) {
+        let ReadFile { name, seen, mut item, moved_from } = file;
+        if let Some(old) = moved_from {
+            item.properties["renamed_from"] = serde_json::json!(old);
+            let rename = Item::new(
+                format!("rename:{old}->{name}"),
                 &self.id,
                 ItemKind::Relationship,
-                timestamp,
+                item.timestamp,
                 serde_json::json!({
-                    "source": claim_id,
-                    "target": item_id,
-                    "type": "has_evidence"
-                })
+                    "source": Item::deterministic_id(&self.id, &old).to_string(),
+                    "target": item.id,
+                    "relation": "renamed_to",
+                    "sha256": item.properties["sha256"],
+                }),
             );
-            deltas.push(Delta::Upsert(claim));
-            deltas.push(Delta::Upsert(rel));
+            self.tombstone(old, running, deltas);
+            deltas.push(Delta::Upsert(rename));
         }
+        deltas.push(Delta::Upsert(item));
+        if self.shred {
+            running.cursor.consumed.insert(name.clone());
+        } else {
+            running.cursor.consumed.remove(&name);
+        }
+        running.cursor.known.insert(name, Some(seen));
+        running.since_checkpoint += 1;
+        running.unsaved = true;
+    }
 
-        Ok(DeltaBatch {
-            connector_id: self.id.clone(),
-            deltas,
-            cursor: Some(SyncToken(
-                serde_json::to_string(&plan.cursor).expect("cursor serialization is infallible"),
-            )),
-        })
+    /// Close a planned batch once its files are all read: attach the
+    /// cursor when a checkpoint is due. `None` when the batch turned out to
+    /// change nothing (files re-read with the same content).
+    fn seal(&self, mut deltas: Vec<Delta>, last: bool, running: &mut Running) -> Option<DeltaBatch> {
+        if last {
+            // Held-back paths nothing moved into were deleted after all.
+            let unclaimed: Vec<String> = running.moved.drain().flat_map(|(_, paths)| paths).collect();
+            for path in unclaimed {
+                self.tombstone(path, running, &mut deltas);
+            }
+        }
+
+        let due = self.batch_size.max(running.cursor.known.len() / CHECKPOINT_FRACTION);
+        // In shred mode every change checkpoints: a file is shredded only
+        // once a cursor marking it consumed has committed.
+        let checkpoint = running.unsaved && (last || self.shred || running.since_checkpoint >= due);
+        if deltas.is_empty() && !checkpoint {
+            return None;
+        }
+        let cursor = checkpoint.then(|| {
+            running.since_checkpoint = 0;
+            running.unsaved = false;
+            SyncToken(serde_json::to_string(&running.cursor).expect("cursor serialization is infallible"))
+        });
+        Some(DeltaBatch { connector_id: self.id.clone(), deltas, cursor })
+    }
+
+    /// Record that the consumed file `path` has left the folder. Its
+    /// items stay as they are: the vault copy is the one that counts now.
+    fn shredded(&self, path: String, running: &mut Running, deltas: &mut Vec<Delta>) {
+        let timestamp = DateTime::<Utc>::from_timestamp_millis(running.cursor.scanned_at_ms).unwrap_or_else(Utc::now);
+        let properties = serde_json::json!({
+            "action": "shredded",
+            "path": path,
+            "file": Item::deterministic_id(&self.id, &path).to_string(),
+        });
+        let record = Item::new(format!("shred:{path}"), &self.id, ItemKind::Event, timestamp, properties);
+        deltas.push(Delta::Upsert(record));
+        running.since_checkpoint += 1;
+        running.unsaved = true;
+    }
+
+    /// Retire `path`: its tombstone, and those of the records it yielded
+    /// that no other file still does.
+    fn tombstone(&self, path: String, running: &mut Running, deltas: &mut Vec<Delta>) {
+        running.cursor.known.remove(&path);
+        let events = running.cursor.events.remove(&path).unwrap_or_default();
+        let contacts = running.cursor.contacts.remove(&path).unwrap_or_default();
+        let notes = running.cursor.notes.remove(&path).unwrap_or_default();
+        let records = running.cursor.records.remove(&path).unwrap_or_default();
+        deltas.push(Delta::Tombstone { source_id: path });
+        retire(events, &running.cursor.events, deltas);
+        retire(contacts, &running.cursor.contacts, deltas);
+        retire(notes, &running.cursor.notes, deltas);
+        retire(records, &running.cursor.records, deltas);
+        running.since_checkpoint += 1;
+        running.unsaved = true;
+    }
+}
+
+/// Deliver the records parsed out of `file`, tombstoning those its
+/// previous read yielded and this one did not.
+fn deliver_records(file: &str, items: Vec<Item>, index: &mut RecordIndex, deltas: &mut Vec<Delta>) {
+    let current: BTreeSet<String> = items.iter().map(|i| i.source_id.clone()).collect();
+    let previous = index.remove(file).unwrap_or_default();
+    let dropped: Vec<String> = previous.difference(&current).cloned().collect();
+    if !current.is_empty() {
+        index.insert(file.to_string(), current);
+    }
+    retire(dropped, index, deltas);
+    deltas.extend(items.into_iter().map(Delta::Upsert));
+}
+
+/// Tombstone each of `source_ids` that no file in `index` yields any more.
+fn retire(source_ids: impl IntoIterator<Item = String>, index: &RecordIndex, deltas: &mut Vec<Delta>) {
+    for source_id in source_ids {
+        if !index.values().any(|ids| ids.contains(&source_id)) {
+            deltas.push(Delta::Tombstone { source_id });
+        }
+    }
+}
+
+/// A file read far enough to decide what to deliver: its item, minus any
+/// chunk list.
+struct ReadFile {
+    name: String,
+    seen: Seen,
+    item: Item,
+    /// The vanished path this file was moved from.
+    moved_from: Option<String>,
+}
+
+/// A file whose content is on its way to the vault, one chunk per batch;
+/// its item follows the last chunk.
+struct Chunking {
+    file: ReadFile,
+    reader: Box<dyn Read + Send>,
+    /// Hash of what was chunked, to check against the item's `sha256`.
+    hasher: Sha256,
+    chunks: Vec<String>,
+}
+
+impl Chunking {
+    /// The next chunk, or `None` once the content is exhausted.
+    fn next_chunk(&mut self) -> std::io::Result<Option<Delta>> {
+        let mut data = Vec::new();
+        (&mut self.reader).take(CHUNK_BYTES as u64).read_to_end(&mut data)?;
+        if data.is_empty() {
+            return Ok(None);
+        }
+        self.hasher.update(&data);
+        let sha256 = sha256_hex(&data);
+        self.chunks.push(sha256.clone());
+        Ok(Some(Delta::Chunk { sha256, data }))
+    }
+}
+
+/// The sync stream's batches, built lazily as it is polled: a planned
+/// batch's files are read when it is reached, and a chunked file's
+/// content goes out one chunk per batch, ahead of the batch carrying its
+/// item.
+struct Batches<'a> {
+    importer: &'a FileImporter,
+    plans: std::vec::IntoIter<PlannedBatch>,
+    running: Running,
+    building: Option<Building>,
+}
+
+/// A planned batch partway through being built.
+struct Building {
+    files: std::vec::IntoIter<Found>,
+    deltas: Vec<Delta>,
+    last: bool,
+    chunking: Option<Chunking>,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        loop {
+            if self.building.is_none() {
+                let Some(plan) = self.plans.next() else { return Ok(None) };
+                let mut deltas = Vec::new();
+                for path in plan.shredded {
+                    importer.shredded(path, &mut self.running, &mut deltas);
+                }
+                for path in plan.tombstones {
+                    importer.tombstone(path, &mut self.running, &mut deltas);
+                }
+                self.building =
+                    Some(Building { files: plan.files.into_iter(), deltas, last: plan.last, chunking: None });
+            }
+            let building = self.building.as_mut().expect("set above");
+
+            if let Some(chunking) = &mut building.chunking {
+                if let Some(chunk) = chunking.next_chunk().map_err(retryable)? {
+                    return Ok(Some(DeltaBatch { connector_id: importer.id.clone(), deltas: vec![chunk], cursor: None }));
+                }
+                let chunking = building.chunking.take().expect("matched above");
+                importer.finish_chunking(chunking, &mut self.running, &mut building.deltas);
+            } else if let Some(found) = building.files.next() {
+                building.chunking = importer.read(found, &mut self.running, &mut building.deltas)?;
+            } else {
+                let Building { deltas, last, .. } = self.building.take().expect("set above");
+                if let Some(batch) = importer.seal(deltas, last, &mut self.running) {
+                    return Ok(Some(batch));
+                }
+            }
+        }
     }
 }
 
-fn retryable(e: std::io::Error) -> SyncError {
-    SyncError::Retryable { source: Box::new(e), retry_after: None }
+/// Overwrite `path` with zeros, flush, and unlink it — if it still holds
+/// the content delivered (one dropped anew since is left to be read). A
+/// symlink is unlinked only: its target is a file of the tree in its own
+/// right.
+fn shred(path: &Path, seen: &Seen) -> std::io::Result<()> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.is_symlink() {
+        return std::fs::remove_file(path);
+    }
+    if metadata.len() != seen.size || hash_file(path)?[..CURSOR_HASH_HEX] != seen.hash {
+        return Ok(());
+    }
+    let mut file = File::options().write(true).open(path)?;
+    let zeros = vec![0u8; CHUNK_BYTES.min(seen.size as usize).max(1)];
+    let mut left = seen.size;
+    while left > 0 {
+        let n = left.min(zeros.len() as u64);
+        std::io::Write::write_all(&mut file, &zeros[..n as usize])?;
+        left -= n;
+    }
+    file.sync_all()?;
+    drop(file);
+    std::fs::remove_file(path)
+}
+
+/// SHA-256 of a file's content in hex, read in bounded chunks.
+fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hex(&hasher.finalize()))
 }
 
 #[async_trait::async_trait]
@@ -232,16 +763,54 @@ impl Connector for FileImporter {
     }
 
     async fn init(&self) -> Result<(), SyncError> {
+        self.filter()?;
+        self.record_sets()?;
         std::fs::create_dir_all(&self.dir).map_err(retryable)?;
         Ok(())
     }
 
+    /// In shred mode: once the batch commits, shred the files it
+    /// delivered. Not the rest its cursor marks consumed — their own
+    /// delivery may have been quarantined — nor any of them when this
+    /// batch is: a dead letter is no copy to rely on.
+    fn on_commit(&self, batch: &DeltaBatch) -> Option<CommitHook> {
+        if !self.shred {
+            return None;
+        }
+        let cursor: FileCursor = serde_json::from_str(&batch.cursor.as_ref()?.0).ok()?;
+        let files: Vec<(PathBuf, Seen)> = batch
+            .deltas
+            .iter()
+            .filter_map(|delta| match delta {
+                Delta::Upsert(item) if item.kind == ItemKind::File => Some(&item.source_id),
+                _ => None,
+            })
+            .filter(|path| cursor.consumed.contains(*path))
+            .filter_map(|path| Some((self.dir.join(path), cursor.known.get(path)?.clone()?)))
+            .collect();
+        // A file that is not shredded now stays consumed, and is delivered
+        // again next pass.
+        (!files.is_empty()).then(|| -> CommitHook {
+            Box::new(move |settled| {
+                if settled != Settled::Committed {
+                    return;
+                }
+                for (path, seen) in files {
+                    let _ = shred(&path, &seen);
+                }
+            })
+        })
+    }
+
     fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
         match self.plan(cursor) {
             Err(e) => Box::pin(stream::iter(vec![Err(e)])),
-            Ok(plans) => {
-                Box::pin(stream::iter(plans).map(move |plan| self.build(plan)))
-            }
+            Ok((plans, running)) => batches::stream(Batches {
+                importer: self,
+                plans: plans.into_iter(),
+                running,
+                building: None,
+            }),
         }
     }
 }
@@ -249,6 +818,7 @@ impl Connector for FileImporter {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use wkyt_core::testing::{drain, last_cursor, tombstones, upserts};
     use futures_util::StreamExt;
     use std::fs;
     use std::path::Path;
@@ -257,30 +827,6 @@ mod tests {
         FileImporter::new("file-import", dir.to_path_buf())
     }
 
-    async fn drain(c: &FileImporter, cursor: Option<SyncToken>) -> Vec<DeltaBatch> {
-        c.sync(cursor)
-            .collect::<Vec<_>>()
-            .await
-            .into_iter()
-            .collect::<Result<Vec<_>, _>>()
-            .unwrap()
-    }
-
-    fn upserts(batches: &[DeltaBatch]) -> Vec<&Item> {
-        batches
-            .iter()
-            .flat_map(|b| &b.deltas)
-            .filter_map(|d| match d {
-                Delta::Upsert(i) => Some(i),
-                _ => None,
-            })
-            .collect()
-    }
-
-    fn last_cursor(batches: &[DeltaBatch]) -> Option<SyncToken> {
-        batches.last().and_then(|b| b.cursor.clone())
-    }
-
     #[tokio::test]
     async fn full_sync_picks_up_supported_files_only() {
         let dir = tempfile::tempdir().unwrap();
@@ -291,7 +837,7 @@ mod tests {
         let c = importer(dir.path());
         let batches = drain(&c, None).await;
         let items = upserts(&batches);
-        assert_eq!(items.len(), 6); // 2 files + 2 claims + 2 relationships
+        assert_eq!(items.len(), 2, "claims are derived by the host; an empty calendar has no events");
 
         let a = items.iter().find(|i| i.source_id == "a.json").unwrap();
         assert_eq!(a.kind, ItemKind::File);
@@ -300,6 +846,8 @@ mod tests {
 
         let b = items.iter().find(|i| i.source_id == "b.ics").unwrap();
         assert!(b.raw_payload.as_ref().unwrap().as_str().unwrap().contains("VCALENDAR"));
+        assert_eq!(b.properties["mime_type"], "text/calendar");
+        assert!(b.properties.get("chunks").is_none(), "small text stays inline");
     }
 
     #[tokio::test]
@@ -326,7 +874,7 @@ mod tests {
 
         let second = drain(&c, cursor).await;
         let items = upserts(&second);
-        assert_eq!(items.len(), 3); // 1 file + 1 claim + 1 relationship
+        assert_eq!(items.len(), 1);
         assert_eq!(items[0].source_id, "old.json");
     }
 
@@ -340,20 +888,469 @@ mod tests {
 
         fs::remove_file(dir.path().join("b.json")).unwrap();
         let batches = drain(&c, cursor).await;
-        let tombs: Vec<_> = batches
+        assert_eq!(tombstones(&batches), vec!["b.json"]);
+
+        // And the tombstone is remembered: next sync is quiet.
+        assert!(drain(&c, last_cursor(&batches)).await.is_empty());
+    }
+
+    fn calendar(uids: &[&str]) -> String {
+        let events: String = uids
             .iter()
-            .flat_map(|b| &b.deltas)
-            .filter_map(|d| match d {
-                Delta::Tombstone { source_id } => Some(source_id.as_str()),
-                _ => None,
-            })
+            .map(|uid| format!("BEGIN:VEVENT\nUID:{uid}\nSUMMARY:{uid}\nDTSTART:20240902T090000Z\nEND:VEVENT\n"))
             .collect();
-        assert_eq!(tombs, vec!["b.json"]);
+        format!("BEGIN:VCALENDAR\n{events}END:VCALENDAR\n")
+    }
+
+    #[tokio::test]
+    async fn calendar_events_follow_re_exports_and_deletion() {
+        let dir = tempfile::tempdir().unwrap();
+        let cal = dir.path().join("cal.ics");
+        fs::write(&cal, calendar(&["a", "b"])).unwrap();
+        let c = importer(dir.path());
+
+        let first = drain(&c, None).await;
+        let mut ids: Vec<_> = upserts(&first).iter().map(|i| (i.kind.clone(), i.source_id.clone())).collect();
+        ids.sort_by(|x, y| x.1.cmp(&y.1));
+        assert_eq!(
+            ids,
+            [
+                (ItemKind::File, "cal.ics".to_string()),
+                (ItemKind::Event, "cal.ics#a".to_string()),
+                (ItemKind::Event, "cal.ics#b".to_string()),
+            ]
+        );
+
+        // Re-exported without "b": "b" is tombstoned, "a" upserted again.
+        std::thread::sleep(std::time::Duration::from_millis(20)); // a distinct mtime
+        fs::write(&cal, calendar(&["a", "c"])).unwrap();
+        let second = drain(&c, last_cursor(&first)).await;
+        assert_eq!(tombstones(&second), ["cal.ics#b"]);
+        assert!(upserts(&second).iter().all(|i| i.properties["sha256"].is_string()), "events too");
+        assert!(upserts(&second).iter().any(|i| i.source_id == "cal.ics#c"));
+
+        // Deleting the file takes its remaining events with it.
+        fs::remove_file(&cal).unwrap();
+        let third = drain(&c, last_cursor(&second)).await;
+        assert_eq!(tombstones(&third), ["cal.ics", "cal.ics#a", "cal.ics#c"]);
+        assert!(drain(&c, last_cursor(&third)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn contacts_shared_between_files_live_until_the_last_is_gone() {
+        let dir = tempfile::tempdir().unwrap();
+        let card = |uid: &str| format!("BEGIN:VCARD\nVERSION:4.0\nUID:{uid}\nFN:{uid}\nORG:Acme\nEND:VCARD\n");
+        fs::write(dir.path().join("all.vcf"), card("ana") + &card("bob")).unwrap();
+        fs::write(dir.path().join("ana.vcf"), card("ana")).unwrap();
+        let c = importer(dir.path());
+
+        let first = drain(&c, None).await;
+        let mut people: Vec<_> =
+            upserts(&first).iter().filter(|i| i.kind == ItemKind::Person).map(|i| i.source_id.clone()).collect();
+        people.sort();
+        people.dedup();
+        assert_eq!(people, ["vcard:ana", "vcard:bob"]);
+
+        // Still listed by ana.vcf: ana and the organization stay; bob's
+        // membership goes with him.
+        fs::remove_file(dir.path().join("all.vcf")).unwrap();
+        let second = drain(&c, last_cursor(&first)).await;
+        assert_eq!(tombstones(&second), ["all.vcf", "member:vcard:bob->org:acme", "vcard:bob"]);
+
+        fs::remove_file(dir.path().join("ana.vcf")).unwrap();
+        let third = drain(&c, last_cursor(&second)).await;
+        assert_eq!(tombstones(&third), ["ana.vcf", "member:vcard:ana->org:acme", "org:acme", "vcard:ana"]);
+    }
+
+    #[tokio::test]
+    async fn notes_link_to_each_other_and_retire_links_with_their_text() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("journal")).unwrap();
+        fs::create_dir_all(dir.path().join(".obsidian")).unwrap();
+        fs::write(dir.path().join(".obsidian/app.md"), "# settings").unwrap();
+        fs::write(dir.path().join("journal/2024-03-01.md"), "# March 1st\n").unwrap();
+        let note = dir.path().join("plan.md");
+        fs::write(&note, "---\ntags: [work]\n---\nSee [[Ideas]] on 2024-03-01 #draft\n").unwrap();
+        let c = FileImporter::notes("notes", dir.path().to_path_buf());
+
+        let first = drain(&c, None).await;
+        let items = upserts(&first);
+        assert!(items.iter().all(|i| !i.source_id.starts_with(".obsidian")));
+        let plan = items.iter().find(|i| i.source_id == "plan.md").unwrap();
+        assert_eq!(plan.properties["title"], "plan");
+        assert_eq!(plan.properties["tags"], serde_json::json!(["draft", "work"]));
+        let mut records: Vec<&str> =
+            items.iter().filter(|i| i.kind != ItemKind::File).map(|i| i.source_id.as_str()).collect();
+        records.sort();
+        assert_eq!(
+            records,
+            [
+                "date:plan.md->journal/2024-03-01.md",
+                "link:plan.md->Ideas.md",
+                "tag:draft",
+                "tag:work",
+                "tagged:plan.md->tag:draft",
+                "tagged:plan.md->tag:work",
+            ]
+        );
+        let link = items.iter().find(|i| i.source_id == "link:plan.md->Ideas.md").unwrap();
+        assert_eq!(link.properties["target"], Item::deterministic_id("notes", "Ideas.md").to_string());
+
+        // Editing away the date and a tag retires just those records;
+        // renaming keeps the shared tag alive; deleting takes the rest.
+        let sorted = |batches: &[DeltaBatch]| {
+            let mut ids: Vec<String> = tombstones(batches).into_iter().map(str::to_string).collect();
+            ids.sort();
+            ids
+        };
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&note, "---\ntags: [work]\n---\nSee [[Ideas]]\n").unwrap();
+        let second = drain(&c, last_cursor(&first)).await;
+        assert_eq!(
+            sorted(&second),
+            ["date:plan.md->journal/2024-03-01.md", "tag:draft", "tagged:plan.md->tag:draft"]
+        );
+        fs::rename(&note, dir.path().join("journal/plan.md")).unwrap();
+        let third = drain(&c, last_cursor(&second)).await;
+        assert_eq!(sorted(&third), ["link:plan.md->Ideas.md", "plan.md", "tagged:plan.md->tag:work"]);
+        fs::remove_file(dir.path().join("journal/plan.md")).unwrap();
+        let fourth = drain(&c, last_cursor(&third)).await;
+        assert_eq!(
+            sorted(&fourth),
+            ["journal/plan.md", "link:journal/plan.md->Ideas.md", "tag:work", "tagged:journal/plan.md->tag:work"]
+        );
+    }
+
+    #[tokio::test]
+    async fn record_exports_become_items_and_lose_records_they_no_longer_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = dir.path().join("orders.ndjson");
+        let line = |id: u32, total: u32| format!("{{\"order\": {{\"id\": {id}}}, \"total\": {total}}}\n");
+        fs::write(&export, line(1, 10) + &line(2, 20) + &line(3, 30)).unwrap();
+        fs::write(dir.path().join("plain.json"), "[1, 2]").unwrap();
+        let c = importer(dir.path()).with_records(Records::new("*.ndjson", ItemKind::Transaction).with_id("/order/id"));
+
+        let first = drain(&c, None).await;
+        let items = upserts(&first);
+        let mut orders: Vec<&str> =
+            items.iter().filter(|i| i.kind == ItemKind::Transaction).map(|i| i.source_id.as_str()).collect();
+        orders.sort();
+        assert_eq!(orders, ["orders.ndjson#1", "orders.ndjson#2", "orders.ndjson#3"]);
+        let file = items.iter().find(|i| i.source_id == "orders.ndjson").unwrap();
+        assert!(file.properties.get("content").is_none(), "the records are the content");
+        let plain = items.iter().find(|i| i.source_id == "plain.json").unwrap();
+        assert_eq!(plain.properties["content"], serde_json::json!([1, 2]), "other files read as before");
+
+        // Re-exported without order 2, and order 3 changed.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&export, line(1, 10) + &line(3, 35)).unwrap();
+        let second = drain(&c, last_cursor(&first)).await;
+        assert_eq!(tombstones(&second), ["orders.ndjson#2"]);
+        let third = upserts(&second).into_iter().find(|i| i.source_id == "orders.ndjson#3").unwrap();
+        assert_eq!(third.properties["total"], 35);
+
+        // Half written: the records stay until it parses again.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&export, line(1, 10) + "{\"order\": ").unwrap();
+        let broken = drain(&c, last_cursor(&second)).await;
+        assert!(tombstones(&broken).is_empty());
+        assert!(upserts(&broken)[0].properties["records_error"].as_str().unwrap().starts_with("line 2"));
+
+        fs::remove_file(&export).unwrap();
+        let last = drain(&c, last_cursor(&broken)).await;
+        let mut gone = tombstones(&last);
+        gone.sort();
+        assert_eq!(gone, ["orders.ndjson", "orders.ndjson#1", "orders.ndjson#3"]);
+    }
+
+    /// What the host does once it has settled each batch.
+    fn settle(c: &FileImporter, batches: &[DeltaBatch], settled: Settled) {
+        for hook in batches.iter().filter_map(|b| c.on_commit(b)) {
+            hook(settled);
+        }
+    }
+
+    fn commit(c: &FileImporter, batches: &[DeltaBatch]) {
+        settle(c, batches, Settled::Committed);
+    }
+
+    #[tokio::test]
+    async fn shred_mode_removes_files_only_after_commit_and_never_tombstones() {
+        let dir = tempfile::tempdir().unwrap();
+        let cal = dir.path().join("cal.ics");
+        fs::write(&cal, calendar(&["a", "b"])).unwrap();
+        fs::write(dir.path().join("n.json"), "{}").unwrap();
+        let c = importer(dir.path()).with_shred_after_import();
+
+        let first = drain(&c, None).await;
+        assert_eq!(upserts(&first).len(), 4);
+        assert!(cal.exists(), "nothing is shredded before its batch commits");
+        commit(&c, &first);
+        assert!(!cal.exists() && !dir.path().join("n.json").exists());
+
+        // Gone, but consumed: no tombstones, only a record of each shred,
+        // once.
+        let second = drain(&c, last_cursor(&first)).await;
+        assert!(tombstones(&second).is_empty());
+        let shreds = upserts(&second);
+        assert_eq!(shreds.iter().map(|i| i.source_id.as_str()).collect::<Vec<_>>(), ["shred:cal.ics", "shred:n.json"]);
+        assert_eq!(shreds[0].properties["action"], "shredded");
+        assert_eq!(shreds[0].properties["file"], Item::deterministic_id("file-import", "cal.ics").to_string());
+        assert!(drain(&c, last_cursor(&second)).await.is_empty());
+
+        // Dropped again with other events: new records, the old ones kept.
+        fs::write(&cal, calendar(&["c"])).unwrap();
+        let third = drain(&c, last_cursor(&first)).await;
+        assert!(tombstones(&third).is_empty());
+        assert!(upserts(&third).iter().any(|i| i.source_id == "cal.ics#c"));
+
+        // A lost hook: the next pass delivers the file once more.
+        let cursor = last_cursor(&third);
+        let fourth = drain(&c, cursor.clone()).await;
+        assert!(upserts(&fourth).iter().any(|i| i.source_id == "cal.ics"));
+        assert!(tombstones(&fourth).is_empty());
+        assert!(cal.exists());
+        // Replaced before the commit: the new content is not shredded.
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(&cal, calendar(&["d"])).unwrap();
+        commit(&c, &fourth);
+        assert!(cal.exists());
+        let fifth = drain(&c, cursor).await;
+        assert!(tombstones(&fifth).is_empty());
+        commit(&c, &fifth);
+        assert!(!cal.exists());
+    }
+
+    #[tokio::test]
+    async fn shred_mode_keeps_files_whose_batch_was_quarantined() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b) = (dir.path().join("a.json"), dir.path().join("b.json"));
+        fs::write(&a, "{}").unwrap();
+        let mut c = importer(dir.path()).with_shred_after_import();
+        c.batch_size = 1;
+
+        let first = drain(&c, None).await;
+        settle(&c, &first, Settled::Quarantined);
+        assert!(a.exists(), "a dead letter is not a copy to shred against");
+
+        // A later commit shreds only what it delivered itself; the
+        // quarantined file is delivered again, and shredded once that commits.
+        fs::write(&b, "{}").unwrap();
+        let second = drain(&c, last_cursor(&first)).await;
+        let delivered: Vec<&str> = upserts(&second).iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(delivered, ["a.json", "b.json"]);
+        let (redelivered, fresh) = second.split_at(1);
+        settle(&c, redelivered, Settled::Quarantined);
+        commit(&c, fresh);
+        assert!(a.exists() && !b.exists());
+        commit(&c, redelivered);
+        assert!(!a.exists());
+    }
+
+    fn set_mtime(path: &Path, mtime: std::time::SystemTime) {
+        fs::File::options().write(true).open(path).unwrap().set_modified(mtime).unwrap();
+    }
+
+    #[tokio::test]
+    async fn edits_are_found_by_content_whatever_the_mtime_says() {
+        let dir = tempfile::tempdir().unwrap();
+        let a = dir.path().join("a.json");
+        fs::write(&a, r#"{"v": 1}"#).unwrap();
+        let c = importer(dir.path());
+        let first = drain(&c, None).await;
+        let mtime = fs::metadata(&a).unwrap().modified().unwrap();
+        assert_eq!(upserts(&first)[0].properties["sha256"].as_str().unwrap().len(), 64);
+
+        // Rewritten within the same mtime tick, same size: the file was
+        // read moments before, so it is read again and the edit is seen.
+        fs::write(&a, r#"{"v": 2}"#).unwrap();
+        set_mtime(&a, mtime);
+        let second = drain(&c, last_cursor(&first)).await;
+        assert_eq!(upserts(&second)[0].properties["content"]["v"], 2);
+
+        // Touched without a change: read, but nothing delivered — only
+        // the cursor moves, so the next pass is quiet.
+        set_mtime(&a, mtime + std::time::Duration::from_secs(60));
+        let touched = drain(&c, last_cursor(&second)).await;
+        assert!(upserts(&touched).is_empty() && tombstones(&touched).is_empty());
+        assert!(last_cursor(&touched).is_some());
+        assert!(drain(&c, last_cursor(&touched)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn renames_keep_a_link_to_the_old_record() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.json"), r#"{"v": 1}"#).unwrap();
+        fs::write(dir.path().join("gone.json"), r#"{"v": 2}"#).unwrap();
+        let c = importer(dir.path());
+        let cursor = last_cursor(&drain(&c, None).await);
+
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::rename(dir.path().join("a.json"), dir.path().join("sub/b.json")).unwrap();
+        fs::remove_file(dir.path().join("gone.json")).unwrap();
+        fs::write(dir.path().join("new.json"), r#"{"v": 3}"#).unwrap(); // same size, other content
+        let batches = drain(&c, cursor).await;
+
+        let mut tombs = tombstones(&batches);
+        tombs.sort();
+        assert_eq!(tombs, ["a.json", "gone.json"]);
+        let items = upserts(&batches);
+        let moved = items.iter().find(|i| i.source_id == "sub/b.json").unwrap();
+        assert_eq!(moved.properties["renamed_from"], "a.json");
+        let link = items.iter().find(|i| i.kind == ItemKind::Relationship).unwrap();
+        assert_eq!(link.properties["relation"], "renamed_to");
+        assert_eq!(link.properties["source"], Item::deterministic_id("file-import", "a.json").to_string());
+        assert_eq!(link.properties["target"], moved.id.as_str());
+        let fresh = items.iter().find(|i| i.source_id == "new.json").unwrap();
+        assert!(fresh.properties.get("renamed_from").is_none());
 
-        // And the tombstone is remembered: next sync is quiet.
         assert!(drain(&c, last_cursor(&batches)).await.is_empty());
     }
 
+    #[tokio::test]
+    async fn binary_and_large_files_arrive_as_chunks_ahead_of_their_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let png = [&b"\x89PNG\r\n\x1a\n"[..], &[0; 100]].concat();
+        fs::write(dir.path().join("photo.png"), &png).unwrap();
+        fs::write(dir.path().join("latin1.txt"), b"caf\xe9").unwrap(); // not UTF-8
+        let big: Vec<u8> = (0..MAX_FILE_BYTES + 10).map(|i| b'a' + (i % 26) as u8).collect();
+        fs::write(dir.path().join("big.log"), &big).unwrap();
+        let c = importer(dir.path()).with_include("*");
+        let batches = drain(&c, None).await;
+
+        // Chunks ride alone and without a cursor, before any item that
+        // lists them.
+        let mut store: HashMap<String, Vec<u8>> = HashMap::new();
+        for batch in &batches {
+            for delta in &batch.deltas {
+                match delta {
+                    Delta::Chunk { sha256, data } => {
+                        assert!(batch.deltas.len() == 1 && batch.cursor.is_none());
+                        assert!(data.len() <= MAX_CHUNK_BYTES);
+                        assert_eq!(&sha256_hex(data), sha256);
+                        store.insert(sha256.clone(), data.clone());
+                    }
+                    Delta::Upsert(item) => {
+                        let chunks = item.properties["chunks"].as_array().cloned().unwrap_or_default();
+                        assert!(chunks.iter().all(|h| store.contains_key(h.as_str().unwrap())));
+                    }
+                    Delta::Tombstone { .. } => {}
+                }
+            }
+        }
+        let items = upserts(&batches);
+        let file = |source_id: &str| *items.iter().find(|i| i.source_id == source_id).unwrap();
+        let content = |item: &Item| -> Vec<u8> {
+            let chunks = item.properties["chunks"].as_array().unwrap();
+            chunks.iter().flat_map(|h| store[h.as_str().unwrap()].clone()).collect()
+        };
+
+        let log = file("big.log");
+        assert_eq!(log.properties["mime_type"], "text/plain");
+        assert_eq!(log.properties["chunks"].as_array().unwrap().len(), 5);
+        assert_eq!(log.properties["sha256"], sha256_hex(&big));
+        assert!(log.raw_payload.is_none());
+        assert_eq!(content(log), big);
+        assert_eq!(file("photo.png").properties["mime_type"], "image/png");
+        assert_eq!(content(file("photo.png")), png);
+        assert_eq!(file("latin1.txt").properties["mime_type"], "application/octet-stream");
+        assert_eq!(content(file("latin1.txt")), b"caf\xe9");
+
+        assert!(drain(&c, last_cursor(&batches)).await.is_empty());
+    }
+
+    /// A JPEG holding nothing but an EXIF block with `fields`.
+    fn jpeg(fields: &[exif::Field]) -> Vec<u8> {
+        let mut writer = exif::experimental::Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let app1 = [&b"Exif\0\0"[..], tiff.get_ref()].concat();
+        let length = (app1.len() + 2) as u16;
+        [&[0xff, 0xd8, 0xff, 0xe1][..], &length.to_be_bytes(), &app1, &[0xff, 0xd9]].concat()
+    }
+
+    #[tokio::test]
+    async fn photos_carry_capture_time_camera_and_location_without_their_bytes() {
+        use exif::{Field, In, Rational, Tag, Value};
+        let field = |tag, value| Field { tag, ifd_num: In::PRIMARY, value };
+        let ascii = |text: &str| Value::Ascii(vec![text.as_bytes().to_vec()]);
+        let rationals = |values: &[(u32, u32)]| {
+            Value::Rational(values.iter().map(|&(num, denom)| Rational { num, denom }).collect())
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let beach = jpeg(&[
+            field(Tag::Make, ascii("Fujifilm")),
+            field(Tag::Model, ascii("X100V")),
+            field(Tag::DateTimeOriginal, ascii("2024:07:14 18:30:05")),
+            field(Tag::OffsetTimeOriginal, ascii("+02:00")),
+            field(Tag::ExposureTime, rationals(&[(1, 250)])),
+            field(Tag::FNumber, rationals(&[(56, 10)])),
+            field(Tag::PhotographicSensitivity, Value::Short(vec![160])),
+            field(Tag::GPSLatitudeRef, ascii("N")),
+            field(Tag::GPSLatitude, rationals(&[(43, 1), (17, 1), (2394, 100)])),
+            field(Tag::GPSLongitudeRef, ascii("W")),
+            field(Tag::GPSLongitude, rationals(&[(2, 1), (0, 1), (0, 1)])),
+            field(Tag::GPSAltitudeRef, Value::Byte(vec![0])),
+            field(Tag::GPSAltitude, rationals(&[(12, 1)])),
+        ]);
+        fs::write(dir.path().join("Beach.JPG"), &beach).unwrap();
+        // No offset: the GPS clock (UTC) dates it.
+        let street = jpeg(&[
+            field(Tag::DateTimeOriginal, ascii("2024:07:15 09:00:00")),
+            field(Tag::GPSDateStamp, ascii("2024:07:15")),
+            field(Tag::GPSTimeStamp, rationals(&[(7, 1), (0, 1), (1, 1)])),
+        ]);
+        fs::write(dir.path().join("street.jpeg"), &street).unwrap();
+        fs::write(dir.path().join("scan.png"), b"\x89PNG\r\n\x1a\n").unwrap(); // no EXIF at all
+        fs::write(dir.path().join("notes.json"), "{}").unwrap();
+        set_mtime(&dir.path().join("street.jpeg"), std::time::UNIX_EPOCH);
+
+        let c = FileImporter::photos("photos", dir.path().to_path_buf());
+        let batches = drain(&c, None).await;
+        assert!(batches.iter().flat_map(|b| &b.deltas).all(|d| matches!(d, Delta::Upsert(_))), "no image bytes");
+        let items = upserts(&batches);
+        let ids: BTreeSet<&str> = items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(ids, BTreeSet::from(["Beach.JPG", "scan.png", "street.jpeg"]));
+        let photo = |source_id: &str| *items.iter().find(|i| i.source_id == source_id).unwrap();
+
+        let beach = photo("Beach.JPG");
+        assert_eq!(beach.timestamp.to_rfc3339(), "2024-07-14T16:30:05+00:00");
+        assert_eq!(beach.properties["captured_at"], "2024-07-14T18:30:05+02:00");
+        assert_eq!(beach.properties["camera"]["make"], "Fujifilm");
+        assert_eq!(beach.properties["camera"]["exposure_time"], "1/250");
+        assert_eq!(beach.properties["camera"]["f_number"], 5.6);
+        assert_eq!(beach.properties["camera"]["iso"], 160);
+        let location = &beach.properties["location"];
+        assert!((location["latitude"].as_f64().unwrap() - 43.2899833).abs() < 1e-6);
+        assert_eq!(location["longitude"], -2.0);
+        assert_eq!(location["altitude_m"], 12.0);
+        assert!(beach.properties.get("chunks").is_none() && beach.raw_payload.is_none());
+
+        let street = photo("street.jpeg");
+        assert_eq!(street.properties["captured_at"], "2024-07-15T09:00:00");
+        assert_eq!(street.timestamp.to_rfc3339(), "2024-07-15T07:00:01+00:00");
+        assert!(street.properties.get("location").is_none());
+        assert!(photo("scan.png").properties.get("captured_at").is_none());
+
+        // Asked for, the bytes come as chunks as for any binary file.
+        let c = FileImporter::photos("photos", dir.path().to_path_buf()).with_image_content();
+        let batches = drain(&c, None).await;
+        let chunked = upserts(&batches).into_iter().filter(|i| i.properties.get("chunks").is_some()).count();
+        assert_eq!(chunked, 3);
+    }
+
+    #[tokio::test]
+    async fn cursors_from_before_calendar_parsing_still_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.json"), "{}").unwrap();
+        let c = importer(dir.path());
+        let old = SyncToken(r#"{"last_mtime_ms": 0, "known": []}"#.into());
+        assert_eq!(upserts(&drain(&c, Some(old)).await).len(), 1);
+    }
+
     #[tokio::test]
     async fn malformed_cursor_demands_full_resync() {
         let dir = tempfile::tempdir().unwrap();
@@ -375,17 +1372,59 @@ mod tests {
         c.batch_size = 4;
 
         let batches = drain(&c, None).await;
-        // batch_size is 4. For 10 files, each file generates 3 items (File, Claim, Rel).
-        // 10 files means 10 iterations * 3 = 30 items.
-        // wait, we changed `deltas.push(claim); deltas.push(rel);` inside the loop.
-        // It pushes 3 items per file. The loop chunk size is `batch_size = 4` files.
         assert_eq!(batches.len(), 3, "10 files / batch_size 4 = 3 batches");
         assert!(batches.iter().all(|b| b.cursor.is_some()), "every batch is a resume point");
-        assert_eq!(upserts(&batches).len(), 30);
+        assert_eq!(upserts(&batches).len(), 10);
 
         // Resuming from the FIRST batch's cursor re-delivers only the rest.
         let resumed = drain(&c, batches[0].cursor.clone()).await;
-        assert_eq!(upserts(&resumed).len(), 18, "first 4 files already committed; 6 remain (6 * 3 = 18)");
+        assert_eq!(upserts(&resumed).len(), 6, "first 4 files already committed");
+    }
+
+    #[tokio::test]
+    async fn nested_trees_are_walked_with_relative_source_ids() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("2024/06")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join("2024/06/a.json"), "{}").unwrap();
+        fs::write(dir.path().join(".git/config.json"), "{}").unwrap();
+        fs::write(dir.path().join("b.md"), "# b").unwrap();
+        let c = importer(dir.path()).with_include("*.json").with_include("*.md").with_exclude(".git");
+
+        let first = drain(&c, None).await;
+        let mut ids: Vec<_> = upserts(&first).iter().map(|i| i.source_id.as_str()).collect();
+        ids.sort();
+        assert_eq!(ids, ["2024/06/a.json", "b.md"]);
+        let a = upserts(&first).into_iter().find(|i| i.source_id == "2024/06/a.json").unwrap();
+        assert_eq!((&a.properties["filename"], &a.properties["path"]), (&"a.json".into(), &"2024/06/a.json".into()));
+
+        fs::remove_dir_all(dir.path().join("2024")).unwrap();
+        assert_eq!(tombstones(&drain(&c, last_cursor(&first)).await), ["2024/06/a.json"]);
+
+        let bad = importer(dir.path()).with_include("a[");
+        assert!(matches!(bad.init().await, Err(SyncError::Fatal { .. })));
+    }
+
+    #[tokio::test]
+    async fn big_first_syncs_checkpoint_less_often_as_the_tree_grows() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..40 {
+            fs::write(dir.path().join(format!("f{i:02}.json")), "{}").unwrap();
+        }
+        let mut c = importer(dir.path());
+        c.batch_size = 1;
+
+        let batches = drain(&c, None).await;
+        let checkpoints: Vec<usize> =
+            batches.iter().enumerate().filter(|(_, b)| b.cursor.is_some()).map(|(i, _)| i).collect();
+        assert_eq!(batches.len(), 40);
+        assert!(checkpoints.len() < 40, "not every batch carries the whole tree");
+        assert_eq!(checkpoints[..8], [0, 1, 2, 3, 4, 5, 6, 7], "small trees still checkpoint every batch");
+        assert_eq!(checkpoints.last(), Some(&39), "the last batch always does");
+
+        // Any checkpoint is a resume point for exactly the files after it.
+        let resumed = drain(&c, batches[checkpoints[10]].cursor.clone()).await;
+        assert_eq!(upserts(&resumed).len(), 40 - checkpoints[10] - 1);
     }
 
     #[tokio::test]
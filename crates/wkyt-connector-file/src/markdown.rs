@@ -0,0 +1,410 @@
+//! Markdown notes, Obsidian-style: a `.md` file's File item is the note.
+//! Its YAML front matter becomes `frontmatter`, its ATX headings
+//! `headings` (`{level, text, line}`, in order), and alongside `title`
+//! (front matter `title`, else the first level-1 heading, else the file
+//! name) come what it points at — `links`, `tags` and `dates` — each of
+//! which is also a record of its own:
+//!
+//! - `[[Target]]`, `[[Target#Heading|alias]]`: a relationship to the
+//!   target note, `links_to` (`embeds` for `![[...]]`), source id
+//!   `link:{file}->{target}` (`embed:...`).
+//! - `#tag`, `#nested/tag`, and front matter `tags`: an
+//!   `ItemKind::Other("tag")` item `tag:{tag}` (shared by every note using
+//!   it, case folded), and a `tagged` relationship to it.
+//! - An inline date (`2024-01-05`): a `mentions_date` relationship to that
+//!   day's daily note, the note a `[[2024-01-05]]` link would reach.
+//!
+//! Targets resolve the way Obsidian resolves them (see [`Names`]): a link
+//! points at the note's deterministic id whether or not the note exists
+//! yet, so a link written before its target stays valid once the target
+//! is created. Links, tags and dates inside code blocks and code spans
+//! are not parsed.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use wkyt_core::{Item, ItemKind};
+
+/// What a note says, beyond its text.
+#[derive(Default)]
+pub(crate) struct Note {
+    pub(crate) frontmatter: Option<Value>,
+    pub(crate) headings: Vec<Value>,
+    pub(crate) links: Vec<Link>,
+    pub(crate) tags: BTreeSet<String>,
+    pub(crate) dates: BTreeSet<NaiveDate>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Link {
+    /// As written, without heading or alias: `Folder/Note`.
+    pub(crate) target: String,
+    pub(crate) heading: Option<String>,
+    pub(crate) alias: Option<String>,
+    pub(crate) embed: bool,
+}
+
+/// Every selected file by its lowercased name, to resolve link targets.
+#[derive(Default)]
+pub(crate) struct Names(HashMap<String, Vec<String>>);
+
+impl Names {
+    pub(crate) fn new<'a>(paths: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut names: HashMap<String, Vec<String>> = HashMap::new();
+        for path in paths {
+            let name = path.rsplit('/').next().unwrap_or(path).to_lowercase();
+            names.entry(name).or_default().push(path.to_string());
+        }
+        Self(names)
+    }
+
+    /// The path `[[target]]` in `from` points at. A target without an
+    /// extension is a note (`.md`). Several files of that name: a path
+    /// suffix in the target picks among them, then the one in `from`'s
+    /// directory, then the shortest path. None: where a new note of that
+    /// name would be created, at the root (or at the target's own path).
+    pub(crate) fn resolve(&self, from: &str, target: &str) -> String {
+        let target = target.trim().trim_start_matches('/');
+        let file = target.rsplit('/').next().unwrap_or(target);
+        let has_extension = file.rsplit_once('.').is_some_and(|(stem, ext)| {
+            !stem.is_empty() && !ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+        let target = if has_extension { target.to_string() } else { format!("{target}.md") };
+        let name = target.rsplit('/').next().unwrap_or(&target).to_lowercase();
+        let lower = target.to_lowercase();
+        let dir = from.rsplit_once('/').map_or("", |(dir, _)| dir);
+
+        let mut candidates: Vec<&String> = self
+            .0
+            .get(&name)
+            .into_iter()
+            .flatten()
+            .filter(|path| {
+                let path = path.to_lowercase();
+                path == lower || path.ends_with(&format!("/{lower}"))
+            })
+            .collect();
+        candidates.sort_by_key(|path| {
+            let here = path.rsplit_once('/').map_or("", |(d, _)| d) == dir;
+            (!here, path.len(), path.to_string())
+        });
+        candidates.first().map_or(target, |path| path.to_string())
+    }
+}
+
+/// Parse a note's front matter, headings, links, tags and dates.
+pub(crate) fn parse(text: &str) -> Note {
+    let mut note = Note::default();
+    let mut lines = text.lines().enumerate().peekable();
+
+    // Front matter: `---` on the first line, up to the next `---` / `...`.
+    if text.starts_with("---") && lines.peek().is_some_and(|(_, l)| l.trim_end() == "---") {
+        lines.next();
+        let mut yaml = String::new();
+        let mut closed = false;
+        for (_, line) in lines.by_ref() {
+            if matches!(line.trim_end(), "---" | "...") {
+                closed = true;
+                break;
+            }
+            yaml.push_str(line);
+            yaml.push('\n');
+        }
+        if closed {
+            // Unparseable YAML is kept out of the properties, not fatal.
+            note.frontmatter = serde_yaml::from_str::<Value>(&yaml).ok().filter(|v| !v.is_null());
+        } else {
+            // No closing fence: not front matter after all.
+            lines = text.lines().enumerate().peekable();
+        }
+    }
+    if let Some(tags) = note.frontmatter.as_ref().and_then(|f| f.get("tags")) {
+        let listed = match tags {
+            Value::Array(tags) => tags.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+            Value::String(tags) => tags.split([',', ' ']).map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+        note.tags.extend(listed.iter().map(|t| t.trim().trim_start_matches('#')).filter(|t| !t.is_empty()).map(fold));
+    }
+
+    let mut fence: Option<&str> = None;
+    for (n, line) in lines {
+        let trimmed = line.trim_start();
+        if let Some(open) = fence {
+            if trimmed.starts_with(open) {
+                fence = None;
+            }
+            continue;
+        }
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            fence = Some(&trimmed[..3]);
+            continue;
+        }
+        let level = trimmed.bytes().take_while(|&b| b == b'#').count();
+        let after = &trimmed[level..];
+        if (1..=6).contains(&level) && (after.is_empty() || after.starts_with([' ', '\t'])) {
+            let text = after.trim().trim_end_matches('#').trim();
+            note.headings.push(json!({ "level": level, "text": text, "line": n + 1 }));
+            scan_inline(text, &mut note);
+            continue;
+        }
+        scan_inline(line, &mut note);
+    }
+    note
+}
+
+/// Links, tags and dates in one line of text, code spans skipped.
+fn scan_inline(line: &str, note: &mut Note) {
+    // Code spans out, then wiki links out (their text is neither tag nor
+    // date), leaving the plain text.
+    let mut plain = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('`') {
+        plain.push_str(&rest[..start]);
+        let ticks = rest[start..].bytes().take_while(|&b| b == b'`').count();
+        let close = "`".repeat(ticks);
+        match rest[start + ticks..].find(&close) {
+            Some(end) => rest = &rest[start + ticks + end + ticks..],
+            None => {
+                rest = &rest[start + ticks..];
+                break;
+            }
+        }
+        plain.push(' ');
+    }
+    plain.push_str(rest);
+
+    let mut text = String::new();
+    let mut rest = plain.as_str();
+    while let Some(start) = rest.find("[[") {
+        let Some(len) = rest[start + 2..].find("]]") else { break };
+        let embed = rest[..start].ends_with('!');
+        text.push_str(&rest[..start - usize::from(embed)]);
+        text.push(' ');
+        if let Some(link) = link(&rest[start + 2..start + 2 + len], embed) {
+            if !note.links.contains(&link) {
+                note.links.push(link);
+            }
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    text.push_str(rest);
+
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        let after_boundary = i == 0 || chars[i - 1].is_whitespace() || matches!(chars[i - 1], '(' | '[' | ',');
+        if c == '#' && after_boundary {
+            let tag: String = chars[i + 1..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                .collect();
+            let tag = tag.trim_end_matches('/');
+            // `#1` is an issue number, not a tag.
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                note.tags.insert(fold(tag));
+            }
+        }
+        let digit_before = i > 0 && chars[i - 1].is_ascii_digit();
+        if c.is_ascii_digit() && !digit_before && i + 10 <= chars.len() {
+            let candidate: String = chars[i..i + 10].iter().collect();
+            let digit_after = chars.get(i + 10).is_some_and(char::is_ascii_digit);
+            if !digit_after {
+                if let Ok(date) = NaiveDate::parse_from_str(&candidate, "%Y-%m-%d") {
+                    if candidate.as_bytes()[4] == b'-' && candidate.as_bytes()[7] == b'-' {
+                        note.dates.insert(date);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The inside of `[[...]]`.
+fn link(inner: &str, embed: bool) -> Option<Link> {
+    let (target, alias) = match inner.split_once('|') {
+        Some((target, alias)) => (target, Some(alias.trim().to_string()).filter(|a| !a.is_empty())),
+        None => (inner, None),
+    };
+    let (target, heading) = match target.split_once('#') {
+        Some((target, heading)) => (target, Some(heading.trim().to_string()).filter(|h| !h.is_empty())),
+        None => (target, None),
+    };
+    // `[[#Heading]]` is a link within the note itself.
+    let target = target.trim();
+    (!target.is_empty()).then(|| Link { target: target.to_string(), heading, alias, embed })
+}
+
+fn fold(tag: &str) -> String {
+    tag.to_lowercase()
+}
+
+/// The note's own properties (merged into its File item) and its
+/// records: relationships to the notes it links to, to the tags it uses
+/// (and those tags), and to the daily notes of the dates it mentions.
+pub(crate) fn records(
+    connector_id: &str,
+    file: &str,
+    note: &Note,
+    names: &Names,
+    timestamp: DateTime<Utc>,
+) -> (Map<String, Value>, Vec<Item>) {
+    let note_id = Item::deterministic_id(connector_id, file).to_string();
+    let relationship = |source_id: String, target_source_id: &str, relation: &str, extra: Value| {
+        let mut properties = json!({
+            "source": note_id,
+            "target": Item::deterministic_id(connector_id, target_source_id).to_string(),
+            "relation": relation,
+        });
+        if let (Value::Object(properties), Value::Object(extra)) = (&mut properties, extra) {
+            properties.extend(extra);
+        }
+        Item::new(source_id, connector_id, ItemKind::Relationship, timestamp, properties)
+    };
+
+    // One record per (target, kind of link); the first link's heading and
+    // alias speak for it.
+    let mut records = BTreeMap::new();
+    let mut links = Vec::new();
+    for link in &note.links {
+        let target = names.resolve(file, &link.target);
+        let (prefix, relation) = if link.embed { ("embed", "embeds") } else { ("link", "links_to") };
+        links.push(json!({
+            "target": link.target,
+            "path": target,
+            "heading": link.heading,
+            "alias": link.alias,
+            "embed": link.embed,
+        }));
+        let source_id = format!("{prefix}:{file}->{target}");
+        records.entry(source_id.clone()).or_insert_with(|| {
+            relationship(source_id, &target, relation, json!({ "heading": link.heading, "alias": link.alias }))
+        });
+    }
+    for tag in &note.tags {
+        let tag_source_id = format!("tag:{tag}");
+        let source_id = format!("tagged:{file}->{tag_source_id}");
+        records.insert(source_id.clone(), relationship(source_id, &tag_source_id, "tagged", json!({})));
+        let kind = ItemKind::Other("tag".into());
+        let tag_item = Item::new(&tag_source_id, connector_id, kind, timestamp, json!({ "name": tag }));
+        records.insert(tag_source_id, tag_item);
+    }
+    let mut dates = Vec::new();
+    for date in &note.dates {
+        let date = date.format("%Y-%m-%d").to_string();
+        let target = names.resolve(file, &date);
+        let source_id = format!("date:{file}->{target}");
+        records.insert(source_id.clone(), relationship(source_id, &target, "mentions_date", json!({ "date": date })));
+        dates.push(date);
+    }
+
+    let title = note
+        .frontmatter
+        .as_ref()
+        .and_then(|f| f.get("title"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .or_else(|| note.headings.iter().find(|h| h["level"] == 1).and_then(|h| h["text"].as_str()).map(str::to_string))
+        .unwrap_or_else(|| {
+            let name = file.rsplit('/').next().unwrap_or(file);
+            name.rsplit_once('.').map_or(name, |(stem, _)| stem).to_string()
+        });
+    let mut properties = Map::new();
+    properties.insert("title".into(), title.into());
+    properties.insert("frontmatter".into(), note.frontmatter.clone().unwrap_or(Value::Null));
+    properties.insert("headings".into(), note.headings.clone().into());
+    properties.insert("links".into(), links.into());
+    properties.insert("tags".into(), note.tags.iter().cloned().collect::<Vec<_>>().into());
+    properties.insert("dates".into(), dates.into());
+    (properties, records.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "---\n\
+        title: Weekly review\n\
+        tags: [work, Planning]\n\
+        rating: 4\n\
+        ---\n\
+        # Review\n\
+        Met with [[People/Ada Lovelace|Ada]] on 2024-01-05 about #project/engine.\n\
+        See ![[diagram.png]] and [[Roadmap#Q1]], [[Roadmap]] again.\n\
+        ## Next  ##\n\
+        Issue #12, not a tag; `#code` and `[[not a link]]` neither.\n\
+        ```\n\
+        # not a heading [[nor a link]] 2024-02-02\n\
+        ```\n\
+        Version 12024-01-05 is no date.\n";
+
+    #[test]
+    fn notes_yield_front_matter_headings_links_tags_and_dates() {
+        let note = parse(NOTE);
+        let frontmatter = note.frontmatter.as_ref().unwrap();
+        assert_eq!(frontmatter["rating"], 4);
+        assert_eq!(
+            note.headings,
+            [json!({ "level": 1, "text": "Review", "line": 6 }), json!({ "level": 2, "text": "Next", "line": 9 })]
+        );
+        let targets: Vec<(&str, bool)> = note.links.iter().map(|l| (l.target.as_str(), l.embed)).collect();
+        assert_eq!(
+            targets,
+            [("People/Ada Lovelace", false), ("diagram.png", true), ("Roadmap", false), ("Roadmap", false)],
+            "one per heading"
+        );
+        assert_eq!(note.links[0].alias.as_deref(), Some("Ada"));
+        assert_eq!(note.links[2].heading.as_deref(), Some("Q1"));
+        assert_eq!(note.tags.iter().collect::<Vec<_>>(), ["planning", "project/engine", "work"]);
+        assert_eq!(note.dates.iter().map(|d| d.to_string()).collect::<Vec<_>>(), ["2024-01-05"]);
+
+        let broken = parse("---\ntitle: [unclosed\n---\nbody #tag\n");
+        assert_eq!(broken.frontmatter, None);
+        assert_eq!(broken.tags.len(), 1);
+        let unfenced = parse("---\n# Heading\n");
+        assert_eq!(unfenced.headings.len(), 1);
+    }
+
+    #[test]
+    fn links_resolve_like_obsidian_and_become_relationships() {
+        let names = Names::new([
+            "journal/2024-01-05.md",
+            "People/Ada Lovelace.md",
+            "archive/Roadmap.md",
+            "work/Roadmap.md",
+            "work/review.md",
+        ]);
+        assert_eq!(names.resolve("work/review.md", "Roadmap"), "work/Roadmap.md", "same folder first");
+        assert_eq!(names.resolve("review.md", "roadmap"), "work/Roadmap.md", "then shortest, then first");
+        assert_eq!(names.resolve("x.md", "archive/Roadmap"), "archive/Roadmap.md");
+        assert_eq!(names.resolve("x.md", "Someday"), "Someday.md", "not yet written");
+        assert_eq!(names.resolve("x.md", "diagram.png"), "diagram.png");
+
+        let note = parse(NOTE);
+        let at = DateTime::from_timestamp(0, 0).unwrap();
+        let (properties, records) = records("notes", "work/review.md", &note, &names, at);
+        assert_eq!(properties["title"], "Weekly review");
+        assert_eq!(properties["links"][0]["path"], "People/Ada Lovelace.md");
+        let ids: Vec<&str> = records.iter().map(|r| r.source_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "date:work/review.md->journal/2024-01-05.md",
+                "embed:work/review.md->diagram.png",
+                "link:work/review.md->People/Ada Lovelace.md",
+                "link:work/review.md->work/Roadmap.md",
+                "tag:planning",
+                "tag:project/engine",
+                "tag:work",
+                "tagged:work/review.md->tag:planning",
+                "tagged:work/review.md->tag:project/engine",
+                "tagged:work/review.md->tag:work",
+            ]
+        );
+        let link = &records[2];
+        assert_eq!(link.properties["source"], Item::deterministic_id("notes", "work/review.md").to_string());
+        assert_eq!(link.properties["target"], Item::deterministic_id("notes", "People/Ada Lovelace.md").to_string());
+        assert_eq!(link.properties["relation"], "links_to");
+        assert_eq!(link.properties["alias"], "Ada");
+        assert_eq!(records[4].kind, ItemKind::Other("tag".into()));
+    }
+}
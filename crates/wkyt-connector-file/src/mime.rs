@@ -0,0 +1,81 @@
+//! MIME sniffing: what a file's bytes are, which decides how its content
+//! reaches the vault.
+//!
+//! Magic numbers come first (via `infer`), so a JPEG named `notes.txt` is
+//! still a JPEG. Bytes with no known signature are text when they are
+//! UTF-8 without NULs, typed by extension; anything else is
+//! `application/octet-stream`. Only text under `MAX_FILE_BYTES` travels
+//! inline in the item — the rest goes to the vault as chunks.
+
+use infer::MatcherType;
+
+/// Bytes examined when only a file's head is read (large files).
+pub(crate) const SNIFF_BYTES: usize = 8 * 1024;
+
+pub(crate) struct Sniffed {
+    pub(crate) mime: &'static str,
+    pub(crate) text: bool,
+}
+
+/// Sniff `head`, the first bytes of the file called `name`. `whole` says
+/// `head` is the entire file; otherwise a multi-byte character cut off at
+/// its end does not count against UTF-8.
+/// A whole `head` sniffed as text is valid UTF-8.
+pub(crate) fn sniff(name: &str, head: &[u8], whole: bool) -> Sniffed {
+    let text = is_text(head, whole);
+    match infer::get(head) {
+        // Markup and scripts are recognised by content too, but whether
+        // they can travel as text is still up to their bytes.
+        Some(kind) if kind.matcher_type() == MatcherType::Text => Sniffed { mime: kind.mime_type(), text },
+        Some(kind) => Sniffed { mime: kind.mime_type(), text: false },
+        None if text => Sniffed { mime: text_mime(name), text },
+        None => Sniffed { mime: "application/octet-stream", text },
+    }
+}
+
+fn is_text(head: &[u8], whole: bool) -> bool {
+    let utf8 = match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // Truncated mid-character (error_len None) only at a cut.
+        Err(e) => !whole && e.error_len().is_none(),
+    };
+    utf8 && !head.contains(&0)
+}
+
+fn text_mime(name: &str) -> &'static str {
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("json") => "application/json",
+        Some("ics") => "text/calendar",
+        Some("vcf") => "text/vcard",
+        Some("md" | "markdown") => "text/markdown",
+        Some("csv") => "text/csv",
+        Some("html" | "htm") => "text/html",
+        Some("xml") => "application/xml",
+        _ => "text/plain",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures_win_over_names_and_text_is_typed_by_extension() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let sniffed = sniff("notes.txt", png, true);
+        assert_eq!((sniffed.mime, sniffed.text), ("image/png", false));
+
+        let sniffed = sniff("a.json", br#"{"k": "caf\xc3\xa9"}"#, true);
+        assert_eq!((sniffed.mime, sniffed.text), ("application/json", true));
+        assert_eq!(sniff("b.MD", b"# b", true).mime, "text/markdown");
+
+        // Latin-1, and NULs: binary.
+        assert!(!sniff("a.txt", b"caf\xe9", true).text);
+        assert!(!sniff("a.txt", b"a\0b", true).text);
+        assert_eq!(sniff("a.txt", b"a\0b", true).mime, "application/octet-stream");
+        // A head cut mid-character is still text; a whole file is not.
+        assert!(sniff("a.txt", b"caf\xc3", false).text);
+        assert!(!sniff("a.txt", b"caf\xc3", true).text);
+    }
+}
@@ -0,0 +1,207 @@
+//! Photos: the EXIF metadata of JPEG, HEIF/HEIC, PNG, TIFF and WebP
+//! images, read into their File item.
+//!
+//! Only the metadata is read — the EXIF block is found by seeking through
+//! the container, so a large photo costs a few reads, not its size. What
+//! a photo records lands in its item's properties:
+//!
+//! - `captured_at`: when it was taken (`DateTimeOriginal`, else
+//!   `DateTimeDigitized`, else `DateTime`), RFC 3339 when the camera
+//!   recorded its offset (`OffsetTime*`), else as local time without one;
+//! - `camera`: `make`, `model`, `lens`, `f_number`, `exposure_time`
+//!   (seconds, as the camera wrote them: `"1/125"`), `iso` and
+//!   `focal_length_mm`, each when recorded;
+//! - `width`, `height` and `orientation` (the EXIF code, 1-8);
+//! - `location`: `latitude` and `longitude` (decimal degrees, south and
+//!   west negative), and `altitude_m` when recorded — the takeout
+//!   importer's form, so both answer the same "where was I" queries.
+//!
+//! The item's timestamp becomes the capture time, not the file's mtime
+//! (which copying and syncing rewrite). A capture time without an offset
+//! is taken as UTC unless the GPS clock, which is always UTC, says when;
+//! a photo recording neither keeps its mtime. A file without EXIF is an
+//! ordinary File item; one whose EXIF does not parse says why under
+//! `exif_error`.
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use exif::{Exif, In, Tag, Value};
+use serde_json::{json, Map, Value as Json};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use wkyt_core::Item;
+
+/// Extensions read as photos.
+const EXTENSIONS: [&str; 8] = ["jpg", "jpeg", "heic", "heif", "png", "tif", "tiff", "webp"];
+
+/// Patterns selecting what [`EXTENSIONS`] names (case-insensitive, as all
+/// patterns are).
+pub(crate) const PATTERNS: [&str; 8] = ["*.jpg", "*.jpeg", "*.heic", "*.heif", "*.png", "*.tif", "*.tiff", "*.webp"];
+
+/// Whether the file called `name` is read as a photo.
+pub(crate) fn is_photo(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    EXTENSIONS.iter().any(|ext| lower.rsplit_once('.').is_some_and(|(_, e)| e == *ext))
+}
+
+/// Read the EXIF of the photo at `path` into `item`.
+pub(crate) fn describe(path: &Path, item: &mut Item) {
+    let exif = match File::open(path)
+        .map_err(exif::Error::Io)
+        .and_then(|file| exif::Reader::new().read_from_container(&mut BufReader::new(file)))
+    {
+        Ok(exif) => exif,
+        Err(exif::Error::NotFound(_)) => return,
+        Err(e) => {
+            item.properties["exif_error"] = json!(e.to_string());
+            return;
+        }
+    };
+    let Json::Object(properties) = &mut item.properties else { return };
+
+    let captured = captured_at(&exif);
+    match &captured {
+        Some(Captured::Exact(at)) => {
+            properties.insert("captured_at".into(), json!(at.to_rfc3339()));
+            item.timestamp = at.with_timezone(&Utc);
+        }
+        Some(Captured::Local(at)) => {
+            properties.insert("captured_at".into(), json!(at.format("%Y-%m-%dT%H:%M:%S%.f").to_string()));
+            item.timestamp = gps_time(&exif).unwrap_or_else(|| at.and_utc());
+        }
+        None => {
+            if let Some(at) = gps_time(&exif) {
+                item.timestamp = at;
+            }
+        }
+    }
+
+    let mut camera = Map::new();
+    let mut put = |key: &str, value: Option<Json>| {
+        if let Some(value) = value {
+            camera.insert(key.into(), value);
+        }
+    };
+    put("make", text(&exif, Tag::Make).map(Json::from));
+    put("model", text(&exif, Tag::Model).map(Json::from));
+    put("lens", text(&exif, Tag::LensModel).map(Json::from));
+    put("f_number", rational(&exif, Tag::FNumber, 0).map(Json::from));
+    put("exposure_time", exposure_time(&exif).map(Json::from));
+    put("iso", uint(&exif, Tag::PhotographicSensitivity).map(Json::from));
+    put("focal_length_mm", rational(&exif, Tag::FocalLength, 0).map(Json::from));
+    if !camera.is_empty() {
+        properties.insert("camera".into(), Json::Object(camera));
+    }
+
+    let width = uint(&exif, Tag::PixelXDimension).or_else(|| uint(&exif, Tag::ImageWidth));
+    let height = uint(&exif, Tag::PixelYDimension).or_else(|| uint(&exif, Tag::ImageLength));
+    for (key, value) in [("width", width), ("height", height), ("orientation", uint(&exif, Tag::Orientation))] {
+        if let Some(value) = value {
+            properties.insert(key.into(), json!(value));
+        }
+    }
+    if let Some(location) = location(&exif) {
+        properties.insert("location".into(), location);
+    }
+}
+
+/// A capture time, with or without the offset it was taken at.
+enum Captured {
+    Exact(DateTime<FixedOffset>),
+    Local(NaiveDateTime),
+}
+
+fn captured_at(exif: &Exif) -> Option<Captured> {
+    let candidates = [
+        (Tag::DateTimeOriginal, Tag::SubSecTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::SubSecTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::SubSecTime, Tag::OffsetTime),
+    ];
+    candidates.into_iter().find_map(|(time, subsec, offset)| {
+        let mut at = exif::DateTime::from_ascii(ascii(exif, time)?).ok()?;
+        if let Some(subsec) = ascii(exif, subsec) {
+            at.parse_subsec(subsec).ok();
+        }
+        if let Some(offset) = ascii(exif, offset) {
+            at.parse_offset(offset).ok();
+        }
+        let local = NaiveDate::from_ymd_opt(at.year.into(), at.month.into(), at.day.into())?.and_hms_nano_opt(
+            at.hour.into(),
+            at.minute.into(),
+            at.second.into(),
+            at.nanosecond.unwrap_or_default(),
+        )?;
+        Some(match at.offset.and_then(|minutes| FixedOffset::east_opt(i32::from(minutes) * 60)) {
+            Some(offset) => Captured::Exact(local.and_local_timezone(offset).single()?),
+            None => Captured::Local(local),
+        })
+    })
+}
+
+/// The GPS receiver's UTC time, when it recorded its date too.
+fn gps_time(exif: &Exif) -> Option<DateTime<Utc>> {
+    let date = std::str::from_utf8(ascii(exif, Tag::GPSDateStamp)?).ok()?;
+    let date = NaiveDate::parse_from_str(date.trim(), "%Y:%m:%d").ok()?;
+    let [h, m, s] = [0, 1, 2].map(|i| rational(exif, Tag::GPSTimeStamp, i));
+    let seconds = h? * 3600.0 + m? * 60.0 + s?;
+    let at = date.and_hms_opt(0, 0, 0)?.and_utc();
+    Some(at + chrono::Duration::milliseconds((seconds * 1000.0).round() as i64))
+}
+
+fn location(exif: &Exif) -> Option<Json> {
+    let degrees = |tag: Tag, reference: Tag, negative: u8| {
+        let [d, m, s] = [0, 1, 2].map(|i| rational(exif, tag, i));
+        let value = d? + m.unwrap_or_default() / 60.0 + s.unwrap_or_default() / 3600.0;
+        let negative = ascii(exif, reference).is_some_and(|r| r.first() == Some(&negative));
+        Some(if negative { -value } else { value })
+    };
+    let latitude = degrees(Tag::GPSLatitude, Tag::GPSLatitudeRef, b'S')?;
+    let longitude = degrees(Tag::GPSLongitude, Tag::GPSLongitudeRef, b'W')?;
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return None;
+    }
+    let mut location = json!({ "latitude": latitude, "longitude": longitude });
+    if let Some(altitude) = rational(exif, Tag::GPSAltitude, 0) {
+        // Reference 1: below sea level.
+        let below = exif.get_field(Tag::GPSAltitudeRef, In::PRIMARY).and_then(|f| f.value.get_uint(0)) == Some(1);
+        location["altitude_m"] = json!(if below { -altitude } else { altitude });
+    }
+    Some(location)
+}
+
+fn exposure_time(exif: &Exif) -> Option<String> {
+    match &exif.get_field(Tag::ExposureTime, In::PRIMARY)?.value {
+        Value::Rational(v) if v.first()?.num != 0 && v.first()?.denom != 0 => {
+            let r = v.first()?;
+            // Whole seconds as such, fractions as `1/n`, the way cameras show them.
+            Some(match r.num >= r.denom {
+                true => r.to_f64().to_string(),
+                false => format!("1/{}", (f64::from(r.denom) / f64::from(r.num)).round()),
+            })
+        }
+        _ => None,
+    }
+}
+
+fn ascii(exif: &Exif, tag: Tag) -> Option<&[u8]> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Ascii(values) => values.first().map(Vec::as_slice).filter(|v| !v.is_empty()),
+        _ => None,
+    }
+}
+
+fn text(exif: &Exif, tag: Tag) -> Option<String> {
+    let text = String::from_utf8_lossy(ascii(exif, tag)?).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn uint(exif: &Exif, tag: Tag) -> Option<u32> {
+    exif.get_field(tag, In::PRIMARY)?.value.get_uint(0)
+}
+
+fn rational(exif: &Exif, tag: Tag, index: usize) -> Option<f64> {
+    match &exif.get_field(tag, In::PRIMARY)?.value {
+        Value::Rational(v) => v.get(index).filter(|r| r.denom != 0).map(|r| r.to_f64()),
+        _ => None,
+    }
+}
@@ -0,0 +1,219 @@
+//! Record files: exports that are really lists of records — a top-level
+//! JSON array, or NDJSON (one JSON value per line) — read as one item per
+//! record instead of one File item holding the whole document.
+//!
+//! Which files are record files, and what their records become, is
+//! configured with [`Records`] through [`FileImporter::with_records`](crate::FileImporter::with_records).
+//! A record's source id is `{file}#{id}`, `id` being the value at the
+//! configured JSON pointer (a string as is, a number in its JSON form); a
+//! record without one is keyed by the hash of its content instead, so it
+//! keeps its id however the export is reordered. Two records with the
+//! same id in one file are one item, the later winning.
+//!
+//! A record's properties are its own fields (a record that is not an
+//! object is kept under `value`), plus `record_file` and `sha256`; its
+//! timestamp is the value at the timestamp pointer — RFC 3339, a plain
+//! date, or Unix seconds or milliseconds — or else the file's mtime.
+
+use crate::walk::Patterns;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::{json, Value};
+use wkyt_core::{Item, ItemKind};
+
+/// Read the files matching a pattern as record lists.
+///
+/// ```no_run
+/// # use wkyt_connector_file::{FileImporter, Records};
+/// # use wkyt_core::ItemKind;
+/// let importer = FileImporter::new("exports", "/data/exports".into()).with_records(
+///     Records::new("orders/*.ndjson", ItemKind::Transaction).with_id("/order/id").with_timestamp("/placed_at"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Records {
+    pattern: String,
+    kind: ItemKind,
+    id: String,
+    timestamp: Option<String>,
+}
+
+impl Records {
+    /// Files matching `pattern` (matched like include patterns: by name
+    /// without a `/`, by relative path with one) hold records of `kind`,
+    /// identified by their `/id` field. Matching files are selected even
+    /// when the include patterns would not select them.
+    pub fn new(pattern: impl Into<String>, kind: ItemKind) -> Self {
+        Self { pattern: pattern.into(), kind, id: "/id".into(), timestamp: None }
+    }
+
+    /// The JSON pointer (RFC 6901) to each record's id. `/id` by default.
+    pub fn with_id(mut self, pointer: impl Into<String>) -> Self {
+        self.id = pointer.into();
+        self
+    }
+
+    /// The JSON pointer to each record's event time. Without one (or
+    /// without a usable value there) a record takes its file's mtime.
+    pub fn with_timestamp(mut self, pointer: impl Into<String>) -> Self {
+        self.timestamp = Some(pointer.into());
+        self
+    }
+
+    pub(crate) fn pattern(&self) -> &str {
+        &self.pattern
+    }
+}
+
+/// The configured record sets, compiled; the first matching a file wins.
+pub(crate) struct RecordSets(Vec<(Patterns, Records)>);
+
+impl RecordSets {
+    pub(crate) fn new(records: &[Records]) -> Result<Self, String> {
+        let mut sets = Vec::new();
+        for set in records {
+            for pointer in std::iter::once(&set.id).chain(&set.timestamp) {
+                if !pointer.is_empty() && !pointer.starts_with('/') {
+                    return Err(format!("records {:?}: {pointer:?} is not a JSON pointer", set.pattern));
+                }
+            }
+            let patterns = Patterns::new(&[&set.pattern]).map_err(|e| e.to_string())?;
+            sets.push((patterns, set.clone()));
+        }
+        Ok(Self(sets))
+    }
+
+    pub(crate) fn find(&self, rel: &str) -> Option<&Records> {
+        self.0.iter().find(|(patterns, _)| patterns.matches_path(rel)).map(|(_, set)| set)
+    }
+}
+
+/// One item per record in `text`: a JSON array's elements, or else the
+/// non-blank lines of NDJSON. `Err` (naming the first bad line) when the
+/// file is neither — a half-written export, say — so the caller can keep
+/// the records it had rather than tombstone them all.
+pub(crate) fn items(
+    connector_id: &str,
+    file: &str,
+    text: &str,
+    set: &Records,
+    fallback: DateTime<Utc>,
+) -> Result<Vec<Item>, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let records: Vec<(Value, String)> = if text.trim_start().starts_with('[') {
+        let Value::Array(elements) = serde_json::from_str(text).map_err(|e| format!("not a JSON array: {e}"))? else {
+            unreachable!("a document starting with `[` is an array")
+        };
+        elements
+            .into_iter()
+            .map(|v| {
+                let raw = v.to_string();
+                (v, raw)
+            })
+            .collect()
+    } else {
+        let mut records = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let value = serde_json::from_str(line).map_err(|e| format!("line {}: {e}", n + 1))?;
+            records.push((value, line.to_string()));
+        }
+        records
+    };
+
+    Ok(records.into_iter().map(|(record, raw)| item(connector_id, file, record, raw, set, fallback)).collect())
+}
+
+fn item(connector_id: &str, file: &str, record: Value, raw: String, set: &Records, fallback: DateTime<Utc>) -> Item {
+    let sha256 = crate::sha256_hex(record.to_string().as_bytes());
+    let id = match record.pointer(&set.id) {
+        Some(Value::String(id)) if !id.is_empty() => id.clone(),
+        Some(id @ Value::Number(_)) => id.to_string(),
+        _ => format!("sha256:{sha256}"),
+    };
+    let timestamp = set.timestamp.as_ref().and_then(|p| record.pointer(p)).and_then(time).unwrap_or(fallback);
+    let mut properties = match record {
+        Value::Object(fields) => Value::Object(fields),
+        value => json!({ "value": value }),
+    };
+    properties["record_file"] = json!(file);
+    properties["sha256"] = json!(sha256);
+    let mut item = Item::new(format!("{file}#{id}"), connector_id, set.kind.clone(), timestamp, properties);
+    item.raw_payload = Some(Value::String(raw));
+    item
+}
+
+/// A timestamp value: RFC 3339, a date (midnight UTC), or Unix time —
+/// milliseconds when too large to be seconds of this era.
+fn time(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => {
+            let s = s.trim();
+            let midnight = |d: NaiveDate| d.and_time(Default::default()).and_utc();
+            let date = || NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(midnight);
+            DateTime::parse_from_rfc3339(s).map(|t| t.with_timezone(&Utc)).ok().or_else(date)
+        }
+        Value::Number(n) => {
+            let n = n.as_f64()?;
+            if n.abs() >= 1e11 {
+                DateTime::from_timestamp_millis(n as i64)
+            } else {
+                DateTime::from_timestamp_millis((n * 1000.0) as i64)
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn arrays_and_ndjson_yield_one_item_per_record() {
+        let set = Records::new("*.json", ItemKind::Transaction).with_id("/meta/id").with_timestamp("/at");
+        let fallback = at("2020-01-01T00:00:00Z");
+        let array = r#"[{"meta": {"id": "a"}, "at": "2024-05-01T10:00:00+02:00", "amount": 3},
+                        {"meta": {"id": 7}, "at": 1714550400},
+                        {"at": "2024-05-03"}, 42]"#;
+        let items = super::items("c", "orders.json", array, &set, fallback).unwrap();
+        let ids: Vec<&str> = items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(&ids[..2], ["orders.json#a", "orders.json#7"]);
+        assert!(ids[2].starts_with("orders.json#sha256:"), "no id: keyed by content");
+        assert_eq!(items[0].kind, ItemKind::Transaction);
+        assert_eq!(items[0].properties["amount"], 3);
+        assert_eq!(items[0].properties["record_file"], "orders.json");
+        assert_eq!(items[0].timestamp, at("2024-05-01T08:00:00Z"));
+        assert_eq!(items[1].timestamp, at("2024-05-01T08:00:00Z"), "Unix seconds");
+        assert_eq!(items[2].timestamp, at("2024-05-03T00:00:00Z"));
+        assert_eq!((items[3].properties["value"].clone(), items[3].timestamp), (json!(42), fallback));
+
+        let ndjson = "{\"meta\": {\"id\": \"a\"}, \"at\": 1714550400000}\n\n{\"meta\": {\"id\": \"b\"}}\n";
+        let items = super::items("c", "orders.ndjson", ndjson, &set, fallback).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].timestamp, at("2024-05-01T08:00:00Z"), "Unix milliseconds");
+        assert_eq!(items[1].raw_payload, Some(json!("{\"meta\": {\"id\": \"b\"}}")));
+
+        let broken = "{\"id\": 1}\n{\"id\": 2, \"trunc";
+        let error = super::items("c", "x.ndjson", broken, &set, fallback).unwrap_err();
+        assert!(error.starts_with("line 2:"), "{error}");
+    }
+
+    #[test]
+    fn record_sets_match_by_name_or_path_and_check_their_pointers() {
+        let sets = RecordSets::new(&[
+            Records::new("exports/**", ItemKind::Other("order".into())),
+            Records::new("*.ndjson", ItemKind::Message),
+        ])
+        .unwrap();
+        assert_eq!(sets.find("exports/2024/a.json").map(|s| s.kind.clone()), Some(ItemKind::Other("order".into())));
+        assert_eq!(sets.find("deep/b.NDJSON").map(|s| s.kind.clone()), Some(ItemKind::Message));
+        assert!(sets.find("other.json").is_none());
+        assert!(RecordSets::new(&[Records::new("*.json", ItemKind::Message).with_id("id")]).is_err());
+    }
+}
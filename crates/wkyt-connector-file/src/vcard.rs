@@ -0,0 +1,297 @@
+//! Minimal vCard 3.0 / 4.0 (RFC 2426 / RFC 6350) reader: each VCARD of a
+//! `.vcf` file becomes one [`ItemKind::Person`], its organizations
+//! [`ItemKind::Organization`]s linked to it.
+//!
+//! Identity follows the card, not the file: a person's source id is
+//! `vcard:{UID}`, so a contact re-exported into another file (or listed in
+//! two address books) stays one item. A card without a UID (invalid, but
+//! common in hand-written files) falls back to `vcard:{file}#{n}`. An
+//! organization is keyed by its name, case and spacing folded
+//! (`org:acme inc.`), and shared by everyone who works there; each
+//! person's membership is a `member_of` relationship carrying their title
+//! and role.
+//!
+//! Emails are normalized the way calendar attendees' are (see
+//! [`normalize_email`]), so a contact and an attendee with the same
+//! address match. Phone numbers keep the written form and add a
+//! digits-only `normalized` one (with the leading `+`, when there is one).
+//! Not handled: vCard 2.1 encodings (QUOTED-PRINTABLE, bare `TEL;WORK`
+//! parameters), PHOTO payloads, and AGENT cards.
+
+use crate::contentline::{components, split_unescaped, Component, Property};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use wkyt_core::identity::normalize_email;
+use wkyt_core::{Item, ItemKind};
+
+/// Person, organization and membership items for every VCARD in `text`,
+/// one item per source id (an organization shared by several cards is
+/// upserted once).
+pub fn people(connector_id: &str, file: &str, text: &str, fallback: DateTime<Utc>) -> Vec<Item> {
+    let mut items = BTreeMap::new();
+    for (n, card) in components(text, "VCARD").into_iter().enumerate() {
+        for item in card_items(connector_id, file, n, card, fallback) {
+            items.insert(item.source_id.clone(), item);
+        }
+    }
+    items.into_values().collect()
+}
+
+fn card_items(connector_id: &str, file: &str, n: usize, card: Component, fallback: DateTime<Utc>) -> Vec<Item> {
+    let source_id = match card.text("UID") {
+        Some(uid) if !uid.trim().is_empty() => format!("vcard:{}", uid.trim()),
+        _ => format!("vcard:{file}#{n}"),
+    };
+    let timestamp = card.get("REV").and_then(|p| instant(&p.value)).unwrap_or(fallback);
+    let person_id = Item::deterministic_id(connector_id, &source_id).to_string();
+
+    let name = card.get("N").map(|p| split_unescaped(&p.value, ';'));
+    let part = |i: usize| name.as_ref().and_then(|n| n.get(i)).filter(|s| !s.is_empty()).cloned();
+    let emails: Vec<Value> = card
+        .all("EMAIL")
+        .map(|p| json!({ "address": normalize_email(&p.value), "types": types(p), "preferred": preferred(p) }))
+        .collect();
+
+    // TITLE and ROLE describe the person's position at their (first)
+    // organization.
+    let mut memberships = Vec::new();
+    let mut organizations = Vec::new();
+    for (i, org) in card.all("ORG").enumerate() {
+        let mut units = split_unescaped(&org.value, ';').into_iter().map(|s| s.trim().to_string());
+        let Some(org_name) = units.next().filter(|s| !s.is_empty()) else { continue };
+        let unit: Vec<String> = units.filter(|s| !s.is_empty()).collect();
+        let org_source_id = format!("org:{}", fold(&org_name));
+        let org_id = Item::deterministic_id(connector_id, &org_source_id).to_string();
+        let (title, role) = match i {
+            0 => (card.text("TITLE"), card.text("ROLE")),
+            _ => (None, None),
+        };
+        organizations.push(json!({
+            "id": org_id,
+            "name": org_name,
+            "unit": unit.join(" / "),
+            "title": title,
+            "role": role,
+        }));
+        memberships.push(Item::new(
+            org_source_id.clone(),
+            connector_id,
+            ItemKind::Organization,
+            timestamp,
+            json!({ "name": org_name }),
+        ));
+        memberships.push(Item::new(
+            format!("member:{source_id}->{org_source_id}"),
+            connector_id,
+            ItemKind::Relationship,
+            timestamp,
+            json!({
+                "source": person_id,
+                "target": org_id,
+                "relation": "member_of",
+                "title": title,
+                "role": role,
+            }),
+        ));
+    }
+
+    let display_name = card
+        .text("FN")
+        .filter(|s| !s.trim().is_empty())
+        .or_else(|| {
+            let parts: Vec<String> = [part(3), part(1), part(2), part(0), part(4)].into_iter().flatten().collect();
+            (!parts.is_empty()).then(|| parts.join(" "))
+        })
+        .or_else(|| emails.first().map(|e| e["address"].as_str().unwrap_or_default().to_string()));
+
+    let properties = json!({
+        "uid": card.text("UID"),
+        "displayName": display_name,
+        "name": {
+            "familyName": part(0),
+            "givenName": part(1),
+            "additionalNames": part(2),
+            "honorificPrefix": part(3),
+            "honorificSuffix": part(4),
+        },
+        "nicknames": card
+            .all("NICKNAME")
+            .flat_map(|p| split_unescaped(&p.value, ','))
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>(),
+        "emails": emails,
+        "phones": card.all("TEL").map(|p| {
+            let number = p.value.trim();
+            let number = number.strip_prefix("tel:").unwrap_or(number);
+            json!({
+                "number": number,
+                "normalized": normalize_phone(number),
+                "types": types(p),
+                "preferred": preferred(p),
+            })
+        }).collect::<Vec<_>>(),
+        "organizations": organizations,
+        "birthday": card.get("BDAY").map(birthday),
+        "vcard_version": card.get("VERSION").map(|p| p.value.trim().to_string()),
+        "contact_file": file,
+        "sha256": crate::sha256_hex(card.raw.as_bytes()),
+    });
+
+    let mut person = Item::new(source_id, connector_id, ItemKind::Person, timestamp, properties);
+    person.raw_payload = Some(Value::String(card.raw));
+    let mut items = vec![person];
+    items.extend(memberships);
+    items
+}
+
+/// TYPE values, `pref` (vCard 3's way of marking the preferred one) aside.
+fn types(prop: &Property) -> Vec<String> {
+    prop.param_values("TYPE").filter(|t| t != "pref").collect()
+}
+
+/// vCard 3 `TYPE=pref`, or vCard 4 `PREF=1` (1 is most preferred).
+fn preferred(prop: &Property) -> bool {
+    prop.param_values("TYPE").any(|t| t == "pref") || prop.param("PREF").is_some_and(|p| p.trim() == "1")
+}
+
+/// Digits only, keeping a leading `+`; an extension (`;ext=`, `x123`) is
+/// dropped.
+fn normalize_phone(number: &str) -> String {
+    let number = number.split([';', 'x', 'X']).next().unwrap_or_default().trim();
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    match number.starts_with('+') {
+        true => format!("+{digits}"),
+        false => digits,
+    }
+}
+
+/// Lowercase with runs of whitespace folded to one space: the key two
+/// spellings of the same organization name share.
+fn fold(name: &str) -> String {
+    name.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+/// BDAY as `{"date": "1990-04-01"}`, `{"date": "--04-01"}` when the year
+/// is not given, or `{"text": ...}` for anything else (`VALUE=text`).
+fn birthday(prop: &Property) -> Value {
+    let value = prop.value.trim();
+    let date = value.split('T').next().unwrap_or_default();
+    let date = match date.strip_prefix("--") {
+        Some(rest) => {
+            let digits: String = rest.chars().filter(char::is_ascii_digit).collect();
+            NaiveDate::parse_from_str(&format!("2000{digits}"), "%Y%m%d")
+                .ok()
+                .filter(|_| digits.len() == 4)
+                .map(|d| d.format("--%m-%d").to_string())
+        }
+        None => {
+            let digits: String = date.chars().filter(char::is_ascii_digit).collect();
+            NaiveDate::parse_from_str(&digits, "%Y%m%d")
+                .ok()
+                .filter(|_| digits.len() == 8)
+                .map(|d| d.format("%Y-%m-%d").to_string())
+        }
+    };
+    match date {
+        Some(date) => json!({ "date": date }),
+        None => json!({ "text": value }),
+    }
+}
+
+/// A REV timestamp (`20240102T030405Z`, or its extended form).
+fn instant(value: &str) -> Option<DateTime<Utc>> {
+    let basic: String = value.trim().chars().filter(|c| !matches!(c, '-' | ':')).collect();
+    let basic = basic.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(basic, "%Y%m%dT%H%M%S").ok().map(|t| t.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CARDS: &str = "BEGIN:VCARD\r\n\
+VERSION:4.0\r\n\
+UID:urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1\r\n\
+FN:Dr. Ana Lee\r\n\
+N:Lee;Ana;Jo;Dr.;\r\n\
+NICKNAME:Annie,AL\r\n\
+EMAIL;TYPE=work;PREF=1:Ana.Lee@Example.com\r\n\
+item1.EMAIL:mailto:ana@home.example\r\n\
+TEL;VALUE=uri;TYPE=\"voice,cell\":tel:+1-555-010-0199\r\n\
+TEL;TYPE=work:(555) 010-0100 x42\r\n\
+ORG:ACME  Inc.;Research;Lab 2\r\n\
+TITLE:Chief Scientist\r\n\
+BDAY:--0401\r\n\
+REV:20240102T030405Z\r\n\
+END:VCARD\r\n\
+BEGIN:VCARD\r\n\
+VERSION:3.0\r\n\
+FN:Bob\r\n\
+EMAIL;TYPE=INTERNET;TYPE=PREF:BOB@example.com\r\n\
+ORG:acme inc.\r\n\
+BDAY:1990-04-01\r\n\
+END:VCARD\r\n";
+
+    fn fallback() -> DateTime<Utc> {
+        DateTime::from_timestamp(0, 0).unwrap()
+    }
+
+    #[test]
+    fn cards_become_people_linked_to_shared_organizations() {
+        let items = people("file-import", "contacts.vcf", CARDS, fallback());
+        let ids: Vec<_> = items.iter().map(|i| (i.kind.clone(), i.source_id.as_str())).collect();
+        assert_eq!(
+            ids,
+            [
+                (ItemKind::Relationship, "member:vcard:contacts.vcf#1->org:acme inc."),
+                (ItemKind::Relationship, "member:vcard:urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1->org:acme inc."),
+                (ItemKind::Organization, "org:acme inc."),
+                (ItemKind::Person, "vcard:contacts.vcf#1"),
+                (ItemKind::Person, "vcard:urn:uuid:4fbe8971-0bc3-424c-9c26-36c3e1eff6b1"),
+            ],
+            "one organization for both spellings"
+        );
+
+        let ana = &items[4];
+        let p = &ana.properties;
+        assert_eq!(p["displayName"], "Dr. Ana Lee");
+        assert_eq!(p["name"]["familyName"], "Lee");
+        assert_eq!(p["name"]["honorificPrefix"], "Dr.");
+        assert_eq!(p["name"]["honorificSuffix"], Value::Null);
+        assert_eq!(p["nicknames"], json!(["Annie", "AL"]));
+        assert_eq!(p["emails"][0], json!({ "address": "ana.lee@example.com", "types": ["work"], "preferred": true }));
+        assert_eq!(p["emails"][1]["address"], "ana@home.example", "grouped, mailto: stripped");
+        assert_eq!(p["phones"][0]["number"], "+1-555-010-0199");
+        assert_eq!(p["phones"][0]["normalized"], "+15550100199");
+        assert_eq!(p["phones"][0]["types"], json!(["voice", "cell"]));
+        assert_eq!(p["phones"][1]["normalized"], "5550100100");
+        assert_eq!(p["organizations"][0]["name"], "ACME  Inc.");
+        assert_eq!(p["organizations"][0]["unit"], "Research / Lab 2");
+        assert_eq!(p["organizations"][0]["title"], "Chief Scientist");
+        assert_eq!(p["organizations"][0]["id"], items[2].id.as_str());
+        assert_eq!(p["birthday"], json!({ "date": "--04-01" }));
+        assert_eq!(ana.timestamp.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+        assert!(ana.raw_payload.as_ref().unwrap().as_str().unwrap().starts_with("BEGIN:VCARD\n"));
+
+        let link = &items[1].properties;
+        assert_eq!(link["relation"], "member_of");
+        assert_eq!((&link["source"], &link["target"]), (&json!(ana.id), &json!(items[2].id)));
+
+        let bob = &items[3].properties;
+        assert_eq!(bob["emails"][0], json!({ "address": "bob@example.com", "types": ["internet"], "preferred": true }));
+        assert_eq!(bob["birthday"], json!({ "date": "1990-04-01" }));
+        assert_eq!(items[3].timestamp, fallback(), "no REV: the file's mtime");
+    }
+
+    #[test]
+    fn sparse_cards_still_get_a_name() {
+        let text = "BEGIN:VCARD\nUID:u1\nN:Lee;Ana;;;\nBDAY;VALUE=text:circa 1800\nEND:VCARD\n\
+                    BEGIN:VCARD\nEMAIL:x@y.z\nEND:VCARD\n";
+        let items = people("file-import", "x.vcf", text, fallback());
+        assert_eq!(items[0].properties["displayName"], "Ana Lee");
+        assert_eq!(items[0].properties["birthday"], json!({ "text": "circa 1800" }));
+        assert_eq!(items[1].properties["displayName"], "x@y.z");
+        assert!(people("file-import", "x.vcf", "not a card", fallback()).is_empty());
+    }
+}
@@ -0,0 +1,221 @@
+//! The metadata-only directory walk behind [`FileImporter`](crate::FileImporter):
+//! which files under the root are selected, and under what source id.
+//!
+//! A file's source id is its path relative to the root, `/`-separated on
+//! every platform. Patterns without a `/` match an entry's name at any
+//! depth (`*.json`, `node_modules`); patterns with one match the whole
+//! relative path (`notes/**/*.md`, where `*` stops at `/`). Matching is
+//! case-insensitive. Includes select files; excludes drop files and prune
+//! whole directories.
+//!
+//! Symlinks: a link to a file is followed when its target resolves inside
+//! the root, and is keyed by the link's own path. A link to a directory is
+//! never descended — its target is either outside the root (an escape) or
+//! already part of the walk (a duplicate, or a loop) — so the walk sees
+//! each real directory exactly once, in the same place on every scan.
+//! Dangling links, and subdirectories that vanish or deny access mid-walk,
+//! are skipped (their known files are then tombstoned like any other
+//! absent file).
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use wkyt_core::files::mtime_ms;
+
+/// Files selected when no include pattern is configured.
+pub(crate) const DEFAULT_INCLUDE: [&str; 3] = ["*.json", "*.ics", "*.vcf"];
+
+/// One compiled pattern list, split by what each pattern is matched
+/// against.
+pub(crate) struct Patterns {
+    by_name: GlobSet,
+    by_path: GlobSet,
+}
+
+impl Patterns {
+    pub(crate) fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, globset::Error> {
+        let mut by_name = GlobSetBuilder::new();
+        let mut by_path = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            let glob = GlobBuilder::new(pattern).case_insensitive(true).literal_separator(true).build()?;
+            if pattern.contains('/') {
+                by_path.add(glob);
+            } else {
+                by_name.add(glob);
+            }
+        }
+        Ok(Self { by_name: by_name.build()?, by_path: by_path.build()? })
+    }
+
+    fn matches(&self, rel: &str, name: &str) -> bool {
+        self.by_name.is_match(name) || self.by_path.is_match(rel)
+    }
+
+    /// Whether the file at relative path `rel` matches.
+    pub(crate) fn matches_path(&self, rel: &str) -> bool {
+        self.matches(rel, rel.rsplit('/').next().unwrap_or(rel))
+    }
+}
+
+/// What the walk selects.
+pub(crate) struct Filter {
+    pub(crate) include: Patterns,
+    pub(crate) exclude: Patterns,
+    /// Directory levels below the root to descend; `None` is unlimited.
+    pub(crate) max_depth: Option<usize>,
+}
+
+impl Filter {
+    /// Whether `rel`, or any directory it sits in, is excluded — i.e. the
+    /// walk would never reach it.
+    pub(crate) fn excluded(&self, rel: &str) -> bool {
+        let mut start = 0;
+        rel.split('/').any(|name| {
+            let prefix = &rel[..start + name.len()];
+            start += name.len() + 1;
+            self.exclude.matches(prefix, name)
+        })
+    }
+}
+
+/// One selected file, as the walk saw it.
+#[derive(Clone, Debug)]
+pub(crate) struct Found {
+    /// Source id: the path relative to the root.
+    pub(crate) path: String,
+    pub(crate) mtime_ms: i64,
+    pub(crate) size: u64,
+}
+
+/// Every selected file under `root`, in no particular order.
+pub(crate) fn scan(root: &Path, filter: &Filter) -> io::Result<Vec<Found>> {
+    let root_canon = root.canonicalize()?;
+    let mut found = Vec::new();
+    let mut pending: Vec<(PathBuf, String, usize)> = vec![(root.to_path_buf(), String::new(), 0)];
+
+    while let Some((dir, prefix, depth)) = pending.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if depth > 0 && skippable(&e) => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if skippable(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            let Ok(name) = entry.file_name().into_string() else { continue };
+            let rel = format!("{prefix}{name}");
+            if filter.exclude.matches(&rel, &name) {
+                continue;
+            }
+            let path = entry.path();
+            let is_link = entry.file_type()?.is_symlink();
+            if is_link && !path.canonicalize().is_ok_and(|target| target.starts_with(&root_canon)) {
+                continue;
+            }
+            // Follows the link, if any.
+            let meta = match fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(e) if skippable(&e) => continue,
+                Err(e) => return Err(e),
+            };
+
+            if meta.is_dir() {
+                if !is_link && filter.max_depth.is_none_or(|max| depth < max) {
+                    pending.push((path, format!("{rel}/"), depth + 1));
+                }
+            } else if meta.is_file() && filter.include.matches(&rel, &name) {
+                found.push(Found { path: rel, mtime_ms: mtime_ms(&meta), size: meta.len() });
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Errors that mean "this entry is not there for us" rather than "the
+/// walk failed".
+fn skippable(e: &io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(include: &[&str], exclude: &[&str], max_depth: Option<usize>) -> Filter {
+        let include = if include.is_empty() { &DEFAULT_INCLUDE[..] } else { include };
+        Filter {
+            include: Patterns::new(include).unwrap(),
+            exclude: Patterns::new(exclude).unwrap(),
+            max_depth,
+        }
+    }
+
+    fn names(root: &Path, filter: &Filter) -> Vec<String> {
+        let mut names: Vec<_> = scan(root, filter).unwrap().into_iter().map(|f| f.path).collect();
+        names.sort();
+        names
+    }
+
+    fn touch(root: &Path, rel: &str) {
+        let path = root.join(rel);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "{}").unwrap();
+    }
+
+    #[test]
+    fn nested_files_are_keyed_by_relative_path_and_filtered() {
+        let dir = tempfile::tempdir().unwrap();
+        for rel in ["a.json", "B.ICS", "x.txt", "sub/c.json", "sub/deep/d.json", "node_modules/e.json", "notes/f.md"] {
+            touch(dir.path(), rel);
+        }
+
+        assert_eq!(
+            names(dir.path(), &filter(&[], &["node_modules"], None)),
+            ["B.ICS", "a.json", "sub/c.json", "sub/deep/d.json"]
+        );
+        assert_eq!(names(dir.path(), &filter(&[], &["node_modules"], Some(1))), ["B.ICS", "a.json", "sub/c.json"]);
+        assert_eq!(names(dir.path(), &filter(&[], &[], Some(0))), ["B.ICS", "a.json"]);
+        assert_eq!(names(dir.path(), &filter(&["notes/*.md", "sub/**/*.json"], &[], None)), [
+            "notes/f.md",
+            "sub/c.json",
+            "sub/deep/d.json"
+        ]);
+        assert_eq!(names(dir.path(), &filter(&[], &["sub/*.json"], None)), [
+            "B.ICS",
+            "a.json",
+            "node_modules/e.json",
+            "sub/deep/d.json"
+        ]);
+        assert!(Patterns::new(&["a[.json"]).is_err());
+
+        let f = filter(&[], &["node_modules", "sub/*.json"], None);
+        assert!(f.excluded("node_modules/x/y.json"));
+        assert!(f.excluded("sub/c.json"));
+        assert!(!f.excluded("sub/deep/d.json"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_stay_inside_the_root_and_never_loop() {
+        use std::os::unix::fs::symlink;
+        let outside = tempfile::tempdir().unwrap();
+        touch(outside.path(), "secret.json");
+        let dir = tempfile::tempdir().unwrap();
+        touch(dir.path(), "sub/a.json");
+        symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+        symlink(outside.path(), dir.path().join("escape")).unwrap();
+        symlink(outside.path().join("secret.json"), dir.path().join("secret.json")).unwrap();
+        symlink(dir.path().join("sub/a.json"), dir.path().join("alias.json")).unwrap();
+        symlink(dir.path().join("missing.json"), dir.path().join("dangling.json")).unwrap();
+        symlink(dir.path().join("sub"), dir.path().join("linked-sub")).unwrap();
+
+        // Directory links ("loop", "escape", "linked-sub") are not entered;
+        // of the file links, only the one resolving inside the root counts.
+        assert_eq!(names(dir.path(), &filter(&[], &[], None)), ["alias.json", "sub/a.json"]);
+    }
+}
@@ -0,0 +1,116 @@
+//! Push mode: [`ChangeNotifier`] over the platform's filesystem change
+//! notifications (inotify on Linux), via `notify`.
+//!
+//! The watch covers the whole root (just the root at `max_depth` 0).
+//! Events are filtered only coarsely — the importer's own reads (access
+//! events) and paths under excluded names or directories are dropped —
+//! and the rest coalesce into one pending wake-up: a burst of writes
+//! before the host wakes costs one pass, and a wake-up for a file the
+//! walk would not select costs a pass that finds nothing. A watcher error
+//! (e.g. the kernel queue overflowed) also wakes the host, since events
+//! may have been lost.
+
+use crate::FileImporter;
+use futures_channel::mpsc;
+use futures_util::Stream;
+use notify::event::{AccessKind, AccessMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use wkyt_core::{ChangeNotifier, ChangeStream, SyncError};
+
+/// The wake-up stream; owns the watcher, so dropping it stops the watch.
+struct Changes {
+    rx: mpsc::Receiver<()>,
+    _watcher: RecommendedWatcher,
+}
+
+impl Stream for Changes {
+    type Item = ();
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        Pin::new(&mut self.rx).poll_next(cx)
+    }
+}
+
+impl ChangeNotifier for FileImporter {
+    fn changes(&self) -> Result<ChangeStream, SyncError> {
+        let fatal = |e: notify::Error| SyncError::Fatal { source: Box::new(e) };
+        let filter = self.filter()?;
+        let root = self.dir.clone();
+        // Capacity 0 (one slot per sender): a full channel already holds
+        // a wake-up, so further events are dropped — that is the coalescing.
+        let (mut tx, rx) = mpsc::channel(0);
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let relevant = match event {
+                Err(_) => true,
+                // Opens and reads, including our own scans; of the access
+                // events only a closed write can mean new content.
+                Ok(Event { kind: EventKind::Access(kind), .. })
+                    if kind != AccessKind::Close(AccessMode::Write) =>
+                {
+                    false
+                }
+                Ok(event) => event.paths.iter().any(|path| {
+                    let rel = path.strip_prefix(&root).ok().and_then(|p| p.to_str());
+                    // The root itself (or a path outside it) is always
+                    // relevant; anything below is unless excluded.
+                    rel.is_none_or(|rel| rel.is_empty() || !filter.excluded(&rel.replace('\\', "/")))
+                }),
+            };
+            if relevant {
+                let _ = tx.try_send(());
+            }
+        })
+        .map_err(fatal)?;
+        let mode = match self.max_depth {
+            Some(0) => RecursiveMode::NonRecursive,
+            _ => RecursiveMode::Recursive,
+        };
+        watcher.watch(&self.dir, mode).map_err(fatal)?;
+        Ok(Box::pin(Changes { rx, _watcher: watcher }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    async fn next_within(changes: &mut ChangeStream, ms: u64) -> bool {
+        tokio::time::timeout(Duration::from_millis(ms), changes.next()).await.is_ok()
+    }
+
+    #[tokio::test]
+    async fn writes_wake_once_per_burst_and_excluded_paths_do_not() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join(".git")).unwrap();
+        let c = FileImporter::new("file-import", dir.path().to_path_buf()).with_exclude(".git");
+        let mut changes = c.changes().unwrap();
+
+        fs::write(dir.path().join(".git/index.json"), "{}").unwrap();
+        assert!(!next_within(&mut changes, 200).await, "excluded directory");
+
+        for i in 0..20 {
+            fs::write(dir.path().join(format!("f{i}.json")), "{}").unwrap();
+        }
+        assert!(next_within(&mut changes, 2000).await);
+        // The burst coalesced: at most the one further wake-up that was
+        // pending when the first was taken.
+        let _ = next_within(&mut changes, 200).await;
+        assert!(!next_within(&mut changes, 200).await);
+
+        // Our own scan (reads only) does not wake anyone.
+        let _ = c.plan(None).unwrap();
+        assert!(!next_within(&mut changes, 200).await);
+    }
+
+    #[test]
+    fn an_unwatchable_root_fails_to_start() {
+        let c = FileImporter::new("file-import", PathBuf::from("/nonexistent/wkyt-test"));
+        assert!(matches!(c.changes(), Err(SyncError::Fatal { .. })));
+    }
+}
@@ -0,0 +1,176 @@
+//! One commit -> its [`ItemKind::Event`], its author as
+//! [`ItemKind::Person`]s, and the relationships between them.
+//!
+//! A commit is keyed `{repo}:{hash}`. Its properties: `repo`, `hash`,
+//! `summary` (the first line), `message`, `author` and `committer`
+//! (`{name, email}`, the author's with its Person's item `id`),
+//! `authored_at` and `committed_at` (RFC 3339 with the signature's own
+//! offset), `parents`, and its changes against its first parent (the
+//! empty tree for a root commit), renames detected: `files_changed`,
+//! `insertions`, `deletions`, and per file `{path, old_path, status,
+//! insertions, deletions}` for up to [`MAX_LISTED_FILES`] files
+//! (`files_truncated` past that; the totals still count every file).
+//! Binary files count as changed with no lines. The timestamp is the
+//! author time.
+//!
+//! An author is an identity — a name and an email, as git records them —
+//! keyed `git:{name} <{email}>`, the email lowercased. The same person
+//! often commits under several names or addresses, which git cannot tell
+//! apart from different people, so identities are not merged: each one
+//! is linked `same_as` to a Person for its email alone (`mailto:{email}`,
+//! the mail importer's form), and identities sharing an email meet there
+//! as candidates for one entity. The commit links to its author's identity
+//! with `authored_by`.
+
+use chrono::{DateTime, FixedOffset, Offset, Utc};
+use git2::{Commit, Delta, Patch, Repository, Signature};
+use serde_json::{json, Value};
+use wkyt_core::identity::normalize_email;
+use wkyt_core::{Item, ItemKind};
+
+/// The most files listed on a commit.
+pub const MAX_LISTED_FILES: usize = 500;
+
+/// The source id of commit `hash` in repository `repo`.
+fn source_id(repo: &str, hash: &str) -> String {
+    format!("{repo}:{hash}")
+}
+
+/// The source id of the author identity of `signature`.
+fn identity_source_id(signature: &Signature) -> String {
+    let (name, email) = identity(signature);
+    format!("git:{name} <{email}>")
+}
+
+/// The source ids a commit's import wrote that are its alone: the event
+/// and its `authored_by` link. The Persons stay; other commits may share
+/// them.
+pub(crate) fn retracted(repo_name: &str, commit: &Commit) -> [String; 2] {
+    let source_id = source_id(repo_name, &commit.id().to_string());
+    let authored_by = format!("authored_by:{source_id}->{}", identity_source_id(&commit.author()));
+    [source_id, authored_by]
+}
+
+/// The commit's event, its author's identity (and email) Persons, and the
+/// `authored_by` (and `same_as`) links.
+pub(crate) fn items(
+    connector_id: &str,
+    repo_name: &str,
+    repo: &Repository,
+    commit: &Commit,
+) -> Result<Vec<Item>, git2::Error> {
+    let hash = commit.id().to_string();
+    let source_id = source_id(repo_name, &hash);
+    let author = commit.author();
+    let committer = commit.committer();
+    let authored_at = when(&author);
+    let timestamp = authored_at.with_timezone(&Utc);
+
+    let (name, email) = identity(&author);
+    let identity_source_id = identity_source_id(&author);
+    let identity_id = Item::deterministic_id(connector_id, &identity_source_id).to_string();
+    let (committer_name, committer_email) = identity(&committer);
+
+    let message = String::from_utf8_lossy(commit.message_bytes()).into_owned();
+    let mut properties = json!({
+        "repo": repo_name,
+        "hash": hash,
+        "summary": message.lines().next().unwrap_or_default(),
+        "message": message,
+        "author": { "name": name, "email": email, "id": identity_id },
+        "committer": { "name": committer_name, "email": committer_email },
+        "authored_at": authored_at.to_rfc3339(),
+        "committed_at": when(&committer).to_rfc3339(),
+        "parents": commit.parent_ids().map(|p| p.to_string()).collect::<Vec<_>>(),
+    });
+    changes(repo, commit, &mut properties)?;
+    let event = Item::new(source_id.clone(), connector_id, ItemKind::Event, timestamp, properties);
+
+    let link = |relation: &str, (source_sid, source): (&str, &str), (target_sid, target): (&str, &str)| {
+        let properties = json!({ "source": source, "target": target, "relation": relation });
+        let link_source_id = format!("{relation}:{source_sid}->{target_sid}");
+        Item::new(link_source_id, connector_id, ItemKind::Relationship, timestamp, properties)
+    };
+    let person = |source_id: &str, display_name: &str| {
+        let properties = json!({ "displayName": display_name, "emails": [{ "address": email }] });
+        Item::new(source_id, connector_id, ItemKind::Person, timestamp, properties)
+    };
+    let authored_by = link("authored_by", (&source_id, &event.id), (&identity_source_id, &identity_id));
+    let mut items = vec![event, person(&identity_source_id, &name), authored_by];
+    if !email.is_empty() {
+        let email_source_id = format!("mailto:{email}");
+        let email_id = Item::deterministic_id(connector_id, &email_source_id).to_string();
+        let mut same_as = link("same_as", (&identity_source_id, &identity_id), (&email_source_id, &email_id));
+        same_as.properties["basis"] = json!("email");
+        items.push(person(&email_source_id, &email));
+        items.push(same_as);
+    }
+    Ok(items)
+}
+
+/// A signature's name, trimmed, and email, normalized as the mail
+/// importer's are.
+fn identity(signature: &Signature) -> (String, String) {
+    let name = String::from_utf8_lossy(signature.name_bytes()).trim().to_string();
+    let email = normalize_email(&String::from_utf8_lossy(signature.email_bytes()));
+    (name, email)
+}
+
+/// A signature's time, in its own offset.
+fn when(signature: &Signature) -> DateTime<FixedOffset> {
+    let time = signature.when();
+    let offset = FixedOffset::east_opt(time.offset_minutes() * 60).unwrap_or(Utc.fix());
+    DateTime::from_timestamp(time.seconds(), 0).unwrap_or_default().with_timezone(&offset)
+}
+
+/// The file stats of `commit` against its first parent, into `properties`.
+fn changes(repo: &Repository, commit: &Commit, properties: &mut Value) -> Result<(), git2::Error> {
+    let tree = commit.tree()?;
+    let parent = match commit.parent_count() {
+        0 => None,
+        _ => Some(commit.parent(0)?.tree()?),
+    };
+    let mut diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&tree), None)?;
+    diff.find_similar(None)?;
+
+    let (mut insertions, mut deletions) = (0, 0);
+    let mut files = Vec::new();
+    for index in 0..diff.deltas().len() {
+        let (added, removed) = match Patch::from_diff(&diff, index)? {
+            Some(patch) => {
+                let (_, added, removed) = patch.line_stats()?;
+                (added, removed)
+            }
+            None => (0, 0),
+        };
+        insertions += added;
+        deletions += removed;
+        if files.len() == MAX_LISTED_FILES {
+            continue;
+        }
+        let delta = diff.get_delta(index).expect("index below deltas().len()");
+        let path = |file: git2::DiffFile| file.path().map(|p| p.to_string_lossy().replace('\\', "/"));
+        let status = match delta.status() {
+            Delta::Added => "added",
+            Delta::Deleted => "deleted",
+            Delta::Renamed => "renamed",
+            Delta::Copied => "copied",
+            Delta::Typechange => "type_changed",
+            _ => "modified",
+        };
+        let old_path = matches!(delta.status(), Delta::Renamed | Delta::Copied).then(|| path(delta.old_file()));
+        files.push(json!({
+            "path": path(delta.new_file()).or_else(|| path(delta.old_file())),
+            "old_path": old_path.flatten(),
+            "status": status,
+            "insertions": added,
+            "deletions": removed,
+        }));
+    }
+    properties["files_changed"] = json!(diff.deltas().len());
+    properties["insertions"] = json!(insertions);
+    properties["deletions"] = json!(deletions);
+    properties["files_truncated"] = json!(diff.deltas().len() > files.len());
+    properties["files"] = json!(files);
+    Ok(())
+}
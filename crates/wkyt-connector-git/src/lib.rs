@@ -0,0 +1,425 @@
+//! Git connector: walks the local branches of one or more local
+//! repositories into one [`ItemKind::Event`] per commit, with its message,
+//! file stats and times, and its author as [`ItemKind::Person`]s (see
+//! [`commit`] for the items). Repositories are read in process through
+//! libgit2; nothing is fetched, so a clone is imported as of its last
+//! fetch or commit.
+//!
+//! Each repository is configured under a name, which prefixes its commits'
+//! source ids (`{name}:{hash}`): the same commit in two clones is two
+//! events, one per repository it is part of. Renaming a repository in the
+//! configuration imports it afresh under the new name.
+//!
+//! Cursor design: per repository, the commit each local branch pointed at
+//! when the repository was last imported
+//! (`{"repos": {"notes": {"refs/heads/main": "4f2a…"}}}`). A pass walks
+//! each repository's commits reachable from its branches now but not from
+//! the recorded tips — oldest first, parents before children — and then
+//! those reachable from the recorded tips but from no branch now: commits
+//! a force-push or a deleted branch dropped from history. Those are
+//! tombstoned, with their `authored_by` links; their authors' Persons stay.
+//! A dropped commit whose objects were already garbage collected cannot be
+//! walked, and its event stays.
+//!
+//! Every batch that imports commits checkpoints: it carries the previous
+//! tips plus the last commit it delivered, under the `checkpoint` key
+//! (which no branch ref can take). A pass resumed from there hides that
+//! commit like a tip, so its ancestors — everything delivered before it on
+//! its line of history — are not walked again; commits delivered from
+//! other branches may be, and re-upserting is idempotent. The
+//! repository's last batch replaces the checkpoint with the tips now.
+
+mod commit;
+
+pub use commit::MAX_LISTED_FILES;
+
+use futures_util::stream;
+use git2::{ErrorClass, ErrorCode, Oid, Repository, Sort};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::path::PathBuf;
+use wkyt_core::batches::{self, Step};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+/// The cursor key of the last commit an unfinished pass delivered.
+const CHECKPOINT: &str = "checkpoint";
+
+pub struct GitHistory {
+    id: String,
+    repos: Vec<(String, PathBuf)>,
+    batch_size: usize,
+}
+
+/// Branch -> tip commit hash, per repository name.
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct GitCursor {
+    repos: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl GitHistory {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self { id: id.into(), repos: Vec::new(), batch_size: DEFAULT_BATCH_SIZE }
+    }
+
+    /// Import the repository at `path` (its working tree or a bare
+    /// repository) under `name`. May be called once per repository.
+    pub fn with_repo(mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.repos.push((name.into(), path.into()));
+        self
+    }
+
+    /// Commits per batch (default 100).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(VecDeque<(String, Repository)>, GitCursor), SyncError> {
+        let cursor: GitCursor = match cursor {
+            None => GitCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        if cursor.repos.values().flat_map(BTreeMap::values).any(|hash| Oid::from_str(hash).is_err()) {
+            return Err(SyncError::ResyncRequired);
+        }
+        let mut repos = VecDeque::new();
+        for (name, path) in &self.repos {
+            if repos.iter().any(|(seen, _)| seen == name) {
+                return Err(SyncError::Fatal { source: format!("repository name {name:?} is configured twice").into() });
+            }
+            let repo = Repository::open(path)
+                .map_err(|e| SyncError::Fatal { source: format!("cannot open repository {path:?}: {e}").into() })?;
+            repos.push_back((name.clone(), repo));
+        }
+        Ok((repos, cursor))
+    }
+}
+
+/// One repository's pass.
+struct RepoPass {
+    name: String,
+    repo: Repository,
+    /// Its branches' tips now, for the cursor.
+    tips: BTreeMap<String, String>,
+    /// What the cursor held for it, for checkpoints.
+    recorded: BTreeMap<String, String>,
+    /// Whether `tips` differ from the cursor's.
+    moved: bool,
+    /// Commits to tombstone, then commits to import, oldest first.
+    gone: VecDeque<Oid>,
+    new: VecDeque<Oid>,
+}
+
+impl RepoPass {
+    fn start(name: String, repo: Repository, since: Option<&BTreeMap<String, String>>) -> Result<Self, git2::Error> {
+        let mut tips = BTreeMap::new();
+        for reference in repo.references_glob("refs/heads/*")? {
+            let reference = reference?;
+            let (Some(branch), Ok(commit)) = (reference.name(), reference.peel_to_commit()) else { continue };
+            tips.insert(branch.to_string(), commit.id().to_string());
+        }
+        let old: Vec<Oid> = since
+            .into_iter()
+            .flat_map(BTreeMap::values)
+            .filter_map(|hash| Oid::from_str(hash).ok())
+            // A recorded tip whose objects are gone cannot be walked from.
+            .filter(|&oid| repo.find_commit(oid).is_ok())
+            .collect();
+        let now: Vec<Oid> = tips.values().filter_map(|hash| Oid::from_str(hash).ok()).collect();
+
+        let new = walk(&repo, &now, &old)?;
+        let gone = walk(&repo, &old, &now)?;
+        let moved = since != Some(&tips);
+        let recorded = since.cloned().unwrap_or_default();
+        Ok(Self { name, repo, tips, recorded, moved, gone, new })
+    }
+}
+
+/// The commits reachable from `from` but not from `hidden`, oldest first.
+fn walk(repo: &Repository, from: &[Oid], hidden: &[Oid]) -> Result<VecDeque<Oid>, git2::Error> {
+    if from.is_empty() {
+        return Ok(VecDeque::new());
+    }
+    let mut revwalk = repo.revwalk()?;
+    revwalk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    for &oid in from {
+        revwalk.push(oid)?;
+    }
+    for &oid in hidden {
+        revwalk.hide(oid)?;
+    }
+    revwalk.collect()
+}
+
+/// The sync stream's batches, built lazily as it is polled.
+struct Batches<'a> {
+    importer: &'a GitHistory,
+    /// Repositories not started yet.
+    repos: VecDeque<(String, Repository)>,
+    current: Option<RepoPass>,
+    /// What the next checkpoint or repository-final batch will carry.
+    cursor: GitCursor,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        let pass = match &mut self.current {
+            Some(pass) => pass,
+            None => {
+                let Some((name, repo)) = self.repos.pop_front() else {
+                    return Ok(None);
+                };
+                let since = self.cursor.repos.get(&name);
+                let pass = RepoPass::start(name, repo, since).map_err(git_error)?;
+                self.current.insert(pass)
+            }
+        };
+
+        let mut deltas = Vec::new();
+        let mut persons = BTreeMap::new();
+        let mut taken = 0;
+        let mut last = None;
+        while taken < importer.batch_size {
+            if let Some(oid) = pass.gone.pop_front() {
+                let commit = pass.repo.find_commit(oid).map_err(git_error)?;
+                for source_id in commit::retracted(&pass.name, &commit) {
+                    deltas.push(Delta::Tombstone { source_id });
+                }
+            } else if let Some(oid) = pass.new.pop_front() {
+                last = Some(oid);
+                let commit = pass.repo.find_commit(oid).map_err(git_error)?;
+                for item in commit::items(&importer.id, &pass.name, &pass.repo, &commit).map_err(git_error)? {
+                    match item.kind {
+                        // Authors and their links: once per batch, however many commits.
+                        ItemKind::Person => {
+                            persons.insert(item.source_id.clone(), item);
+                        }
+                        ItemKind::Relationship if item.properties["relation"] == "same_as" => {
+                            persons.insert(item.source_id.clone(), item);
+                        }
+                        _ => deltas.push(Delta::Upsert(item)),
+                    }
+                }
+            } else {
+                break;
+            }
+            taken += 1;
+        }
+        deltas.splice(0..0, persons.into_values().map(Delta::Upsert));
+
+        let finished = pass.gone.is_empty() && pass.new.is_empty();
+        let cursor = if finished {
+            let pass = self.current.take().expect("a repository pass is in progress");
+            if deltas.is_empty() && !pass.moved {
+                // Nothing changed here; on to the next repository.
+                return self.step();
+            }
+            self.cursor.repos.insert(pass.name, pass.tips);
+            Some(SyncToken(serde_json::to_string(&self.cursor).expect("cursor serialization is infallible")))
+        } else if let Some(last) = last {
+            let mut checkpoint = pass.recorded.clone();
+            checkpoint.insert(CHECKPOINT.to_string(), last.to_string());
+            self.cursor.repos.insert(pass.name.clone(), checkpoint);
+            Some(SyncToken(serde_json::to_string(&self.cursor).expect("cursor serialization is infallible")))
+        } else {
+            None
+        };
+        Ok(Some(DeltaBatch { connector_id: importer.id.clone(), deltas, cursor }))
+    }
+}
+
+/// A locked ref or an unreadable file is worth another try; anything else
+/// (a corrupt object, a missing one mid-walk) needs the repository fixed.
+fn git_error(e: git2::Error) -> SyncError {
+    if e.code() == ErrorCode::Locked || e.class() == ErrorClass::Os {
+        SyncError::Retryable { source: Box::new(e), retry_after: None }
+    } else {
+        SyncError::Fatal { source: Box::new(e) }
+    }
+}
+
+#[async_trait::async_trait]
+impl Connector for GitHistory {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((repos, cursor)) => {
+                batches::stream(Batches { importer: self, repos, current: None, cursor })
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, tombstones, upserts_of};
+    use futures_util::StreamExt;
+    use git2::{Signature, Time};
+    use std::path::Path;
+    use wkyt_core::Item;
+
+    fn summaries(batches: &[DeltaBatch]) -> Vec<&str> {
+        upserts_of(batches, ItemKind::Event).iter().map(|i| i.properties["summary"].as_str().unwrap()).collect()
+    }
+
+    /// Write `files` into the working tree and commit them on HEAD as
+    /// `author` at `seconds` (UTC+2).
+    fn commit(repo: &Repository, files: &[(&str, &str)], author: (&str, &str), seconds: i64, message: &str) -> Oid {
+        let workdir = repo.workdir().unwrap();
+        let mut index = repo.index().unwrap();
+        for (path, contents) in files {
+            std::fs::write(workdir.join(path), contents).unwrap();
+            index.add_path(Path::new(path)).unwrap();
+        }
+        index.write().unwrap();
+        let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+        let signature = Signature::new(author.0, author.1, &Time::new(seconds, 120)).unwrap();
+        let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+        let parents: Vec<_> = parent.iter().collect();
+        repo.commit(Some("HEAD"), &signature, &signature, message, &tree, &parents).unwrap()
+    }
+
+    const ANA: (&str, &str) = ("Ana Lima", "Ana@Example.com");
+
+    #[tokio::test]
+    async fn commits_become_events_with_file_stats_and_authors() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        commit(&repo, &[("a.txt", "one\ntwo\n"), ("b.txt", "x\n")], ANA, 1_704_103_200, "Start\n\nWith two files.");
+        std::fs::remove_file(dir.path().join("b.txt")).unwrap();
+        repo.index().unwrap().remove_path(Path::new("b.txt")).unwrap();
+        let second = commit(&repo, &[("a.txt", "one\n2\nthree\n")], ("Ana", "ana@example.com"), 1_704_106_800, "Edit");
+
+        let c = GitHistory::new("git").with_repo("notes", dir.path()).with_batch_size(1);
+        let batches = drain(&c, None).await;
+        assert_eq!(summaries(&batches), ["Start", "Edit"], "oldest first");
+        assert_eq!(batches.len(), 2);
+        assert!(batches[0].cursor.as_ref().unwrap().0.contains(CHECKPOINT));
+        assert!(!batches[1].cursor.as_ref().unwrap().0.contains(CHECKPOINT), "the last batch records the tips");
+
+        let events = upserts_of(&batches, ItemKind::Event);
+        let edit = &events[1].properties;
+        assert_eq!(events[1].source_id, format!("notes:{second}"));
+        assert_eq!(edit["repo"], "notes");
+        assert_eq!(edit["authored_at"], "2024-01-01T13:00:00+02:00");
+        assert_eq!(events[1].timestamp.to_rfc3339(), "2024-01-01T11:00:00+00:00");
+        assert_eq!(edit["parents"][0], events[0].properties["hash"]);
+        assert_eq!(edit["files_changed"], 2);
+        assert_eq!((edit["insertions"].as_u64(), edit["deletions"].as_u64()), (Some(2), Some(2)));
+        let files = edit["files"].as_array().unwrap();
+        assert_eq!(files[0]["path"], "a.txt");
+        assert_eq!(files[0]["status"], "modified");
+        assert_eq!(files[1]["status"], "deleted");
+        assert_eq!(edit["files_truncated"], false);
+        assert_eq!(events[0].properties["message"], "Start\n\nWith two files.");
+
+        // Two identities, one address: both are same_as candidates for it.
+        let persons = upserts_of(&batches, ItemKind::Person);
+        let identity = persons.iter().find(|p| p.source_id == "git:Ana Lima <ana@example.com>").unwrap();
+        assert_eq!(events[0].properties["author"]["id"], identity.id);
+        let email_id = Item::deterministic_id("git", "mailto:ana@example.com").to_string();
+        let links = upserts_of(&batches, ItemKind::Relationship);
+        let same_as: Vec<_> = links.iter().filter(|l| l.properties["relation"] == "same_as").collect();
+        assert_eq!(same_as.len(), 2);
+        assert!(same_as.iter().all(|l| l.properties["target"] == email_id.as_str()));
+        let authored_by = links.iter().find(|l| l.properties["relation"] == "authored_by").unwrap();
+        assert_eq!(authored_by.properties["source"], events[0].id);
+        assert_eq!(authored_by.properties["target"], identity.id);
+    }
+
+    #[tokio::test]
+    async fn later_passes_import_new_commits_and_tombstone_force_pushed_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let base = commit(&repo, &[("a.txt", "a\n")], ANA, 1_704_103_200, "Base");
+        let dropped = commit(&repo, &[("a.txt", "b\n")], ANA, 1_704_103_300, "Dropped");
+        let c = GitHistory::new("git").with_repo("notes", dir.path());
+        let cursor = last_cursor(&drain(&c, None).await);
+
+        assert!(drain(&c, cursor.clone()).await.is_empty(), "nothing moved");
+
+        // Rewrite history: the branch goes back to `base` and on from there.
+        let head = repo.head().unwrap().name().unwrap().to_string();
+        repo.reference(&head, base, true, "reset").unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        commit(&repo, &[("c.txt", "c\n")], ANA, 1_704_103_400, "Replacement");
+
+        let batches = drain(&c, cursor).await;
+        assert_eq!(summaries(&batches), ["Replacement"]);
+        let dropped = format!("notes:{dropped}");
+        let authored_by = format!("authored_by:{dropped}->git:Ana Lima <ana@example.com>");
+        assert_eq!(tombstones(&batches), [dropped.as_str(), authored_by.as_str()]);
+        assert!(upserts_of(&batches, ItemKind::Relationship)
+            .iter()
+            .any(|l| l.source_id.starts_with("authored_by:notes:") && l.source_id.contains("->git:Ana Lima")));
+
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+        // Deleting a branch moves the tips without touching history the
+        // remaining branch still reaches.
+        repo.branch("side", &repo.find_commit(base).unwrap(), false).unwrap();
+        let batches = drain(&c, cursor).await;
+        assert!(batches.iter().all(|b| b.deltas.is_empty()));
+        assert!(last_cursor(&batches).unwrap().0.contains("refs/heads/side"));
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_import_resumes_after_its_last_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        let first = commit(&repo, &[("a.txt", "1\n")], ANA, 1_704_103_200, "First");
+        let second = commit(&repo, &[("a.txt", "2\n")], ANA, 1_704_103_300, "Second");
+        commit(&repo, &[("a.txt", "3\n")], ANA, 1_704_103_400, "Third");
+        let c = GitHistory::new("git").with_repo("notes", dir.path()).with_batch_size(1);
+
+        let batches = drain(&c, None).await;
+        assert!(batches.iter().all(|b| b.cursor.is_some()), "every batch of commits checkpoints");
+        assert_eq!(summaries(&drain(&c, batches[0].cursor.clone()).await), ["Second", "Third"]);
+
+        // A checkpointed commit that history then drops is tombstoned.
+        let head = repo.head().unwrap().name().unwrap().to_string();
+        repo.reference(&head, first, true, "reset").unwrap();
+        repo.checkout_head(Some(git2::build::CheckoutBuilder::new().force())).unwrap();
+        commit(&repo, &[("b.txt", "b\n")], ANA, 1_704_103_500, "Other");
+        let batches = drain(&c, batches[1].cursor.clone()).await;
+        assert_eq!(summaries(&batches), ["Other"]);
+        assert_eq!(tombstones(&batches)[0], format!("notes:{second}"));
+        assert!(drain(&c, last_cursor(&batches)).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn configuration_and_cursor_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        Repository::init(dir.path()).unwrap();
+
+        let twice = GitHistory::new("git").with_repo("a", dir.path()).with_repo("a", dir.path());
+        let missing = GitHistory::new("git").with_repo("a", dir.path().join("nope"));
+        for c in [twice, missing] {
+            let results: Vec<_> = c.sync(None).collect().await;
+            assert!(matches!(results.as_slice(), [Err(SyncError::Fatal { .. })]));
+        }
+
+        let c = GitHistory::new("git").with_repo("a", dir.path());
+        for bad in ["not json", r#"{"repos": {"a": {"refs/heads/main": "xyz"}}}"#] {
+            let results: Vec<_> = c.sync(Some(SyncToken(bad.into()))).collect().await;
+            assert!(matches!(results.as_slice(), [Err(SyncError::ResyncRequired)]));
+        }
+
+        // An empty repository records its (empty) tips once.
+        let batches = drain(&c, None).await;
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].deltas.is_empty());
+        assert!(drain(&c, last_cursor(&batches)).await.is_empty());
+    }
+}
@@ -239,7 +239,8 @@ pub async fn fetch_calendar_events(
     Ok(batches)
 }
 
-/// Convert a single Calendar event to Deltas (Event, Claim, Relationship).
+/// Convert a single Calendar event to its delta: an Event upsert, or a
+/// tombstone when cancelled.
 fn event_to_deltas(connector_id: &str, event: &CalendarEvent) -> Vec<Delta> {
     // Cancelled events are tombstones.
     if event.status.as_deref() == Some("cancelled") {
@@ -293,42 +294,7 @@ fn event_to_deltas(connector_id: &str, event: &CalendarEvent) -> Vec<Delta> {
     // Stash the raw Google response for traceability.
     item.raw_payload = serde_json::to_value(event).ok();
 
-    let event_id = item.id.clone();
-    
-    // Derive a claim from this event
-    let claim_source_id = format!("{}-claim", event.id);
-    let claim = Item::new(
-        &claim_source_id,
-        connector_id,
-        ItemKind::Claim,
-        timestamp,
-        json!({
-            "assertion": format!("Calendar event '{}' took place", event.summary.as_deref().unwrap_or("Unknown")),
-            "source": "google_calendar",
-            "epistemic_type": wkyt_core::EpistemicType::ImportedAssertion,
-        })
-    );
-    let claim_id = claim.id.clone();
-
-    // Create a relationship (evidence linkage)
-    let rel_source_id = format!("{}-rel", event.id);
-    let rel = Item::new(
-        &rel_source_id,
-        connector_id,
-        ItemKind::Relationship,
-        timestamp,
-        json!({
-            "source": claim_id,
-            "target": event_id,
-            "type": "has_evidence"
-        })
-    );
-
-    vec![
-        Delta::Upsert(item),
-        Delta::Upsert(claim),
-        Delta::Upsert(rel),
-    ]
+    vec![Delta::Upsert(item)]
 }
 
 /// Parse the event's start time. Prefer `dateTime`; fall back to `date`
@@ -9,7 +9,7 @@ use wkyt_connector_google::{
 };
 use wkyt_core::{Connector, ItemKind};
 use wkyt_vault::{KeyService, MemoryKekStore, Vault};
-use wkyt_host::run_pipeline_once;
+use wkyt_host::{EvidenceClaims, Pipeline};
 
 // Helper to get a free port
 fn get_free_port() -> u16 {
@@ -111,16 +111,27 @@ async fn test_google_calendar_ingestion_loop() {
         ],
         "nextSyncToken": "mock-next-sync-token"
     }"#;
+    // The incremental sync after it: the meeting was cancelled.
+    let cancellation_response = r#"{
+        "items": [
+            {
+                "id": "evt-id-1",
+                "status": "cancelled"
+            }
+        ],
+        "nextSyncToken": "mock-sync-token-after-cancel"
+    }"#;
 
     let mock_server_handle = tokio::spawn(async move {
         let listener = TokioTcpListener::bind(format!("127.0.0.1:{}", mock_api_port)).await.unwrap();
-        if let Ok((mut stream, _)) = listener.accept().await {
+        for body in [calendar_response, cancellation_response] {
+            let Ok((mut stream, _)) = listener.accept().await else { break };
             let mut buf = [0u8; 1024];
             let _ = stream.read(&mut buf).await;
             let response = format!(
                 "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
-                calendar_response.len(),
-                calendar_response
+                body.len(),
+                body
             );
             let _ = stream.write_all(response.as_bytes()).await;
             let _ = stream.flush().await;
@@ -145,28 +156,41 @@ async fn test_google_calendar_ingestion_loop() {
     }).await.unwrap();
 
     // Run pipeline once
-    let stats = run_pipeline_once(&connector, vault.clone()).await.unwrap();
+    let pipeline = Pipeline::new(vault.clone()).with_processor(EvidenceClaims);
+    let stats = pipeline.run_once(&connector).await.unwrap();
     
     // Assert statistics:
-    // Only 1 event is confirmed (Upsert), which also emits a Claim and a Relationship.
-    // The other is cancelled (Tombstone), yielding 1 delta.
-    // Total deltas: 3 for the event + 1 tombstone = 4.
+    // Only 1 event is confirmed (Upsert); the other is cancelled (Tombstone).
+    // The confirmed one derives a Claim and its evidence Relationship.
     assert_eq!(stats.batches_applied, 1);
-    assert_eq!(stats.deltas_applied, 4);
-
-    let v = vault.lock().unwrap();
-    assert_eq!(v.item_count().unwrap(), 3); // 1 Event + 1 Claim + 1 Relationship
-
-    // Verify properties of the ingested event
-    let items = v.items("google-calendar").unwrap();
-    let event = items.iter().find(|i| i.source_id == "evt-id-1").unwrap();
-    assert_eq!(event.properties["summary"], "Project Review Meeting");
-    assert_eq!(event.properties["location"], "Conference Room A");
-    assert_eq!(event.kind, ItemKind::Event);
-
-    // Check that the cursor has been persisted in the vault
-    let cursor = v.cursor("google-calendar").unwrap().unwrap();
-    assert!(cursor.0.contains("mock-next-sync-token"));
+    assert_eq!(stats.deltas_applied, 2);
+    assert_eq!(stats.deltas_derived, 2);
+
+    {
+        let v = vault.lock().unwrap();
+        assert_eq!(v.item_count().unwrap(), 3); // 1 Event + 1 Claim + 1 Relationship
+
+        // Verify properties of the ingested event
+        let items = v.items("google-calendar").unwrap();
+        let event = items.iter().find(|i| i.source_id == "evt-id-1").unwrap();
+        assert_eq!(event.properties["summary"], "Project Review Meeting");
+        assert_eq!(event.properties["location"], "Conference Room A");
+        assert_eq!(event.kind, ItemKind::Event);
+
+        // Check that the cursor has been persisted in the vault
+        let cursor = v.cursor("google-calendar").unwrap().unwrap();
+        assert!(cursor.0.contains("mock-next-sync-token"));
+    }
+
+    // Incremental pass: the cancellation tombstones the event, and the
+    // claim that it took place goes with it, in the same transaction.
+    let stats = pipeline.run_once(&connector).await.unwrap();
+    assert_eq!(stats.deltas_applied, 1);
+    {
+        let v = vault.lock().unwrap();
+        assert_eq!(v.item_count().unwrap(), 0);
+        assert!(v.temporal_claims_with_evidence().unwrap().is_empty());
+    }
 
     mock_server_handle.await.unwrap();
 }
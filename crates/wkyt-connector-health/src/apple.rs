@@ -0,0 +1,163 @@
+//! Apple Health `export.xml` (unzipped from the Health app's
+//! `export.zip`), read as a stream of XML events: exports run to
+//! gigabytes, so records are handed out one at a time as the file is read.
+//!
+//! Each top-level `Record` with a numeric `value` is a sample — quantity
+//! types such as heart rate, steps or body mass. Category records (sleep
+//! analysis, stand hours), whose values are names, are skipped, as are
+//! workouts, activity summaries, and the records inside a `Correlation`
+//! (blood pressure, food), which the export also lists on their own. A
+//! file whose root is not `HealthData` — the `export_cda.xml` beside it,
+//! say — yields nothing.
+//!
+//! Apple Health writes percentages as fractions (`0.97` for 97 %); they
+//! are read as percentages. Times carry their offset
+//! (`2024-07-03 09:00:12 +0200`).
+
+use crate::{parse_time, Sample};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::{self, BufRead};
+
+pub(crate) struct Records<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    /// Whether the root element has been seen (and was `HealthData`).
+    started: bool,
+    /// How many `Correlation` elements the reader is inside.
+    correlation: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Records<R> {
+    pub(crate) fn new(input: R) -> Self {
+        Self { reader: Reader::from_reader(input), buf: Vec::new(), started: false, correlation: 0, done: false }
+    }
+
+    fn step(&mut self) -> io::Result<Option<Sample>> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf).map_err(xml_error)? {
+                Event::Start(e) | Event::Empty(e) if !self.started => {
+                    if e.local_name().as_ref() != b"HealthData" {
+                        return Ok(None);
+                    }
+                    self.started = true;
+                }
+                Event::Start(e) if e.local_name().as_ref() == b"Correlation" => self.correlation += 1,
+                Event::End(e) if e.local_name().as_ref() == b"Correlation" => {
+                    self.correlation = self.correlation.saturating_sub(1);
+                }
+                Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"Record" && self.correlation == 0 => {
+                    if let Some(sample) = record(&e)? {
+                        return Ok(Some(sample));
+                    }
+                }
+                Event::Eof => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Records<R> {
+    type Item = io::Result<Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.step().transpose();
+        if !matches!(next, Some(Ok(_))) {
+            self.done = true;
+        }
+        next
+    }
+}
+
+/// The sample a `Record` holds, `None` when it has no numeric value or
+/// no readable start.
+fn record(e: &BytesStart) -> io::Result<Option<Sample>> {
+    let attribute = |name: &str| -> io::Result<Option<String>> {
+        match e.try_get_attribute(name).map_err(|e| xml_error(e.into()))? {
+            Some(attribute) => {
+                let value = attribute.unescape_value().map_err(xml_error)?;
+                Ok(Some(value.trim().to_string()).filter(|v| !v.is_empty()))
+            }
+            None => Ok(None),
+        }
+    };
+    let (Some(kind), Some(value)) = (attribute("type")?, attribute("value")?) else { return Ok(None) };
+    let Ok(mut value) = value.parse::<f64>() else { return Ok(None) };
+    let Some(start) = attribute("startDate")?.as_deref().and_then(parse_time) else { return Ok(None) };
+    let end = attribute("endDate")?.as_deref().and_then(parse_time).unwrap_or(start);
+    let unit = attribute("unit")?.unwrap_or_default();
+    if unit == "%" {
+        value *= 100.0;
+    }
+    let mut sample = Sample::new(&kind, value, &unit, start, end);
+    sample.source = attribute("sourceName")?;
+    sample.device = attribute("device")?.as_deref().and_then(device_name);
+    sample.created = attribute("creationDate")?.as_deref().and_then(parse_time);
+    Ok(Some(sample))
+}
+
+/// The `name:` field of a device description,
+/// `<<HKDevice: 0x283a9c2d0>, name:Apple Watch, manufacturer:Apple Inc., …>`;
+/// the rest of it (an object address among it) varies between exports.
+fn device_name(device: &str) -> Option<String> {
+    let name = &device[device.find("name:")? + "name:".len()..];
+    let end = name.find(", ").or_else(|| name.find('>')).unwrap_or(name.len());
+    Some(name[..end].trim().to_string()).filter(|n| !n.is_empty())
+}
+
+fn xml_error(e: quick_xml::Error) -> io::Error {
+    match e {
+        quick_xml::Error::Io(e) => io::Error::new(e.kind(), e.to_string()),
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_top_level_records_are_samples() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <!DOCTYPE HealthData [ <!ELEMENT HealthData (ExportDate,Me,(Record|Correlation|Workout)*)> ]>
+            <HealthData locale="en_US">
+              <ExportDate value="2024-07-04 08:00:00 +0200"/>
+              <Record type="HKQuantityTypeIdentifierHeartRate" sourceName="Ana&#8217;s Watch"
+                device="&lt;&lt;HKDevice: 0x283a9c2d0&gt;, name:Apple Watch, manufacturer:Apple Inc., model:Watch&gt;"
+                unit="count/min" creationDate="2024-07-03 09:01:00 +0200"
+                startDate="2024-07-03 09:00:12 +0200" endDate="2024-07-03 09:00:12 +0200" value="72">
+                <MetadataEntry key="HKMetadataKeyHeartRateMotionContext" value="1"/>
+              </Record>
+              <Record type="HKCategoryTypeIdentifierSleepAnalysis" sourceName="Watch"
+                startDate="2024-07-02 23:00:00 +0200" endDate="2024-07-03 07:00:00 +0200"
+                value="HKCategoryValueSleepAnalysisAsleepCore"/>
+              <Correlation type="HKCorrelationTypeIdentifierBloodPressure" startDate="2024-07-03 10:00:00 +0200">
+                <Record type="HKQuantityTypeIdentifierBloodPressureSystolic" unit="mmHg" value="120"
+                  startDate="2024-07-03 10:00:00 +0200"/>
+              </Correlation>
+              <Record type="HKQuantityTypeIdentifierOxygenSaturation" sourceName="Watch" unit="%" value="0.97"
+                startDate="2024-07-03 10:05:00 +0200"/>
+            </HealthData>"#;
+        let samples: Vec<Sample> = Records::new(xml.as_bytes()).collect::<io::Result<_>>().unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].metric, "heart_rate");
+        assert_eq!(samples[0].source.as_deref(), Some("Ana’s Watch"));
+        assert_eq!(samples[0].device.as_deref(), Some("Apple Watch"));
+        assert_eq!(samples[0].start.to_rfc3339(), "2024-07-03T09:00:12+02:00");
+        assert_eq!(samples[0].created.unwrap().to_rfc3339(), "2024-07-03T09:01:00+02:00");
+        assert_eq!((samples[1].metric.as_str(), samples[1].value), ("oxygen_saturation", 97.0));
+        assert_eq!(samples[1].end, samples[1].start);
+
+        let cda = r#"<ClinicalDocument><Record type="x" value="1" startDate="2024-07-03"/></ClinicalDocument>"#;
+        assert_eq!(Records::new(cda.as_bytes()).count(), 0);
+        let truncated = r#"<HealthData><Record type="HKQuantityTypeIdentifierStepCount" value="1"#;
+        let last = Records::new(truncated.as_bytes()).last().unwrap();
+        assert_eq!(last.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
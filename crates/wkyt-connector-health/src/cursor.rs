@@ -0,0 +1,38 @@
+//! The health importer's cursor: the JSON document inside its
+//! `SyncToken`.
+//!
+//! Per export file, the mtime and size it was read at, the metrics that
+//! were aggregated by day when it was, and the latest creation time among
+//! its samples (Apple Health records one; CSV rows do not):
+//!
+//! ```json
+//! { "files": { "apple_health_export/export.xml":
+//!                { "mtime_ms": 1729246500000, "size": 2147483648,
+//!                  "daily": ["heart_rate"], "created_through": "2024-10-18T08:55:00+02:00" } } }
+//! ```
+//!
+//! A file is read again when its mtime or size changed, or when the
+//! aggregated metrics did. A changed file with the same settings only has
+//! the samples created since `created_through` (less a week's slack, for
+//! devices that sync late) written again, and the days holding them;
+//! with other settings, everything is, and what the old settings wrote
+//! differently is retired.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct HealthCursor {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) files: BTreeMap<String, FileState>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FileState {
+    pub(crate) mtime_ms: i64,
+    pub(crate) size: u64,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub(crate) daily: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) created_through: Option<String>,
+}
@@ -0,0 +1,633 @@
+//! Health and fitness metrics importer: reads an Apple Health export
+//! ([`apple`]) and metrics CSV files ([`table`]) — a single file, or a
+//! folder of them — as [`ItemKind::Metric`] items.
+//!
+//! - A sample is keyed `sample:{metric}:{source}:{start}`, with `/{end}`
+//!   when it spans an interval; times in RFC 3339 UTC. Its properties:
+//!   `metric`, `value` and `unit` (normalized, see [`units`]), the
+//!   `original` `{value, unit}` when conversion changed them, `start` and
+//!   `end` with the offset they were recorded at, `source`, `device` and
+//!   the `export_file`. Its timestamp is its start.
+//! - A metric aggregated by day ([`HealthMetrics::with_daily`]) is one
+//!   item per local day, source and unit instead, keyed
+//!   `daily:{metric}:{unit}:{date}:{source}`: its `value` is the day's sum
+//!   for cumulative metrics (counts, energy, distances, dietary intake,
+//!   activity times) and the mean otherwise (`aggregation` says which),
+//!   with `min`, `max`, `count`, and the first sample's `start` and the
+//!   last one's `end`. Sources are kept apart because a phone and a
+//!   watch both count the same steps. A year of heart rate is then 365
+//!   items rather than a few hundred thousand.
+//!
+//! Ids are derived from the data alone, so re-importing an export, or a
+//! newer one holding the same samples, lands on the same items. Like the
+//! statement importer this is an import, not a mirror: a deleted file
+//! retires nothing, and neither does a sample deleted in the Health app.
+//!
+//! Exports are streamed, never held whole; batches are handed out as the
+//! file is read, and only its last one carries the cursor (layout in
+//! [`cursor`]). A file that turns out malformed partway (still being
+//! copied, say) is abandoned and read again by the next pass.
+
+mod apple;
+mod cursor;
+mod table;
+mod units;
+
+use chrono::{DateTime, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, SecondsFormat, Utc};
+use cursor::{FileState, HealthCursor};
+use futures_util::stream;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet, HashSet, VecDeque};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use wkyt_core::batches::{self, Step};
+use wkyt_core::files::{mtime_ms, retryable, Found, Walk};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+/// How far before a file's `created_through` a re-read starts writing
+/// samples again: devices sync into Apple Health late, with their own
+/// creation times.
+const CREATION_SLACK_DAYS: i64 = 7;
+/// Stands for every metric in [`HealthMetrics::with_daily`].
+pub const ALL_METRICS: &str = "*";
+
+pub struct HealthMetrics {
+    id: String,
+    path: PathBuf,
+    batch_size: usize,
+    /// Metrics aggregated by day, normalized.
+    daily: BTreeSet<String>,
+}
+
+/// One measurement, its name and unit normalized.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Sample {
+    pub(crate) metric: String,
+    pub(crate) value: f64,
+    pub(crate) unit: String,
+    /// As written, when normalizing changed it.
+    pub(crate) original: Option<(f64, String)>,
+    pub(crate) start: DateTime<FixedOffset>,
+    pub(crate) end: DateTime<FixedOffset>,
+    pub(crate) source: Option<String>,
+    pub(crate) device: Option<String>,
+    /// When the source recorded it, where known.
+    pub(crate) created: Option<DateTime<FixedOffset>>,
+}
+
+impl Sample {
+    pub(crate) fn new(
+        metric: &str,
+        value: f64,
+        unit: &str,
+        start: DateTime<FixedOffset>,
+        end: DateTime<FixedOffset>,
+    ) -> Self {
+        let (normalized, canonical) = units::normalize(value, unit);
+        let original = (canonical != unit || normalized != value).then(|| (value, unit.to_string()));
+        Self {
+            metric: units::metric_name(metric),
+            value: normalized,
+            unit: canonical,
+            original,
+            start,
+            end,
+            source: None,
+            device: None,
+            created: None,
+        }
+    }
+}
+
+/// A metric's samples on one local day, from one source, in one unit.
+type DayKey = (String, String, NaiveDate, String);
+
+struct Day {
+    sum: f64,
+    min: f64,
+    max: f64,
+    count: u64,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    /// Whether it holds a sample new since the last read.
+    dirty: bool,
+}
+
+impl HealthMetrics {
+    /// `path` is an Apple Health `export.xml`, a metrics `.csv`, or a
+    /// folder holding them.
+    pub fn new(id: impl Into<String>, path: PathBuf) -> Self {
+        Self { id: id.into(), path, batch_size: DEFAULT_BATCH_SIZE, daily: BTreeSet::new() }
+    }
+
+    /// Items per batch (default 500).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Aggregate `metric` (by its normalized name, such as `heart_rate`,
+    /// or [`ALL_METRICS`]) into one item per day instead of one per sample
+    /// (repeatable). Changing the set re-reads every file, retiring the
+    /// items the old setting wrote.
+    pub fn with_daily(mut self, metric: &str) -> Self {
+        let metric = if metric == ALL_METRICS { metric.to_string() } else { units::metric_name(metric) };
+        self.daily.insert(metric);
+        self
+    }
+
+    /// The folder paths are relative to, and the export files under it
+    /// (just `path` when that is a file).
+    fn scan(&self) -> Result<(PathBuf, Vec<Found>), SyncError> {
+        let meta = std::fs::metadata(&self.path)
+            .map_err(|e| SyncError::Fatal { source: format!("health path {:?}: {e}", self.path).into() })?;
+        if meta.is_dir() {
+            let found = Walk::new().files(&self.path, wanted).map_err(retryable)?;
+            return Ok((self.path.clone(), found));
+        }
+        let name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        if !wanted(&name) {
+            let source = format!("{:?} is neither a folder nor an XML / CSV file", self.path);
+            return Err(SyncError::Fatal { source: source.into() });
+        }
+        let base = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok((base, vec![Found { path: name, mtime_ms: mtime_ms(&meta), size: meta.len() }]))
+    }
+
+    /// Decide what to read: files new, changed, or read with other
+    /// settings. Files gone are dropped from the cursor; the flag says
+    /// whether that needs saving.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(PathBuf, Vec<Found>, HealthCursor, bool), SyncError> {
+        let mut prev: HealthCursor = match cursor {
+            None => HealthCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        let (base, found) = self.scan()?;
+        let present: HashSet<&str> = found.iter().map(|f| f.path.as_str()).collect();
+        let before = prev.files.len();
+        prev.files.retain(|path, _| present.contains(path.as_str()));
+        let unsaved = prev.files.len() != before;
+        let changed = found
+            .into_iter()
+            .filter(|f| match prev.files.get(&f.path) {
+                Some(state) => state.mtime_ms != f.mtime_ms || state.size != f.size || state.daily != self.daily,
+                None => true,
+            })
+            .collect();
+        Ok((base, changed, prev, unsaved))
+    }
+
+    /// Start reading `found`, which `prev` describes as last read.
+    fn open(&self, base: &Path, found: Found, prev: Option<&FileState>) -> Result<Pass, SyncError> {
+        let file = File::open(base.join(&found.path)).map_err(retryable)?;
+        let samples: Box<dyn Iterator<Item = io::Result<Sample>> + Send> =
+            if found.path.to_ascii_lowercase().ends_with(".xml") {
+                Box::new(apple::Records::new(BufReader::new(file)))
+            } else {
+                match table::open(file).map_err(retryable)? {
+                    Some(rows) => Box::new(rows),
+                    // Some other CSV: nothing to yield.
+                    None => Box::new(std::iter::empty()),
+                }
+            };
+        let created_through = prev.and_then(|s| s.created_through.as_deref()).and_then(parse_time);
+        let (since, before) = match prev {
+            Some(state) if state.daily != self.daily => (None, Some(state.daily.clone())),
+            _ => (created_through.map(|t| t - Duration::days(CREATION_SLACK_DAYS)), None),
+        };
+        Ok(Pass {
+            found,
+            samples,
+            since,
+            before,
+            created_through,
+            days: BTreeMap::new(),
+            retired: BTreeSet::new(),
+            deltas: Vec::new(),
+        })
+    }
+
+    fn sample_item(&self, file: &str, sample: &Sample) -> Item {
+        let properties = json!({
+            "metric": sample.metric,
+            "value": sample.value,
+            "unit": sample.unit,
+            "original": sample.original.as_ref().map(|(value, unit)| json!({ "value": value, "unit": unit })),
+            "start": local(sample.start),
+            "end": local(sample.end),
+            "source": sample.source,
+            "device": sample.device,
+            "export_file": file,
+        });
+        Item::new(sample_id(sample), &self.id, ItemKind::Metric, sample.start.with_timezone(&Utc), properties)
+    }
+
+    fn day_item(&self, file: &str, key: &DayKey, day: &Day) -> Item {
+        let (metric, source, date, unit) = key;
+        let (aggregation, value) = match cumulative(metric, unit) {
+            true => ("sum", day.sum),
+            false => ("mean", day.sum / day.count as f64),
+        };
+        let properties = json!({
+            "metric": metric,
+            "aggregation": aggregation,
+            "value": value,
+            "unit": unit,
+            "date": date.to_string(),
+            "min": day.min,
+            "max": day.max,
+            "count": day.count,
+            "start": local(day.start),
+            "end": local(day.end),
+            "source": Some(source).filter(|s| !s.is_empty()),
+            "export_file": file,
+        });
+        Item::new(day_id(key), &self.id, ItemKind::Metric, day.start.with_timezone(&Utc), properties)
+    }
+}
+
+/// Whether `metric` is aggregated by day in `daily`.
+fn is_daily(daily: &BTreeSet<String>, metric: &str) -> bool {
+    daily.contains(ALL_METRICS) || daily.contains(metric)
+}
+
+/// Whether a day's samples of `metric` add up (steps) rather than average
+/// out (heart rate).
+fn cumulative(metric: &str, unit: &str) -> bool {
+    const ACTIVITY_TIMES: [&str; 4] =
+        ["apple_exercise_time", "apple_stand_time", "apple_move_time", "time_in_daylight"];
+    matches!(unit, "count" | "kcal")
+        || metric.starts_with("distance")
+        || metric.starts_with("dietary_")
+        || ACTIVITY_TIMES.contains(&metric)
+}
+
+fn sample_id(sample: &Sample) -> String {
+    let source = sample.source.as_deref().unwrap_or_default();
+    let (start, end) = (sample.start.with_timezone(&Utc), sample.end.with_timezone(&Utc));
+    match start == end {
+        true => format!("sample:{}:{source}:{}", sample.metric, instant(start)),
+        false => format!("sample:{}:{source}:{}/{}", sample.metric, instant(start), instant(end)),
+    }
+}
+
+fn day_key(sample: &Sample) -> DayKey {
+    let source = sample.source.clone().unwrap_or_default();
+    (sample.metric.clone(), source, sample.start.date_naive(), sample.unit.clone())
+}
+
+fn day_id((metric, source, date, unit): &DayKey) -> String {
+    format!("daily:{metric}:{unit}:{date}:{source}")
+}
+
+/// One export file being read.
+struct Pass {
+    found: Found,
+    samples: Box<dyn Iterator<Item = io::Result<Sample>> + Send>,
+    /// Samples created before this were written by an earlier read.
+    since: Option<DateTime<FixedOffset>>,
+    /// The metrics aggregated when the file was last read, when that
+    /// differs from now.
+    before: Option<BTreeSet<String>>,
+    /// The latest creation time seen, starting from the cursor's.
+    created_through: Option<DateTime<FixedOffset>>,
+    days: BTreeMap<DayKey, Day>,
+    /// Day items the old settings wrote for metrics now kept as samples.
+    retired: BTreeSet<String>,
+    /// Built, not yet batched.
+    deltas: Vec<Delta>,
+}
+
+impl Pass {
+    fn add(&mut self, importer: &HealthMetrics, sample: Sample) {
+        let new = match (self.since, sample.created) {
+            (Some(since), Some(created)) => created > since,
+            _ => true,
+        };
+        if let Some(created) = sample.created {
+            self.created_through = self.created_through.max(Some(created));
+        }
+        let was_daily = self.before.as_ref().map(|before| is_daily(before, &sample.metric));
+        if !is_daily(&importer.daily, &sample.metric) {
+            if new {
+                self.deltas.push(Delta::Upsert(importer.sample_item(&self.found.path, &sample)));
+            }
+            if was_daily == Some(true) {
+                self.retired.insert(day_id(&day_key(&sample)));
+            }
+            return;
+        }
+        if was_daily == Some(false) {
+            self.deltas.push(Delta::Tombstone { source_id: sample_id(&sample) });
+        }
+        let day = self.days.entry(day_key(&sample)).or_insert(Day {
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+            count: 0,
+            start: sample.start,
+            end: sample.end,
+            dirty: false,
+        });
+        day.sum += sample.value;
+        day.min = day.min.min(sample.value);
+        day.max = day.max.max(sample.value);
+        day.count += 1;
+        day.start = day.start.min(sample.start);
+        day.end = day.end.max(sample.end);
+        day.dirty |= new;
+    }
+
+    /// The file read through: its day items and retirements, and its
+    /// state for the cursor.
+    fn finish(mut self, importer: &HealthMetrics) -> (Found, Vec<Delta>, FileState) {
+        for (key, day) in &self.days {
+            if day.dirty {
+                self.deltas.push(Delta::Upsert(importer.day_item(&self.found.path, key, day)));
+            }
+        }
+        self.deltas.extend(self.retired.into_iter().map(|source_id| Delta::Tombstone { source_id }));
+        let state = FileState {
+            mtime_ms: self.found.mtime_ms,
+            size: self.found.size,
+            daily: importer.daily.clone(),
+            created_through: self.created_through.map(local),
+        };
+        (self.found, self.deltas, state)
+    }
+}
+
+/// Whether a file named `name` may be a health export.
+fn wanted(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    lower.ends_with(".xml") || lower.ends_with(".csv")
+}
+
+/// The sync stream's batches, built lazily as it is polled: one file at
+/// a time, its last batch carrying the cursor.
+struct Batches<'a> {
+    importer: &'a HealthMetrics,
+    base: PathBuf,
+    files: std::vec::IntoIter<Found>,
+    pass: Option<Pass>,
+    cursor: HealthCursor,
+    /// Whether the cursor changed without a file to carry it.
+    unsaved: bool,
+    /// Built, not yet handed out.
+    outbox: VecDeque<DeltaBatch>,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        loop {
+            if let Some(batch) = self.outbox.pop_front() {
+                return Ok(Some(batch));
+            }
+            let Some(pass) = &mut self.pass else {
+                match self.files.next() {
+                    Some(found) => {
+                        let prev = self.cursor.files.get(&found.path);
+                        self.pass = Some(importer.open(&self.base, found, prev)?);
+                    }
+                    None if std::mem::take(&mut self.unsaved) => self.enqueue(Vec::new(), true),
+                    None => return Ok(None),
+                }
+                continue;
+            };
+            match pass.samples.next() {
+                Some(Ok(sample)) => {
+                    pass.add(importer, sample);
+                    // Batches fill as the file is read; one is held back
+                    // until a delta beyond it shows it is not the last.
+                    if pass.deltas.len() > importer.batch_size {
+                        let deltas: Vec<Delta> = pass.deltas.drain(..importer.batch_size).collect();
+                        self.enqueue(deltas, false);
+                    }
+                }
+                // Malformed: left as it was, for the next pass.
+                Some(Err(e)) if e.kind() == io::ErrorKind::InvalidData => self.pass = None,
+                Some(Err(e)) => return Err(retryable(e)),
+                None => {
+                    let (found, deltas, state) = self.pass.take().expect("a pass is open").finish(importer);
+                    self.cursor.files.insert(found.path, state);
+                    self.unsaved = false;
+                    self.enqueue(deltas, true);
+                }
+            }
+        }
+    }
+}
+
+impl Batches<'_> {
+
+    /// Queue `deltas` in batches; with `save`, the last (or an empty one)
+    /// carries the cursor as it is now.
+    fn enqueue(&mut self, deltas: Vec<Delta>, save: bool) {
+        let importer = self.importer;
+        let mut chunks: Vec<Vec<Delta>> = deltas.chunks(importer.batch_size).map(<[Delta]>::to_vec).collect();
+        if chunks.is_empty() && save {
+            chunks.push(Vec::new());
+        }
+        let last = chunks.len().saturating_sub(1);
+        for (i, deltas) in chunks.into_iter().enumerate() {
+            let cursor = (save && i == last).then(|| {
+                SyncToken(serde_json::to_string(&self.cursor).expect("cursor serialization is infallible"))
+            });
+            self.outbox.push_back(DeltaBatch { connector_id: importer.id.clone(), deltas, cursor });
+        }
+    }
+}
+
+/// A time in an export: RFC 3339, Apple Health's `2024-07-03 09:00:12
+/// +0200`, or without an offset (taken as UTC), down to a bare date.
+pub(crate) fn parse_time(text: &str) -> Option<DateTime<FixedOffset>> {
+    let text = text.trim();
+    DateTime::parse_from_rfc3339(text)
+        .or_else(|_| DateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S %z"))
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+                .or_else(|| NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().map(|d| d.and_time(NaiveTime::MIN)))
+                .map(|t| t.and_utc().fixed_offset())
+        })
+}
+
+fn instant(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn local(t: DateTime<FixedOffset>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[async_trait::async_trait]
+impl Connector for HealthMetrics {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((base, files, cursor, unsaved)) => batches::stream(Batches {
+                importer: self,
+                base,
+                files: files.into_iter(),
+                pass: None,
+                cursor,
+                unsaved,
+                outbox: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, tombstones, upserts};
+    use futures_util::StreamExt;
+    use std::fs;
+
+    /// An Apple Health export of `records`: (type, unit, value, start,
+    /// creation time), from one watch.
+    fn export(records: &[(&str, &str, &str, &str, &str)]) -> String {
+        let records: String = records
+            .iter()
+            .map(|(kind, unit, value, start, created)| {
+                format!(
+                    r#"<Record type="HKQuantityTypeIdentifier{kind}" sourceName="Watch" unit="{unit}" value="{value}"
+                         creationDate="{created}" startDate="{start}" endDate="{start}"/>"#
+                )
+            })
+            .collect();
+        format!(r#"<?xml version="1.0"?><HealthData locale="en_US">{records}</HealthData>"#)
+    }
+
+    const RECORDS: [(&str, &str, &str, &str, &str); 4] = [
+        ("HeartRate", "count/min", "72", "2024-07-03 09:00:00 +0200", "2024-07-03 09:01:00 +0200"),
+        ("HeartRate", "count/min", "80", "2024-07-03 09:05:00 +0200", "2024-07-03 09:06:00 +0200"),
+        ("BodyMass", "lb", "165", "2024-07-03 07:30:00 +0200", "2024-07-03 07:30:00 +0200"),
+        ("HeartRate", "count/min", "90", "2024-07-20 18:00:00 +0200", "2024-07-20 18:00:30 +0200"),
+    ];
+
+    #[tokio::test]
+    async fn apple_exports_stream_samples_and_newer_exports_write_only_what_is_new() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.xml");
+        fs::write(&path, export(&RECORDS)).unwrap();
+        let c = HealthMetrics::new("health", dir.path().to_path_buf()).with_batch_size(2);
+
+        let batches = drain(&c, None).await;
+        assert_eq!(batches.len(), 2, "streamed in batches of two");
+        assert!(batches[0].cursor.is_none() && batches[1].cursor.is_some());
+        assert_eq!(upserts(&batches).len(), 4);
+        let items = upserts(&batches);
+        let ids: Vec<&str> = items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(
+            ids[..3],
+            [
+                "sample:heart_rate:Watch:2024-07-03T07:00:00Z",
+                "sample:heart_rate:Watch:2024-07-03T07:05:00Z",
+                "sample:body_mass:Watch:2024-07-03T05:30:00Z",
+            ]
+        );
+        assert!(items.iter().all(|i| i.kind == ItemKind::Metric));
+        assert_eq!(items[0].properties["start"], "2024-07-03T09:00:00+02:00");
+        assert_eq!(items[0].properties["original"], serde_json::Value::Null);
+        let weight = &items[2].properties;
+        assert!((weight["value"].as_f64().unwrap() - 74.842_741).abs() < 1e-6);
+        assert_eq!(weight["unit"], "kg");
+        assert_eq!(weight["original"], json!({ "value": 165.0, "unit": "lb" }));
+
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty(), "nothing changed");
+
+        // A newer export holds the old samples and one more: that one is
+        // written, with those created in the week before the last read.
+        let newer = ("StepCount", "count", "412", "2024-07-22 08:00:00 +0200", "2024-07-22 08:10:00 +0200");
+        let mut records = RECORDS.to_vec();
+        records.push(newer);
+        fs::write(&path, export(&records)).unwrap();
+        let batches = drain(&c, cursor).await;
+        let ids: Vec<&str> = upserts(&batches).iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            ["sample:heart_rate:Watch:2024-07-20T16:00:00Z", "sample:step_count:Watch:2024-07-22T06:00:00Z"]
+        );
+
+        // One being copied in is left for the next pass.
+        fs::write(&path, &export(&records)[..200]).unwrap();
+        let batches = drain(&c, last_cursor(&batches)).await;
+        assert!(upserts(&batches).is_empty());
+        assert!(last_cursor(&batches).is_none());
+    }
+
+    #[tokio::test]
+    async fn daily_aggregates_replace_samples_and_switching_back_retires_them() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("metrics.csv"),
+            "metric,value,unit,timestamp,source\n\
+             heart rate,60,bpm,2024-07-03T08:00:00+02:00,Strap\n\
+             heart rate,90,bpm,2024-07-03T20:00:00+02:00,Strap\n\
+             heart rate,70,bpm,2024-07-04T01:00:00+02:00,Strap\n\
+             steps,1200,count,2024-07-03T09:00:00+02:00,Phone\n\
+             steps,800,count,2024-07-03T18:00:00+02:00,Phone\n\
+             steps,1900,count,2024-07-03T18:00:00+02:00,Watch\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("statement.csv"), "Date,Amount,Payee\n2024-07-03,-12.50,Cafe\n").unwrap();
+        let batches = drain(&HealthMetrics::new("health", dir.path().to_path_buf()), None).await;
+        assert_eq!(upserts(&batches).len(), 6, "the statement is no metrics file");
+        let cursor = last_cursor(&batches);
+
+        let c = HealthMetrics::new("health", dir.path().to_path_buf()).with_daily("Heart Rate").with_daily("steps");
+        let batches = drain(&c, cursor).await;
+        assert_eq!(tombstones(&batches).len(), 6);
+        assert!(tombstones(&batches).contains(&"sample:heart_rate:Strap:2024-07-03T06:00:00Z"));
+        let items = upserts(&batches);
+        let ids: Vec<&str> = items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "daily:heart_rate:count/min:2024-07-03:Strap",
+                "daily:heart_rate:count/min:2024-07-04:Strap",
+                "daily:steps:count:2024-07-03:Phone",
+                "daily:steps:count:2024-07-03:Watch",
+            ]
+        );
+        let day = &items[0].properties;
+        assert_eq!((day["aggregation"].as_str(), day["value"].as_f64()), (Some("mean"), Some(75.0)));
+        assert_eq!((day["min"].as_f64(), day["max"].as_f64()), (Some(60.0), Some(90.0)));
+        assert_eq!(day["count"], 2);
+        assert_eq!(day["start"], "2024-07-03T08:00:00+02:00");
+        assert_eq!(items[0].timestamp.to_rfc3339(), "2024-07-03T06:00:00+00:00");
+        let steps = &items[2].properties;
+        assert_eq!((steps["aggregation"].as_str(), steps["value"].as_f64()), (Some("sum"), Some(2000.0)));
+
+        // Steps back to samples: their days are retired, heart rate stays.
+        let c = HealthMetrics::new("health", dir.path().to_path_buf()).with_daily("heart_rate");
+        let batches = drain(&c, last_cursor(&batches)).await;
+        assert_eq!(
+            tombstones(&batches),
+            ["daily:steps:count:2024-07-03:Phone", "daily:steps:count:2024-07-03:Watch"]
+        );
+        assert_eq!(upserts(&batches).len(), 3 + 2);
+
+        let results: Vec<_> = c.sync(Some(SyncToken("nope".into()))).collect().await;
+        assert!(matches!(results.as_slice(), [Err(SyncError::ResyncRequired)]));
+        let missing = HealthMetrics::new("health", dir.path().join("missing"));
+        let results: Vec<_> = missing.sync(None).collect().await;
+        assert!(matches!(results.as_slice(), [Err(SyncError::Fatal { .. })]));
+    }
+}
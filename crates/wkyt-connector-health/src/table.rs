@@ -0,0 +1,139 @@
+//! Generic metrics CSV: one sample per row, columns found by header
+//! (matched trimmed and case-insensitively):
+//!
+//! ```text
+//! metric,value,unit,timestamp,source
+//! Heart rate,72,bpm,2024-07-03T09:00:00+02:00,Chest strap
+//! weight,165,lb,2024-07-03,
+//! ```
+//!
+//! `metric` (or `name`, `type`), `value` and `timestamp` (or `time`,
+//! `date`, `start`) are required; `unit`, `end` and `source` are not. A
+//! file without the required headers is some other CSV and yields
+//! nothing. Rows whose value is not a number or whose time does not parse
+//! are skipped.
+
+use crate::{parse_time, Sample};
+use std::fs::File;
+use std::io;
+
+/// Header names each column is found by, in preference order.
+const METRIC: &[&str] = &["metric", "name", "type"];
+const VALUE: &[&str] = &["value"];
+const TIME: &[&str] = &["timestamp", "time", "date", "start"];
+const END: &[&str] = &["end"];
+const UNIT: &[&str] = &["unit", "units"];
+const SOURCE: &[&str] = &["source"];
+
+pub(crate) struct Rows {
+    reader: csv::Reader<File>,
+    record: csv::StringRecord,
+    metric: usize,
+    value: usize,
+    time: usize,
+    end: Option<usize>,
+    unit: Option<usize>,
+    source: Option<usize>,
+    done: bool,
+}
+
+/// The rows of a metrics CSV, `None` when `file` is not one.
+pub(crate) fn open(file: File) -> io::Result<Option<Rows>> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).trim(csv::Trim::All).from_reader(file);
+    let headers = match reader.headers() {
+        Ok(headers) => headers.iter().map(str::to_lowercase).collect::<Vec<_>>(),
+        Err(e) if e.is_io_error() => return Err(io_error(e)),
+        Err(_) => return Ok(None),
+    };
+    let find = |names: &[&str]| names.iter().find_map(|n| headers.iter().position(|h| h == n));
+    let (Some(metric), Some(value), Some(time)) = (find(METRIC), find(VALUE), find(TIME)) else { return Ok(None) };
+    Ok(Some(Rows {
+        reader,
+        record: csv::StringRecord::new(),
+        metric,
+        value,
+        time,
+        end: find(END),
+        unit: find(UNIT),
+        source: find(SOURCE),
+        done: false,
+    }))
+}
+
+impl Rows {
+    /// The sample on the current row, if it has one.
+    fn sample(&self) -> Option<Sample> {
+        let field = |i: usize| self.record.get(i).filter(|f| !f.is_empty());
+        let metric = field(self.metric)?;
+        let value = field(self.value)?.parse::<f64>().ok().filter(|v| v.is_finite())?;
+        let start = parse_time(field(self.time)?)?;
+        let end = self.end.and_then(field).and_then(parse_time).unwrap_or(start);
+        let unit = self.unit.and_then(field).unwrap_or_default();
+        let mut sample = Sample::new(metric, value, unit, start, end);
+        sample.source = self.source.and_then(field).map(String::from);
+        Some(sample)
+    }
+}
+
+impl Iterator for Rows {
+    type Item = io::Result<Sample>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            match self.reader.read_record(&mut self.record) {
+                Ok(true) => {
+                    if let Some(sample) = self.sample() {
+                        return Some(Ok(sample));
+                    }
+                }
+                Ok(false) => self.done = true,
+                Err(e) if e.is_io_error() => {
+                    self.done = true;
+                    return Some(Err(io_error(e)));
+                }
+                // A malformed row (bad UTF-8, say) is skipped like any
+                // other unreadable one.
+                Err(_) => {}
+            }
+        }
+        None
+    }
+}
+
+fn io_error(e: csv::Error) -> io::Error {
+    match e.into_kind() {
+        csv::ErrorKind::Io(e) => e,
+        kind => io::Error::new(io::ErrorKind::InvalidData, format!("{kind:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn rows_by_header_with_bad_ones_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics.csv");
+        fs::write(
+            &path,
+            "Source, Timestamp ,Metric,Value,Unit\n\
+             Strap,2024-07-03T09:00:00+02:00,Heart rate,72,bpm\n\
+             ,2024-07-03,weight,165,lb\n\
+             ,2024-07-03,weight,n/a,lb\n\
+             ,yesterday,weight,80,kg\n",
+        )
+        .unwrap();
+        let rows: Vec<Sample> = open(File::open(&path).unwrap()).unwrap().unwrap().collect::<io::Result<_>>().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!((rows[0].metric.as_str(), rows[0].unit.as_str()), ("heart_rate", "count/min"));
+        assert_eq!(rows[0].source.as_deref(), Some("Strap"));
+        assert!((rows[1].value - 74.842_741).abs() < 1e-6);
+        assert_eq!(rows[1].start.to_rfc3339(), "2024-07-03T00:00:00+00:00");
+        assert_eq!(rows[1].source, None);
+
+        fs::write(&path, "Date,Amount,Payee\n2024-07-03,-12.50,Cafe\n").unwrap();
+        assert!(open(File::open(&path).unwrap()).unwrap().is_none());
+    }
+}
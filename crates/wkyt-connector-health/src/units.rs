@@ -0,0 +1,149 @@
+//! Metric names and units, normalized so that the same quantity reads the
+//! same whichever export it came from.
+//!
+//! Names are snake case: `HKQuantityTypeIdentifierHeartRate`,
+//! `HeartRate` and `Heart rate` are all `heart_rate`. Values are converted
+//! to one unit per dimension — kilograms, metres, kilocalories, seconds,
+//! degrees Celsius, litres, metres per second, beats (`count`) per minute,
+//! and mg/dL for blood glucose. A unit not listed here is kept as written,
+//! value unchanged.
+
+/// The Apple Health prefixes stripped from sample types.
+const APPLE_PREFIXES: [&str; 2] = ["HKQuantityTypeIdentifier", "HKCategoryTypeIdentifier"];
+
+/// Unit aliases and how to convert from them: (unit, canonical unit,
+/// factor). Temperatures, which need an offset, are handled apart.
+const FACTORS: &[(&str, &str, f64)] = &[
+    // Mass
+    ("kg", "kg", 1.0),
+    ("g", "kg", 1e-3),
+    ("mg", "kg", 1e-6),
+    ("mcg", "kg", 1e-9),
+    ("lb", "kg", 0.453_592_37),
+    ("lbs", "kg", 0.453_592_37),
+    ("oz", "kg", 0.028_349_523_125),
+    ("st", "kg", 6.350_293_18),
+    // Length
+    ("m", "m", 1.0),
+    ("km", "m", 1e3),
+    ("cm", "m", 1e-2),
+    ("mm", "m", 1e-3),
+    ("mi", "m", 1_609.344),
+    ("yd", "m", 0.9144),
+    ("ft", "m", 0.3048),
+    ("in", "m", 0.0254),
+    // Energy
+    ("kcal", "kcal", 1.0),
+    ("Cal", "kcal", 1.0),
+    ("cal", "kcal", 1e-3),
+    ("kJ", "kcal", 1.0 / 4.184),
+    ("J", "kcal", 1.0 / 4_184.0),
+    // Time
+    ("s", "s", 1.0),
+    ("sec", "s", 1.0),
+    ("ms", "s", 1e-3),
+    ("min", "s", 60.0),
+    ("h", "s", 3_600.0),
+    ("hr", "s", 3_600.0),
+    ("d", "s", 86_400.0),
+    // Volume
+    ("L", "L", 1.0),
+    ("l", "L", 1.0),
+    ("mL", "L", 1e-3),
+    ("ml", "L", 1e-3),
+    ("fl_oz_us", "L", 0.029_573_529_562_5),
+    ("cup_us", "L", 0.236_588_236_5),
+    // Speed
+    ("m/s", "m/s", 1.0),
+    ("km/hr", "m/s", 1.0 / 3.6),
+    ("km/h", "m/s", 1.0 / 3.6),
+    ("mi/hr", "m/s", 0.447_04),
+    ("mph", "m/s", 0.447_04),
+    // Rates
+    ("count/min", "count/min", 1.0),
+    ("bpm", "count/min", 1.0),
+    ("beats/min", "count/min", 1.0),
+    ("count/s", "count/min", 60.0),
+    // Blood glucose
+    ("mg/dL", "mg/dL", 1.0),
+    ("mmol/L", "mg/dL", GLUCOSE_MOLAR_MASS / 10.0),
+];
+
+/// g/mol; what a bare `mmol/L` blood glucose reading is converted with.
+const GLUCOSE_MOLAR_MASS: f64 = 180.155_88;
+
+/// `name` as a metric name: an Apple Health type loses its prefix, and
+/// camel case and separators become snake case.
+pub(crate) fn metric_name(name: &str) -> String {
+    let name = APPLE_PREFIXES.iter().find_map(|p| name.trim().strip_prefix(p)).unwrap_or(name.trim());
+    let chars: Vec<char> = name.chars().collect();
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !out.is_empty() && !out.ends_with('_') {
+                out.push('_');
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 && !out.ends_with('_') {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                out.push('_');
+            }
+        }
+        out.extend(c.to_lowercase());
+    }
+    out.trim_end_matches('_').to_string()
+}
+
+/// `value` in `unit`, converted to its dimension's canonical unit.
+pub(crate) fn normalize(value: f64, unit: &str) -> (f64, String) {
+    let unit = unit.trim();
+    if let Some((_, canonical, factor)) = FACTORS.iter().find(|(alias, ..)| *alias == unit) {
+        return (value * factor, canonical.to_string());
+    }
+    match unit {
+        "degC" | "°C" | "C" => return (value, "degC".into()),
+        "degF" | "°F" | "F" => return ((value - 32.0) * 5.0 / 9.0, "degC".into()),
+        "K" => return (value - 273.15, "degC".into()),
+        _ => {}
+    }
+    // Apple Health's molar units carry the molar mass: `mmol<180.15588>/L`.
+    if let Some(mass) = unit.strip_prefix("mmol<").and_then(|u| u.strip_suffix(">/L")) {
+        if let Ok(mass) = mass.parse::<f64>() {
+            return (value * mass / 10.0, "mg/dL".into());
+        }
+    }
+    (value, unit.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f64, String), value: f64, unit: &str) -> bool {
+        (a.0 - value).abs() < 1e-6 && a.1 == unit
+    }
+
+    #[test]
+    fn names_from_apple_types_and_free_text_agree() {
+        assert_eq!(metric_name("HKQuantityTypeIdentifierHeartRate"), "heart_rate");
+        assert_eq!(metric_name("HeartRate"), "heart_rate");
+        assert_eq!(metric_name(" Heart rate "), "heart_rate");
+        assert_eq!(metric_name("HKQuantityTypeIdentifierVO2Max"), "vo2_max");
+        assert_eq!(metric_name("HKQuantityTypeIdentifierHeartRateVariabilitySDNN"), "heart_rate_variability_sdnn");
+        assert_eq!(metric_name("body-mass (kg)"), "body_mass_kg");
+    }
+
+    #[test]
+    fn units_convert_to_one_per_dimension() {
+        assert!(close(normalize(165.0, "lb"), 74.842_741, "kg"));
+        assert!(close(normalize(5.2, "km"), 5_200.0, "m"));
+        assert!(close(normalize(98.6, "degF"), 37.0, "degC"));
+        assert!(close(normalize(418.4, "kJ"), 100.0, "kcal"));
+        assert!(close(normalize(5.5, "mmol<180.1558800000541>/L"), 99.085_734, "mg/dL"));
+        assert!(close(normalize(72.0, "bpm"), 72.0, "count/min"));
+        assert!(close(normalize(45.0, "dBASPL"), 45.0, "dBASPL"));
+    }
+}
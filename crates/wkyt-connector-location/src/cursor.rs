@@ -0,0 +1,47 @@
+//! The location importer's cursor: the JSON document inside its
+//! `SyncToken`.
+//!
+//! Per export file, the mtime and size it was read at, the settings its
+//! stays were derived with, and the source ids it yielded:
+//!
+//! ```json
+//! { "files": { "2024/july.gpx":
+//!                { "mtime_ms": 1729246500000, "size": 48211, "settings": "100/100/600",
+//!                  "items": ["stay:2024-07-03T09:00:00Z", "waypoint:43.2995,-2.0105:Cafe"] } } }
+//! ```
+//!
+//! A file is read again when its mtime or size changed, or when the
+//! importer's settings did (the same track then yields other stays, or
+//! the same ones at other coordinates). The ids let a re-read or deleted
+//! file's items be retired: an id is tombstoned once no file lists it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct LocationCursor {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) files: BTreeMap<String, FileState>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct FileState {
+    pub(crate) mtime_ms: i64,
+    pub(crate) size: u64,
+    pub(crate) settings: String,
+    #[serde(default)]
+    pub(crate) items: BTreeSet<String>,
+}
+
+impl LocationCursor {
+    /// Record what `path` yields now, and return the ids it yielded before
+    /// that no file yields any more.
+    pub(crate) fn replace(&mut self, path: &str, state: Option<FileState>) -> Vec<String> {
+        let before = match state {
+            Some(state) => self.files.insert(path.to_string(), state),
+            None => self.files.remove(path),
+        };
+        let before = before.map(|s| s.items).unwrap_or_default();
+        before.into_iter().filter(|id| !self.files.values().any(|f| f.items.contains(id))).collect()
+    }
+}
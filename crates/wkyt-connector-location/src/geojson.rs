@@ -0,0 +1,149 @@
+//! GeoJSON exports: a `FeatureCollection`, or a single `Feature`.
+//!
+//! Tracks are `LineString` / `MultiLineString` features whose times ride
+//! alongside the coordinates, as GPX converters and tracking apps write
+//! them: a `coordTimes` (or `times`) property parallel to the coordinates,
+//! or a fourth coordinate holding Unix seconds. `Point` features with a
+//! `time` (or `timestamp`) property are fixes; without one they are
+//! waypoints, named by their `name` (or `title`). Anything else — polygons,
+//! untimed lines — is skipped. Coordinates are `[longitude, latitude,
+//! elevation?]`, as the format has them.
+
+use crate::stays::{Detector, Fix};
+use crate::{parse_time, Parsed, Waypoint};
+use serde_json::Value;
+
+/// Whether `value` is GeoJSON this reader knows; a `.json` file that is
+/// not is no location export.
+pub(crate) fn is_geojson(value: &Value) -> bool {
+    matches!(value["type"].as_str(), Some("FeatureCollection" | "Feature"))
+}
+
+/// The export in `value`, its tracks condensed by `detector` once all
+/// their fixes are read, in time order.
+pub(crate) fn read(value: &Value, detector: Detector) -> Parsed {
+    let mut parsed = Parsed::default();
+    let mut fixes = Vec::new();
+    let features = match value["type"].as_str() {
+        Some("FeatureCollection") => value["features"].as_array().map(Vec::as_slice).unwrap_or_default(),
+        Some("Feature") => std::slice::from_ref(value),
+        _ => &[],
+    };
+    for feature in features {
+        let properties = &feature["properties"];
+        let geometry = &feature["geometry"];
+        let coordinates = &geometry["coordinates"];
+        match geometry["type"].as_str() {
+            Some("Point") => point(coordinates, properties, &mut parsed, &mut fixes),
+            Some("LineString") => line(coordinates, times(properties), &mut fixes),
+            Some("MultiLineString") => {
+                let lines = coordinates.as_array().map(Vec::as_slice).unwrap_or_default();
+                let times = times(properties);
+                for (i, coordinates) in lines.iter().enumerate() {
+                    // Per-line time arrays, one for each line.
+                    line(coordinates, times.and_then(|t| t.get(i)), &mut fixes);
+                }
+            }
+            _ => {}
+        }
+    }
+    parsed.stays = detector.condense(fixes);
+    parsed
+}
+
+fn times(properties: &Value) -> Option<&Value> {
+    properties.get("coordTimes").or_else(|| properties.get("times")).filter(|t| t.is_array())
+}
+
+fn point(coordinates: &Value, properties: &Value, parsed: &mut Parsed, fixes: &mut Vec<Fix>) {
+    let Some((latitude, longitude)) = position(coordinates) else { return };
+    let time = properties.get("time").or_else(|| properties.get("timestamp"));
+    match time.and_then(moment).or_else(|| coordinates.get(3).and_then(moment)) {
+        Some(at) => fixes.push(Fix { at, latitude, longitude }),
+        None => {
+            let text = |keys: &[&str]| {
+                let text = keys.iter().find_map(|k| properties[k].as_str()).map(str::trim);
+                text.filter(|t| !t.is_empty()).map(String::from)
+            };
+            parsed.waypoints.push(Waypoint {
+                name: text(&["name", "title"]),
+                description: text(&["description", "desc"]),
+                at: None,
+                latitude,
+                longitude,
+                elevation_m: coordinates.get(2).and_then(Value::as_f64),
+            });
+        }
+    }
+}
+
+fn line(coordinates: &Value, times: Option<&Value>, fixes: &mut Vec<Fix>) {
+    let Some(positions) = coordinates.as_array() else { return };
+    for (i, coordinates) in positions.iter().enumerate() {
+        let Some((latitude, longitude)) = position(coordinates) else { continue };
+        let time = times.and_then(|t| t.get(i)).or_else(|| coordinates.get(3));
+        if let Some(at) = time.and_then(moment) {
+            fixes.push(Fix { at, latitude, longitude });
+        }
+    }
+}
+
+/// `[longitude, latitude, ...]` as (latitude, longitude), in range.
+fn position(coordinates: &Value) -> Option<(f64, f64)> {
+    let longitude = coordinates.get(0)?.as_f64()?;
+    let latitude = coordinates.get(1)?.as_f64()?;
+    ((-90.0..=90.0).contains(&latitude) && (-180.0..=180.0).contains(&longitude)).then_some((latitude, longitude))
+}
+
+/// A time as a string, or as Unix seconds (or milliseconds, when too
+/// large to be seconds).
+fn moment(value: &Value) -> Option<chrono::DateTime<chrono::Utc>> {
+    match value {
+        Value::String(text) => parse_time(text),
+        Value::Number(n) => {
+            let n = n.as_f64()?;
+            match n.abs() < 1e11 {
+                true => chrono::DateTime::from_timestamp_millis((n * 1000.0) as i64),
+                false => chrono::DateTime::from_timestamp_millis(n as i64),
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+    use serde_json::json;
+
+    #[test]
+    fn lines_with_parallel_times_points_and_waypoints() {
+        let value = json!({
+            "type": "FeatureCollection",
+            "features": [
+                { "type": "Feature", "properties": { "coordTimes": ["2024-07-03T09:00:00Z", "2024-07-03T09:05:00Z"] },
+                  "geometry": { "type": "LineString", "coordinates": [[-2.0, 43.29], [-2.0, 43.291, 4.0]] } },
+                { "type": "Feature", "properties": {},
+                  "geometry": { "type": "MultiLineString",
+                                "coordinates": [[[-2.0, 43.29, 0, 1_720_000_000]], [[-2.0, 43.29]]] } },
+                { "type": "Feature", "properties": { "timestamp": 1_720_000_600_000_i64 },
+                  "geometry": { "type": "Point", "coordinates": [-2.0, 43.29] } },
+                { "type": "Feature", "properties": { "title": "Lighthouse" },
+                  "geometry": { "type": "Point", "coordinates": [-2.1, 43.4, 50.0] } },
+                { "type": "Feature", "properties": {},
+                  "geometry": { "type": "Polygon", "coordinates": [[[-2.0, 43.0], [-2.1, 43.0], [-2.0, 43.0]]] } }
+            ]
+        });
+        assert!(is_geojson(&value));
+        // One stay of every fix read.
+        let parsed = read(&value, Detector::new(f64::MAX, Duration::zero()));
+        let stay = &parsed.stays[0];
+        assert_eq!((stay.start.timestamp(), stay.end.timestamp(), stay.fixes), (1_719_997_200, 1_720_000_600, 4));
+        assert!((stay.latitude - 43.29025).abs() < 1e-9);
+        assert_eq!(parsed.waypoints.len(), 1);
+        assert_eq!(parsed.waypoints[0].name.as_deref(), Some("Lighthouse"));
+        assert_eq!(parsed.waypoints[0].elevation_m, Some(50.0));
+        assert!(!is_geojson(&json!({ "records": [] })));
+    }
+}
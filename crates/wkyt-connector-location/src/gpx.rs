@@ -0,0 +1,143 @@
+//! GPX 1.0 / 1.1: the timed points of its tracks (`trk/trkseg/trkpt`) and
+//! its waypoints (`wpt`), read as a stream of XML events. Track points go
+//! to the stay [`Detector`] as they are read, in file order: GPX records
+//! them as they were taken, so no more of a track than the detector holds
+//! is ever in memory.
+//!
+//! Track points without a `time` cannot be placed in time and are
+//! skipped. Routes (`rte`) are plans rather than places visited, and are
+//! skipped too. Extensions are ignored.
+
+use crate::stays::{Detector, Fix};
+use crate::{parse_time, Parsed, Waypoint};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::io::BufRead;
+
+/// A `trkpt` or `wpt` partway through being read.
+#[derive(Default)]
+struct Point {
+    waypoint: bool,
+    latitude: f64,
+    longitude: f64,
+    time: Option<String>,
+    name: Option<String>,
+    description: Option<String>,
+    elevation: Option<String>,
+}
+
+pub(crate) fn read(input: impl BufRead, mut detector: Detector) -> Result<Parsed, quick_xml::Error> {
+    let mut reader = Reader::from_reader(input);
+    let mut buf = Vec::new();
+    let mut parsed = Parsed::default();
+    let mut point: Option<Point> = None;
+    // The point's child element whose text is being read.
+    let mut field: Option<Vec<u8>> = None;
+    loop {
+        match reader.read_event_into(&mut buf)? {
+            Event::Start(e) => match (e.local_name().as_ref(), &point) {
+                (b"trkpt" | b"wpt", None) => point = start(&e)?,
+                (name @ (b"time" | b"name" | b"desc" | b"ele"), Some(_)) => field = Some(name.to_vec()),
+                _ => {}
+            },
+            // A self-closing point has no time; a waypoint needs none.
+            Event::Empty(e) if e.local_name().as_ref() == b"wpt" && point.is_none() => {
+                if let Some(p) = start(&e)? {
+                    finish(p, &mut parsed, &mut detector);
+                }
+            }
+            Event::Text(text) => {
+                if let (Some(point), Some(field)) = (&mut point, &field) {
+                    let text = text.unescape()?.trim().to_string();
+                    let slot = match field.as_slice() {
+                        b"time" => &mut point.time,
+                        b"name" => &mut point.name,
+                        b"desc" => &mut point.description,
+                        _ => &mut point.elevation,
+                    };
+                    *slot = Some(text).filter(|t| !t.is_empty());
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"trkpt" | b"wpt" => {
+                    if let Some(p) = point.take() {
+                        finish(p, &mut parsed, &mut detector);
+                    }
+                }
+                _ => field = None,
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    parsed.stays = detector.finish();
+    Ok(parsed)
+}
+
+/// A point from its start tag, `None` when its coordinates are missing or
+/// out of range.
+fn start(e: &BytesStart) -> Result<Option<Point>, quick_xml::Error> {
+    let coordinate = |name: &str| -> Result<Option<f64>, quick_xml::Error> {
+        Ok(match e.try_get_attribute(name)? {
+            Some(attribute) => attribute.unescape_value()?.trim().parse().ok(),
+            None => None,
+        })
+    };
+    let (Some(latitude), Some(longitude)) = (coordinate("lat")?, coordinate("lon")?) else { return Ok(None) };
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Ok(None);
+    }
+    let waypoint = e.local_name().as_ref() == b"wpt";
+    Ok(Some(Point { waypoint, latitude, longitude, ..Point::default() }))
+}
+
+fn finish(point: Point, parsed: &mut Parsed, detector: &mut Detector) {
+    let at = point.time.as_deref().and_then(parse_time);
+    let (latitude, longitude) = (point.latitude, point.longitude);
+    if point.waypoint {
+        parsed.waypoints.push(Waypoint {
+            name: point.name,
+            description: point.description,
+            at,
+            latitude,
+            longitude,
+            elevation_m: point.elevation.and_then(|e| e.parse().ok()),
+        });
+    } else if let Some(at) = at {
+        detector.push(Fix { at, latitude, longitude });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn timed_track_points_and_waypoints_are_read() {
+        let gpx = r#"<?xml version="1.0"?>
+            <gpx version="1.1" xmlns="http://www.topografix.com/GPX/1/1">
+              <wpt lat="43.3" lon="-2.01"><name>Caf&#233; &amp; bar</name><ele>12.5</ele></wpt>
+              <wpt lat="43.4" lon="-2.1"/>
+              <rte><rtept lat="1" lon="1"><time>2024-07-03T09:00:00Z</time></rtept></rte>
+              <trk><name>Morning</name><trkseg>
+                <trkpt lat="43.29" lon="-2.0"><ele>3</ele><time>2024-07-03T09:00:00Z</time></trkpt>
+                <trkpt lat="43.29" lon="-2.0"/>
+                <trkpt lat="95" lon="-2.0"><time>2024-07-03T09:01:00Z</time></trkpt>
+                <trkpt lat="43.291" lon="-2.0"><time>2024-07-03T11:02:00+02:00</time></trkpt>
+              </trkseg></trk>
+            </gpx>"#;
+        // One stay of everything: what reaches the detector.
+        let everything = || Detector::new(f64::MAX, Duration::zero());
+        let parsed = read(gpx.as_bytes(), everything()).unwrap();
+        assert_eq!(parsed.stays.len(), 1);
+        assert_eq!(parsed.stays[0].fixes, 2, "untimed, out-of-range and route points are skipped");
+        assert_eq!(parsed.stays[0].end.to_rfc3339(), "2024-07-03T09:02:00+00:00");
+        assert_eq!(parsed.waypoints.len(), 2);
+        assert_eq!(parsed.waypoints[0].name.as_deref(), Some("Café & bar"));
+        assert_eq!(parsed.waypoints[0].elevation_m, Some(12.5));
+        assert!(parsed.waypoints[0].at.is_none());
+        assert!(read("<gpx><trk></gpx>".as_bytes(), everything()).is_err());
+    }
+}
@@ -0,0 +1,470 @@
+//! Location-history importer: reads GPX files and GeoJSON exports — a
+//! single file, or a folder of them — and condenses their tracks into
+//! stays, so "where was I when this happened" has an answer without one
+//! item per recorded point.
+//!
+//! - A stay (see [`stays`]) is an [`ItemKind::Event`] keyed
+//!   `stay:{start}`, the start in RFC 3339 UTC: its `start`, `end`,
+//!   `dwell_seconds`, the `location` it was spent at (the centroid of its
+//!   fixes, as `{latitude, longitude}`, the takeout importer's form), how
+//!   many `fixes` it condenses and the `export_file` it came from.
+//! - A waypoint (GPX `wpt`, an untimed GeoJSON point) is a `place` item
+//!   keyed `waypoint:{latitude},{longitude}:{name}`, with its `name`,
+//!   `description`, `location`, `elevation_m` and `time` when recorded;
+//!   its timestamp is that time, or the file's mtime.
+//!
+//! The movement between stays is not imported, nor are the raw fixes:
+//! a year of tracks is millions of points, and the vault is not a GIS.
+//!
+//! Every coordinate written — stays, waypoints, and the ids built from
+//! them — is first coarsened to the centre of a grid cell of the spatial
+//! granularity ([`LocationHistory::with_granularity_m`], 100 m by
+//! default), so no finer position than the cell reaches the vault.
+//!
+//! Ids are derived from the data alone, never from the file or the order
+//! of reading: the same track exported twice, or re-imported, lands on the
+//! same items. The cursor (layout in [`cursor`]) records, per file, what
+//! it yielded: a file read again (changed, or read with new settings)
+//! has the items it no longer yields tombstoned, and a deleted file all of
+//! its own — an item two files yield lives until neither does. A file
+//! that does not parse (still being written, say) is skipped and keeps
+//! what it yielded before; the next pass tries it again. Each file's last
+//! batch carries the cursor.
+
+mod cursor;
+mod geojson;
+mod gpx;
+mod stays;
+
+use chrono::{DateTime, Duration, NaiveDateTime, SecondsFormat, Utc};
+use cursor::{FileState, LocationCursor};
+use futures_util::stream;
+use serde_json::json;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use wkyt_core::batches::{self, Step};
+use wkyt_core::files::{from_millis, mtime_ms, retryable, Found, Walk};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+pub const DEFAULT_GRANULARITY_M: f64 = 100.0;
+pub const DEFAULT_STAY_RADIUS_M: f64 = 100.0;
+pub const DEFAULT_MIN_DWELL_MINUTES: i64 = 10;
+/// Largest GeoJSON file read (it is parsed whole). GPX is streamed, at
+/// any size: its track points are condensed into stays as they are read.
+pub const MAX_GEOJSON_BYTES: u64 = 256 * 1024 * 1024;
+
+pub struct LocationHistory {
+    id: String,
+    path: PathBuf,
+    batch_size: usize,
+    granularity_m: f64,
+    radius_m: f64,
+    min_dwell: Duration,
+}
+
+/// What an export holds.
+#[derive(Default)]
+pub(crate) struct Parsed {
+    pub(crate) stays: Vec<stays::Stay>,
+    pub(crate) waypoints: Vec<Waypoint>,
+}
+
+pub(crate) struct Waypoint {
+    pub(crate) name: Option<String>,
+    pub(crate) description: Option<String>,
+    pub(crate) at: Option<DateTime<Utc>>,
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+    pub(crate) elevation_m: Option<f64>,
+}
+
+impl LocationHistory {
+    /// `path` is a `.gpx`, `.geojson` or `.json` file, or a folder holding
+    /// them.
+    pub fn new(id: impl Into<String>, path: PathBuf) -> Self {
+        Self {
+            id: id.into(),
+            path,
+            batch_size: DEFAULT_BATCH_SIZE,
+            granularity_m: DEFAULT_GRANULARITY_M,
+            radius_m: DEFAULT_STAY_RADIUS_M,
+            min_dwell: Duration::minutes(DEFAULT_MIN_DWELL_MINUTES),
+        }
+    }
+
+    /// Items per batch (default 100).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Side of the grid cells coordinates are coarsened to, in metres
+    /// (default 100); `0` keeps them exact. Changing it re-reads every
+    /// file, rewriting the coordinates already imported.
+    pub fn with_granularity_m(mut self, granularity_m: f64) -> Self {
+        self.granularity_m = granularity_m.max(0.0);
+        self
+    }
+
+    /// How far a track may wander from where a stay began and still be in
+    /// it, in metres (default 100).
+    pub fn with_stay_radius_m(mut self, radius_m: f64) -> Self {
+        self.radius_m = radius_m.max(0.0);
+        self
+    }
+
+    /// The shortest time in one place that counts as a stay (default 10
+    /// minutes).
+    pub fn with_min_dwell(mut self, min_dwell: Duration) -> Self {
+        self.min_dwell = min_dwell;
+        self
+    }
+
+    fn detector(&self) -> stays::Detector {
+        stays::Detector::new(self.radius_m, self.min_dwell)
+    }
+
+    /// What stays and coordinates depend on, recorded per file.
+    fn settings(&self) -> String {
+        format!("{}/{}/{}", self.granularity_m, self.radius_m, self.min_dwell.num_seconds())
+    }
+
+    /// The folder paths are relative to, and the export files under it
+    /// (just `path` when that is a file).
+    fn scan(&self) -> Result<(PathBuf, Vec<Found>), SyncError> {
+        let meta = std::fs::metadata(&self.path)
+            .map_err(|e| SyncError::Fatal { source: format!("location path {:?}: {e}", self.path).into() })?;
+        if meta.is_dir() {
+            let found = Walk::new().files(&self.path, wanted).map_err(retryable)?;
+            return Ok((self.path.clone(), found));
+        }
+        let name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        if !wanted(&name) {
+            let source = format!("{:?} is neither a folder nor a GPX / GeoJSON file", self.path);
+            return Err(SyncError::Fatal { source: source.into() });
+        }
+        let base = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok((base, vec![Found { path: name, mtime_ms: mtime_ms(&meta), size: meta.len() }]))
+    }
+
+    /// Decide what to read: files new, changed, or read with other
+    /// settings. Also returns the files that are gone and the starting
+    /// cursor.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(PathBuf, Vec<Found>, Vec<String>, LocationCursor), SyncError> {
+        let prev: LocationCursor = match cursor {
+            None => LocationCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        let (base, found) = self.scan()?;
+        let present: HashSet<&str> = found.iter().map(|f| f.path.as_str()).collect();
+        let removed = prev.files.keys().filter(|p| !present.contains(p.as_str())).cloned().collect();
+        let settings = self.settings();
+        let changed = found
+            .into_iter()
+            .filter(|f| match prev.files.get(&f.path) {
+                Some(state) => state.mtime_ms != f.mtime_ms || state.size != f.size || state.settings != settings,
+                None => true,
+            })
+            .collect();
+        Ok((base, changed, removed, prev))
+    }
+
+    /// What export file `found` holds, or `None` when it does not parse.
+    fn read(&self, base: &Path, found: &Found) -> Result<Option<Parsed>, SyncError> {
+        let file = File::open(base.join(&found.path)).map_err(retryable)?;
+        if found.path.to_ascii_lowercase().ends_with(".gpx") {
+            return match gpx::read(BufReader::new(file), self.detector()) {
+                Ok(parsed) => Ok(Some(parsed)),
+                Err(quick_xml::Error::Io(e)) => Err(SyncError::Retryable { source: Box::new(e), retry_after: None }),
+                Err(_) => Ok(None),
+            };
+        }
+        if found.size > MAX_GEOJSON_BYTES {
+            return Ok(None);
+        }
+        let mut bytes = Vec::new();
+        file.take(MAX_GEOJSON_BYTES).read_to_end(&mut bytes).map_err(retryable)?;
+        Ok(match serde_json::from_slice(&bytes) {
+            Ok(value) if geojson::is_geojson(&value) => Some(geojson::read(&value, self.detector())),
+            // Some other JSON: no location export, nothing to yield.
+            Ok(_) => Some(Parsed::default()),
+            Err(_) => None,
+        })
+    }
+
+    /// The stays and waypoints in `parsed`, read from `file`.
+    fn items(&self, file: &str, mtime: DateTime<Utc>, parsed: Parsed) -> Vec<Item> {
+        let location = |latitude: f64, longitude: f64| {
+            let (latitude, longitude) = stays::coarsen(latitude, longitude, self.granularity_m);
+            (json!({ "latitude": latitude, "longitude": longitude }), format!("{latitude},{longitude}"))
+        };
+        let mut items = Vec::new();
+        for stay in parsed.stays {
+            let (point, _) = location(stay.latitude, stay.longitude);
+            let properties = json!({
+                "type": "stay",
+                "start": instant(stay.start),
+                "end": instant(stay.end),
+                "dwell_seconds": (stay.end - stay.start).num_seconds(),
+                "location": point,
+                "granularity_m": self.granularity_m,
+                "fixes": stay.fixes,
+                "export_file": file,
+            });
+            let source_id = format!("stay:{}", instant(stay.start));
+            items.push(Item::new(source_id, &self.id, ItemKind::Event, stay.start, properties));
+        }
+        for waypoint in parsed.waypoints {
+            let (point, key) = location(waypoint.latitude, waypoint.longitude);
+            let source_id = match &waypoint.name {
+                Some(name) => format!("waypoint:{key}:{name}"),
+                None => format!("waypoint:{key}"),
+            };
+            let properties = json!({
+                "type": "waypoint",
+                "name": waypoint.name,
+                "description": waypoint.description,
+                "location": point,
+                "granularity_m": self.granularity_m,
+                "elevation_m": waypoint.elevation_m,
+                "time": waypoint.at.map(instant),
+                "export_file": file,
+            });
+            let kind = ItemKind::Other("place".into());
+            items.push(Item::new(source_id, &self.id, kind, waypoint.at.unwrap_or(mtime), properties));
+        }
+        items
+    }
+}
+
+/// Whether a file named `name` may be a location export.
+fn wanted(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    [".gpx", ".geojson", ".json"].iter().any(|ext| lower.ends_with(ext))
+}
+
+/// The sync stream's batches, built lazily as it is polled: one file at
+/// a time, its last batch carrying the cursor.
+struct Batches<'a> {
+    importer: &'a LocationHistory,
+    base: PathBuf,
+    /// Files gone since the cursor, retired by the first batches.
+    removed: Vec<String>,
+    files: std::vec::IntoIter<Found>,
+    cursor: LocationCursor,
+    /// Built, not yet handed out.
+    outbox: VecDeque<DeltaBatch>,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        while self.outbox.is_empty() {
+            if !self.removed.is_empty() {
+                let mut retired = Vec::new();
+                for path in std::mem::take(&mut self.removed) {
+                    retired.extend(self.cursor.replace(&path, None));
+                }
+                self.enqueue(retired, Vec::new());
+                continue;
+            }
+            let Some(found) = self.files.next() else { return Ok(None) };
+            // Unparseable: left as it was, for the next pass.
+            let Some(parsed) = importer.read(&self.base, &found)? else { continue };
+            let items = importer.items(&found.path, from_millis(found.mtime_ms), parsed);
+            let state = FileState {
+                mtime_ms: found.mtime_ms,
+                size: found.size,
+                settings: importer.settings(),
+                items: items.iter().map(|i| i.source_id.clone()).collect(),
+            };
+            let retired = self.cursor.replace(&found.path, Some(state));
+            self.enqueue(retired, items);
+        }
+        Ok(self.outbox.pop_front())
+    }
+}
+
+impl Batches<'_> {
+
+    /// Queue the tombstones, then the upserts, in batches; the last (or
+    /// an empty one) carries the cursor as it is now.
+    fn enqueue(&mut self, retired: Vec<String>, items: Vec<Item>) {
+        let importer = self.importer;
+        let deltas: Vec<Delta> = retired
+            .into_iter()
+            .map(|source_id| Delta::Tombstone { source_id })
+            .chain(items.into_iter().map(Delta::Upsert))
+            .collect();
+        let mut chunks: Vec<Vec<Delta>> = deltas.chunks(importer.batch_size).map(<[Delta]>::to_vec).collect();
+        if chunks.is_empty() {
+            chunks.push(Vec::new());
+        }
+        let last = chunks.len() - 1;
+        for (i, deltas) in chunks.into_iter().enumerate() {
+            let cursor = (i == last).then(|| {
+                SyncToken(serde_json::to_string(&self.cursor).expect("cursor serialization is infallible"))
+            });
+            self.outbox.push_back(DeltaBatch { connector_id: importer.id.clone(), deltas, cursor });
+        }
+    }
+}
+
+/// A time in an export: RFC 3339, or without an offset (taken as UTC).
+pub(crate) fn parse_time(text: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(text)
+        .map(|t| t.with_timezone(&Utc))
+        .ok()
+        .or_else(|| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S%.f").ok().map(|t| t.and_utc()))
+}
+
+fn instant(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[async_trait::async_trait]
+impl Connector for LocationHistory {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((base, files, removed, cursor)) => batches::stream(Batches {
+                importer: self,
+                base,
+                removed,
+                files: files.into_iter(),
+                cursor,
+                outbox: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, tombstones, upserts};
+    use futures_util::StreamExt;
+    use std::fs;
+
+    /// (minute after 09:00 UTC on 2024-07-03, latitude, longitude)
+    const MORNING: [(i64, f64, f64); 7] = [
+        (0, 43.29000, -2.00000), // home
+        (10, 43.29020, -2.00010),
+        (25, 43.29010, -2.00000),
+        (30, 43.29500, -2.00500), // walking
+        (35, 43.30000, -2.01000), // cafe
+        (50, 43.30010, -2.01000),
+        (60, 43.31000, -2.02000),
+    ];
+
+    fn gpx(fixes: &[(i64, f64, f64)]) -> String {
+        let points: String = fixes
+            .iter()
+            .map(|(minute, lat, lon)| {
+                let at = DateTime::from_timestamp(1_719_997_200 + minute * 60, 0).unwrap();
+                format!(r#"<trkpt lat="{lat}" lon="{lon}"><time>{}</time></trkpt>"#, instant(at))
+            })
+            .collect();
+        format!(
+            r#"<gpx version="1.1"><wpt lat="43.30003" lon="-2.01004"><name>Cafe</name></wpt>
+               <trk><trkseg>{points}</trkseg></trk></gpx>"#
+        )
+    }
+
+    fn geojson(fixes: &[(i64, f64, f64)]) -> String {
+        let times: Vec<i64> = fixes.iter().map(|(minute, ..)| 1_719_997_200 + minute * 60).collect();
+        let coordinates: Vec<[f64; 2]> = fixes.iter().map(|&(_, lat, lon)| [lon, lat]).collect();
+        json!({ "type": "Feature", "properties": { "times": times },
+                "geometry": { "type": "LineString", "coordinates": coordinates } })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn tracks_become_coarsened_stays_that_re_imports_land_on() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("july.gpx"), gpx(&MORNING)).unwrap();
+        let c = LocationHistory::new("location", dir.path().to_path_buf()).with_granularity_m(500.0);
+
+        let batches = drain(&c, None).await;
+        let items = upserts(&batches);
+        let ids: Vec<&str> = items.iter().map(|i| i.source_id.as_str()).collect();
+        let cafe = format!("waypoint:{}:Cafe", {
+            let (lat, lon) = stays::coarsen(43.30003, -2.01004, 500.0);
+            format!("{lat},{lon}")
+        });
+        assert_eq!(ids, ["stay:2024-07-03T09:00:00Z", "stay:2024-07-03T09:35:00Z", cafe.as_str()]);
+        let home = &items[0];
+        assert_eq!(home.kind, ItemKind::Event);
+        assert_eq!(home.properties["end"], "2024-07-03T09:25:00Z");
+        assert_eq!(home.properties["dwell_seconds"], 25 * 60);
+        assert_eq!(home.properties["fixes"], 3);
+        let (lat, lon) = stays::coarsen(43.2901, -2.0000333, 500.0);
+        assert_eq!(home.properties["location"], json!({ "latitude": lat, "longitude": lon }));
+        assert_eq!(items[2].kind, ItemKind::Other("place".into()));
+        assert!(batches.last().unwrap().cursor.is_some());
+
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty(), "nothing changed");
+
+        // The same track exported again lands on the same items.
+        fs::write(dir.path().join("july.geojson"), geojson(&MORNING)).unwrap();
+        fs::write(dir.path().join("settings.json"), r#"{"theme": "dark"}"#).unwrap();
+        let batches = drain(&c, cursor).await;
+        let ids: Vec<&str> = upserts(&batches).iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(ids, ["stay:2024-07-03T09:00:00Z", "stay:2024-07-03T09:35:00Z"]);
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty(), "the other JSON is remembered too");
+
+        // Gone from one file, a stay lives while the other still yields it.
+        fs::write(dir.path().join("july.gpx"), gpx(&MORNING[..4])).unwrap();
+        let batches = drain(&c, cursor).await;
+        assert!(tombstones(&batches).is_empty());
+        fs::remove_file(dir.path().join("july.geojson")).unwrap();
+        let batches = drain(&c, last_cursor(&batches)).await;
+        assert_eq!(tombstones(&batches), ["stay:2024-07-03T09:35:00Z"]);
+    }
+
+    #[tokio::test]
+    async fn new_settings_rewrite_and_bad_files_wait() {
+        let dir = tempfile::tempdir().unwrap();
+        let track = dir.path().join("track.gpx");
+        fs::write(&track, gpx(&MORNING)).unwrap();
+        fs::write(dir.path().join("partial.gpx"), "<gpx><trk><trkseg><trkpt").unwrap();
+        let batches = drain(&LocationHistory::new("location", dir.path().to_path_buf()), None).await;
+        assert_eq!(upserts(&batches).len(), 3, "the partial file yields nothing yet");
+        let cursor = last_cursor(&batches);
+
+        // Exact coordinates, and a dwell time the cafe stop falls short of.
+        let c = LocationHistory::new("location", dir.path().to_path_buf())
+            .with_granularity_m(0.0)
+            .with_min_dwell(Duration::minutes(20));
+        let batches = drain(&c, cursor).await;
+        // The waypoint's id holds its coarsened position, so it moves.
+        let (lat, lon) = stays::coarsen(43.30003, -2.01004, DEFAULT_GRANULARITY_M);
+        let old_cafe = format!("waypoint:{lat},{lon}:Cafe");
+        assert_eq!(tombstones(&batches), ["stay:2024-07-03T09:35:00Z", old_cafe.as_str()]);
+        let items = upserts(&batches);
+        assert_eq!(items[1].source_id, "waypoint:43.30003,-2.01004:Cafe");
+        assert_eq!(items[0].properties["location"]["longitude"], -2.000033);
+        assert_eq!(items[1].properties["location"], json!({ "latitude": 43.30003, "longitude": -2.01004 }));
+
+        let results: Vec<_> = c.sync(Some(SyncToken("nope".into()))).collect().await;
+        assert!(matches!(results.as_slice(), [Err(SyncError::ResyncRequired)]));
+        let missing = LocationHistory::new("location", dir.path().join("missing"));
+        let results: Vec<_> = missing.sync(None).collect().await;
+        assert!(matches!(results.as_slice(), [Err(SyncError::Fatal { .. })]));
+        let single = LocationHistory::new("location", track);
+        assert_eq!(upserts(&drain(&single, None).await).len(), 3);
+    }
+}
@@ -0,0 +1,257 @@
+//! Condensing a track into stays, and coarsening coordinates.
+//!
+//! A stay is a stretch of a track that keeps within the stay radius of
+//! its first fix for at least the minimum dwell time. The fixes are taken
+//! in time order; from each fix, the track is followed while it stays
+//! within the radius, and the stretch is a stay when it lasted long enough
+//! — the next stay is looked for after it. Otherwise the search moves on
+//! one fix. A device that stops recording while still (overnight, say)
+//! and resumes within the radius makes one stay across the gap.
+//!
+//! The result depends only on the fixes, the radius and the dwell time,
+//! so the same track always yields the same stays, whichever file it
+//! came from.
+//!
+//! A [`Detector`] takes the fixes one at a time, in time order, and holds
+//! only those the search may still return to: the ones since the current
+//! anchor, before they have lasted the dwell time. Once they have, the
+//! stay is kept as running sums until the track leaves it.
+
+use chrono::{DateTime, Duration, Utc};
+use std::collections::VecDeque;
+
+/// Mean Earth radius, in metres.
+const EARTH_RADIUS_M: f64 = 6_371_008.8;
+/// Metres per degree of latitude.
+const METRES_PER_DEGREE: f64 = 111_320.0;
+
+/// One timed position from a track.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Fix {
+    pub(crate) at: DateTime<Utc>,
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+}
+
+/// A stretch of a track spent in one place.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Stay {
+    pub(crate) start: DateTime<Utc>,
+    pub(crate) end: DateTime<Utc>,
+    /// The mean position of its fixes, exact.
+    pub(crate) latitude: f64,
+    pub(crate) longitude: f64,
+    pub(crate) fixes: usize,
+}
+
+/// Finds the stays in a track fed to it fix by fix.
+pub(crate) struct Detector {
+    radius_m: f64,
+    min_dwell: Duration,
+    /// The anchor and the fixes after it, all within the radius of it,
+    /// not yet lasting the dwell time.
+    pending: VecDeque<Fix>,
+    /// The stay the track is in, once it has lasted the dwell time.
+    open: Option<Open>,
+    last: Option<DateTime<Utc>>,
+    stays: Vec<Stay>,
+}
+
+/// A stay still being extended: its anchor, latest fix and sums.
+struct Open {
+    anchor: Fix,
+    end: DateTime<Utc>,
+    latitude: f64,
+    longitude: f64,
+    fixes: usize,
+}
+
+impl Open {
+    fn add(&mut self, fix: &Fix) {
+        self.end = fix.at;
+        self.latitude += fix.latitude;
+        self.longitude += fix.longitude;
+        self.fixes += 1;
+    }
+
+    fn close(self) -> Stay {
+        let n = self.fixes as f64;
+        Stay {
+            start: self.anchor.at,
+            end: self.end,
+            latitude: self.latitude / n,
+            longitude: self.longitude / n,
+            fixes: self.fixes,
+        }
+    }
+}
+
+impl Detector {
+    pub(crate) fn new(radius_m: f64, min_dwell: Duration) -> Self {
+        Self { radius_m, min_dwell, pending: VecDeque::new(), open: None, last: None, stays: Vec::new() }
+    }
+
+    /// The stays in `fixes`, which are sorted by time here.
+    pub(crate) fn condense(mut self, mut fixes: Vec<Fix>) -> Vec<Stay> {
+        fixes.sort_by_key(|f| f.at);
+        fixes.dedup_by_key(|f| f.at);
+        for fix in fixes {
+            self.push(fix);
+        }
+        self.finish()
+    }
+
+    /// The next fix of the track. One at the time of the fix before it is
+    /// skipped; one before it ends the track there and starts another, so
+    /// a file of tracks out of time order is condensed one run at a time.
+    pub(crate) fn push(&mut self, fix: Fix) {
+        match self.last {
+            Some(last) if fix.at == last => return,
+            Some(last) if fix.at < last => self.end_track(),
+            _ => {}
+        }
+        self.last = Some(fix.at);
+        if let Some(open) = &mut self.open {
+            if distance_m(&open.anchor, &fix) <= self.radius_m {
+                open.add(&fix);
+                return;
+            }
+            // Left the stay: the search goes on from this fix.
+            self.stays.extend(self.open.take().map(Open::close));
+        }
+        self.pending.push_back(fix);
+        self.settle();
+    }
+
+    /// Restore the invariant on `pending` after a fix was added: drop
+    /// anchors whose stretch ended too short, and open a stay once the
+    /// stretch from the anchor has lasted the dwell time.
+    fn settle(&mut self) {
+        while let Some(&anchor) = self.pending.front() {
+            // Only a stretch that reaches the newest fix can still last
+            // long enough: an earlier one is inside a stretch already
+            // found too short.
+            if self.pending.iter().any(|f| distance_m(&anchor, f) > self.radius_m) {
+                self.pending.pop_front();
+                continue;
+            }
+            let last = self.pending.back().expect("the anchor is in it");
+            if last.at - anchor.at >= self.min_dwell {
+                let mut open = Open { anchor, end: anchor.at, latitude: 0.0, longitude: 0.0, fixes: 0 };
+                for fix in self.pending.drain(..) {
+                    open.add(&fix);
+                }
+                self.open = Some(open);
+            }
+            return;
+        }
+    }
+
+    fn end_track(&mut self) {
+        self.stays.extend(self.open.take().map(Open::close));
+        // What is pending never lasted the dwell time, nor does any part
+        // of it.
+        self.pending.clear();
+    }
+
+    pub(crate) fn finish(mut self) -> Vec<Stay> {
+        self.end_track();
+        self.stays
+    }
+}
+
+/// Great-circle distance between two fixes, in metres.
+fn distance_m(a: &Fix, b: &Fix) -> f64 {
+    let (lat_a, lat_b) = (a.latitude.to_radians(), b.latitude.to_radians());
+    let d_lat = lat_b - lat_a;
+    let d_lon = (b.longitude - a.longitude).to_radians();
+    let h = (d_lat / 2.0).sin().powi(2) + lat_a.cos() * lat_b.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// The centre of the grid cell of side `granularity_m` holding the point,
+/// rounded to six decimals (about 10 cm); `0` only rounds. Every point in
+/// a cell reports the same position, so nothing finer than the cell can
+/// be read back.
+pub(crate) fn coarsen(latitude: f64, longitude: f64, granularity_m: f64) -> (f64, f64) {
+    let round = |degrees: f64| (degrees * 1e6).round() / 1e6;
+    if granularity_m <= 0.0 {
+        return (round(latitude), round(longitude));
+    }
+    let lat_step = granularity_m / METRES_PER_DEGREE;
+    let latitude = ((latitude / lat_step).floor() + 0.5) * lat_step;
+    // Cells keep their width in metres away from the equator; near the
+    // poles one cell spans every longitude.
+    let lon_step = (granularity_m / (METRES_PER_DEGREE * latitude.to_radians().cos().max(1e-9))).min(360.0);
+    let longitude = ((longitude + 180.0) / lon_step).floor().mul_add(lon_step, lon_step / 2.0) - 180.0;
+    (round(latitude.clamp(-90.0, 90.0)), round(longitude.clamp(-180.0, 180.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn fix(minute: i64, latitude: f64, longitude: f64) -> Fix {
+        let at = DateTime::from_timestamp(1_720_000_000 + minute * 60, 0).unwrap();
+        Fix { at, latitude, longitude }
+    }
+
+    #[test]
+    fn stays_need_the_dwell_time_within_the_radius() {
+        let home = (43.2900, -2.0000);
+        let cafe = (43.3000, -2.0100); // ~1.4 km away
+        let mut fixes = vec![
+            fix(0, home.0, home.1),
+            fix(5, home.0 + 0.0003, home.1), // ~33 m: still home
+            fix(30, home.0, home.1 + 0.0003),
+            fix(35, 43.295, -2.005), // walking
+            fix(40, cafe.0, cafe.1),
+            fix(45, cafe.0, cafe.1),  // five minutes: too short
+            fix(50, 43.31, -2.02),
+        ];
+        fixes.reverse();
+        let stays = Detector::new(100.0, Duration::minutes(10)).condense(fixes);
+        assert_eq!(stays.len(), 1);
+        assert_eq!((stays[0].start, stays[0].end, stays[0].fixes), (fix(0, 0.0, 0.0).at, fix(30, 0.0, 0.0).at, 3));
+        assert!((stays[0].latitude - 43.2901).abs() < 1e-9);
+    }
+
+    #[test]
+    fn a_short_stretch_is_searched_again_from_its_next_fix() {
+        let home = (43.2900, -2.0000);
+        // From the first fix the track leaves its radius after five
+        // minutes; from the second it stays put for twenty.
+        let fixes = vec![
+            fix(0, home.0 - 0.0008, home.1),
+            fix(5, home.0, home.1),
+            fix(6, home.0 + 0.0008, home.1), // ~89 m from the second, ~178 m from the first
+            fix(25, home.0, home.1),
+            fix(40, 43.31, -2.02),
+        ];
+        let stays = Detector::new(100.0, Duration::minutes(10)).condense(fixes.clone());
+        assert_eq!(stays.len(), 1);
+        assert_eq!((stays[0].start, stays[0].end, stays[0].fixes), (fixes[1].at, fixes[3].at, 3));
+
+        // Fed one by one, with a repeated fix and a second run of the
+        // track out of time order.
+        let mut detector = Detector::new(100.0, Duration::minutes(10));
+        let earlier = [fix(-120, home.0, home.1), fix(-100, home.0, home.1)];
+        for f in fixes.iter().chain(&fixes[4..]).chain(&earlier) {
+            detector.push(*f);
+        }
+        let streamed = detector.finish();
+        assert_eq!(streamed[0], stays[0]);
+        assert_eq!((streamed[1].start, streamed[1].end), (earlier[0].at, earlier[1].at));
+    }
+
+    #[test]
+    fn coarsened_points_in_one_cell_coincide() {
+        let a = coarsen(43.28991, -2.00004, 500.0);
+        let b = coarsen(43.29010, -2.00030, 500.0);
+        assert_eq!(a, b);
+        assert!((a.0 - 43.28991).abs() < 500.0 / METRES_PER_DEGREE);
+        assert_ne!(coarsen(43.28991, -2.00004, 0.0), coarsen(43.29010, -2.00030, 0.0));
+        assert_eq!(coarsen(43.28991234567, -2.0, 0.0), (43.289912, -2.0));
+    }
+}
@@ -0,0 +1,104 @@
+//! The mail importer's cursor: the JSON document inside its `SyncToken`.
+//!
+//! Per mbox, where the next read starts — a byte offset, the number of
+//! messages before it, and a hash of the file's head — and per `.eml`
+//! file, the mtime and size it was read at:
+//!
+//! ```json
+//! { "mbox": { "Takeout/All mail.mbox": { "offset": 52428800, "messages": 8120,
+//!                                        "head": "9f86d081884c7d65" } },
+//!   "eml": { "saved/invoice.eml": [1720000000000, 48213] } }
+//! ```
+//!
+//! mbox files are appended to, so a read resumes at `offset` as long as
+//! the file still looks like the one it was taken from: at least that
+//! long, its first [`HEAD_BYTES`] (or first `offset` bytes, if fewer)
+//! hashing to `head`, and a message starting at `offset`. A file that
+//! was compacted, rewritten or replaced fails one of those and is read
+//! again from the start; its messages upsert over themselves.
+
+use crate::mbox;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Bytes at the start of an mbox whose hash the cursor keeps.
+pub(crate) const HEAD_BYTES: u64 = 4096;
+/// Hex digits of the head's SHA-256 kept in the cursor.
+const HEAD_HASH_HEX: usize = 16;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct MailCursor {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) mbox: BTreeMap<String, Position>,
+    /// `.eml` path -> (mtime ms, size) when last read.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) eml: BTreeMap<String, (i64, u64)>,
+}
+
+/// Where reading an mbox resumes.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Position {
+    /// Byte offset just past the last message read.
+    pub(crate) offset: u64,
+    /// Messages read before `offset`.
+    pub(crate) messages: u64,
+    /// Hash of the first `min(offset, HEAD_BYTES)` bytes.
+    pub(crate) head: String,
+}
+
+/// Where to read `path` (of `size` bytes) from, given where the last pass
+/// left it: there if the file is still that file, else the start.
+pub(crate) fn resume(path: &Path, size: u64, known: Option<&Position>) -> io::Result<Position> {
+    let Some(known) = known else { return Ok(Position::default()) };
+    if known.offset > size {
+        return Ok(Position::default());
+    }
+    let mut file = File::open(path)?;
+    if head(&mut file, known.offset)? != known.head || !mbox::is_boundary(&mut file, known.offset, size)? {
+        return Ok(Position::default());
+    }
+    Ok(known.clone())
+}
+
+/// Hash of the first `min(offset, HEAD_BYTES)` bytes of `file`, read from
+/// its start.
+pub(crate) fn head(file: &mut File, offset: u64) -> io::Result<String> {
+    use std::io::{Seek, SeekFrom};
+    file.seek(SeekFrom::Start(0))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file.take(offset.min(HEAD_BYTES)), &mut hasher)?;
+    Ok(crate::hex(&hasher.finalize())[..HEAD_HASH_HEX].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn reads_resume_only_in_the_file_they_were_taken_from() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.mbox");
+        let first = "From a Thu Jan  4 10:00:00 2024\n\none\n\n";
+        fs::write(&path, format!("{first}From b Thu Jan  4 10:00:00 2024\n\ntwo\n")).unwrap();
+        let offset = first.len() as u64;
+        let size = fs::metadata(&path).unwrap().len();
+        let taken = Position { offset, messages: 1, head: head(&mut File::open(&path).unwrap(), offset).unwrap() };
+
+        assert_eq!(resume(&path, size, None).unwrap(), Position::default());
+        assert_eq!(resume(&path, size, Some(&taken)).unwrap(), taken);
+        // Not at a message boundary.
+        let inside = Position { offset: offset - 2, ..taken.clone() };
+        assert_eq!(resume(&path, size, Some(&inside)).unwrap(), Position::default());
+        // Past the end: the file was truncated.
+        assert_eq!(resume(&path, offset - 1, Some(&taken)).unwrap(), Position::default());
+
+        // Same length, different head: another file.
+        fs::write(&path, format!("{}From b Thu Jan  4 10:00:00 2024\n\ntwo\n", first.replace("one", "eno"))).unwrap();
+        assert_eq!(resume(&path, size, Some(&taken)).unwrap(), Position::default());
+    }
+}
@@ -0,0 +1,409 @@
+//! Email-archive connector: imports the mail under one local directory
+//! tree — mbox files (`*.mbox`, or named `mbox`, as Takeout and most
+//! clients export them) and single messages saved as `*.eml` — as
+//! [`ItemKind::Message`] items, their senders and recipients as
+//! [`ItemKind::Person`]s linked to them (see [`message`] for identity and
+//! properties). Hidden files and directories are skipped and symlinks are
+//! not followed.
+//!
+//! This is an import, not a mirror: mail archives grow by appending, and
+//! a message is not tombstoned when it disappears from one (a mailbox
+//! compacted after a delete, an `.eml` moved away). Paths that are gone
+//! are only dropped from the cursor.
+//!
+//! Memory stays bounded however large an archive is: mbox files are
+//! streamed one message at a time (see [`mbox`]), a message is held only
+//! while it is parsed, and at most [`MAX_MESSAGE_BYTES`] of it. Batches
+//! are built lazily as the stream is polled, `batch_size` messages each
+//! (plus their people and links).
+//!
+//! Cursor design: a byte offset per mbox, checked against the file before
+//! it is trusted, and an mtime and size per `.eml` (layout in
+//! [`cursor`]). A grown mbox is read from where the last pass stopped; a
+//! rewritten one, from the start again. Checkpoints are taken as in the
+//! file importer: once the messages since the last one reach an eighth of
+//! the `.eml` files known (and at least a batch), and always with the
+//! last batch. Each is exact — every message before an mbox's recorded
+//! offset has been delivered — so an interrupted first import of a large
+//! archive resumes where it stopped rather than starting over.
+
+mod cursor;
+mod mbox;
+mod message;
+
+pub use mbox::MAX_MESSAGE_BYTES;
+pub use message::MAX_BODY_CHARS;
+
+use chrono::{DateTime, Utc};
+use cursor::{MailCursor, Position};
+use futures_util::stream;
+use mbox::Mbox;
+use message::Origin;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use wkyt_core::batches::{self, Step};
+use wkyt_core::hash::{hex, sha256_hex};
+use wkyt_core::files::{from_millis, retryable, Walk};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+/// A checkpoint is due once the messages since the last one reach
+/// `eml.len() / CHECKPOINT_FRACTION`.
+const CHECKPOINT_FRACTION: usize = 8;
+
+pub struct MailImporter {
+    id: String,
+    dir: PathBuf,
+    batch_size: usize,
+}
+
+/// A mail file found by the scan.
+struct Found {
+    /// Relative to the root, `/`-separated.
+    path: String,
+    kind: Kind,
+    mtime_ms: i64,
+    size: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Mbox,
+    Eml,
+}
+
+/// A file with something to read this pass.
+enum Source {
+    Mbox { path: String, mtime_ms: i64, from: Position },
+    Eml { path: String, mtime_ms: i64, size: u64 },
+}
+
+impl MailImporter {
+    pub fn new(id: impl Into<String>, dir: PathBuf) -> Self {
+        Self { id: id.into(), dir, batch_size: DEFAULT_BATCH_SIZE }
+    }
+
+    /// Messages per batch (default 100).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Scan the tree and decide what to read: grown (or rewritten) mbox
+    /// files and new or changed `.eml` files. Also returns the starting
+    /// cursor, minus paths that are gone, and whether that differs from
+    /// the one passed in.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(Vec<Source>, MailCursor, bool), SyncError> {
+        let mut prev: MailCursor = match cursor {
+            None => MailCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        if !self.dir.is_dir() {
+            return Err(SyncError::Fatal { source: format!("mail directory {:?} does not exist", self.dir).into() });
+        }
+
+        let found = scan(&self.dir).map_err(retryable)?;
+
+        let present: HashSet<&str> = found.iter().map(|f| f.path.as_str()).collect();
+        let known = prev.mbox.len() + prev.eml.len();
+        prev.mbox.retain(|path, _| present.contains(path.as_str()));
+        prev.eml.retain(|path, _| present.contains(path.as_str()));
+        let mut unsaved = prev.mbox.len() + prev.eml.len() != known;
+
+        let mut sources = Vec::new();
+        for f in &found {
+            match f.kind {
+                Kind::Mbox => {
+                    let known = prev.mbox.get(&f.path);
+                    let from = cursor::resume(&self.dir.join(&f.path), f.size, known).map_err(retryable)?;
+                    if known != Some(&from) {
+                        // Rewritten: forget the old position now, so a
+                        // checkpoint before this file is reached does not
+                        // carry it.
+                        prev.mbox.remove(&f.path);
+                        unsaved = true;
+                    }
+                    if from.offset < f.size {
+                        sources.push(Source::Mbox { path: f.path.clone(), mtime_ms: f.mtime_ms, from });
+                    }
+                }
+                Kind::Eml => {
+                    if prev.eml.get(&f.path) != Some(&(f.mtime_ms, f.size)) {
+                        sources.push(Source::Eml { path: f.path.clone(), mtime_ms: f.mtime_ms, size: f.size });
+                    }
+                }
+            }
+        }
+        Ok((sources, prev, unsaved))
+    }
+}
+
+/// The mail files under `dir`, sorted by path.
+fn scan(dir: &Path) -> std::io::Result<Vec<Found>> {
+    let files = Walk::new().files(dir, |name| kind(name).is_some())?;
+    Ok(files
+        .into_iter()
+        .filter_map(|f| {
+            let kind = kind(f.path.rsplit('/').next().unwrap_or_default())?;
+            Some(Found { path: f.path, kind, mtime_ms: f.mtime_ms, size: f.size })
+        })
+        .collect())
+}
+
+/// How a file named `name` holds mail, if it does.
+fn kind(name: &str) -> Option<Kind> {
+    let lower = name.to_ascii_lowercase();
+    if lower.ends_with(".eml") {
+        Some(Kind::Eml)
+    } else if lower.ends_with(".mbox") || lower == "mbox" {
+        Some(Kind::Mbox)
+    } else {
+        None
+    }
+}
+
+/// An mbox partway through being read.
+struct Reading {
+    path: String,
+    mbox: Mbox<BufReader<File>>,
+    /// Past the last message delivered.
+    at: Position,
+    mtime: DateTime<Utc>,
+}
+
+/// The sync stream's batches, built lazily as it is polled.
+struct Batches<'a> {
+    importer: &'a MailImporter,
+    sources: std::vec::IntoIter<Source>,
+    reading: Option<Reading>,
+    cursor: MailCursor,
+    /// Messages delivered since the last checkpoint.
+    since_checkpoint: usize,
+    /// Whether `cursor` (with `reading`'s position) differs from the last
+    /// one handed out.
+    unsaved: bool,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        let mut deltas = Vec::new();
+        let mut people = HashSet::new();
+        let mut deliver = |items: Vec<wkyt_core::Item>| {
+            for item in items {
+                // One upsert per person per batch, however many messages
+                // they are on.
+                if item.kind == ItemKind::Person && !people.insert(item.source_id.clone()) {
+                    continue;
+                }
+                deltas.push(Delta::Upsert(item));
+            }
+        };
+
+        let mut messages = 0;
+        while messages < importer.batch_size {
+            if let Some(reading) = &mut self.reading {
+                match reading.mbox.next_message().map_err(retryable)? {
+                    Some(raw) => {
+                        let fallback = mbox::from_line_date(&raw.from_line).unwrap_or(reading.mtime);
+                        let origin = Origin {
+                            mailbox: &reading.path,
+                            offset: Some(raw.offset),
+                            truncated: raw.truncated,
+                            fallback,
+                        };
+                        deliver(message::items(&importer.id, &raw.bytes, origin));
+                        reading.at.offset = raw.end;
+                        reading.at.messages += 1;
+                        messages += 1;
+                    }
+                    None => {
+                        let reading = self.reading.take().expect("matched above");
+                        let path = importer.dir.join(&reading.path);
+                        self.cursor.mbox.insert(reading.path, settle(&path, reading.at)?);
+                    }
+                }
+                continue;
+            }
+            match self.sources.next() {
+                None => break,
+                Some(Source::Mbox { path, mtime_ms, from }) => {
+                    let mut file = File::open(importer.dir.join(&path)).map_err(retryable)?;
+                    file.seek(SeekFrom::Start(from.offset)).map_err(retryable)?;
+                    let mbox = Mbox::new(BufReader::new(file), from.offset);
+                    self.reading = Some(Reading { path, mbox, at: from, mtime: from_millis(mtime_ms) });
+                }
+                Some(Source::Eml { path, mtime_ms, size }) => {
+                    let mut bytes = Vec::new();
+                    let file = File::open(importer.dir.join(&path)).map_err(retryable)?;
+                    file.take(MAX_MESSAGE_BYTES as u64).read_to_end(&mut bytes).map_err(retryable)?;
+                    let origin = Origin {
+                        mailbox: &path,
+                        offset: None,
+                        truncated: size > MAX_MESSAGE_BYTES as u64,
+                        fallback: from_millis(mtime_ms),
+                    };
+                    deliver(message::items(&importer.id, &bytes, origin));
+                    self.cursor.eml.insert(path, (mtime_ms, size));
+                    messages += 1;
+                }
+            }
+        }
+
+        self.since_checkpoint += messages;
+        self.unsaved |= messages > 0;
+        let last = self.reading.is_none() && self.sources.len() == 0;
+        let due = importer.batch_size.max(self.cursor.eml.len() / CHECKPOINT_FRACTION);
+        let checkpoint = self.unsaved && (last || self.since_checkpoint >= due);
+        if deltas.is_empty() && !checkpoint {
+            return Ok(None);
+        }
+        let cursor = if checkpoint {
+            let mut cursor = self.cursor.clone();
+            if let Some(reading) = &self.reading {
+                let path = importer.dir.join(&reading.path);
+                cursor.mbox.insert(reading.path.clone(), settle(&path, reading.at.clone())?);
+            }
+            self.since_checkpoint = 0;
+            self.unsaved = false;
+            Some(SyncToken(serde_json::to_string(&cursor).expect("cursor serialization is infallible")))
+        } else {
+            None
+        };
+        Ok(Some(DeltaBatch { connector_id: importer.id.clone(), deltas, cursor }))
+    }
+}
+
+/// `at`, with the head hash for its offset.
+fn settle(path: &Path, mut at: Position) -> Result<Position, SyncError> {
+    at.head = cursor::head(&mut File::open(path).map_err(retryable)?, at.offset).map_err(retryable)?;
+    Ok(at)
+}
+
+#[async_trait::async_trait]
+impl Connector for MailImporter {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        std::fs::create_dir_all(&self.dir).map_err(retryable)?;
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((sources, cursor, unsaved)) => batches::stream(Batches {
+                importer: self,
+                sources: sources.into_iter(),
+                reading: None,
+                cursor,
+                since_checkpoint: 0,
+                unsaved,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, upserts_of};
+    use futures_util::StreamExt;
+    use std::fs;
+    use std::io::Write;
+
+    fn message_ids(batches: &[DeltaBatch]) -> Vec<&str> {
+        upserts_of(batches, ItemKind::Message).iter().map(|i| i.source_id.as_str()).collect()
+    }
+
+    fn mail(n: usize) -> String {
+        format!(
+            "From sender@example.com Thu Jan  4 10:00:00 2024\n\
+             Message-ID: <m{n}@example.com>\n\
+             From: Sender <sender@example.com>\n\
+             To: you@example.com\n\
+             Subject: number {n}\n\
+             \n\
+             body {n}\n\
+             \n"
+        )
+    }
+
+    #[tokio::test]
+    async fn mbox_imports_resume_at_the_offset_they_stopped_at() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("Takeout")).unwrap();
+        let path = dir.path().join("Takeout/All mail.mbox");
+        fs::write(&path, (0..5).map(mail).collect::<String>()).unwrap();
+        let c = MailImporter::new("mail", dir.path().to_path_buf()).with_batch_size(2);
+
+        let batches = drain(&c, None).await;
+        let all: Vec<String> = (0..5).map(|n| format!("mid:m{n}@example.com")).collect();
+        assert_eq!(message_ids(&batches), all);
+        assert_eq!(batches.len(), 3);
+        assert!(batches.iter().all(|b| b.cursor.is_some()), "small cursors checkpoint every batch");
+        // Each sender is upserted once per batch.
+        assert_eq!(upserts_of(&batches[..1], ItemKind::Person).len(), 2);
+        assert_eq!(upserts_of(&batches, ItemKind::Relationship).len(), 10);
+
+        // An interrupted import picks up after the last checkpoint.
+        let first = batches[0].cursor.clone();
+        assert_eq!(message_ids(&drain(&c, first).await), all[2..]);
+
+        // Appended mail is all the next pass reads.
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+        fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(mail(5).as_bytes()).unwrap();
+        let batches = drain(&c, cursor.clone()).await;
+        assert_eq!(message_ids(&batches), ["mid:m5@example.com"]);
+        let position: serde_json::Value = serde_json::from_str(&last_cursor(&batches).unwrap().0).unwrap();
+        assert_eq!(position["mbox"]["Takeout/All mail.mbox"]["messages"], 6);
+
+        // A mailbox rewritten in place (a message deleted and the file
+        // compacted) is read from the start again; nothing is tombstoned.
+        fs::write(&path, [0, 2, 3, 4, 5].map(mail).concat()).unwrap();
+        let batches = drain(&c, cursor).await;
+        assert_eq!(message_ids(&batches).len(), 5);
+        assert!(batches.iter().flat_map(|b| &b.deltas).all(|d| matches!(d, Delta::Upsert(_))));
+    }
+
+    #[tokio::test]
+    async fn eml_files_are_read_when_new_or_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("saved/.trash")).unwrap();
+        let eml = |n| mail(n).split_once('\n').unwrap().1.to_string();
+        fs::write(dir.path().join("saved/a.eml"), eml(1)).unwrap();
+        fs::write(dir.path().join("saved/b.EML"), eml(2)).unwrap();
+        fs::write(dir.path().join("saved/.trash/c.eml"), eml(3)).unwrap();
+        fs::write(dir.path().join("saved/notes.txt"), "not mail").unwrap();
+        let c = MailImporter::new("mail", dir.path().to_path_buf());
+
+        let batches = drain(&c, None).await;
+        assert_eq!(message_ids(&batches), ["mid:m1@example.com", "mid:m2@example.com"]);
+        let message = upserts_of(&batches, ItemKind::Message)[0];
+        assert_eq!(message.properties["mailbox"], "saved/a.eml");
+        assert_eq!(message.properties["offset"], serde_json::Value::Null);
+        assert_eq!(message.properties["body"], "body 1\n\n");
+
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+        fs::write(dir.path().join("saved/a.eml"), eml(1).replace("number 1", "number one")).unwrap();
+        fs::remove_file(dir.path().join("saved/b.EML")).unwrap();
+        let batches = drain(&c, cursor).await;
+        assert_eq!(message_ids(&batches), ["mid:m1@example.com"]);
+        let cursor: serde_json::Value = serde_json::from_str(&last_cursor(&batches).unwrap().0).unwrap();
+        assert_eq!(cursor["eml"].as_object().unwrap().keys().collect::<Vec<_>>(), ["saved/a.eml"]);
+    }
+
+    #[tokio::test]
+    async fn malformed_cursor_demands_full_resync() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = MailImporter::new("mail", dir.path().to_path_buf());
+        let results: Vec<_> = c.sync(Some(SyncToken("not json".into()))).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::ResyncRequired)]));
+    }
+}
@@ -0,0 +1,208 @@
+//! Streaming mbox reader: splits a file into raw messages one at a time,
+//! tracking the byte offset of each, so a pass can stop after any message
+//! and the next can seek straight past it.
+//!
+//! A message starts at a `From ` line at the start of the file (or of a
+//! resumed read) or after a blank line; the blank line before it belongs
+//! to the separator, not the message. Quoted `>From ` lines are unquoted
+//! the mboxrd way (one `>` dropped), which also undoes mboxo quoting.
+//! Lines are read in pieces of at most [`LINE_BYTES`] and a message is
+//! kept up to [`MAX_MESSAGE_BYTES`] (the rest skipped, the message marked
+//! truncated), so memory stays bounded whatever the file holds.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use std::io::{self, BufRead, Read, Seek, SeekFrom};
+
+/// The most of one message kept in memory; the rest is skipped.
+pub const MAX_MESSAGE_BYTES: usize = 32 * 1024 * 1024;
+/// The longest piece of a line read at once.
+const LINE_BYTES: u64 = 64 * 1024;
+
+/// One message as stored in the mbox, quoting undone.
+pub(crate) struct RawMessage {
+    /// Byte offset of its `From ` line.
+    pub(crate) offset: u64,
+    /// Byte offset just past it: where the next message (or the end of
+    /// the file) starts.
+    pub(crate) end: u64,
+    /// The separator line, without its line ending.
+    pub(crate) from_line: String,
+    pub(crate) bytes: Vec<u8>,
+    /// Whether `bytes` stops at [`MAX_MESSAGE_BYTES`].
+    pub(crate) truncated: bool,
+}
+
+pub(crate) struct Mbox<R> {
+    reader: R,
+    /// Bytes consumed so far.
+    offset: u64,
+    /// Whether the next read starts a line.
+    line_start: bool,
+    /// A separator already read: the start of the next message.
+    pending: Option<Vec<u8>>,
+}
+
+impl<R: BufRead> Mbox<R> {
+    /// Read from `reader`, positioned at byte `offset` of the file — the
+    /// start, or the end of a message read before.
+    pub(crate) fn new(reader: R, offset: u64) -> Self {
+        Self { reader, offset, line_start: true, pending: None }
+    }
+
+    /// The next piece of a line: up to its end, or [`LINE_BYTES`].
+    fn piece(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut piece = Vec::new();
+        (&mut self.reader).take(LINE_BYTES).read_until(b'\n', &mut piece)?;
+        if piece.is_empty() {
+            return Ok(None);
+        }
+        self.offset += piece.len() as u64;
+        Ok(Some(piece))
+    }
+
+    /// The next message, or `None` at the end of the file.
+    pub(crate) fn next_message(&mut self) -> io::Result<Option<RawMessage>> {
+        // Anything before the first separator (there should be nothing)
+        // is skipped.
+        let from_line = match self.pending.take() {
+            Some(line) => line,
+            None => loop {
+                let starts_line = self.line_start;
+                let Some(piece) = self.piece()? else { return Ok(None) };
+                self.line_start = piece.ends_with(b"\n");
+                if starts_line && piece.starts_with(b"From ") {
+                    break piece;
+                }
+            },
+        };
+        let offset = self.offset - from_line.len() as u64;
+        // A From line longer than a piece: its remainder is not content.
+        while !self.line_start {
+            let Some(piece) = self.piece()? else { break };
+            self.line_start = piece.ends_with(b"\n");
+        }
+
+        let mut bytes = Vec::new();
+        let mut truncated = false;
+        let mut blank_before = false;
+        loop {
+            let starts_line = self.line_start;
+            let Some(mut piece) = self.piece()? else { break };
+            self.line_start = piece.ends_with(b"\n");
+            if starts_line {
+                if blank_before && piece.starts_with(b"From ") {
+                    self.pending = Some(piece);
+                    break;
+                }
+                if is_quoted_from(&piece) {
+                    piece.remove(0);
+                }
+                blank_before = matches!(&piece[..], b"\n" | b"\r\n");
+            }
+            let room = MAX_MESSAGE_BYTES - bytes.len();
+            if piece.len() > room {
+                truncated = true;
+            }
+            bytes.extend_from_slice(&piece[..piece.len().min(room)]);
+        }
+        // The blank line ahead of the next separator is the separator's.
+        if self.pending.is_some() && !truncated {
+            let trim = if bytes.ends_with(b"\r\n") { 2 } else { usize::from(bytes.ends_with(b"\n")) };
+            bytes.truncate(bytes.len() - trim);
+        }
+
+        let end = self.offset - self.pending.as_ref().map_or(0, |p| p.len() as u64);
+        let from_line = String::from_utf8_lossy(&from_line).trim_end().to_string();
+        Ok(Some(RawMessage { offset, end, from_line, bytes, truncated }))
+    }
+}
+
+/// `>From `, `>>From `, ...: a body line that would otherwise have read
+/// as a separator.
+fn is_quoted_from(line: &[u8]) -> bool {
+    let quotes = line.iter().take_while(|&&b| b == b'>').count();
+    quotes > 0 && line[quotes..].starts_with(b"From ")
+}
+
+/// Whether `offset` in `file` is somewhere a read may resume: the end of
+/// the file, or a `From ` line following a line ending.
+pub(crate) fn is_boundary(file: &mut (impl Read + Seek), offset: u64, size: u64) -> io::Result<bool> {
+    if offset == 0 || offset == size {
+        return Ok(true);
+    }
+    if offset > size {
+        return Ok(false);
+    }
+    file.seek(SeekFrom::Start(offset - 1))?;
+    let mut around = Vec::with_capacity(6);
+    file.take(6).read_to_end(&mut around)?;
+    Ok(around.first() == Some(&b'\n') && around[1..].starts_with(b"From "))
+}
+
+/// The date at the end of a `From sender Thu Jan  4 10:00:00 2024` line,
+/// taken as UTC (the format carries no zone).
+pub(crate) fn from_line_date(line: &str) -> Option<DateTime<Utc>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let date = words.get(words.len().checked_sub(5)?..)?.join(" ");
+    NaiveDateTime::parse_from_str(&date, "%a %b %d %H:%M:%S %Y").ok().map(|t| t.and_utc())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const MBOX: &str = "From a@example.com Thu Jan  4 10:00:00 2024\n\
+        Subject: one\n\
+        \n\
+        >From the start\n\
+        no blank, so not a separator:\n\
+        From here on\n\
+        \n\
+        From b@example.com Fri Jan  5 11:30:00 2024\n\
+        Subject: two\n\
+        \n\
+        body\n";
+
+    fn all(mbox: &mut Mbox<impl BufRead>) -> Vec<RawMessage> {
+        std::iter::from_fn(|| mbox.next_message().unwrap()).collect()
+    }
+
+    #[test]
+    fn messages_split_at_separators_with_offsets_to_resume_from() {
+        let messages = all(&mut Mbox::new(Cursor::new(MBOX), 0));
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0].bytes,
+            b"Subject: one\n\nFrom the start\nno blank, so not a separator:\nFrom here on\n"
+        );
+        assert_eq!(messages[1].bytes, b"Subject: two\n\nbody\n");
+        assert_eq!(messages[0].offset, 0);
+        assert_eq!(messages[0].end, messages[1].offset);
+        assert_eq!(messages[1].end, MBOX.len() as u64);
+        assert_eq!(from_line_date(&messages[1].from_line).unwrap().to_rfc3339(), "2024-01-05T11:30:00+00:00");
+
+        // Resuming at the second message's offset reads just that one.
+        let mut file = Cursor::new(MBOX);
+        let at = messages[1].offset;
+        assert!(is_boundary(&mut file, at, MBOX.len() as u64).unwrap());
+        assert!(!is_boundary(&mut file, at + 1, MBOX.len() as u64).unwrap());
+        file.seek(SeekFrom::Start(at)).unwrap();
+        let resumed = all(&mut Mbox::new(file, at));
+        assert_eq!(resumed.len(), 1);
+        assert_eq!((resumed[0].offset, resumed[0].end), (messages[1].offset, messages[1].end));
+    }
+
+    #[test]
+    fn oversized_messages_are_cut_and_the_split_still_holds() {
+        let big = "x".repeat(MAX_MESSAGE_BYTES + 10);
+        let from = "From a Thu Jan  4 10:00:00 2024";
+        let text = format!("{from}\nSubject: big\n\n{big}\n\n{from}\n\nok\n");
+        let messages = all(&mut Mbox::new(Cursor::new(text), 0));
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].truncated);
+        assert_eq!(messages[0].bytes.len(), MAX_MESSAGE_BYTES);
+        assert!(!messages[1].truncated);
+        assert_eq!(messages[1].bytes, b"\nok\n");
+    }
+}
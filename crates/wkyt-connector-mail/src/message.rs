@@ -0,0 +1,265 @@
+//! One raw RFC 5322 message -> its [`ItemKind::Message`], plus a
+//! [`ItemKind::Person`] per address it was sent from or to, linked to it.
+//!
+//! A message is keyed by its Message-ID as a `mid:` URI (RFC 2392), angle
+//! brackets dropped, so the same message in two mailboxes (Sent and a
+//! reply thread, an mbox and a saved `.eml`) is one item; one without a
+//! Message-ID by the SHA-256 of its bytes (`sha256:{hex}`). A person is
+//! keyed by their normalized address as a `mailto:` URI and carries only
+//! that address: the display name varies from message to message, so it
+//! stays on the message's address lists and on the link. Links are
+//! relationships from the message to the person, `sent_by` for From and
+//! `sent_to` for To/Cc/Bcc (with the header in `field`).
+//!
+//! Properties: `messageId`, `subject`, `date`, `from`, `sender`, `to`,
+//! `cc`, `bcc`, `replyTo` (lists of `{address, name}`), `inReplyTo` and
+//! `references` (Message-IDs), `headers` (every header as written,
+//! unfolded, in order), `body` (the first text part, HTML converted,
+//! cut at [`MAX_BODY_CHARS`] with `bodyTruncated`), `attachments`
+//! (`{filename, mimeType, size, contentId, sha256}`; contents are not
+//! kept), and where it was read: `mailbox`, `offset` (mbox only),
+//! `size_bytes`, `truncated`. `raw_payload` is the header block.
+
+use chrono::{DateTime, Utc};
+use mail_parser::{Address, HeaderValue, MessageParser, MimeHeaders};
+use serde_json::{json, Value};
+use wkyt_core::identity::normalize_email;
+use wkyt_core::{Item, ItemKind};
+
+/// The most of a message's text body kept, in characters.
+pub const MAX_BODY_CHARS: usize = 64 * 1024;
+
+/// Where a message was read from.
+pub(crate) struct Origin<'a> {
+    /// Path of the mbox or `.eml` file, relative to the root.
+    pub(crate) mailbox: &'a str,
+    /// Byte offset of the message in its mbox.
+    pub(crate) offset: Option<u64>,
+    /// Whether the message was cut at `MAX_MESSAGE_BYTES`.
+    pub(crate) truncated: bool,
+    /// Timestamp when the message has no usable Date header.
+    pub(crate) fallback: DateTime<Utc>,
+}
+
+/// The message item first, then its people and their links; empty when
+/// `bytes` holds no message at all.
+pub(crate) fn items(connector_id: &str, bytes: &[u8], origin: Origin<'_>) -> Vec<Item> {
+    let Some(message) = MessageParser::default().parse(bytes) else { return Vec::new() };
+
+    let source_id = match message.message_id().map(str::trim).filter(|id| !id.is_empty()) {
+        Some(id) => format!("mid:{id}"),
+        None => format!("sha256:{}", crate::sha256_hex(bytes)),
+    };
+    let message_id = Item::deterministic_id(connector_id, &source_id).to_string();
+    let timestamp = message
+        .date()
+        .filter(|d| d.is_valid())
+        .and_then(|d| DateTime::from_timestamp(d.to_timestamp(), 0))
+        .unwrap_or(origin.fallback);
+
+    let mut people = Vec::new();
+    let mut links = Vec::new();
+    let mut addresses = |field: &str, header: Option<&Address>| -> Vec<Value> {
+        let mut listed = Vec::new();
+        for addr in header.into_iter().flat_map(|a| a.iter()) {
+            let Some(address) = addr.address().map(normalize_email).filter(|a| !a.is_empty()) else { continue };
+            let name = addr.name().map(str::trim).filter(|n| !n.is_empty());
+            listed.push(json!({ "address": address, "name": name }));
+            if field == "sender" || field == "replyTo" {
+                continue;
+            }
+            let person_source_id = format!("mailto:{address}");
+            let person_id = Item::deterministic_id(connector_id, &person_source_id).to_string();
+            let relation = if field == "from" { "sent_by" } else { "sent_to" };
+            links.push(Item::new(
+                format!("{field}:{source_id}->{person_source_id}"),
+                connector_id,
+                ItemKind::Relationship,
+                timestamp,
+                json!({
+                    "source": message_id,
+                    "target": person_id,
+                    "relation": relation,
+                    "field": field,
+                    "name": name,
+                }),
+            ));
+            if people.iter().any(|p: &Item| p.source_id == person_source_id) {
+                continue;
+            }
+            people.push(Item::new(
+                person_source_id,
+                connector_id,
+                ItemKind::Person,
+                timestamp,
+                json!({ "displayName": address, "emails": [{ "address": address }] }),
+            ));
+        }
+        listed
+    };
+    let from = addresses("from", message.from());
+    let sender = addresses("sender", message.sender());
+    let to = addresses("to", message.to());
+    let cc = addresses("cc", message.cc());
+    let bcc = addresses("bcc", message.bcc());
+    let reply_to = addresses("replyTo", message.reply_to());
+
+    let (body, body_truncated) = match message.body_text(0) {
+        Some(text) => match text.char_indices().nth(MAX_BODY_CHARS) {
+            Some((cut, _)) => (Some(text[..cut].to_string()), true),
+            None => (Some(text.into_owned()), false),
+        },
+        None => (None, false),
+    };
+    let attachments: Vec<Value> = message
+        .attachments()
+        .map(|part| {
+            let mime_type = part.content_type().map(|ct| match ct.subtype() {
+                Some(subtype) => format!("{}/{}", ct.ctype(), subtype).to_lowercase(),
+                None => ct.ctype().to_lowercase(),
+            });
+            json!({
+                "filename": part.attachment_name(),
+                "mimeType": mime_type,
+                "size": part.contents().len(),
+                "contentId": part.content_id(),
+                "sha256": crate::sha256_hex(part.contents()),
+            })
+        })
+        .collect();
+    let headers: Vec<Value> =
+        message.headers_raw().map(|(name, value)| json!([name, unfold(value)])).collect();
+    let header_block = &bytes[..(message.root_part().raw_body_offset() as usize).min(bytes.len())];
+
+    let properties = json!({
+        "messageId": message.message_id(),
+        "subject": message.subject(),
+        "date": message.date().filter(|d| d.is_valid()).map(|d| d.to_rfc3339()),
+        "from": from,
+        "sender": sender,
+        "to": to,
+        "cc": cc,
+        "bcc": bcc,
+        "replyTo": reply_to,
+        "inReplyTo": ids(message.in_reply_to()),
+        "references": ids(message.references()),
+        "headers": headers,
+        "body": body,
+        "bodyTruncated": body_truncated,
+        "attachments": attachments,
+        "mailbox": origin.mailbox,
+        "offset": origin.offset,
+        "size_bytes": bytes.len(),
+        "truncated": origin.truncated,
+    });
+    let mut item = Item::new(source_id, connector_id, ItemKind::Message, timestamp, properties);
+    item.raw_payload = Some(Value::String(String::from_utf8_lossy(header_block).into_owned()));
+
+    let mut items = vec![item];
+    items.extend(people);
+    items.extend(links);
+    items
+}
+
+/// Message-IDs of an In-Reply-To or References header.
+fn ids(value: &HeaderValue) -> Vec<String> {
+    match value {
+        HeaderValue::Text(id) => vec![id.to_string()],
+        HeaderValue::TextList(ids) => ids.iter().map(|id| id.to_string()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A raw header value on one line: folds joined, ends trimmed.
+fn unfold(value: &str) -> String {
+    value.split(['\r', '\n']).map(str::trim).filter(|s| !s.is_empty()).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(truncated: bool) -> Origin<'static> {
+        Origin {
+            mailbox: "inbox.mbox",
+            offset: Some(0),
+            truncated,
+            fallback: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    const MULTIPART: &str = "Message-ID: <abc@mail.example>\r\n\
+        Date: Thu, 04 Jan 2024 10:00:00 +0100\r\n\
+        From: \"Ada Lovelace\" <Ada@Example.com>\r\n\
+        To: bob@example.com, Carol <carol@example.com>\r\n\
+        Cc: ada@example.com\r\n\
+        Subject: =?utf-8?q?Caf=C3=A9?= plans\r\n\
+        In-Reply-To: <prev@mail.example>\r\n\
+        References: <root@mail.example>\r\n \
+        <prev@mail.example>\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"b\"\r\n\
+        \r\n\
+        --b\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        \r\n\
+        See you there.\r\n\
+        --b\r\n\
+        Content-Type: application/pdf\r\n\
+        Content-Disposition: attachment; filename=\"menu.pdf\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        JVBERi0=\r\n\
+        --b--\r\n";
+
+    #[test]
+    fn messages_carry_headers_body_attachments_and_links_to_people() {
+        let items = items("mail", MULTIPART.as_bytes(), origin(false));
+        let message = &items[0];
+        assert_eq!(message.kind, ItemKind::Message);
+        assert_eq!(message.source_id, "mid:abc@mail.example");
+        assert_eq!(message.timestamp.to_rfc3339(), "2024-01-04T09:00:00+00:00");
+        let p = &message.properties;
+        assert_eq!(p["subject"], "Café plans");
+        assert_eq!(p["from"], json!([{ "address": "ada@example.com", "name": "Ada Lovelace" }]));
+        assert_eq!(p["to"][1], json!({ "address": "carol@example.com", "name": "Carol" }));
+        assert_eq!(p["inReplyTo"], json!(["prev@mail.example"]));
+        assert_eq!(p["references"], json!(["root@mail.example", "prev@mail.example"]));
+        assert_eq!(p["body"], "See you there.");
+        assert_eq!(p["attachments"][0]["filename"], "menu.pdf");
+        assert_eq!(p["attachments"][0]["mimeType"], "application/pdf");
+        assert_eq!(p["attachments"][0]["size"], 5);
+        assert_eq!(p["attachments"][0]["sha256"], crate::sha256_hex(b"%PDF-"));
+        let references = json!(["References", "<root@mail.example> <prev@mail.example>"]);
+        assert!(p["headers"].as_array().unwrap().contains(&references));
+        assert!(message.raw_payload.as_ref().unwrap().as_str().unwrap().starts_with("Message-ID:"));
+        assert!(!message.raw_payload.as_ref().unwrap().as_str().unwrap().contains("See you there"));
+
+        let people: Vec<&str> =
+            items.iter().filter(|i| i.kind == ItemKind::Person).map(|i| i.source_id.as_str()).collect();
+        assert_eq!(people, ["mailto:ada@example.com", "mailto:bob@example.com", "mailto:carol@example.com"]);
+        let links: Vec<&Item> = items.iter().filter(|i| i.kind == ItemKind::Relationship).collect();
+        assert_eq!(links.len(), 4);
+        assert_eq!(links[0].source_id, "from:mid:abc@mail.example->mailto:ada@example.com");
+        assert_eq!(links[0].properties["relation"], "sent_by");
+        assert_eq!(links[0].properties["source"], message.id);
+        assert_eq!(links[0].properties["target"], Item::deterministic_id("mail", "mailto:ada@example.com").to_string());
+        assert_eq!(links[3].properties["relation"], "sent_to");
+        assert_eq!(links[3].properties["field"], "cc");
+    }
+
+    #[test]
+    fn messages_without_an_id_or_date_fall_back_to_content_and_origin() {
+        let bytes = b"From: x@example.com\n\nhello\n";
+        let message = &items("mail", bytes, origin(true))[0];
+        assert_eq!(message.source_id, format!("sha256:{}", crate::sha256_hex(bytes)));
+        assert_eq!(message.timestamp, origin(true).fallback);
+        assert_eq!(message.properties["truncated"], true);
+        assert_eq!(message.properties["date"], Value::Null);
+
+        let long = format!("Message-ID: <l@x>\n\n{}", "é".repeat(MAX_BODY_CHARS + 1));
+        let message = &items("mail", long.as_bytes(), origin(false))[0];
+        assert_eq!(message.properties["body"].as_str().unwrap().chars().count(), MAX_BODY_CHARS);
+        assert_eq!(message.properties["bodyTruncated"], true);
+    }
+}
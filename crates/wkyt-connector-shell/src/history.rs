@@ -0,0 +1,257 @@
+//! Reading a history file from a byte offset, entry by entry.
+//!
+//! Two formats, told apart line by line (a file switched from one shell
+//! to the other holds both):
+//!
+//! - zsh `EXTENDED_HISTORY`: `: 1720000000:5;git push`, the start in Unix
+//!   seconds and the duration in seconds. A command spanning lines has
+//!   each embedded newline written as `\` + newline. zsh also "metafies"
+//!   some bytes (`0x83` followed by the byte XOR `0x20`), which are
+//!   restored.
+//! - bash with `HISTTIMEFORMAT` set: a `#1720000000` line before each
+//!   command. Every line up to the next timestamp is the command's, so
+//!   multi-line commands saved with `lithist` stay whole.
+//!
+//! Lines with no time — bash without `HISTTIMEFORMAT`, plain zsh — cannot
+//! be placed in time and are skipped. Only complete entries are handed
+//! out: a partial last line (the shell is writing it) or a bash timestamp
+//! with no command yet is left for the next read, and
+//! [`Reader::consumed`] stops before it.
+
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+/// zsh's metafication marker.
+const META: u8 = 0x83;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Entry {
+    pub(crate) at: DateTime<Utc>,
+    /// Seconds; zsh records it, bash does not.
+    pub(crate) duration: Option<i64>,
+    pub(crate) command: String,
+    pub(crate) shell: &'static str,
+}
+
+/// A bash entry whose command lines are still being read.
+struct Pending {
+    at: i64,
+    lines: Vec<String>,
+}
+
+/// One line, or why there is none.
+enum Line {
+    Complete(Vec<u8>),
+    /// Not yet ended by a newline: still being written.
+    Partial,
+    Eof,
+}
+
+pub(crate) struct Reader<R> {
+    input: R,
+    /// Offset of the next byte read.
+    pos: u64,
+    /// Offset through which every entry has been handed out or skipped.
+    consumed: u64,
+    pending: Option<Pending>,
+    /// Read, not yet handed out: an entry (or `None`, for lines skipped)
+    /// and the offset just past it.
+    ready: VecDeque<(Option<Entry>, u64)>,
+    done: bool,
+}
+
+impl<R: BufRead> Reader<R> {
+    /// Read `input`, which starts at byte `offset` of its file.
+    pub(crate) fn new(input: R, offset: u64) -> Self {
+        Self { input, pos: offset, consumed: offset, pending: None, ready: VecDeque::new(), done: false }
+    }
+
+    /// The offset to resume from: just past the last entry handed out, or
+    /// past lines skipped after it.
+    pub(crate) fn consumed(&self) -> u64 {
+        self.consumed
+    }
+
+    pub(crate) fn next_entry(&mut self) -> io::Result<Option<Entry>> {
+        loop {
+            if let Some((entry, end)) = self.ready.pop_front() {
+                self.consumed = end;
+                match entry {
+                    Some(entry) => return Ok(Some(entry)),
+                    None => continue,
+                }
+            }
+            if self.done {
+                return Ok(None);
+            }
+            let start = self.pos;
+            let line = match self.line()? {
+                Line::Complete(line) => line,
+                Line::Partial => {
+                    // Whatever the pending entry is, it may not be whole.
+                    self.pending = None;
+                    self.done = true;
+                    continue;
+                }
+                Line::Eof => {
+                    self.done = true;
+                    if let Some(pending) = self.pending.take() {
+                        // A timestamp with no command yet: the rest is
+                        // on its way.
+                        if !pending.lines.is_empty() {
+                            self.ready.push_back((bash_entry(pending), start));
+                        }
+                    }
+                    continue;
+                }
+            };
+            if let Some(at) = bash_timestamp(&line) {
+                if let Some(done) = self.pending.replace(Pending { at, lines: Vec::new() }) {
+                    self.ready.push_back((bash_entry(done), start));
+                }
+                continue;
+            }
+            if let Some((at, duration, first)) = zsh_header(&line) {
+                if let Some(done) = self.pending.take() {
+                    self.ready.push_back((bash_entry(done), start));
+                }
+                let mut command = first.to_vec();
+                // An embedded newline is written as `\` + newline.
+                while command.ends_with(b"\\") {
+                    command.pop();
+                    command.push(b'\n');
+                    match self.line()? {
+                        Line::Complete(next) => command.extend_from_slice(&next),
+                        Line::Partial | Line::Eof => {
+                            self.done = true;
+                            break;
+                        }
+                    }
+                }
+                if !self.done {
+                    let entry = DateTime::from_timestamp(at, 0).map(|at| Entry {
+                        at,
+                        duration: Some(duration),
+                        command: String::from_utf8_lossy(&unmetafy(&command)).trim_end().to_string(),
+                        shell: "zsh",
+                    });
+                    self.ready.push_back((entry, self.pos));
+                }
+                continue;
+            }
+            match &mut self.pending {
+                Some(pending) => pending.lines.push(String::from_utf8_lossy(&line).into_owned()),
+                // Untimed.
+                None => self.ready.push_back((None, self.pos)),
+            }
+        }
+    }
+
+    /// The next line, without its line ending.
+    fn line(&mut self) -> io::Result<Line> {
+        let mut line = Vec::new();
+        let n = self.input.read_until(b'\n', &mut line)?;
+        if n == 0 {
+            return Ok(Line::Eof);
+        }
+        if !line.ends_with(b"\n") {
+            return Ok(Line::Partial);
+        }
+        self.pos += n as u64;
+        line.pop();
+        if line.ends_with(b"\r") {
+            line.pop();
+        }
+        Ok(Line::Complete(line))
+    }
+}
+
+fn bash_entry(pending: Pending) -> Option<Entry> {
+    let command = pending.lines.join("\n").trim_end().to_string();
+    let at = DateTime::from_timestamp(pending.at, 0)?;
+    (!command.trim().is_empty()).then_some(Entry { at, duration: None, command, shell: "bash" })
+}
+
+/// `#1720000000`: the time of the bash command on the next line.
+fn bash_timestamp(line: &[u8]) -> Option<i64> {
+    let digits = line.strip_prefix(b"#")?;
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    std::str::from_utf8(digits).ok()?.parse().ok()
+}
+
+/// `: 1720000000:5;command` -> (start, duration, first command line).
+fn zsh_header(line: &[u8]) -> Option<(i64, i64, &[u8])> {
+    let rest = line.strip_prefix(b": ")?;
+    let semicolon = rest.iter().position(|&b| b == b';')?;
+    let (start, duration) = std::str::from_utf8(&rest[..semicolon]).ok()?.split_once(':')?;
+    Some((start.trim().parse().ok()?, duration.trim().parse().ok()?, &rest[semicolon + 1..]))
+}
+
+fn unmetafy(bytes: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            META => out.extend(bytes.next().map(|&next| next ^ 0x20)),
+            b => out.push(b),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: &[u8], offset: u64) -> (Vec<Entry>, u64) {
+        let mut reader = Reader::new(&text[offset as usize..], offset);
+        let mut entries = Vec::new();
+        while let Some(entry) = reader.next_entry().unwrap() {
+            entries.push(entry);
+        }
+        (entries, reader.consumed())
+    }
+
+    #[test]
+    fn zsh_and_bash_entries_with_multi_line_commands() {
+        let mut text = b": 1720000000:5;git push\n: 1720000010:0;for f in *; do\\\n  echo $f\\\ndone\n".to_vec();
+        // "à" is 0xC3 0xA0, and zsh writes 0xA0 as META, 0x80.
+        text.extend_from_slice(b": 1720000020:0;echo voil\xc3\x83\x80\nplain untimed line\n");
+        text.extend_from_slice(b"#1720000030\nssh host\n#1720000040\ncat <<EOF\nhello\nEOF\n");
+        let (entries, consumed) = read(&text, 0);
+        let commands: Vec<&str> = entries.iter().map(|e| e.command.as_str()).collect();
+        assert_eq!(
+            commands,
+            ["git push", "for f in *; do\n  echo $f\ndone", "echo voilà", "ssh host", "cat <<EOF\nhello\nEOF"]
+        );
+        assert_eq!((entries[0].duration, entries[0].shell), (Some(5), "zsh"));
+        assert_eq!((entries[3].duration, entries[3].shell), (None, "bash"));
+        assert_eq!(entries[3].at.timestamp(), 1_720_000_030);
+        assert_eq!(consumed, text.len() as u64);
+
+        // Resumed from an offset, only what follows is read.
+        let offset = text.windows(12).position(|w| w == b"#1720000040\n").unwrap() as u64;
+        assert_eq!(read(&text, offset).0.len(), 1);
+    }
+
+    #[test]
+    fn incomplete_entries_are_left_for_the_next_read() {
+        let text = b": 1720000000:0;ls\n: 1720000010:0;make \\\n";
+        let (entries, consumed) = read(text, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(consumed, 18, "stops before the unfinished command");
+
+        let text = b"#1720000000\nls\n#1720000010\n";
+        let (entries, consumed) = read(text, 0);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(consumed, 15, "a timestamp waits for its command");
+
+        // The partial line is the second command's; the first is whole.
+        let text = b"#1720000000\nls\n#1720000010\nmake te";
+        let (entries, consumed) = read(text, 0);
+        assert_eq!((entries.len(), consumed), (1, 15));
+    }
+}
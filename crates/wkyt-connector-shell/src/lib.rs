@@ -0,0 +1,506 @@
+//! Shell history connector: reads zsh `EXTENDED_HISTORY` files and bash
+//! histories saved with `HISTTIMEFORMAT` (formats in [`history`]), and
+//! turns each command into an [`ItemKind::Event`].
+//!
+//! Histories are configured by name: a history file
+//! ([`ShellHistory::with_history`]), or the tree a per-directory history
+//! plugin keeps ([`ShellHistory::with_directory_history`]), one
+//! `{root}{directory}/history` file per working directory. A command
+//! read from such a file is tagged with that directory; the plain history
+//! formats do not record one.
+//!
+//! A command's properties: `command`, the `program` it runs (its first
+//! word, past variable assignments, `sudo`, `env`, `time` and `nohup`),
+//! the `shell`, the `history` it was read from, `working_directory` when
+//! known, and `start`, `duration_seconds` and `end` when zsh recorded a
+//! duration. Its timestamp is its start. It is keyed
+//! `cmd:{start}:{hash}`, the start in Unix seconds and the hash of the
+//! command — not the history it came from, since per-directory history
+//! plugins usually write each command to the main history as well.
+//! A plain history's copy of a command takes its directory from the
+//! directory histories, read whole to look it up whenever a plain one has
+//! something new, so it never clears what the tagged copy wrote — also
+//! when a trimmed main history is read again without its directory
+//! copies. Two runs of one command in the same second are one event.
+//!
+//! Every command passes the secret scrubber ([`scrub`]) first; one it
+//! drops is skipped as if it were never in the file.
+//!
+//! Cursor: per history file, the byte offset read up to and a hash of
+//! the bytes before it
+//! (`{"files": {"laptop": [48211, "9f86d081884c7d65"]}}`). History files
+//! only grow until the shell trims them to its size limit, rewriting them
+//! whole; then the bytes before the offset change and the file is read
+//! again from the start, upserting each command over itself. Commands
+//! trimmed away are not retired: like the statement importer this is an
+//! import, not a mirror. Every batch carries the cursor, so a pass
+//! interrupted partway resumes where its last batch left off.
+
+mod history;
+mod scrub;
+
+pub use scrub::DEFAULT_PATTERNS;
+
+use futures_util::stream;
+use history::{Entry, Reader};
+use scrub::Scrubber;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use wkyt_core::batches::{self, Step};
+use wkyt_core::hash::sha256_hex;
+use wkyt_core::files::{retryable, Walk};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+
+const DEFAULT_BATCH_SIZE: usize = 256;
+/// How many bytes before a history's offset the cursor hashes.
+const TAIL_BYTES: u64 = 64;
+/// Hex digits kept of the tail hash, and of a command's hash in its id.
+const HASH_HEX: usize = 16;
+/// The file a per-directory history plugin keeps in each directory.
+const DIRECTORY_HISTORY_FILE: &str = "history";
+/// Wrappers skipped to find the program a command runs.
+const WRAPPERS: [&str; 4] = ["sudo", "env", "time", "nohup"];
+
+pub struct ShellHistory {
+    id: String,
+    histories: Vec<(String, PathBuf)>,
+    directory_histories: Vec<(String, PathBuf)>,
+    scrub_patterns: Vec<String>,
+    batch_size: usize,
+}
+
+/// `{"files": {key: [offset, tail_hash]}}`; a directory history's files
+/// are keyed `{name}:{directory}`.
+#[derive(Serialize, Deserialize, Default, Clone)]
+struct ShellCursor {
+    files: BTreeMap<String, (u64, String)>,
+}
+
+/// A history file.
+struct Source {
+    /// Its key in the cursor.
+    key: String,
+    /// The configured history name.
+    history: String,
+    path: PathBuf,
+    working_directory: Option<String>,
+}
+
+/// A directory history file, and the directory it is for.
+type Tagged = (PathBuf, String);
+
+/// A history file with something new to read, and where to start.
+struct Planned {
+    source: Source,
+    offset: u64,
+}
+
+impl ShellHistory {
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            histories: Vec::new(),
+            directory_histories: Vec::new(),
+            scrub_patterns: Vec::new(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Import the history file at `path` (`~/.zsh_history`,
+    /// `~/.bash_history`) under `name`. May be called once per history.
+    pub fn with_history(mut self, name: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        self.histories.push((name.into(), path.into()));
+        self
+    }
+
+    /// Import the per-directory history tree at `root` (the
+    /// `per-directory-history` zsh plugin's `~/.directory_history`) under
+    /// `name`, tagging each command with the directory its file is for.
+    pub fn with_directory_history(mut self, name: impl Into<String>, root: impl Into<PathBuf>) -> Self {
+        self.directory_histories.push((name.into(), root.into()));
+        self
+    }
+
+    /// Drop commands matching any of `patterns` (regular expressions) as
+    /// well as those the built-in rules ([`DEFAULT_PATTERNS`]) catch.
+    pub fn with_scrub_patterns(mut self, patterns: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        self.scrub_patterns.extend(patterns.into_iter().map(|p| p.as_ref().to_string()));
+        self
+    }
+
+    /// Commands per batch (default 256).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    fn scrubber(&self) -> Result<Scrubber, SyncError> {
+        Scrubber::new(&self.scrub_patterns).map_err(|e| SyncError::Fatal { source: Box::new(e) })
+    }
+
+    /// The history files with something new to read, every directory
+    /// history file with its directory, and the starting cursor; the flag
+    /// says whether the cursor changed (histories gone) before anything is
+    /// read.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(Vec<Planned>, Vec<Tagged>, ShellCursor, bool), SyncError> {
+        let mut prev: ShellCursor = match cursor {
+            None => ShellCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        let mut names = HashSet::new();
+        for (name, _) in self.histories.iter().chain(&self.directory_histories) {
+            if !names.insert(name.as_str()) {
+                return Err(SyncError::Fatal { source: format!("history name {name:?} is configured twice").into() });
+            }
+        }
+
+        let mut sources = Vec::new();
+        for (name, path) in &self.histories {
+            if !path.is_file() {
+                return Err(SyncError::Fatal { source: format!("history file {path:?} does not exist").into() });
+            }
+            let (history, path, working_directory) = (name.clone(), path.clone(), None);
+            sources.push(Source { key: name.clone(), history, path, working_directory });
+        }
+        for (name, root) in &self.directory_histories {
+            if !root.is_dir() {
+                return Err(SyncError::Fatal { source: format!("history directory {root:?} does not exist").into() });
+            }
+            for directory in scan(root).map_err(retryable)? {
+                let path = root.join(directory.trim_start_matches('/')).join(DIRECTORY_HISTORY_FILE);
+                let key = format!("{name}:{directory}");
+                sources.push(Source { key, history: name.clone(), path, working_directory: Some(directory) });
+            }
+        }
+
+        let tagged = sources.iter().filter_map(|s| Some((s.path.clone(), s.working_directory.clone()?))).collect();
+        let keys: HashSet<&str> = sources.iter().map(|s| s.key.as_str()).collect();
+        let before = prev.files.len();
+        prev.files.retain(|key, _| keys.contains(key.as_str()));
+        let unsaved = prev.files.len() != before;
+        let mut planned = Vec::new();
+        for source in sources {
+            let len = std::fs::metadata(&source.path).map_err(retryable)?.len();
+            let offset = match prev.files.get(&source.key) {
+                Some((offset, hash)) if *offset <= len && tail(&source.path, *offset).map_err(retryable)? == *hash => {
+                    *offset
+                }
+                // Rewritten (or new): from the start.
+                _ => 0,
+            };
+            let unchanged = prev.files.get(&source.key).is_some_and(|(o, _)| *o == offset);
+            if !(unchanged && offset == len) {
+                planned.push(Planned { source, offset });
+            }
+        }
+        Ok((planned, tagged, prev, unsaved))
+    }
+
+    /// `entry` as an event, in `working_directory` unless its history
+    /// knows better.
+    fn item(&self, source: &Source, entry: Entry, working_directory: Option<&String>) -> Item {
+        let source_id = command_id(&entry);
+        let working_directory = source.working_directory.as_ref().or(working_directory);
+        let end = entry.duration.map(|d| entry.at + chrono::Duration::seconds(d));
+        let properties = json!({
+            "command": entry.command,
+            "program": program(&entry.command),
+            "shell": entry.shell,
+            "history": source.history,
+            "working_directory": working_directory,
+            "start": entry.at.to_rfc3339(),
+            "duration_seconds": entry.duration,
+            "end": end.map(|t| t.to_rfc3339()),
+        });
+        Item::new(source_id, &self.id, ItemKind::Event, entry.at, properties)
+    }
+}
+
+/// `cmd:{start}:{hash}`, whichever history `entry` was read from.
+fn command_id(entry: &Entry) -> String {
+    let hash = sha256_hex(entry.command.as_bytes());
+    format!("cmd:{}:{}", entry.at.timestamp(), &hash[..HASH_HEX])
+}
+
+/// The directory each command in the directory history files `tagged`
+/// was run in, by its source id.
+fn directories(tagged: &[Tagged]) -> io::Result<HashMap<String, String>> {
+    let mut found = HashMap::new();
+    for (path, directory) in tagged {
+        let mut reader = Reader::new(BufReader::new(File::open(path)?), 0);
+        while let Some(entry) = reader.next_entry()? {
+            found.insert(command_id(&entry), directory.clone());
+        }
+    }
+    Ok(found)
+}
+
+/// The program `command` runs: its first word past variable assignments
+/// and wrappers, without its directory.
+fn program(command: &str) -> Option<String> {
+    let word = command
+        .split_whitespace()
+        .find(|w| !w.contains('=') && !WRAPPERS.contains(w) && !w.starts_with('-'))?;
+    Some(word.rsplit('/').next().unwrap_or(word).to_string()).filter(|p| !p.is_empty())
+}
+
+/// The directories under `root` (as absolute paths below it, `/` for
+/// the root itself) that hold a history file, sorted. Hidden directories
+/// are walked, since they are directories like any other to the plugin.
+fn scan(root: &Path) -> io::Result<Vec<String>> {
+    let files = Walk::new().with_hidden(true).files(root, |name| name == DIRECTORY_HISTORY_FILE)?;
+    let mut found: Vec<String> = files
+        .into_iter()
+        .map(|f| format!("/{}", f.path[..f.path.len() - DIRECTORY_HISTORY_FILE.len()].trim_end_matches('/')))
+        .collect();
+    found.sort();
+    Ok(found)
+}
+
+/// The hash of the (up to) [`TAIL_BYTES`] bytes of `path` before
+/// `offset`.
+fn tail(path: &Path, offset: u64) -> io::Result<String> {
+    let start = offset.saturating_sub(TAIL_BYTES);
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut bytes = Vec::new();
+    file.take(offset - start).read_to_end(&mut bytes)?;
+    Ok(sha256_hex(&bytes)[..HASH_HEX].to_string())
+}
+
+/// The sync stream's batches, built lazily as it is polled: one history
+/// at a time, each batch carrying the cursor.
+struct Batches<'a> {
+    importer: &'a ShellHistory,
+    scrubber: Scrubber,
+    planned: std::vec::IntoIter<Planned>,
+    open: Option<(Source, Reader<BufReader<File>>)>,
+    /// Every directory history file, with its directory.
+    tagged: Vec<Tagged>,
+    /// [`directories`] over `tagged`, read once a plain history is opened.
+    directories: Option<HashMap<String, String>>,
+    cursor: ShellCursor,
+    /// Whether the cursor changed without a batch to carry it.
+    unsaved: bool,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        let importer = self.importer;
+        loop {
+            let Some((source, reader)) = &mut self.open else {
+                match self.planned.next() {
+                    Some(Planned { source, offset }) => {
+                        if source.working_directory.is_none() && self.directories.is_none() {
+                            self.directories = Some(directories(&self.tagged).map_err(retryable)?);
+                        }
+                        let mut file = File::open(&source.path).map_err(retryable)?;
+                        file.seek(SeekFrom::Start(offset)).map_err(retryable)?;
+                        self.open = Some((source, Reader::new(BufReader::new(file), offset)));
+                        continue;
+                    }
+                    None if std::mem::take(&mut self.unsaved) => return Ok(Some(self.batch(Vec::new()))),
+                    None => return Ok(None),
+                }
+            };
+            let mut deltas = Vec::new();
+            let mut finished = false;
+            while deltas.len() < importer.batch_size {
+                match reader.next_entry().map_err(retryable)? {
+                    // Scrubbed before an item exists.
+                    Some(entry) if self.scrubber.drops(&entry.command) => {}
+                    Some(entry) => {
+                        let directory = self.directories.as_ref().and_then(|d| d.get(&command_id(&entry)));
+                        deltas.push(Delta::Upsert(importer.item(source, entry, directory)));
+                    }
+                    None => {
+                        finished = true;
+                        break;
+                    }
+                }
+            }
+            let offset = reader.consumed();
+            let state = (offset, tail(&source.path, offset).map_err(retryable)?);
+            let moved = self.cursor.files.get(&source.key) != Some(&state);
+            if moved {
+                self.cursor.files.insert(source.key.clone(), state);
+            }
+            if finished {
+                self.open = None;
+            }
+            if moved || !deltas.is_empty() {
+                self.unsaved = false;
+                return Ok(Some(self.batch(deltas)));
+            }
+        }
+    }
+}
+
+impl Batches<'_> {
+
+    fn batch(&self, deltas: Vec<Delta>) -> DeltaBatch {
+        let cursor = SyncToken(serde_json::to_string(&self.cursor).expect("cursor serialization is infallible"));
+        DeltaBatch { connector_id: self.importer.id.clone(), deltas, cursor: Some(cursor) }
+    }
+}
+
+#[async_trait::async_trait]
+impl Connector for ShellHistory {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        match self.scrubber().and_then(|scrubber| Ok((scrubber, self.plan(cursor)?))) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((scrubber, (planned, tagged, cursor, unsaved))) => batches::stream(Batches {
+                importer: self,
+                scrubber,
+                planned: planned.into_iter(),
+                open: None,
+                tagged,
+                directories: None,
+                cursor,
+                unsaved,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, upserts};
+    use futures_util::StreamExt;
+    use std::fs;
+    use std::io::Write;
+
+    fn commands(batches: &[DeltaBatch]) -> Vec<&str> {
+        upserts(batches).iter().map(|i| i.properties["command"].as_str().unwrap()).collect()
+    }
+
+    fn append(path: &Path, text: &str) {
+        fs::OpenOptions::new().append(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn histories_resume_from_their_offset_and_start_over_when_rewritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let zsh = dir.path().join(".zsh_history");
+        fs::write(
+            &zsh,
+            ": 1720000000:12;cargo build\n\
+             : 1720000020:0;export GITHUB_TOKEN=ghp_0123456789abcdefghij\n\
+             : 1720000030:3;sudo -E /usr/bin/git push\n",
+        )
+        .unwrap();
+        let bash = dir.path().join(".bash_history");
+        fs::write(&bash, "#1719990000\nls -la\n").unwrap();
+        let c = ShellHistory::new("shell").with_history("zsh", &zsh).with_history("bash", &bash).with_batch_size(2);
+
+        let batches = drain(&c, None).await;
+        assert!(batches.iter().all(|b| b.cursor.is_some()), "every batch carries the cursor");
+        assert_eq!(commands(&batches), ["cargo build", "sudo -E /usr/bin/git push", "ls -la"]);
+        let items = upserts(&batches);
+        let hash = sha256_hex(b"cargo build");
+        assert_eq!(items[0].source_id, format!("cmd:1720000000:{}", &hash[..HASH_HEX]));
+        assert_eq!(items[0].kind, ItemKind::Event);
+        assert_eq!(items[0].properties["end"], "2024-07-03T09:46:52+00:00");
+        assert_eq!(items[0].properties["duration_seconds"], 12);
+        assert_eq!(items[1].properties["program"], "git");
+        assert_eq!(items[2].properties["shell"], "bash");
+        assert_eq!(items[2].properties["duration_seconds"], serde_json::Value::Null);
+        let cursor = last_cursor(&batches);
+        assert!(drain(&c, cursor.clone()).await.is_empty(), "nothing new");
+
+        // Appended: only the new command; a line still being written waits.
+        append(&zsh, ": 1720000040:1;make test\n: 1720000050:0;make li");
+        let batches = drain(&c, cursor).await;
+        assert_eq!(commands(&batches), ["make test"]);
+        append(&zsh, "nt\n");
+        let batches = drain(&c, last_cursor(&batches)).await;
+        assert_eq!(commands(&batches), ["make lint"]);
+
+        // Trimmed by the shell: read again from the start.
+        fs::write(&zsh, ": 1720000040:1;make test\n: 1720000050:0;make lint\n").unwrap();
+        let batches = drain(&c, last_cursor(&batches)).await;
+        assert_eq!(commands(&batches), ["make test", "make lint"]);
+
+        let results: Vec<_> = c.sync(Some(SyncToken("nope".into()))).collect().await;
+        assert!(matches!(results.as_slice(), [Err(SyncError::ResyncRequired)]));
+        let broken = [
+            ShellHistory::new("shell").with_history("zsh", dir.path().join("missing")),
+            ShellHistory::new("shell").with_history("zsh", &zsh).with_history("zsh", &bash),
+            ShellHistory::new("shell").with_history("zsh", &zsh).with_scrub_patterns(["("]),
+        ];
+        for c in &broken {
+            let results: Vec<_> = c.sync(None).collect().await;
+            assert!(matches!(results.as_slice(), [Err(SyncError::Fatal { .. })]));
+        }
+    }
+
+    #[tokio::test]
+    async fn directory_histories_tag_commands_with_their_directory() {
+        let home = tempfile::tempdir().unwrap();
+        let main = home.path().join(".zsh_history");
+        fs::write(&main, ": 1720000000:0;cargo test\n: 1720000100:0;ls\n").unwrap();
+        let root = home.path().join(".directory_history");
+        let project = root.join("home/ana/src/wkyt");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("history"), ": 1720000000:0;cargo test\n").unwrap();
+        fs::create_dir_all(root.join("tmp")).unwrap();
+        fs::write(root.join("tmp/history"), ": 1720000200:0;rm -rf scratch\n").unwrap();
+        let c = ShellHistory::new("shell")
+            .with_directory_history("dirs", &root)
+            .with_history("zsh", &main)
+            .with_scrub_patterns(["^rm -rf"]);
+
+        let batches = drain(&c, None).await;
+        let items = upserts(&batches);
+        assert_eq!(commands(&batches), ["cargo test", "ls", "cargo test"]);
+        assert_eq!(items[0].source_id, items[2].source_id, "one command, written to both histories");
+        assert_eq!(items[0].properties["working_directory"], "/home/ana/src/wkyt", "looked up for the plain copy");
+        assert_eq!(items[1].properties["working_directory"], serde_json::Value::Null);
+        assert_eq!(items[2].properties["working_directory"], "/home/ana/src/wkyt");
+        assert_eq!(items[2].properties["history"], "dirs");
+        let cursor = last_cursor(&batches).unwrap();
+        assert!(cursor.0.contains("dirs:/tmp"), "the scrubbed file is read through");
+
+        fs::remove_dir_all(root.join("tmp")).unwrap();
+        let batches = drain(&c, Some(cursor)).await;
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].deltas.is_empty());
+        assert!(!last_cursor(&batches).unwrap().0.contains("dirs:/tmp"));
+    }
+
+    #[tokio::test]
+    async fn a_trimmed_main_history_read_again_keeps_its_commands_directories() {
+        let home = tempfile::tempdir().unwrap();
+        let main = home.path().join(".zsh_history");
+        fs::write(&main, ": 1720000000:0;vim notes\n: 1720000050:0;cargo test\n: 1720000100:0;ls\n").unwrap();
+        let root = home.path().join(".directory_history");
+        let project = root.join("home/ana/src/wkyt");
+        fs::create_dir_all(&project).unwrap();
+        fs::write(project.join("history"), ": 1720000050:0;cargo test\n").unwrap();
+        let c = ShellHistory::new("shell").with_history("zsh", &main).with_directory_history("dirs", &root);
+        let cursor = last_cursor(&drain(&c, None).await);
+
+        // The shell trims the main history; the directory one is unchanged,
+        // so only the main history is read again.
+        fs::write(&main, ": 1720000050:0;cargo test\n: 1720000100:0;ls\n").unwrap();
+        let batches = drain(&c, cursor).await;
+        assert_eq!(commands(&batches), ["cargo test", "ls"]);
+        let items = upserts(&batches);
+        assert_eq!(items[0].properties["working_directory"], "/home/ana/src/wkyt", "not reset to null");
+        assert_eq!(items[0].properties["history"], "zsh");
+        assert_eq!(items[1].properties["working_directory"], serde_json::Value::Null);
+    }
+}
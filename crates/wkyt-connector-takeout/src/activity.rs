@@ -0,0 +1,79 @@
+//! My Activity (`My Activity/{product}/MyActivity.json`): searches, visits,
+//! videos watched and the like, each an [`ItemKind::Event`] holding the
+//! record's own fields.
+//!
+//! Records have no id. One is keyed `activity:{time}:{hash}`, the hash
+//! (16 hex digits of SHA-256) over its product header, title and title
+//! URL — the fields that tell two records at the same instant apart and
+//! that a later export writes the same way, unlike the optional fields
+//! Google keeps adding.
+
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use wkyt_core::hash::hex;
+use wkyt_core::{Item, ItemKind};
+
+/// Whether `value` is a My Activity export: an array of records with a
+/// `header` and a `time`.
+pub(crate) fn is_activity(value: &Value) -> bool {
+    value
+        .as_array()
+        .and_then(|records| records.first())
+        .is_some_and(|first| first["header"].is_string() && first["time"].is_string())
+}
+
+pub(crate) fn items(connector_id: &str, entry: &str, value: &Value) -> Vec<Item> {
+    let Some(records) = value.as_array() else { return Vec::new() };
+    records.iter().filter_map(|record| item(connector_id, entry, record)).collect()
+}
+
+fn item(connector_id: &str, entry: &str, record: &Value) -> Option<Item> {
+    let time = record["time"].as_str()?;
+    let timestamp = DateTime::parse_from_rfc3339(time).ok()?.with_timezone(&Utc);
+    let mut hasher = Sha256::new();
+    for field in ["header", "title", "titleUrl"] {
+        hasher.update(record[field].as_str().unwrap_or_default().as_bytes());
+        hasher.update([0x1f]);
+    }
+    let hash = hex(&hasher.finalize()[..8]);
+
+    let mut properties = match record {
+        Value::Object(fields) => Value::Object(fields.clone()),
+        _ => return None,
+    };
+    properties["type"] = json!("activity");
+    properties["export_file"] = json!(entry);
+    let mut item = Item::new(format!("activity:{time}:{hash}"), connector_id, ItemKind::Event, timestamp, properties);
+    item.raw_payload = Some(record.clone());
+    Some(item)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_are_keyed_by_time_and_what_they_were() {
+        let export = json!([
+            { "header": "Search", "title": "Searched for sourdough", "time": "2020-03-01T09:00:00.123Z",
+              "titleUrl": "https://www.google.com/search?q=sourdough", "products": ["Search"] },
+            { "header": "Search", "title": "Searched for starter", "time": "2020-03-01T09:00:00.123Z",
+              "products": ["Search"], "activityControls": ["Web & App Activity"] },
+            { "header": "YouTube", "title": "no time" }
+        ]);
+        assert!(is_activity(&export));
+        let items = items("takeout", "My Activity/Search/MyActivity.json", &export);
+        assert_eq!(items.len(), 2);
+        assert_ne!(items[0].source_id, items[1].source_id, "same instant, different search");
+        assert!(items[0].source_id.starts_with("activity:2020-03-01T09:00:00.123Z:"));
+        assert_eq!(items[1].properties["title"], "Searched for starter");
+        assert_eq!(items[1].properties["export_file"], "My Activity/Search/MyActivity.json");
+
+        // An added field does not change a record's key.
+        let mut later = export.clone();
+        later[0]["details"] = json!([{ "name": "From Google Ads" }]);
+        assert_eq!(super::items("takeout", "x.json", &later)[0].source_id, items[0].source_id);
+        assert!(!is_activity(&json!({ "header": "Search", "time": "2020-03-01T09:00:00Z" })));
+    }
+}
@@ -0,0 +1,139 @@
+//! Takeout calendars (`Calendar/*.ics`, one file per calendar) as the live
+//! Google Calendar connector's events.
+//!
+//! Each VEVENT is read by the file importer's iCalendar reader and then
+//! re-keyed the way the Calendar API keys it: the event id is the UID
+//! without its `@google.com` suffix, and an overridden occurrence of a
+//! recurring event (a VEVENT with a RECURRENCE-ID) is the instance
+//! `{id}_{original start}` — `YYYYMMDDTHHMMSSZ` in UTC, or `YYYYMMDD` for
+//! an all-day event — with `recurringEventId` naming the series. The items
+//! belong to [`LIVE_CONNECTOR_ID`], so a later online sync upserts over
+//! them instead of adding a second copy.
+//!
+//! What does not line up: the live connector asks for single events, so it
+//! stores every occurrence of a series as its own instance, while the
+//! export has one master VEVENT with an RRULE (kept under the series id,
+//! occurrences not expanded) plus its overrides. Events created outside
+//! Google carry a foreign UID, which the API replaces with an id of its
+//! own; those keep the UID and will not meet their online copy.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde_json::{json, Value};
+use wkyt_core::{Item, ItemKind};
+
+/// The id the Google Calendar connector (`wkyt-connector-google`) syncs
+/// under.
+pub const LIVE_CONNECTOR_ID: &str = "google-calendar";
+
+/// The events of the calendar export at `entry` (a path inside the
+/// export), as the live connector's items. Events starting at or after
+/// `cutoff` are left out.
+pub(crate) fn events(entry: &str, text: &str, fallback: DateTime<Utc>, cutoff: Option<DateTime<Utc>>) -> Vec<Item> {
+    let calendar = entry.rsplit('/').next().unwrap_or(entry);
+    let calendar = calendar.strip_suffix(".ics").unwrap_or(calendar);
+    wkyt_connector_file::ical::events(LIVE_CONNECTOR_ID, entry, text, fallback)
+        .into_iter()
+        .filter(|event| cutoff.is_none_or(|cutoff| event.timestamp < cutoff))
+        .filter_map(|event| rekey(event, calendar))
+        .collect()
+}
+
+/// `event` under its Calendar API id; `None` when it has no UID.
+fn rekey(event: Item, calendar: &str) -> Option<Item> {
+    let mut properties = event.properties;
+    let uid = properties["uid"].as_str()?.to_string();
+    let series = uid.strip_suffix("@google.com").unwrap_or(&uid).to_string();
+    let source_id = match properties.get("originalStartTime").filter(|t| !t.is_null()) {
+        None => {
+            properties["recurringEventId"] = Value::Null;
+            series
+        }
+        Some(original) => {
+            let suffix = instance_suffix(original)?;
+            properties["recurringEventId"] = json!(series);
+            format!("{series}_{suffix}")
+        }
+    };
+    properties["calendar"] = json!(calendar);
+    let mut item = Item::new(source_id, LIVE_CONNECTOR_ID, ItemKind::Event, event.timestamp, properties);
+    item.raw_payload = event.raw_payload;
+    Some(item)
+}
+
+/// The instance-id suffix for an occurrence originally at `original` (a
+/// `{date}` or `{dateTime}` object). A floating time is taken as UTC.
+fn instance_suffix(original: &Value) -> Option<String> {
+    if let Some(date) = original["date"].as_str() {
+        return Some(date.replace('-', ""));
+    }
+    let text = original["dateTime"].as_str()?;
+    let utc = match DateTime::parse_from_rfc3339(text) {
+        Ok(zoned) => zoned.with_timezone(&Utc),
+        Err(_) => NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M:%S").ok()?.and_utc(),
+    };
+    Some(utc.format("%Y%m%dT%H%M%SZ").to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAL: &str = "BEGIN:VCALENDAR\r\n\
+BEGIN:VEVENT\r\n\
+UID:5ud2mh3c1n0v9u2dh3lq8vgv4s@google.com\r\n\
+SUMMARY:Standup\r\n\
+DTSTART;TZID=Europe/Berlin:20200106T093000\r\n\
+RRULE:FREQ=WEEKLY;BYDAY=MO\r\n\
+CREATED:20191220T080000Z\r\n\
+LAST-MODIFIED:20200107T080000Z\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:5ud2mh3c1n0v9u2dh3lq8vgv4s@google.com\r\n\
+RECURRENCE-ID;TZID=Europe/Berlin:20200113T093000\r\n\
+SUMMARY:Standup (late)\r\n\
+DTSTART;TZID=Europe/Berlin:20200113T110000\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:0b1kqqk0f2b4r7lq3q2l9d3bcf@google.com\r\n\
+RECURRENCE-ID;VALUE=DATE:20200301\r\n\
+SUMMARY:Moved holiday\r\n\
+DTSTART;VALUE=DATE:20200302\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:040000008200E00074C5B7101A82E008@example.com\r\n\
+SUMMARY:Invite from elsewhere\r\n\
+DTSTART:20200110T150000Z\r\n\
+END:VEVENT\r\n\
+BEGIN:VEVENT\r\n\
+UID:recent@google.com\r\n\
+DTSTART:20240110T150000Z\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+    #[test]
+    fn events_take_the_ids_the_calendar_api_gives_them() {
+        let fallback = DateTime::UNIX_EPOCH;
+        let cutoff = "2023-01-01T00:00:00Z".parse().ok();
+        let items = events("Takeout/Calendar/ana@example.com.ics", CAL, fallback, cutoff);
+        let ids: Vec<&str> = items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "5ud2mh3c1n0v9u2dh3lq8vgv4s",
+                "5ud2mh3c1n0v9u2dh3lq8vgv4s_20200113T083000Z",
+                "0b1kqqk0f2b4r7lq3q2l9d3bcf_20200301",
+                "040000008200E00074C5B7101A82E008@example.com",
+            ],
+            "the recent event is the live connector's"
+        );
+        assert!(items.iter().all(|i| i.connector_id == LIVE_CONNECTOR_ID));
+        assert_eq!(items[0].id, Item::deterministic_id(LIVE_CONNECTOR_ID, "5ud2mh3c1n0v9u2dh3lq8vgv4s").to_string());
+        assert_eq!(items[0].properties["recurringEventId"], Value::Null);
+        assert_eq!(items[0].properties["updated"], "2020-01-07T08:00:00+00:00");
+        assert_eq!(items[0].properties["calendar"], "ana@example.com");
+        assert_eq!(items[1].properties["recurringEventId"], "5ud2mh3c1n0v9u2dh3lq8vgv4s");
+        assert_eq!(items[1].properties["summary"], "Standup (late)");
+
+        assert_eq!(events("Calendar/x.ics", CAL, fallback, None).len(), 5);
+    }
+}
@@ -0,0 +1,42 @@
+//! The Takeout importer's cursor: the JSON document inside its `SyncToken`.
+//!
+//! Per source — an archive, or a loose file of an extracted export — the
+//! mtime and size it was read at, how many of its entries have been
+//! delivered, and whether that is all of them:
+//!
+//! ```json
+//! { "sources": { "takeout-20241018T101500Z-001.zip":
+//!                  { "mtime_ms": 1729246500000, "size": 2147483648, "read": 412, "done": false },
+//!                "Takeout/Contacts/My Contacts/My Contacts.vcf":
+//!                  { "mtime_ms": 1729246500000, "size": 18211, "read": 1, "done": true } } }
+//! ```
+//!
+//! A finished source is skipped while its mtime and size hold. An
+//! unfinished archive resumes at entry `read` — a zip's central directory
+//! lists its entries in a fixed order, so the index is stable while the
+//! file is. A source whose mtime or size changed is read from the start.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub(crate) struct TakeoutCursor {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) sources: BTreeMap<String, Progress>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Progress {
+    pub(crate) mtime_ms: i64,
+    pub(crate) size: u64,
+    /// Entries delivered, in archive order.
+    pub(crate) read: usize,
+    pub(crate) done: bool,
+}
+
+impl Progress {
+    /// Whether this progress was made on the file as it is now.
+    pub(crate) fn same_file(&self, mtime_ms: i64, size: u64) -> bool {
+        self.mtime_ms == mtime_ms && self.size == size
+    }
+}
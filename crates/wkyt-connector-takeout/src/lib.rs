@@ -0,0 +1,513 @@
+//! Google Takeout importer: reads an export — a Takeout `.zip`, a folder
+//! of them (a large export is split into `takeout-…-001.zip`, `-002.zip`,
+//! ...), or the extracted folder — and turns what it knows into items:
+//!
+//! - calendars (`.ics`) into the live Google Calendar connector's
+//!   [`ItemKind::Event`]s, under its connector id and the ids the Calendar
+//!   API gives them (see [`calendar`]), so a later online sync updates the
+//!   same items rather than adding copies;
+//! - contacts (`.vcf`) into [`ItemKind::Person`]s and their organizations,
+//!   read as the file importer reads address books;
+//! - Semantic Location History into visits and trips (see [`location`])
+//!   and My Activity into activity records (see [`activity`]), both
+//!   [`ItemKind::Event`]s.
+//!
+//! Files are recognized by extension, and JSON ones by their shape rather
+//! than their path: Takeout localizes its folder names. Everything else in
+//! the export — photos, Drive files, mail (which the mail importer reads)
+//! — is skipped without being decompressed.
+//!
+//! Only calendar events starting before the calendar cutoff are imported
+//! (by default, [`LIVE_LOOKBACK_DAYS`] before the pass): later ones are
+//! the live connector's, and an export is older than what it fetches, so
+//! importing them could overwrite a newer copy with a stale one.
+//!
+//! Memory stays bounded however large the export is: an archive is read
+//! through its central directory, one entry at a time, and an entry is
+//! held only while it is parsed, up to [`MAX_ENTRY_BYTES`] (larger ones,
+//! such as a `Records.json` of raw fixes, are skipped). Batches are built
+//! lazily as the stream is polled.
+//!
+//! This is an import of a snapshot, not a mirror: nothing is tombstoned
+//! when it is missing from a later export, or when an archive is deleted
+//! after its import. The cursor records, per archive or loose file, how
+//! far it was read (layout in [`cursor`]); every own batch that follows a
+//! delivered entry carries it, so an interrupted import resumes at the
+//! next entry, and a re-downloaded archive (new mtime or size) is read
+//! again, its items upserting over themselves. Calendar batches go out
+//! under [`LIVE_CONNECTOR_ID`] without a cursor, so they never touch the
+//! live connector's sync token; the takeout batch carrying the checkpoint
+//! follows them. A file that is not a readable zip (still downloading,
+//! say) is skipped and left out of the cursor, so the next pass tries it
+//! again.
+
+mod activity;
+mod calendar;
+mod cursor;
+mod location;
+
+pub use calendar::LIVE_CONNECTOR_ID;
+
+use chrono::{DateTime, Duration, Utc};
+use cursor::{Progress, TakeoutCursor};
+use futures_util::stream;
+use std::collections::{HashSet, VecDeque};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use wkyt_core::batches::{self, Step};
+use wkyt_core::files::{from_millis, mtime_ms, retryable, Walk};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, SyncError, SyncToken};
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+const DEFAULT_BATCH_SIZE: usize = 100;
+/// How far back the live Calendar connector reads on a full sync (its
+/// `DEFAULT_LOOKBACK_DAYS`); the default calendar cutoff is this long
+/// before the pass.
+pub const LIVE_LOOKBACK_DAYS: i64 = 90;
+/// Largest entry read. A larger one is skipped.
+pub const MAX_ENTRY_BYTES: u64 = 256 * 1024 * 1024;
+
+pub struct TakeoutImporter {
+    id: String,
+    path: PathBuf,
+    batch_size: usize,
+    cutoff: Cutoff,
+}
+
+#[derive(Clone, Copy)]
+enum Cutoff {
+    /// `LIVE_LOOKBACK_DAYS` before the pass.
+    Live,
+    At(Option<DateTime<Utc>>),
+}
+
+/// An archive or loose export file found by the scan.
+struct Found {
+    /// Relative to the scanned folder, `/`-separated.
+    path: String,
+    archive: bool,
+    mtime_ms: i64,
+    size: u64,
+}
+
+/// Something to read this pass.
+enum Source {
+    Archive { path: String, mtime_ms: i64, size: u64, from: usize },
+    Loose { path: String, mtime_ms: i64, size: u64 },
+}
+
+impl TakeoutImporter {
+    /// `path` is a Takeout `.zip`, or a folder holding archives, extracted
+    /// exports, or both.
+    pub fn new(id: impl Into<String>, path: PathBuf) -> Self {
+        Self { id: id.into(), path, batch_size: DEFAULT_BATCH_SIZE, cutoff: Cutoff::Live }
+    }
+
+    /// Items per batch (default 100).
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Import only calendar events starting before `cutoff`; `None`
+    /// imports them all. Defaults to [`LIVE_LOOKBACK_DAYS`] before each
+    /// pass — set `None` when the live connector is not in use.
+    pub fn with_calendar_cutoff(mut self, cutoff: Option<DateTime<Utc>>) -> Self {
+        self.cutoff = Cutoff::At(cutoff);
+        self
+    }
+
+    /// The folder paths are relative to, and the archives and loose files
+    /// under it (just `path` when that is an archive).
+    fn scan(&self) -> Result<(PathBuf, Vec<Found>), SyncError> {
+        let meta = std::fs::metadata(&self.path)
+            .map_err(|e| SyncError::Fatal { source: format!("takeout path {:?}: {e}", self.path).into() })?;
+        if meta.is_dir() {
+            return Ok((self.path.clone(), scan(&self.path).map_err(retryable)?));
+        }
+        let name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or_default().to_string();
+        if !archive(&name) {
+            return Err(SyncError::Fatal { source: format!("{:?} is neither a folder nor a .zip", self.path).into() });
+        }
+        let base = self.path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok((base, vec![Found { path: name, archive: true, mtime_ms: mtime_ms(&meta), size: meta.len() }]))
+    }
+
+    /// Decide what to read: sources not finished on their current
+    /// contents. Also returns the starting cursor, minus sources that are
+    /// gone or changed, and whether that differs from the one passed in.
+    fn plan(&self, cursor: Option<SyncToken>) -> Result<(PathBuf, Vec<Source>, TakeoutCursor, bool), SyncError> {
+        let mut prev: TakeoutCursor = match cursor {
+            None => TakeoutCursor::default(),
+            Some(tok) => serde_json::from_str(&tok.0).map_err(|_| SyncError::ResyncRequired)?,
+        };
+        let (base, found) = self.scan()?;
+
+        let present: HashSet<&str> = found.iter().map(|f| f.path.as_str()).collect();
+        let known = prev.sources.len();
+        prev.sources.retain(|path, _| present.contains(path.as_str()));
+        let mut unsaved = prev.sources.len() != known;
+
+        let mut sources = Vec::new();
+        for f in &found {
+            let from = match prev.sources.get(&f.path) {
+                Some(p) if p.same_file(f.mtime_ms, f.size) && p.done => continue,
+                Some(p) if p.same_file(f.mtime_ms, f.size) => p.read,
+                Some(_) => {
+                    // Replaced: forget the old progress now, so a
+                    // checkpoint before this source is reached does not
+                    // carry it.
+                    prev.sources.remove(&f.path);
+                    unsaved = true;
+                    0
+                }
+                None => 0,
+            };
+            let (path, mtime_ms, size) = (f.path.clone(), f.mtime_ms, f.size);
+            sources.push(match f.archive {
+                true => Source::Archive { path, mtime_ms, size, from },
+                false => Source::Loose { path, mtime_ms, size },
+            });
+        }
+        Ok((base, sources, prev, unsaved))
+    }
+
+    /// The items of export file `name`, by what it is; none for a file the
+    /// importer does not know.
+    fn entry_items(
+        &self,
+        name: &str,
+        bytes: &[u8],
+        fallback: DateTime<Utc>,
+        cutoff: Option<DateTime<Utc>>,
+    ) -> Vec<Item> {
+        let lower = name.to_ascii_lowercase();
+        if lower.ends_with(".ics") {
+            return calendar::events(name, &String::from_utf8_lossy(bytes), fallback, cutoff);
+        }
+        if lower.ends_with(".vcf") {
+            return wkyt_connector_file::vcard::people(&self.id, name, &String::from_utf8_lossy(bytes), fallback);
+        }
+        let Ok(value) = serde_json::from_slice(bytes) else { return Vec::new() };
+        if location::is_timeline(&value) {
+            location::items(&self.id, name, &value)
+        } else if activity::is_activity(&value) {
+            activity::items(&self.id, name, &value)
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+/// Whether an entry named `name` may hold something the importer reads.
+fn wanted(name: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    [".ics", ".vcf", ".json"].iter().any(|ext| lower.ends_with(ext))
+}
+
+/// The archives and wanted loose files under `dir`, sorted by path.
+fn scan(dir: &Path) -> std::io::Result<Vec<Found>> {
+    let files = Walk::new().files(dir, |name| archive(name) || wanted(name))?;
+    Ok(files
+        .into_iter()
+        .map(|f| Found { archive: archive(&f.path), path: f.path, mtime_ms: f.mtime_ms, size: f.size })
+        .collect())
+}
+
+fn archive(name: &str) -> bool {
+    name.to_ascii_lowercase().ends_with(".zip")
+}
+
+/// An archive partway through being read.
+struct Reading {
+    path: String,
+    archive: ZipArchive<BufReader<File>>,
+    /// The next entry to read.
+    next: usize,
+    mtime: DateTime<Utc>,
+}
+
+/// The sync stream's batches, built lazily as it is polled.
+struct Batches<'a> {
+    importer: &'a TakeoutImporter,
+    base: PathBuf,
+    cutoff: Option<DateTime<Utc>>,
+    sources: std::vec::IntoIter<Source>,
+    reading: Option<Reading>,
+    cursor: TakeoutCursor,
+    /// Built, not yet handed out.
+    outbox: VecDeque<DeltaBatch>,
+    /// Whether `cursor` differs from the last one handed out.
+    unsaved: bool,
+}
+
+impl Step for Batches<'_> {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+        if let Some(batch) = self.outbox.pop_front() {
+            return Ok(Some(batch));
+        }
+        let importer = self.importer;
+        let mut items = Vec::new();
+        while items.len() < importer.batch_size {
+            if let Some(reading) = &mut self.reading {
+                let progress = self.cursor.sources.get_mut(&reading.path).expect("inserted on open");
+                if reading.next == reading.archive.len() {
+                    progress.done = true;
+                    self.reading = None;
+                    continue;
+                }
+                let index = reading.next;
+                let name = reading.archive.name_for_index(index).unwrap_or_default().to_string();
+                let mut bytes = None;
+                if wanted(&name) {
+                    let entry = reading.archive.by_index(index).map_err(zip_error)?;
+                    if !entry.is_dir() && entry.size() <= MAX_ENTRY_BYTES {
+                        bytes = Some(read_all(entry)?);
+                    }
+                }
+                if let Some(bytes) = bytes {
+                    items.extend(importer.entry_items(&name, &bytes, reading.mtime, self.cutoff));
+                }
+                reading.next += 1;
+                progress.read = reading.next;
+                self.unsaved = true;
+                continue;
+            }
+            match self.sources.next() {
+                None => break,
+                Some(Source::Archive { path, mtime_ms, size, from }) => {
+                    let file = File::open(self.base.join(&path)).map_err(retryable)?;
+                    let archive = match ZipArchive::new(BufReader::new(file)) {
+                        Ok(archive) => archive,
+                        Err(ZipError::Io(e)) => return Err(retryable(e)),
+                        // Not (yet) a zip: left for the next pass.
+                        Err(_) => continue,
+                    };
+                    let progress = Progress { mtime_ms, size, read: from, done: false };
+                    self.cursor.sources.insert(path.clone(), progress);
+                    self.reading = Some(Reading { path, archive, next: from, mtime: from_millis(mtime_ms) });
+                }
+                Some(Source::Loose { path, mtime_ms, size }) => {
+                    let file = File::open(self.base.join(&path)).map_err(retryable)?;
+                    if size <= MAX_ENTRY_BYTES {
+                        let bytes = read_all(file)?;
+                        items.extend(importer.entry_items(&path, &bytes, from_millis(mtime_ms), self.cutoff));
+                    }
+                    self.cursor.sources.insert(path, Progress { mtime_ms, size, read: 1, done: true });
+                    self.unsaved = true;
+                }
+            }
+        }
+
+        // Calendar items first, under the live connector's id and without
+        // a cursor; then this importer's, the last batch carrying the
+        // checkpoint, which covers every entry whose items went before it.
+        let (calendar, own): (Vec<Item>, Vec<Item>) =
+            items.into_iter().partition(|item| item.connector_id == LIVE_CONNECTOR_ID);
+        for chunk in calendar.chunks(importer.batch_size) {
+            self.outbox.push_back(batch(LIVE_CONNECTOR_ID, chunk));
+        }
+        for chunk in own.chunks(importer.batch_size) {
+            self.outbox.push_back(batch(&importer.id, chunk));
+        }
+        if self.unsaved {
+            if self.outbox.back().is_none_or(|b| b.connector_id != importer.id) {
+                self.outbox.push_back(batch(&importer.id, &[]));
+            }
+            let cursor = serde_json::to_string(&self.cursor).expect("cursor serialization is infallible");
+            self.outbox.back_mut().expect("pushed above").cursor = Some(SyncToken(cursor));
+            self.unsaved = false;
+        }
+        Ok(self.outbox.pop_front())
+    }
+}
+
+fn batch(connector_id: &str, items: &[Item]) -> DeltaBatch {
+    DeltaBatch {
+        connector_id: connector_id.to_string(),
+        deltas: items.iter().cloned().map(Delta::Upsert).collect(),
+        cursor: None,
+    }
+}
+
+fn read_all(reader: impl Read) -> Result<Vec<u8>, SyncError> {
+    let mut bytes = Vec::new();
+    reader.take(MAX_ENTRY_BYTES).read_to_end(&mut bytes).map_err(retryable)?;
+    Ok(bytes)
+}
+
+fn zip_error(e: ZipError) -> SyncError {
+    match e {
+        ZipError::Io(e) => retryable(e),
+        e => SyncError::Fatal { source: Box::new(e) },
+    }
+}
+
+#[async_trait::async_trait]
+impl Connector for TakeoutImporter {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        let cutoff = match self.cutoff {
+            Cutoff::Live => Some(Utc::now() - Duration::days(LIVE_LOOKBACK_DAYS)),
+            Cutoff::At(cutoff) => cutoff,
+        };
+        match self.plan(cursor) {
+            Err(e) => Box::pin(stream::iter(vec![Err(e)])),
+            Ok((base, sources, cursor, unsaved)) => batches::stream(Batches {
+                importer: self,
+                base,
+                cutoff,
+                sources: sources.into_iter(),
+                reading: None,
+                cursor,
+                outbox: VecDeque::new(),
+                unsaved,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wkyt_core::testing::{drain, last_cursor, upserts};
+    use futures_util::StreamExt;
+    use std::fs;
+    use std::io::Write;
+    use wkyt_core::ItemKind;
+    use zip::write::SimpleFileOptions;
+
+    /// The items upserted under `connector_id`, in order.
+    fn upserts_under<'a>(batches: &'a [DeltaBatch], connector_id: &str) -> Vec<&'a Item> {
+        upserts(batches).into_iter().filter(|i| i.connector_id == connector_id).collect()
+    }
+
+    const ICS: &str = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:abc123@google.com\nSUMMARY:Dentist\n\
+                       DTSTART:20190405T080000Z\nEND:VEVENT\nEND:VCALENDAR\n";
+    const VCF: &str = "BEGIN:VCARD\nVERSION:3.0\nFN:Ana Lee\nEMAIL;TYPE=INTERNET:Ana@Example.com\nEND:VCARD\n";
+    const TIMELINE: &str = r#"{"timelineObjects": [{"placeVisit": {"location": {"placeId": "p1", "name": "Cafe"},
+                              "duration": {"startTimestamp": "2019-04-05T09:00:00Z"}}}]}"#;
+    const ACTIVITY: &str = r#"[{"header": "Search", "title": "Searched for x", "time": "2019-04-05T10:00:00Z"}]"#;
+
+    fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, content) in entries {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn export() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("Takeout/Calendar/ana@example.com.ics", ICS),
+            ("Takeout/Contacts/My Contacts/My Contacts.vcf", VCF),
+            ("Takeout/Google Photos/IMG_0001.jpg", "\u{ff}\u{d8} not read"),
+            ("Takeout/Standortverlauf/Semantic Location History/2019/2019_APRIL.json", TIMELINE),
+            ("Takeout/My Activity/Search/MyActivity.json", ACTIVITY),
+            ("Takeout/Drive/settings.json", r#"{"theme": "dark"}"#),
+        ]
+    }
+
+    #[tokio::test]
+    async fn archives_become_live_calendar_events_and_own_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("takeout-001.zip");
+        write_zip(&zip, &export());
+        let c = TakeoutImporter::new("takeout", zip.clone());
+
+        let batches = drain(&c, None).await;
+        assert_eq!(batches.iter().map(|b| b.connector_id.as_str()).collect::<Vec<_>>(), [LIVE_CONNECTOR_ID, "takeout"]);
+        assert_eq!(batches[0].cursor, None, "the live connector's sync token is left alone");
+        let event = upserts_under(&batches, LIVE_CONNECTOR_ID)[0];
+        assert_eq!(event.source_id, "abc123");
+        assert_eq!(event.id, Item::deterministic_id(LIVE_CONNECTOR_ID, "abc123").to_string());
+
+        let own = upserts_under(&batches, "takeout");
+        let kinds: Vec<(&ItemKind, &str)> =
+            own.iter().map(|i| (&i.kind, i.properties["type"].as_str().unwrap_or(""))).collect();
+        assert_eq!(kinds, [(&ItemKind::Person, ""), (&ItemKind::Event, "place_visit"), (&ItemKind::Event, "activity")]);
+        assert_eq!(own[0].properties["emails"][0]["address"], "ana@example.com");
+
+        let cursor = last_cursor(&batches);
+        let progress: serde_json::Value = serde_json::from_str(&cursor.as_ref().unwrap().0).unwrap();
+        assert_eq!(progress["sources"]["takeout-001.zip"]["read"], 6);
+        assert_eq!(progress["sources"]["takeout-001.zip"]["done"], true);
+        assert!(drain(&c, cursor.clone()).await.is_empty());
+
+        // A re-downloaded archive is read again; nothing is tombstoned.
+        write_zip(&zip, &export()[..2]);
+        let batches = drain(&c, cursor).await;
+        assert_eq!(upserts_under(&batches, LIVE_CONNECTOR_ID).len() + upserts_under(&batches, "takeout").len(), 2);
+        assert!(batches.iter().flat_map(|b| &b.deltas).all(|d| matches!(d, Delta::Upsert(_))));
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_import_resumes_at_the_next_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let zip = dir.path().join("takeout.zip");
+        write_zip(&zip, &export());
+        let c = TakeoutImporter::new("takeout", zip).with_batch_size(1);
+
+        let batches = drain(&c, None).await;
+        let checkpoints: Vec<usize> =
+            batches.iter().enumerate().filter(|(_, b)| b.cursor.is_some()).map(|(i, _)| i).collect();
+        assert_eq!(checkpoints.len(), 5, "one per entry that yielded items, and one at the end");
+        assert!(checkpoints.iter().all(|&i| batches[i].connector_id == "takeout"));
+
+        // The first checkpoint follows the calendar entry: the rest is
+        // read from the contacts on.
+        let first = batches[checkpoints[0]].cursor.clone();
+        let rest = drain(&c, first).await;
+        assert!(upserts_under(&rest, LIVE_CONNECTOR_ID).is_empty());
+        assert_eq!(upserts_under(&rest, "takeout").len(), 3);
+    }
+
+    #[tokio::test]
+    async fn extracted_exports_and_calendar_cutoffs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("Takeout/Calendar")).unwrap();
+        fs::create_dir_all(dir.path().join("Takeout/Contacts")).unwrap();
+        fs::write(dir.path().join("Takeout/Calendar/ana@example.com.ics"), ICS).unwrap();
+        fs::write(dir.path().join("Takeout/Contacts/all.vcf"), VCF).unwrap();
+        fs::write(dir.path().join("Takeout/archive_browser.html"), "<html>").unwrap();
+        fs::write(dir.path().join("partial.zip"), "PK still downloading").unwrap();
+
+        let cutoff = "2019-01-01T00:00:00Z".parse().ok();
+        let c = TakeoutImporter::new("takeout", dir.path().to_path_buf()).with_calendar_cutoff(cutoff);
+        let batches = drain(&c, None).await;
+        assert!(upserts_under(&batches, LIVE_CONNECTOR_ID).is_empty(), "the event is after the cutoff");
+        assert_eq!(upserts_under(&batches, "takeout")[0].source_id, "vcard:Takeout/Contacts/all.vcf#0");
+        let cursor = last_cursor(&batches);
+        let progress: serde_json::Value = serde_json::from_str(&cursor.as_ref().unwrap().0).unwrap();
+        let sources: Vec<&String> = progress["sources"].as_object().unwrap().keys().collect();
+        assert_eq!(sources, ["Takeout/Calendar/ana@example.com.ics", "Takeout/Contacts/all.vcf"]);
+
+        // Deleting an imported file only drops it from the cursor.
+        fs::remove_file(dir.path().join("Takeout/Contacts/all.vcf")).unwrap();
+        let batches = drain(&c, cursor).await;
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].deltas.is_empty() && batches[0].cursor.is_some());
+    }
+
+    #[tokio::test]
+    async fn bad_paths_and_cursors_fail_the_pass() {
+        let dir = tempfile::tempdir().unwrap();
+        let c = TakeoutImporter::new("takeout", dir.path().join("missing.zip"));
+        let results: Vec<_> = c.sync(None).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::Fatal { .. })]));
+
+        let c = TakeoutImporter::new("takeout", dir.path().to_path_buf());
+        let results: Vec<_> = c.sync(Some(SyncToken("not json".into()))).collect().await;
+        assert!(matches!(results[..], [Err(SyncError::ResyncRequired)]));
+    }
+}
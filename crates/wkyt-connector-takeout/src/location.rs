@@ -0,0 +1,129 @@
+//! Semantic Location History (`Location History/Semantic Location
+//! History/{year}/{year}_{MONTH}.json`): the visits and trips Google
+//! condensed the raw fixes into, each an [`ItemKind::Event`].
+//!
+//! A `placeVisit` is keyed `visit:{start}:{placeId}`, an `activitySegment`
+//! `segment:{start}`, the start normalized to RFC 3339 in UTC with
+//! milliseconds — older exports write `startTimestampMs` strings, newer
+//! ones `startTimestamp`, and both name the same instant. The raw fixes of
+//! `Records.json` are not read: one item per fix would be millions of
+//! them, and this history already summarizes them.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{json, Value};
+use wkyt_core::{Item, ItemKind};
+
+/// Whether `value` is a Semantic Location History month.
+pub(crate) fn is_timeline(value: &Value) -> bool {
+    value.get("timelineObjects").is_some_and(Value::is_array)
+}
+
+pub(crate) fn items(connector_id: &str, entry: &str, value: &Value) -> Vec<Item> {
+    let Some(objects) = value["timelineObjects"].as_array() else { return Vec::new() };
+    objects
+        .iter()
+        .filter_map(|object| {
+            if let Some(visit) = object.get("placeVisit") {
+                visit_item(connector_id, entry, visit)
+            } else {
+                object.get("activitySegment").and_then(|segment| segment_item(connector_id, entry, segment))
+            }
+        })
+        .collect()
+}
+
+fn visit_item(connector_id: &str, entry: &str, visit: &Value) -> Option<Item> {
+    let (start, end) = duration(&visit["duration"])?;
+    let place = &visit["location"];
+    let place_id = place["placeId"].as_str().unwrap_or_default();
+    let properties = json!({
+        "type": "place_visit",
+        "name": place["name"],
+        "address": place["address"],
+        "placeId": place["placeId"],
+        "semanticType": place["semanticType"],
+        "location": point(place),
+        "start": instant(start),
+        "end": end.map(instant),
+        "confidence": visit["placeConfidence"],
+        "export_file": entry,
+    });
+    let source_id = format!("visit:{}:{place_id}", instant(start));
+    let mut item = Item::new(source_id, connector_id, ItemKind::Event, start, properties);
+    item.raw_payload = Some(visit.clone());
+    Some(item)
+}
+
+fn segment_item(connector_id: &str, entry: &str, segment: &Value) -> Option<Item> {
+    let (start, end) = duration(&segment["duration"])?;
+    let properties = json!({
+        "type": "activity_segment",
+        "activityType": segment["activityType"],
+        "distance": segment["distance"],
+        "startLocation": point(&segment["startLocation"]),
+        "endLocation": point(&segment["endLocation"]),
+        "start": instant(start),
+        "end": end.map(instant),
+        "confidence": segment["confidence"],
+        "export_file": entry,
+    });
+    let source_id = format!("segment:{}", instant(start));
+    let mut item = Item::new(source_id, connector_id, ItemKind::Event, start, properties);
+    item.raw_payload = Some(segment.clone());
+    Some(item)
+}
+
+/// A `duration`'s start (required) and end, in either export format.
+fn duration(duration: &Value) -> Option<(DateTime<Utc>, Option<DateTime<Utc>>)> {
+    let at = |name: &str| {
+        let iso = duration[format!("{name}Timestamp")].as_str();
+        let millis = duration[format!("{name}TimestampMs")].as_str();
+        iso.and_then(|t| DateTime::parse_from_rfc3339(t).ok().map(|t| t.with_timezone(&Utc)))
+            .or_else(|| millis?.parse().ok().and_then(DateTime::from_timestamp_millis))
+    };
+    Some((at("start")?, at("end")))
+}
+
+/// `{latitude, longitude}` from a location's E7 fixed-point fields.
+fn point(location: &Value) -> Value {
+    match (location["latitudeE7"].as_i64(), location["longitudeE7"].as_i64()) {
+        (Some(lat), Some(lng)) => json!({ "latitude": lat as f64 / 1e7, "longitude": lng as f64 / 1e7 }),
+        _ => Value::Null,
+    }
+}
+
+fn instant(t: DateTime<Utc>) -> String {
+    t.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn visits_and_segments_are_keyed_by_their_start_in_either_format() {
+        let month = json!({ "timelineObjects": [
+            { "activitySegment": {
+                "startLocation": { "latitudeE7": 525200660, "longitudeE7": 134049540 },
+                "endLocation": { "latitudeE7": 525163000, "longitudeE7": 133777000 },
+                "duration": { "startTimestampMs": "1578297600000", "endTimestampMs": "1578299400000" },
+                "distance": 2310, "activityType": "CYCLING", "confidence": "HIGH" } },
+            { "placeVisit": {
+                "location": { "latitudeE7": 525163000, "longitudeE7": 133777000, "placeId": "ChIJ_abc",
+                              "name": "Office", "address": "Pariser Platz 1" },
+                "duration": { "startTimestamp": "2020-01-06T08:30:00.000Z", "endTimestamp": "2020-01-06T17:00:00Z" },
+                "placeConfidence": "HIGH_CONFIDENCE" } },
+            { "placeVisit": { "location": { "name": "no duration" } } }
+        ]});
+        assert!(is_timeline(&month));
+        let items = items("takeout", "Semantic Location History/2020/2020_JANUARY.json", &month);
+        assert_eq!(items.len(), 2, "a visit without a start is skipped");
+        assert_eq!(items[0].source_id, "segment:2020-01-06T08:00:00.000Z");
+        assert_eq!(items[0].properties["startLocation"]["latitude"], 52.520066);
+        assert_eq!(items[0].properties["end"], "2020-01-06T08:30:00.000Z");
+        assert_eq!(items[1].source_id, "visit:2020-01-06T08:30:00.000Z:ChIJ_abc");
+        assert_eq!(items[1].properties["name"], "Office");
+        assert_eq!(items[1].timestamp.to_rfc3339(), "2020-01-06T08:30:00+00:00");
+        assert!(!is_timeline(&json!([{ "timelineObjects": [] }])));
+    }
+}
@@ -0,0 +1,68 @@
+//! Sync streams built one batch at a time, for the importers that read
+//! their source as the stream is polled.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::{DeltaBatch, DeltaStream, SyncError};
+
+/// A source of batches: `Ok(None)` once there are no more.
+pub trait Step {
+    fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError>;
+}
+
+/// The batches `steps` builds, as a sync stream. It ends at the first
+/// `Ok(None)` or at its first error, after handing the error out; `step`
+/// is not called again either way.
+pub fn stream<'a, S: Step + Send + Unpin + 'a>(steps: S) -> DeltaStream<'a> {
+    Box::pin(Steps { steps, done: false })
+}
+
+struct Steps<S> {
+    steps: S,
+    done: bool,
+}
+
+impl<S: Step + Unpin> Stream for Steps<S> {
+    type Item = Result<DeltaBatch, SyncError>;
+
+    fn poll_next(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let next = this.steps.step().transpose();
+        this.done = !matches!(next, Some(Ok(_)));
+        Poll::Ready(next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+
+    struct Countdown(Vec<Result<Option<DeltaBatch>, SyncError>>);
+
+    impl Step for Countdown {
+        fn step(&mut self) -> Result<Option<DeltaBatch>, SyncError> {
+            self.0.pop().expect("not called after the end")
+        }
+    }
+
+    fn batch() -> Option<DeltaBatch> {
+        Some(DeltaBatch { connector_id: "c".into(), deltas: Vec::new(), cursor: None })
+    }
+
+    #[tokio::test]
+    async fn the_stream_ends_at_its_first_error_or_at_none() {
+        let steps = Countdown(vec![Ok(batch()), Err(SyncError::ResyncRequired), Ok(batch())]);
+        let results: Vec<_> = stream(steps).collect().await;
+        assert!(matches!(results[..], [Ok(_), Err(SyncError::ResyncRequired)]));
+
+        let steps = Countdown(vec![Ok(batch()), Ok(None), Ok(batch())]);
+        assert_eq!(stream(steps).collect::<Vec<_>>().await.len(), 1);
+    }
+}
@@ -7,6 +7,20 @@ use std::pin::Pin;
 /// only, so implementing crates choose their own combinator library.
 pub type DeltaStream<'a> = Pin<Box<dyn Stream<Item = Result<DeltaBatch, SyncError>> + Send + 'a>>;
 
+/// Connector-side work to run once a batch it produced is settled, told
+/// how; see [`Connector::on_commit`].
+pub type CommitHook = Box<dyn FnOnce(Settled) + Send>;
+
+/// How the host settled a delivered batch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Settled {
+    /// Applied: its vault transaction committed.
+    Committed,
+    /// Set aside in the vault's dead-letter quarantine instead, its cursor
+    /// advanced all the same.
+    Quarantined,
+}
+
 /// The contract every data connector fulfills (revised per the M0 review;
 /// supersedes the old init/full_sync/incremental_sync shape).
 #[async_trait::async_trait]
@@ -26,4 +40,37 @@ pub trait Connector: Send + Sync {
     /// the orchestrator to abandon the stream, discard the stored cursor,
     /// and restart with `sync(None)`.
     fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_>;
+
+    /// Work that may only happen once `batch` is durable — e.g. removing a
+    /// source file the vault now holds. The host asks as it publishes each
+    /// batch and runs the hook when the batch's delivery is acked, i.e.
+    /// after its vault transaction commits or it is quarantined, saying
+    /// which. A quarantined batch lives only as a dead letter, which an
+    /// operator may discard, so work that destroys the source must wait
+    /// for [`Settled::Committed`]. A hook can be lost — a crash, a pass
+    /// spooled while the vault is locked — so the work must be redoable
+    /// by a later pass from the committed cursor. None by default.
+    fn on_commit(&self, batch: &DeltaBatch) -> Option<CommitHook> {
+        let _ = batch;
+        None
+    }
+}
+
+/// Wake-ups from a watched source. Each item means "the source changed
+/// since the previous item" — it says nothing about what changed, which
+/// the next [`Connector::sync`] from the stored cursor works out as usual.
+/// The stream ending means the watch died; fall back to polling.
+pub type ChangeStream = Pin<Box<dyn Stream<Item = ()> + Send>>;
+
+/// Optional companion to [`Connector`] for sources that can report
+/// changes as they happen, so the host syncs on change instead of on a
+/// timer (see `wkyt_host::Watch`).
+///
+/// Notifications are hints, not a delivery guarantee: a watcher may drop
+/// or coalesce them (kernel queue overflow, a change made while it was
+/// being set up), so hosts still rescan periodically. Implementations
+/// should likewise prefer a spurious wake-up to a missed one.
+pub trait ChangeNotifier: Connector {
+    /// Start watching. Dropping the stream stops the watch.
+    fn changes(&self) -> Result<ChangeStream, SyncError>;
 }
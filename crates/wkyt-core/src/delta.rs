@@ -7,11 +7,21 @@ use serde::{Deserialize, Serialize};
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SyncToken(pub String);
 
+/// Upper bound on a [`Delta::Chunk`]'s `data`; validation rejects more.
+pub const MAX_CHUNK_BYTES: usize = 1024 * 1024;
+
 /// One change from a source. Tombstones are how deletions reach the vault.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Delta {
     Upsert(Item),
     Tombstone { source_id: String },
+    /// A piece of content too large or too binary to ride inline in an
+    /// item, addressed by the lowercase hex SHA-256 of `data`. An item
+    /// references its content as the ordered list of its chunks' hashes
+    /// under a `chunks` property; each chunk must commit no later than the
+    /// first item referencing it. Content-addressed, so re-delivery and
+    /// shared content are free: the vault keeps one copy per hash.
+    Chunk { sha256: String, data: Vec<u8> },
 }
 
 /// The unit of transfer AND the unit of durability: the vault applies
@@ -0,0 +1,167 @@
+//! Helpers shared by the importers that read a folder of exported files:
+//! one directory walk, and the conversions every such importer needs.
+
+use std::fs::{self, Metadata};
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use chrono::{DateTime, Utc};
+
+use crate::SyncError;
+
+/// A file [`Walk::files`] selected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Found {
+    /// Relative to the walked folder, `/`-separated.
+    pub path: String,
+    pub mtime_ms: i64,
+    pub size: u64,
+}
+
+/// A recursive walk of a folder. Symlinks are not followed, and a
+/// subdirectory that vanishes or cannot be read mid-walk is skipped rather
+/// than failing the whole pass; only the root itself must be readable.
+#[derive(Debug, Clone, Default)]
+pub struct Walk {
+    hidden: bool,
+}
+
+impl Walk {
+    /// Hidden (dot-named) entries are skipped by default.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also walk hidden directories and select hidden files.
+    pub fn with_hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+
+    /// The regular files under `root` whose name `wanted` accepts, sorted
+    /// by path.
+    pub fn files(&self, root: &Path, wanted: impl Fn(&str) -> bool) -> io::Result<Vec<Found>> {
+        let mut found = Vec::new();
+        let mut pending: Vec<(PathBuf, String)> = vec![(root.to_path_buf(), String::new())];
+        while let Some((dir, prefix)) = pending.pop() {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(e) if !prefix.is_empty() && skippable(&e) => continue,
+                Err(e) => return Err(e),
+            };
+            for entry in entries {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) if skippable(&e) => continue,
+                    Err(e) => return Err(e),
+                };
+                let Ok(name) = entry.file_name().into_string() else { continue };
+                if !self.hidden && name.starts_with('.') {
+                    continue;
+                }
+                let path = format!("{prefix}{name}");
+                // Not followed: the entry's own type, not its target's.
+                let file_type = match entry.file_type() {
+                    Ok(file_type) => file_type,
+                    Err(e) if skippable(&e) => continue,
+                    Err(e) => return Err(e),
+                };
+                if file_type.is_dir() {
+                    pending.push((entry.path(), format!("{path}/")));
+                } else if file_type.is_file() && wanted(&name) {
+                    let meta = match entry.metadata() {
+                        Ok(meta) => meta,
+                        Err(e) if skippable(&e) => continue,
+                        Err(e) => return Err(e),
+                    };
+                    found.push(Found { path, mtime_ms: mtime_ms(&meta), size: meta.len() });
+                }
+            }
+        }
+        found.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(found)
+    }
+}
+
+/// Errors that mean "this entry is not there for us" rather than "the
+/// walk failed".
+fn skippable(e: &io::Error) -> bool {
+    matches!(e.kind(), ErrorKind::NotFound | ErrorKind::PermissionDenied)
+}
+
+/// A file's modification time in Unix milliseconds; 0 when unknown.
+pub fn mtime_ms(meta: &Metadata) -> i64 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_millis() as i64)
+}
+
+/// The inverse of [`mtime_ms`], falling back to now when out of range.
+pub fn from_millis(ms: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(ms).unwrap_or_else(Utc::now)
+}
+
+/// A local I/O failure, worth retrying on the next pass.
+pub fn retryable(e: io::Error) -> SyncError {
+    SyncError::Retryable { source: Box::new(e), retry_after: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(found: &[Found]) -> Vec<&str> {
+        found.iter().map(|f| f.path.as_str()).collect()
+    }
+
+    #[test]
+    fn walks_subfolders_in_path_order_and_skips_hidden_entries() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("b/c")).unwrap();
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        for path in ["z.csv", "b/a.csv", "b/c/d.csv", "b/skip.txt", ".hidden.csv", ".git/x.csv"] {
+            fs::write(dir.join(path), "x").unwrap();
+        }
+        let csv = |name: &str| name.ends_with(".csv");
+
+        let found = Walk::new().files(dir, csv).unwrap();
+        assert_eq!(paths(&found), ["b/a.csv", "b/c/d.csv", "z.csv"]);
+        assert_eq!(found[0].size, 1);
+        assert!(found[0].mtime_ms > 0);
+
+        let found = Walk::new().with_hidden(true).files(dir, csv).unwrap();
+        assert_eq!(paths(&found), [".git/x.csv", ".hidden.csv", "b/a.csv", "b/c/d.csv", "z.csv"]);
+    }
+
+    #[test]
+    fn a_missing_root_fails_the_walk() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("absent");
+        assert_eq!(Walk::new().files(&dir, |_| true).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn an_unreadable_subfolder_is_skipped() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        fs::create_dir_all(dir.join("locked")).unwrap();
+        fs::write(dir.join("locked/a.csv"), "x").unwrap();
+        fs::write(dir.join("b.csv"), "x").unwrap();
+        fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o000)).unwrap();
+        // Root ignores permissions, so only check the skip when it applies.
+        let readable = fs::read_dir(dir.join("locked")).is_ok();
+
+        let found = Walk::new().files(dir, |_| true);
+        fs::set_permissions(dir.join("locked"), fs::Permissions::from_mode(0o755)).unwrap();
+        let found = found.unwrap();
+        if !readable {
+            assert_eq!(paths(&found), ["b.csv"]);
+        }
+    }
+}
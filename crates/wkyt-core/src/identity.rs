@@ -0,0 +1,25 @@
+//! The forms identifiers take when sources are matched against each
+//! other, so every importer spells them the same way.
+
+/// An email address in the one form every source agrees on, so people
+/// and calendar attendees can be matched by it: no `mailto:` scheme or
+/// angle brackets, no surrounding space, lowercase.
+pub fn normalize_email(value: &str) -> String {
+    let value = value.trim();
+    let value = match value.get(..7) {
+        Some(scheme) if scheme.eq_ignore_ascii_case("mailto:") => &value[7..],
+        _ => value,
+    };
+    value.trim().trim_start_matches('<').trim_end_matches('>').to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_the_scheme_brackets_space_and_case() {
+        assert_eq!(normalize_email(" MAILTO:<Ana.Lee@Example.COM> "), "ana.lee@example.com");
+        assert_eq!(normalize_email("<bo@x.org>"), "bo@x.org");
+    }
+}
@@ -2,20 +2,34 @@
 //!
 //! Everything ingestion-related agrees on lives here: the [`Item`] model,
 //! the [`Delta`]/[`DeltaBatch`] change representation, the [`SyncError`]
-//! taxonomy the orchestrator dispatches on, and the [`Connector`] trait.
+//! taxonomy the orchestrator dispatches on, the [`Connector`] trait (with
+//! its optional [`ChangeNotifier`] companion for push-mode sources), and
+//! the [`Processor`] trait for derivations over committed changes.
+//! [`files`] holds the folder walk shared by the file-based importers,
+//! [`batches`] the lazily built sync stream they return, [`hash`] the
+//! content hashes they record, and [`identity`] the normalized forms
+//! sources are matched by.
 //! See `DECISIONS.md` D11–D13 for the rationale behind each shape.
 
+pub mod batches;
 mod connector;
 mod delta;
 mod error;
+pub mod files;
+pub mod hash;
+pub mod identity;
 mod item;
+mod processor;
 pub mod proto;
+#[cfg(feature = "test-support")]
+pub mod testing;
 mod capability;
 mod agent;
-pub use connector::{Connector, DeltaStream};
-pub use delta::{Delta, DeltaBatch, SyncToken};
+pub use connector::{ChangeNotifier, ChangeStream, CommitHook, Connector, DeltaStream, Settled};
+pub use delta::{Delta, DeltaBatch, SyncToken, MAX_CHUNK_BYTES};
 pub use error::SyncError;
 pub use item::{EpistemicType, Item, ItemKind, WKYT_NAMESPACE};
+pub use processor::{derived_item, derived_source_id, Processor};
 pub use proto::CodecError;
 pub use capability::{CapabilityManifest, CapabilityInvocation, CapabilityResult};
 pub use agent::{AgentManifest, AgentRole, AgentInvocation, AgentResult};
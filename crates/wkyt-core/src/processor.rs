@@ -0,0 +1,105 @@
+use crate::delta::Delta;
+use crate::item::{Item, ItemKind};
+use serde_json::{json, Value};
+
+/// A derivation rule the host runs over committed connector changes.
+///
+/// Connectors report what a source holds; processors derive what follows
+/// from it (a claim that a file exists, with the file as its evidence).
+/// Keeping derivations out of connectors gives every claim one convention
+/// for assertion text and provenance, whatever the source.
+///
+/// Derived items belong to the processor, not to the input's connector:
+/// their `connector_id` is [`Processor::id`] and their `source_id` is
+/// [`derived_source_id`] over the input item's id, so they are
+/// deterministic (D13) and never collide with source records. Build them
+/// with [`derived_item`], which also stamps the provenance.
+///
+/// The host feeds processors connector batches only — derived deltas are
+/// not fed back in, so rules cannot chain or loop.
+pub trait Processor: Send + Sync {
+    /// Stable identifier; the `connector_id` of every item this processor
+    /// derives. Must not contain U+001F, nor equal any connector's id.
+    fn id(&self) -> &str;
+
+    /// Versioned rule name, e.g. `"evidence-claims/v1"`, recorded on every
+    /// derived item. Bump the version when the derivation changes meaning.
+    fn rule(&self) -> &str;
+
+    /// Derived deltas for one committed upsert; empty when the rule does
+    /// not apply to `item`.
+    fn on_upsert(&self, item: &Item) -> Vec<Delta>;
+
+    /// Derived deltas for one committed tombstone of the record whose id
+    /// was `item_id`. Tombstoning a derived record that was never written
+    /// is a no-op, so rules need not know what they derived before.
+    fn on_tombstone(&self, item_id: &str) -> Vec<Delta> {
+        let _ = item_id;
+        Vec::new()
+    }
+}
+
+/// The `source_id` of the item `role` derived from the item `input_id`.
+pub fn derived_source_id(input_id: &str, role: &str) -> String {
+    format!("{input_id}/{role}")
+}
+
+/// A derived item owned by `processor`: event time taken from `input`,
+/// and a `provenance` property naming the rule and the input record.
+pub fn derived_item<P: Processor + ?Sized>(
+    processor: &P,
+    input: &Item,
+    role: &str,
+    kind: ItemKind,
+    mut properties: Value,
+) -> Item {
+    properties["provenance"] = json!({
+        "rule": processor.rule(),
+        "input_id": input.id,
+        "input_connector_id": input.connector_id,
+        "input_source_id": input.source_id,
+    });
+    Item::new(
+        derived_source_id(&input.id, role),
+        processor.id(),
+        kind,
+        input.timestamp,
+        properties,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    struct Echo;
+
+    impl Processor for Echo {
+        fn id(&self) -> &str {
+            "echo"
+        }
+        fn rule(&self) -> &str {
+            "echo/v1"
+        }
+        fn on_upsert(&self, item: &Item) -> Vec<Delta> {
+            vec![Delta::Upsert(derived_item(self, item, "copy", item.kind.clone(), json!({})))]
+        }
+    }
+
+    #[test]
+    fn derived_items_are_deterministic_and_carry_provenance() {
+        let input = Item::new("a.json", "file-import", ItemKind::File, Utc::now(), json!({}));
+        let [Delta::Upsert(a)] = &Echo.on_upsert(&input)[..] else { panic!() };
+        let [Delta::Upsert(b)] = &Echo.on_upsert(&input)[..] else { panic!() };
+        assert_eq!(a.id, b.id);
+        assert_eq!(a.connector_id, "echo");
+        assert_eq!(a.source_id, format!("{}/copy", input.id));
+        assert_eq!(a.id, Item::deterministic_id("echo", &a.source_id).to_string());
+        assert_eq!(a.timestamp, input.timestamp);
+        assert_eq!(a.properties["provenance"]["rule"], "echo/v1");
+        assert_eq!(a.properties["provenance"]["input_id"], input.id.as_str());
+        assert_eq!(a.properties["provenance"]["input_connector_id"], "file-import");
+        assert!(Echo.on_tombstone(&input.id).is_empty());
+    }
+}
@@ -58,6 +58,7 @@ impl From<&Item> for v1::Item {
             ingested_at_ms: to_millis(item.ingested_at),
             properties_json: item.properties.to_string(),
             raw_payload_json: item.raw_payload.as_ref().map(|v| v.to_string()),
+            valid_to_ms: item.valid_to.map(to_millis),
         }
     }
 }
@@ -79,7 +80,7 @@ impl TryFrom<v1::Item> for Item {
                 .as_deref()
                 .map(|raw| json_field("raw_payload_json", raw))
                 .transpose()?,
-            valid_to: None,
+            valid_to: p.valid_to_ms.map(from_millis).transpose()?,
         })
     }
 }
@@ -91,6 +92,10 @@ impl From<&Delta> for v1::Delta {
             Delta::Tombstone { source_id } => v1::delta::Delta::Tombstone(v1::Tombstone {
                 source_id: source_id.clone(),
             }),
+            Delta::Chunk { sha256, data } => v1::delta::Delta::Chunk(v1::Chunk {
+                sha256: sha256.clone(),
+                data: data.clone(),
+            }),
         };
         v1::Delta { delta: Some(inner) }
     }
@@ -105,6 +110,10 @@ impl TryFrom<v1::Delta> for Delta {
             v1::delta::Delta::Tombstone(t) => Ok(Delta::Tombstone {
                 source_id: t.source_id,
             }),
+            v1::delta::Delta::Chunk(c) => Ok(Delta::Chunk {
+                sha256: c.sha256,
+                data: c.data,
+            }),
         }
     }
 }
@@ -0,0 +1,45 @@
+//! Helpers for connector tests (feature `test-support`): draining a sync
+//! pass and picking its batches apart.
+
+use futures_util::StreamExt;
+
+use crate::{Connector, Delta, DeltaBatch, Item, ItemKind, SyncToken};
+
+/// Every batch of one pass from `cursor`, panicking on an error.
+pub async fn drain(connector: &dyn Connector, cursor: Option<SyncToken>) -> Vec<DeltaBatch> {
+    connector.sync(cursor).collect::<Vec<_>>().await.into_iter().collect::<Result<Vec<_>, _>>().unwrap()
+}
+
+/// The items upserted, in order.
+pub fn upserts(batches: &[DeltaBatch]) -> Vec<&Item> {
+    batches
+        .iter()
+        .flat_map(|b| &b.deltas)
+        .filter_map(|d| match d {
+            Delta::Upsert(i) => Some(i),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The items of `kind` upserted, in order.
+pub fn upserts_of(batches: &[DeltaBatch], kind: ItemKind) -> Vec<&Item> {
+    upserts(batches).into_iter().filter(|i| i.kind == kind).collect()
+}
+
+/// The source ids tombstoned, in order.
+pub fn tombstones(batches: &[DeltaBatch]) -> Vec<&str> {
+    batches
+        .iter()
+        .flat_map(|b| &b.deltas)
+        .filter_map(|d| match d {
+            Delta::Tombstone { source_id } => Some(source_id.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The last cursor the pass handed out.
+pub fn last_cursor(batches: &[DeltaBatch]) -> Option<SyncToken> {
+    batches.iter().rev().find_map(|b| b.cursor.clone())
+}
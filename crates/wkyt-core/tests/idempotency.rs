@@ -35,6 +35,7 @@ impl FakeVault {
                         self.deleted.insert(id, source_id.clone());
                     }
                 }
+                Delta::Chunk { .. } => {}
             }
         }
         if let Some(cursor) = &batch.cursor {
@@ -116,6 +117,7 @@ fn proto_round_trip_preserves_batches_exactly() {
     // at the contract's granularity, so pin ingested_at to a sub-ms-free
     // value rather than the nanosecond-bearing Utc::now() default.
     item.ingested_at = ts("2024-07-04T12:34:56.789Z");
+    item.valid_to = Some(ts("2024-07-05T00:00:00Z"));
 
     let original = batch(
         vec![
@@ -123,6 +125,10 @@ fn proto_round_trip_preserves_batches_exactly() {
             Delta::Tombstone {
                 source_id: "evt-9".into(),
             },
+            Delta::Chunk {
+                sha256: "00".repeat(32),
+                data: vec![0, 159, 255],
+            },
         ],
         Some("sync-token-123"),
     );
@@ -0,0 +1,302 @@
+//! Derivations: running registered [`Processor`]s over each connector
+//! batch, and the processors the app ships.
+//!
+//! A processor's output for one input batch is one derived batch under the
+//! processor's own id. It commits in the input's own transaction, each
+//! derived batch under a savepoint (see `Vault::apply_batch_with_derived`):
+//! the input is not redelivered once it commits, so a derivation applied
+//! any later could be lost to a crash in between. A derived batch that
+//! fails validation or apply is quarantined in that same transaction
+//! without holding the input back, and
+//! [`retry_dead_letter`](crate::retry_dead_letter) replays it.
+//!
+//! Vaults from before D18 also hold the claims connectors built
+//! themselves. The first pass with [`EvidenceClaims`] registered retires
+//! them, deriving their replacements in the same transaction (see
+//! [`retire_legacy_claims`]).
+
+use crate::validate::{reasons, validate_batch};
+use crate::HostError;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use wkyt_core::{derived_item, derived_source_id, Delta, DeltaBatch, EpistemicType, Item, ItemKind, Processor};
+use wkyt_vault::{DerivedBatch, Vault};
+
+/// Set once the legacy claims are retired.
+const LEGACY_CLAIMS_RETIRED: &str = "legacy_claims_retired";
+
+/// What the processors did with one committed batch.
+#[derive(Default)]
+pub(crate) struct Derived {
+    pub(crate) deltas: u64,
+    /// Ids of the processors whose derived batch was quarantined.
+    pub(crate) quarantined: Vec<String>,
+}
+
+impl Derived {
+    /// Tally `derived` once committed, `quarantined` being the indices the
+    /// vault quarantined instead of applying.
+    pub(crate) fn committed(derived: &[DerivedBatch], quarantined: &[usize]) -> Self {
+        let mut outcome = Derived::default();
+        for (index, d) in derived.iter().enumerate() {
+            match quarantined.contains(&index) {
+                true => outcome.quarantined.push(d.batch.connector_id.clone()),
+                false => outcome.deltas += d.batch.deltas.len() as u64,
+            }
+        }
+        outcome
+    }
+}
+
+/// What every processor derives from `batch`, each derived batch already
+/// validated. Processors read only the batch, never the vault, so this
+/// runs before the batch applies.
+pub(crate) fn derive(processors: &[Arc<dyn Processor>], batch: &DeltaBatch) -> Vec<DerivedBatch> {
+    let mut derived = Vec::new();
+    for processor in processors {
+        let deltas: Vec<Delta> = batch
+            .deltas
+            .iter()
+            .flat_map(|delta| match delta {
+                Delta::Upsert(item) => processor.on_upsert(item),
+                Delta::Tombstone { source_id } => processor
+                    .on_tombstone(&Item::deterministic_id(&batch.connector_id, source_id).to_string()),
+                // Content, not a record: nothing to derive from.
+                Delta::Chunk { .. } => Vec::new(),
+            })
+            .collect();
+        if deltas.is_empty() {
+            continue;
+        }
+        let batch = DeltaBatch { connector_id: processor.id().to_string(), deltas, cursor: None };
+        let rejections = reasons(&validate_batch(&batch));
+        derived.push(DerivedBatch { batch, rejections });
+    }
+    derived
+}
+
+/// Tombstone the `{record}-claim` claims and `{record}-rel` links
+/// connectors wrote before D18, and derive their replacements from the
+/// records they cited, connector by connector, each in one transaction.
+/// Runs once per vault, and only when [`EvidenceClaims`] is registered:
+/// without it there is nothing to replace them with. Blocking (sqlite).
+pub(crate) fn retire_legacy_claims(vault: &mut Vault, processors: &[Arc<dyn Processor>]) -> Result<(), HostError> {
+    if !processors.iter().any(|p| p.id() == EvidenceClaims::ID) || vault.get_meta(LEGACY_CLAIMS_RETIRED)?.is_some() {
+        return Ok(());
+    }
+    let legacy = vault.legacy_claims()?;
+    let mut by_connector: BTreeMap<String, (Vec<Delta>, Vec<Delta>)> = BTreeMap::new();
+    for item in legacy.items {
+        let (retired, _) = by_connector.entry(item.connector_id).or_default();
+        retired.push(Delta::Tombstone { source_id: item.source_id });
+    }
+    for item in legacy.evidence {
+        let (_, cited) = by_connector.entry(item.connector_id.clone()).or_default();
+        cited.push(Delta::Upsert(item));
+    }
+    for (connector_id, (retired, cited)) in by_connector {
+        // Derived from the records as they stand, not re-applied: the
+        // records themselves are unchanged.
+        let cited = DeltaBatch { connector_id: connector_id.clone(), deltas: cited, cursor: None };
+        let derived = derive(processors, &cited);
+        vault.apply_batch_with_derived(&DeltaBatch { connector_id, deltas: retired, cursor: None }, &derived)?;
+    }
+    vault.put_meta(LEGACY_CLAIMS_RETIRED, "1")?;
+    Ok(())
+}
+
+/// [`retire_legacy_claims`] off the async threads, ahead of a pass.
+pub(crate) async fn retire_legacy_claims_async(
+    vault: &Arc<Mutex<Vault>>,
+    processors: &[Arc<dyn Processor>],
+) -> Result<(), HostError> {
+    let (vault, processors) = (Arc::clone(vault), processors.to_vec());
+    tokio::task::spawn_blocking(move || retire_legacy_claims(&mut vault.lock().unwrap(), &processors))
+        .await
+        .map_err(|e| HostError::Join(e.to_string()))?
+}
+
+/// `evidence-claims/v2`: a claim that each file was imported and each
+/// calendar event took place, linked to that record by a `has_evidence`
+/// relationship. A calendar event is an event with a Calendar-shaped
+/// `start` (`{"dateTime": ..}` or `{"date": ..}`), as the Google connector
+/// and the iCalendar reader write it; visits, commits, stays, commands and
+/// other events derive nothing, and neither do other kinds. Deleting the
+/// record needs no rule here: the vault retracts a claim whose evidence is
+/// all tombstoned, in the tombstone's own transaction (D19). A shredded
+/// drop leaves the folder with no tombstone, its File kept in the vault:
+/// the importer's `"action": "shredded"` event retracts the file's claim
+/// and link instead, in that event's transaction.
+pub struct EvidenceClaims;
+
+impl EvidenceClaims {
+    pub const ID: &'static str = "evidence-claims";
+    pub const RULE: &'static str = "evidence-claims/v2";
+}
+
+impl Processor for EvidenceClaims {
+    fn id(&self) -> &str {
+        Self::ID
+    }
+
+    fn rule(&self) -> &str {
+        Self::RULE
+    }
+
+    fn on_upsert(&self, item: &Item) -> Vec<Delta> {
+        if let Some(file) = shredded_file(item) {
+            let retract = |role| Delta::Tombstone { source_id: derived_source_id(file, role) };
+            return vec![retract("claim"), retract("evidence")];
+        }
+        let text = |key: &str, fallback: &str| {
+            item.properties.get(key).and_then(|v| v.as_str()).unwrap_or(fallback).to_string()
+        };
+        let (assertion, epistemic_type) = match item.kind {
+            ItemKind::File => (
+                format!("File '{}' was imported from the watched directory", text("filename", &item.source_id)),
+                EpistemicType::Observation,
+            ),
+            ItemKind::Event if is_calendar_event(item) => (
+                format!("Calendar event '{}' took place", text("summary", "Unknown")),
+                EpistemicType::ImportedAssertion,
+            ),
+            _ => return Vec::new(),
+        };
+        let claim = derived_item(
+            self,
+            item,
+            "claim",
+            ItemKind::Claim,
+            json!({ "assertion": assertion, "epistemic_type": epistemic_type }),
+        );
+        let evidence = derived_item(
+            self,
+            item,
+            "evidence",
+            ItemKind::Relationship,
+            json!({ "source": claim.id, "target": item.id, "relation": "has_evidence" }),
+        );
+        vec![Delta::Upsert(claim), Delta::Upsert(evidence)]
+    }
+}
+
+/// The id of the File a shred event records as gone from the folder.
+fn shredded_file(item: &Item) -> Option<&str> {
+    let action = item.properties.get("action").and_then(|a| a.as_str());
+    let shredded = item.kind == ItemKind::Event && action == Some("shredded");
+    item.properties.get("file").and_then(|f| f.as_str()).filter(|_| shredded)
+}
+
+/// Whether `item.properties.start` is a Calendar event time.
+fn is_calendar_event(item: &Item) -> bool {
+    item.properties
+        .get("start")
+        .and_then(|start| start.as_object())
+        .is_some_and(|start| start.contains_key("dateTime") || start.contains_key("date"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use wkyt_vault::{KeyService, MemoryKekStore};
+
+    #[test]
+    fn files_and_events_get_a_claim_with_evidence() {
+        let file = Item::new("a.json", "file-import", ItemKind::File, Utc::now(), json!({ "filename": "a.json" }));
+        let [Delta::Upsert(claim), Delta::Upsert(rel)] = &EvidenceClaims.on_upsert(&file)[..] else {
+            panic!("claim + relationship")
+        };
+        assert_eq!(claim.connector_id, EvidenceClaims::ID);
+        assert_eq!(claim.properties["assertion"], "File 'a.json' was imported from the watched directory");
+        assert_eq!(claim.properties["epistemic_type"], "observation");
+        assert_eq!(claim.properties["provenance"]["rule"], EvidenceClaims::RULE);
+        assert_eq!(rel.properties["source"], claim.id.as_str());
+        assert_eq!(rel.properties["target"], file.id.as_str());
+        assert_eq!(rel.properties["relation"], "has_evidence");
+        let (claim_id, rel_id) = (claim.id.clone(), rel.id.clone());
+
+        let start = json!({ "dateTime": "2024-07-03T09:00:00Z" });
+        let event = Item::new(
+            "evt-1",
+            "google-calendar",
+            ItemKind::Event,
+            Utc::now(),
+            json!({ "summary": "standup", "start": start }),
+        );
+        let derived = EvidenceClaims.on_upsert(&event);
+        let Delta::Upsert(claim) = &derived[0] else { panic!() };
+        assert_eq!(claim.properties["assertion"], "Calendar event 'standup' took place");
+        assert_eq!(claim.properties["provenance"]["input_connector_id"], "google-calendar");
+
+        let person = Item::new("p", "contacts", ItemKind::Person, Utc::now(), json!({}));
+        assert!(EvidenceClaims.on_upsert(&person).is_empty());
+
+        // Other events did not "take place" as calendar events.
+        let stay = json!({ "summary": "Home", "start": "2024-07-03T09:00:00Z" });
+        let visit = Item::new("visit:1", "browser", ItemKind::Event, Utc::now(), json!({ "title": "docs" }));
+        let stay = Item::new("stay:1", "location", ItemKind::Event, Utc::now(), stay);
+        assert!(EvidenceClaims.on_upsert(&visit).is_empty());
+        assert!(EvidenceClaims.on_upsert(&stay).is_empty());
+
+        // A shredded drop retracts its file's claim and link.
+        let shred = json!({ "action": "shredded", "path": "a.json", "file": file.id });
+        let shred = Item::new("shred:a.json", "file-import", ItemKind::Event, Utc::now(), shred);
+        let retracted: Vec<_> = EvidenceClaims
+            .on_upsert(&shred)
+            .into_iter()
+            .map(|d| match d {
+                Delta::Tombstone { source_id } => Item::deterministic_id(EvidenceClaims::ID, &source_id).to_string(),
+                _ => panic!("tombstones only"),
+            })
+            .collect();
+        assert_eq!(retracted, [claim_id, rel_id]);
+    }
+
+    #[test]
+    fn legacy_claims_are_retired_once_and_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let (dek, _recovery) = KeyService::new(MemoryKekStore::default(), dir.path()).provision().unwrap();
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+
+        // What the file importer wrote before D18.
+        let file = Item::new("a.json", "file-import", ItemKind::File, Utc::now(), json!({ "filename": "a.json" }));
+        let old_claim = Item::new(
+            "a.json-claim",
+            "file-import",
+            ItemKind::Claim,
+            Utc::now(),
+            json!({ "assertion": "File 'a.json' exists in the watched directory", "source": "file_importer" }),
+        );
+        let old_link = Item::new(
+            "a.json-rel",
+            "file-import",
+            ItemKind::Relationship,
+            Utc::now(),
+            json!({ "source": old_claim.id, "target": file.id, "type": "has_evidence" }),
+        );
+        let deltas = vec![Delta::Upsert(file.clone()), Delta::Upsert(old_claim), Delta::Upsert(old_link)];
+        vault.apply_batch(&DeltaBatch { connector_id: "file-import".into(), deltas, cursor: None }).unwrap();
+
+        // Without the processor that replaces them, they stay.
+        retire_legacy_claims(&mut vault, &[]).unwrap();
+        assert_eq!(vault.items("file-import").unwrap().len(), 3);
+
+        let processors: Vec<Arc<dyn Processor>> = vec![Arc::new(EvidenceClaims)];
+        retire_legacy_claims(&mut vault, &processors).unwrap();
+        let live: Vec<String> = vault.items("file-import").unwrap().into_iter().map(|i| i.source_id).collect();
+        assert_eq!(live, ["a.json"]);
+        let derived = vault.items(EvidenceClaims::ID).unwrap();
+        assert_eq!(derived.len(), 2, "claim and evidence link");
+        assert!(derived.iter().any(|i| i.properties["target"] == file.id.as_str()));
+
+        // Once: a later legacy-shaped row is left alone.
+        assert_eq!(vault.get_meta(LEGACY_CLAIMS_RETIRED).unwrap().as_deref(), Some("1"));
+        let stray = json!({ "source": "file_importer" });
+        let deltas = vec![Delta::Upsert(Item::new("b-claim", "file-import", ItemKind::Claim, Utc::now(), stray))];
+        vault.apply_batch(&DeltaBatch { connector_id: "file-import".into(), deltas, cursor: None }).unwrap();
+        retire_legacy_claims(&mut vault, &processors).unwrap();
+        assert_eq!(vault.items("file-import").unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,224 @@
+//! Dry-run passes: what a sync *would* change, with nothing written.
+//!
+//! [`Pipeline::run_dry`] pumps the connector from the committed cursor
+//! exactly as a live pass does (same `ResyncRequired` restart), into a
+//! publisher that only collects the batches. Batches validation would
+//! quarantine are reported and otherwise skipped, as the live consumer
+//! would skip them; their cursors still advance, as quarantine does. The
+//! rest go, with what the registered processors derive from them, through
+//! `Vault::preview`: applied in one transaction that is rolled back, so
+//! derived claims and the retractions tombstones cascade to are reported
+//! alongside the connector's own records. A record touched several times
+//! in one pass is reported once, by its net effect against what the vault
+//! holds now. Content chunks are not records: they are validated with
+//! their batch but not reported.
+
+use crate::derive::derive;
+use crate::validate::validate_batch;
+use crate::{pump, HostError, Pipeline, Rejection, Settling};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use wkyt_broker::{BusError, BusPublisher};
+use wkyt_core::{Connector, Delta, DeltaBatch, Item, SyncToken};
+use wkyt_vault::{PreviewChange, Vault};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DryRunReport {
+    pub connector_id: String,
+    /// Batches the connector produced, rejected ones included.
+    pub batches: u64,
+    /// Records with no live row that the pass would create (or revive),
+    /// derived ones included.
+    pub new_items: Vec<Item>,
+    pub changed_items: Vec<ItemChange>,
+    /// Live rows that a tombstone in the pass would delete, directly or by
+    /// cascade.
+    pub deleted_items: Vec<Item>,
+    /// Upserts that would rewrite a row to what it already holds.
+    pub unchanged: u64,
+    /// Per batch that validation would quarantine, why.
+    pub rejected_batches: Vec<Vec<Rejection>>,
+    /// The cursor the vault would hold after the pass.
+    pub resulting_cursor: Option<SyncToken>,
+}
+
+/// A live row an upsert would rewrite. `ingested_at` is ignored: every
+/// upsert refreshes it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ItemChange {
+    pub before: Item,
+    pub after: Item,
+    /// Leaf-level property differences. Empty when only `kind`,
+    /// `timestamp`, `valid_to` or `raw_payload` changed.
+    pub property_diffs: Vec<PropertyDiff>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyDiff {
+    /// JSON Pointer (RFC 6901) into `properties`.
+    pub path: String,
+    /// `None` when the key is absent on that side.
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+}
+
+/// Stream `connector` from the committed cursor and report what applying
+/// its batches would change, with no processors; see
+/// [`Pipeline::run_dry`].
+pub async fn run_pipeline_dry<C: Connector + ?Sized>(
+    connector: &C,
+    vault: Arc<Mutex<Vault>>,
+) -> Result<DryRunReport, HostError> {
+    Pipeline::new(vault).run_dry(connector).await
+}
+
+impl Pipeline {
+    /// Stream `connector` from the committed cursor and report what
+    /// applying its batches, and what the registered processors derive
+    /// from them, would change. Neither the vault nor the cursor is
+    /// written; the connector's own `init` still runs, as it does for a
+    /// live pass.
+    pub async fn run_dry<C: Connector + ?Sized>(&self, connector: &C) -> Result<DryRunReport, HostError> {
+        connector.init().await?;
+        let starting_cursor = self.vault.lock().unwrap().cursor(connector.id())?;
+
+        let publisher = DryRunPublisher {
+            collected: Mutex::new(Collected { cursor: starting_cursor.clone(), ..Collected::default() }),
+        };
+        // Nothing is acked, so no commit hook runs or is settled.
+        pump(connector, &publisher, starting_cursor, &Settling::default()).await?;
+        let collected = publisher.collected.into_inner().unwrap();
+
+        let (vault, processors) = (Arc::clone(&self.vault), self.processors.clone());
+        let batches = collected.batches;
+        // Preview is blocking (sqlite); keep it off the async threads.
+        let changes = tokio::task::spawn_blocking(move || {
+            let batches: Vec<_> = batches
+                .into_iter()
+                .map(|batch| {
+                    let derived = derive(&processors, &batch);
+                    (batch, derived)
+                })
+                .collect();
+            vault.lock().unwrap().preview(&batches)
+        })
+        .await
+        .map_err(|e| HostError::Join(e.to_string()))??;
+
+        let mut report = DryRunReport {
+            connector_id: connector.id().to_string(),
+            batches: collected.count,
+            rejected_batches: collected.rejected,
+            resulting_cursor: collected.cursor,
+            ..DryRunReport::default()
+        };
+        for PreviewChange { before, after } in changes {
+            match (before, after) {
+                (None, Some(after)) => report.new_items.push(after),
+                (Some(before), None) => report.deleted_items.push(before),
+                (Some(before), Some(after)) if same_row(&before, &after) => report.unchanged += 1,
+                (Some(before), Some(after)) => {
+                    let mut property_diffs = Vec::new();
+                    diff_values(&mut String::new(), Some(&before.properties), Some(&after.properties), &mut property_diffs);
+                    report.changed_items.push(ItemChange { before, after, property_diffs });
+                }
+                (None, None) => {}
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// What the pass produced: the batches that would apply, and the
+/// bookkeeping for the report.
+#[derive(Default)]
+struct Collected {
+    batches: Vec<DeltaBatch>,
+    count: u64,
+    rejected: Vec<Vec<Rejection>>,
+    cursor: Option<SyncToken>,
+}
+
+/// Whether an upsert of `after` would leave the stored row as it is.
+fn same_row(before: &Item, after: &Item) -> bool {
+    before.kind == after.kind
+        && before.timestamp == after.timestamp
+        && before.valid_to == after.valid_to
+        && before.properties == after.properties
+        && before.raw_payload == after.raw_payload
+}
+
+/// Recurse through objects; anything else (arrays included) compares whole.
+fn diff_values(path: &mut String, before: Option<&Value>, after: Option<&Value>, out: &mut Vec<PropertyDiff>) {
+    if let (Some(Value::Object(b)), Some(Value::Object(a))) = (before, after) {
+        let keys: BTreeSet<&String> = b.keys().chain(a.keys()).collect();
+        for key in keys {
+            let len = path.len();
+            path.push('/');
+            path.push_str(&key.replace('~', "~0").replace('/', "~1"));
+            diff_values(path, b.get(key), a.get(key), out);
+            path.truncate(len);
+        }
+    } else if before != after {
+        out.push(PropertyDiff { path: path.clone(), before: before.cloned(), after: after.cloned() });
+    }
+}
+
+/// Collects published batches for the preview, writing nothing.
+struct DryRunPublisher {
+    collected: Mutex<Collected>,
+}
+
+#[async_trait::async_trait]
+impl BusPublisher for DryRunPublisher {
+    async fn publish(&self, mut batch: DeltaBatch) -> Result<(), BusError> {
+        let rejections = validate_batch(&batch);
+        let mut collected = self.collected.lock().unwrap();
+        collected.count += 1;
+        if batch.cursor.is_some() {
+            collected.cursor = batch.cursor.clone();
+        }
+        if !rejections.is_empty() {
+            collected.rejected.push(rejections);
+            return Ok(());
+        }
+        // Validated and not reported: no need to hold the bytes.
+        batch.deltas.retain(|delta| !matches!(delta, Delta::Chunk { .. }));
+        collected.batches.push(batch);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn diffs(before: Value, after: Value) -> Vec<PropertyDiff> {
+        let mut out = Vec::new();
+        diff_values(&mut String::new(), Some(&before), Some(&after), &mut out);
+        out
+    }
+
+    #[test]
+    fn diffs_are_leaf_level_json_pointers() {
+        let out = diffs(
+            json!({ "summary": "standup", "loc": { "room": "4a", "floor": 2 }, "tags": [1], "a/b": 1 }),
+            json!({ "summary": "standup", "loc": { "room": "4b" }, "tags": [1, 2], "new": true, "a/b": 1 }),
+        );
+        let paths: Vec<_> = out.iter().map(|d| d.path.as_str()).collect();
+        assert_eq!(paths, ["/loc/floor", "/loc/room", "/new", "/tags"]);
+        assert_eq!(out[0].after, None, "removed key");
+        assert_eq!(out[1].before, Some(json!("4a")));
+        assert_eq!(out[2].before, None, "added key");
+    }
+
+    #[test]
+    fn pointer_segments_are_escaped_and_scalar_roots_compare_whole() {
+        let out = diffs(json!({ "a/b": { "~x": 1 } }), json!({ "a/b": { "~x": 2 } }));
+        assert_eq!(out[0].path, "/a~1b/~0x");
+        let out = diffs(json!("old"), json!("new"));
+        assert_eq!(out, [PropertyDiff { path: String::new(), before: Some(json!("old")), after: Some(json!("new")) }]);
+    }
+}
@@ -9,7 +9,10 @@
 //! The consumer acks a delivery **only after** [`Vault::apply_batch`]'s
 //! transaction commits — before that, a crash leaves the cursor
 //! un-advanced and the batch is simply re-delivered by the next run
-//! (idempotent by D13).
+//! (idempotent by D13). Work a connector may only do once its batch is
+//! durable ([`Connector::on_commit`]) rides the delivery as an ack hook,
+//! so it runs with the ack and never before the commit — on a blocking
+//! thread, since such work is file I/O.
 //!
 //! Error policy by taxonomy (`SyncError`):
 //! - `ResyncRequired` mid-stream → the stored cursor is abandoned and the
@@ -20,12 +23,60 @@
 //!   per-connector lease (callers must not run one connector concurrently).
 //! - `AuthRequired` / `Fatal` → surfaced; the connector needs operator or
 //!   re-auth attention.
+//!
+//! While the vault is locked, [`run_pipeline_spooled`] runs the same pump
+//! into an encrypted [`Spool`] instead (D16); [`drain_spool`] later feeds
+//! the spooled batches through the ordinary consumer once the vault opens.
+//! Drain before the next live pass: a live pass that commits a newer
+//! cursor first would otherwise be rewound by the older spooled one. A
+//! pipeline given the spool ([`Pipeline::with_spool`]) enforces this and
+//! refuses a connector's live pass while any of its batches are spooled.
+//!
+//! Every delivered batch is validated first and, if it is malformed or
+//! keeps failing to apply, quarantined in the vault's dead-letter table
+//! with its cursor advanced — see [`validate_batch`].
+//!
+//! Registered [`Processor`]s derive from every batch (claims and their
+//! evidence links, see [`EvidenceClaims`]), and what they derive commits
+//! in the batch's own transaction — connectors emit only what their source
+//! holds. See [`Pipeline::with_processor`].
+//!
+//! [`Pipeline::run_dry`] previews a pass: same stream, same validation,
+//! same processors and cascades, nothing written — a report of new,
+//! changed and deleted items and where the cursor would land.
+//!
+//! [`Pipeline::into_shared`] is the long-lived mode: one fair bus and one
+//! consumer shared by every connector, instead of a bus and consumer task
+//! per pass (see [`SharedHost`]).
+//!
+//! [`Pipeline::run_watched`] is push mode for connectors that also
+//! implement `ChangeNotifier`: a pass on each debounced change, with a
+//! periodic fallback rescan, instead of a pass per polling interval.
+//!
+//! [`Pipeline::with_metrics`] feeds an optional `wkyt_metrics` registry:
+//! bus counters and depth, per-batch apply latency, failed passes by
+//! connector and error class, and vault item/size gauges after each pass.
 
 use futures_util::StreamExt;
 use std::sync::{Arc, Mutex};
-use wkyt_broker::{in_process, BusError, BusPublisher, BusSubscriber};
-use wkyt_core::{Connector, SyncError, SyncToken};
-use wkyt_vault::{Vault, VaultError};
+use std::time::Instant;
+use wkyt_broker::{in_process, in_process_with_metrics, BusError, BusPublisher, BusSubscriber};
+use wkyt_core::{Connector, DeltaBatch, Processor, Settled, SyncError, SyncToken};
+use wkyt_metrics::Metrics;
+use wkyt_vault::{Spool, SpoolError, Vault, VaultError};
+
+mod derive;
+mod dry_run;
+mod shared;
+mod validate;
+mod watch;
+use derive::{derive, retire_legacy_claims_async};
+pub use derive::EvidenceClaims;
+pub use dry_run::{run_pipeline_dry, DryRunReport, ItemChange, PropertyDiff};
+pub use shared::SharedHost;
+pub use validate::{retry_dead_letter, validate_batch, Rejection, MAX_APPLY_ATTEMPTS};
+pub use watch::{Wake, Watch};
+use validate::{validate_and_apply, Applied};
 
 #[derive(Debug, thiserror::Error)]
 pub enum HostError {
@@ -35,55 +86,353 @@ pub enum HostError {
     Vault(#[from] VaultError),
     #[error("bus: {0}")]
     Bus(#[from] BusError),
+    #[error("spool: {0}")]
+    Spool(#[from] SpoolError),
+    #[error("{0} has spooled batches; drain the spool before a live pass")]
+    SpoolPending(String),
     #[error("consumer task panicked or was cancelled: {0}")]
     Join(String),
+    #[error("batch rejected: {}", .0.iter().map(ToString::to_string).collect::<Vec<_>>().join("; "))]
+    Rejected(Vec<Rejection>),
 }
 
-#[derive(Debug, Default, PartialEq, Eq)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct PipelineStats {
     pub batches_applied: u64,
     pub deltas_applied: u64,
+    /// Batches moved to the vault's dead-letter quarantine instead of
+    /// applied (their cursors still advanced).
+    pub batches_quarantined: u64,
+    /// Deltas registered processors derived from the applied batches.
+    pub deltas_derived: u64,
+}
+
+impl PipelineStats {
+    pub(crate) fn absorb(&mut self, other: &PipelineStats) {
+        self.batches_applied += other.batches_applied;
+        self.deltas_applied += other.deltas_applied;
+        self.batches_quarantined += other.batches_quarantined;
+        self.deltas_derived += other.deltas_derived;
+    }
 }
 
-/// Run one full pipeline pass for `connector`: resume from the vault's
-/// committed cursor, stream batches over a bounded bus, apply each to the
-/// vault, ack after commit. Returns once the stream is drained and every
-/// in-flight batch is applied (or the first error).
+/// The registered processors, shared read-only with the consumer.
+type Processors = Arc<[Arc<dyn Processor>]>;
+
+impl HostError {
+    /// Short, bounded-cardinality class for telemetry labels.
+    pub fn metric_kind(&self) -> &'static str {
+        match self {
+            HostError::Sync(SyncError::Retryable { .. }) => "retryable",
+            HostError::Sync(SyncError::AuthRequired { .. }) => "auth_required",
+            HostError::Sync(SyncError::ResyncRequired) => "resync_required",
+            HostError::Sync(SyncError::Fatal { .. }) => "fatal",
+            HostError::Vault(_) => "vault",
+            HostError::Bus(_) => "bus",
+            HostError::Spool(_) => "spool",
+            HostError::SpoolPending(_) => "spool_pending",
+            HostError::Join(_) => "join",
+            HostError::Rejected(_) => "rejected",
+        }
+    }
+}
+
+/// Run one full pipeline pass for `connector` with no telemetry; see
+/// [`Pipeline::run_once`].
 pub async fn run_pipeline_once<C: Connector + ?Sized>(
     connector: &C,
     vault: Arc<Mutex<Vault>>,
 ) -> Result<PipelineStats, HostError> {
-    connector.init().await?;
-    let starting_cursor = vault.lock().unwrap().cursor(connector.id())?;
+    Pipeline::new(vault).run_once(connector).await
+}
+
+/// Pipeline configuration over one vault. Cheap to build per pass.
+pub struct Pipeline {
+    vault: Arc<Mutex<Vault>>,
+    metrics: Option<Metrics>,
+    processors: Vec<Arc<dyn Processor>>,
+    spool: Option<Arc<Spool>>,
+}
+
+impl Pipeline {
+    pub fn new(vault: Arc<Mutex<Vault>>) -> Self {
+        Self { vault, metrics: None, processors: Vec::new(), spool: None }
+    }
 
-    let (publisher, subscriber) = in_process(8);
-    let consumer = tokio::spawn(consume(subscriber, Arc::clone(&vault)));
+    /// Record bus counters, apply latency, per-connector errors and vault
+    /// size gauges into `metrics`.
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
 
-    let pump_result = pump(connector, &publisher, starting_cursor).await;
+    /// Run `processor` over every batch, in registration order, committing
+    /// what it derives in the batch's transaction.
+    pub fn with_processor(mut self, processor: impl Processor + 'static) -> Self {
+        self.processors.push(Arc::new(processor));
+        self
+    }
 
-    // Closing the publisher lets the consumer drain and finish.
-    drop(publisher);
-    let stats = consumer.await.map_err(|e| HostError::Join(e.to_string()))??;
+    /// The spool locked passes write to: a live pass for a connector with
+    /// batches still in it fails with [`HostError::SpoolPending`] instead of
+    /// committing a cursor the drain would later rewind.
+    pub fn with_spool(mut self, spool: Arc<Spool>) -> Self {
+        self.spool = Some(spool);
+        self
+    }
 
-    // Consumer success with a pump failure still reports the pump failure:
-    // partial progress is durable (cursor committed per batch), and the
-    // caller's next run resumes from it.
-    pump_result?;
-    Ok(stats)
+    /// Resume from the vault's committed cursor, stream batches over a
+    /// bounded bus, apply each to the vault, ack after commit. Returns once
+    /// the stream is drained and every in-flight batch is applied (or the
+    /// first error).
+    pub async fn run_once<C: Connector + ?Sized>(
+        &self,
+        connector: &C,
+    ) -> Result<PipelineStats, HostError> {
+        let result = self.run_once_inner(connector).await;
+        record_pass(&self.vault, self.metrics.as_ref(), connector.id(), &result).await;
+        result
+    }
+
+    /// Turn this configuration into a long-lived [`SharedHost`]: one fair
+    /// bus (`per_connector_capacity` batches per connector) and one
+    /// consumer for every connector. Must be called inside a tokio runtime.
+    pub fn into_shared(self, per_connector_capacity: usize) -> SharedHost {
+        SharedHost::start(self.vault, self.metrics, self.processors.into(), self.spool, per_connector_capacity)
+    }
+
+    /// Apply every spooled batch to the (now open) vault, oldest first,
+    /// through the normal consumer: each batch's file is removed only after
+    /// its transaction commits and the delivery is acked. Global sequence
+    /// order implies per-connector cursor order. A removal that fails is
+    /// the drain's error: the batch would otherwise be replayed later.
+    pub async fn drain_spool(&self, spool: Arc<Spool>) -> Result<PipelineStats, HostError> {
+        let s = Arc::clone(&spool);
+        let pending = tokio::task::spawn_blocking(move || s.pending())
+            .await
+            .map_err(|e| HostError::Join(e.to_string()))??;
+        let Some(&last) = pending.last() else {
+            return Ok(PipelineStats::default());
+        };
+        retire_legacy_claims_async(&self.vault, &self.processors).await?;
+
+        let (publisher, subscriber) = in_process(8);
+        let consumer = tokio::spawn(consume(
+            subscriber,
+            Arc::clone(&self.vault),
+            self.metrics.clone(),
+            self.processors.clone().into(),
+            Settling::default(),
+        ));
+
+        // The first removal to fail, kept for the result: the ack hook has
+        // nowhere else to send it.
+        let removal_error: Arc<Mutex<Option<SpoolError>>> = Arc::default();
+        let feed_result: Result<(), HostError> = async {
+            for seq in pending {
+                let s = Arc::clone(&spool);
+                let batch = tokio::task::spawn_blocking(move || s.read(seq))
+                    .await
+                    .map_err(|e| HostError::Join(e.to_string()))??;
+                let s = Arc::clone(&spool);
+                let failed = Arc::clone(&removal_error);
+                let on_ack = Box::new(move || {
+                    if let Err(e) = s.remove_through(seq) {
+                        failed.lock().unwrap().get_or_insert(e);
+                    }
+                });
+                publisher.publish_with_ack_hook(batch, on_ack).await?;
+            }
+            Ok(())
+        }
+        .await;
+
+        drop(publisher);
+        let stats = consumer.await.map_err(|e| HostError::Join(e.to_string()))??;
+        feed_result?;
+        if let Some(e) = removal_error.lock().unwrap().take() {
+            return Err(e.into());
+        }
+
+        // Everything through `last` committed; make sure none of it lingers
+        // to be replayed over a newer cursor later.
+        tokio::task::spawn_blocking(move || spool.remove_through(last))
+            .await
+            .map_err(|e| HostError::Join(e.to_string()))??;
+        Ok(stats)
+    }
+
+    async fn run_once_inner<C: Connector + ?Sized>(
+        &self,
+        connector: &C,
+    ) -> Result<PipelineStats, HostError> {
+        refuse_if_spooled(self.spool.as_ref(), connector.id()).await?;
+        retire_legacy_claims_async(&self.vault, &self.processors).await?;
+        connector.init().await?;
+        let starting_cursor = self.vault.lock().unwrap().cursor(connector.id())?;
+
+        let (publisher, subscriber) = match &self.metrics {
+            Some(m) => in_process_with_metrics(8, m.clone()),
+            None => in_process(8),
+        };
+        let settling = Settling::default();
+        let consumer = tokio::spawn(consume(
+            subscriber,
+            Arc::clone(&self.vault),
+            self.metrics.clone(),
+            self.processors.clone().into(),
+            Arc::clone(&settling),
+        ));
+
+        let pump_result = pump(connector, &publisher, starting_cursor, &settling).await;
+
+        // Closing the publisher lets the consumer drain and finish.
+        drop(publisher);
+        let stats = consumer.await.map_err(|e| HostError::Join(e.to_string()))??;
+
+        // Consumer success with a pump failure still reports the pump failure:
+        // partial progress is durable (cursor committed per batch), and the
+        // caller's next run resumes from it.
+        pump_result?;
+        Ok(stats)
+    }
+}
+
+/// A live pass must not commit ahead of batches still waiting in the
+/// spool: draining them afterwards would rewind the cursor.
+pub(crate) async fn refuse_if_spooled(spool: Option<&Arc<Spool>>, connector_id: &str) -> Result<(), HostError> {
+    let Some(spool) = spool.cloned() else {
+        return Ok(());
+    };
+    let id = connector_id.to_string();
+    let held = tokio::task::spawn_blocking(move || spool.holds(&id))
+        .await
+        .map_err(|e| HostError::Join(e.to_string()))??;
+    match held {
+        true => Err(HostError::SpoolPending(connector_id.to_string())),
+        false => Ok(()),
+    }
+}
+
+/// Post-pass telemetry shared by every pipeline mode.
+async fn record_pass<T>(
+    vault: &Arc<Mutex<Vault>>,
+    metrics: Option<&Metrics>,
+    connector_id: &str,
+    result: &Result<T, HostError>,
+) {
+    if let Some(metrics) = metrics {
+        if let Err(e) = result {
+            metrics.connector_error(connector_id, e.metric_kind());
+        }
+        // Gauges are a snapshot; a failure to take one is not the pass's
+        // failure.
+        let _ = refresh_vault_gauges(Arc::clone(vault), metrics.clone()).await;
+    }
+}
+
+async fn refresh_vault_gauges(vault: Arc<Mutex<Vault>>, metrics: Metrics) -> Result<(), HostError> {
+    tokio::task::spawn_blocking(move || -> Result<(), HostError> {
+        let v = vault.lock().unwrap();
+        metrics.set_vault_items(v.item_counts_by_kind()?);
+        metrics.set_vault_database_bytes(v.database_size_bytes()?);
+        Ok(())
+    })
+    .await
+    .map_err(|e| HostError::Join(e.to_string()))?
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SpoolStats {
+    pub batches_spooled: u64,
+    pub deltas_spooled: u64,
+}
+
+/// A [`BusPublisher`] with no consumer behind it: every batch is sealed
+/// into the spool and is durable once `publish` returns.
+pub struct SpoolPublisher {
+    spool: Arc<Spool>,
+    stats: Mutex<SpoolStats>,
+}
+
+impl SpoolPublisher {
+    pub fn new(spool: Arc<Spool>) -> Self {
+        Self { spool, stats: Mutex::new(SpoolStats::default()) }
+    }
+
+    /// What this publisher has durably spooled so far.
+    pub fn stats(&self) -> SpoolStats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl BusPublisher for SpoolPublisher {
+    async fn publish(&self, batch: DeltaBatch) -> Result<(), BusError> {
+        let spool = Arc::clone(&self.spool);
+        let delta_count = batch.deltas.len() as u64;
+        // Encrypt + fsync is blocking; keep it off the async threads.
+        tokio::task::spawn_blocking(move || spool.append(&batch))
+            .await
+            .map_err(|e| BusError::Transport(e.to_string().into()))?
+            .map_err(|e| BusError::Transport(Box::new(e)))?;
+        let mut stats = self.stats.lock().unwrap();
+        stats.batches_spooled += 1;
+        stats.deltas_spooled += delta_count;
+        Ok(())
+    }
+}
+
+/// Run one pipeline pass for `connector` while the vault is locked,
+/// sealing every batch into `spool`. Resumes from the newest cursor this
+/// connector already spooled, else from `committed` (the vault's cursor,
+/// if the caller cached it before locking; `None` means a full sync).
+pub async fn run_pipeline_spooled<C: Connector + ?Sized>(
+    connector: &C,
+    spool: Arc<Spool>,
+    committed: Option<SyncToken>,
+) -> Result<SpoolStats, HostError> {
+    connector.init().await?;
+    let s = Arc::clone(&spool);
+    let id = connector.id().to_string();
+    let spooled = tokio::task::spawn_blocking(move || s.last_cursor(&id))
+        .await
+        .map_err(|e| HostError::Join(e.to_string()))??;
+    let starting_cursor = spooled.or(committed);
+
+    let publisher = SpoolPublisher::new(spool);
+    // Batches spooled before a pump failure are durable and drain like any
+    // other; the caller's next pass resumes after them. The spool drops
+    // commit hooks unrun, so nothing is ever settled here.
+    pump(connector, &publisher, starting_cursor, &Settling::default()).await?;
+    Ok(publisher.stats())
+}
+
+/// Drain `spool` into the (now open) vault with no processors or
+/// telemetry; see [`Pipeline::drain_spool`].
+pub async fn drain_spool(
+    spool: Arc<Spool>,
+    vault: Arc<Mutex<Vault>>,
+) -> Result<PipelineStats, HostError> {
+    Pipeline::new(vault).drain_spool(spool).await
 }
 
 /// Drain the connector's stream into the bus, honoring the error taxonomy:
 /// `ResyncRequired` triggers exactly one restart from `None`; a second
 /// `ResyncRequired` (a full sync demanding a full resync) surfaces as the
 /// error it is rather than looping.
+///
+/// Commit hooks are told how their batch was settled through `settling`,
+/// which the consumer sets before each ack.
 async fn pump<C: Connector + ?Sized>(
     connector: &C,
     publisher: &impl BusPublisher,
     starting_cursor: Option<SyncToken>,
+    settling: &Settling,
 ) -> Result<(), HostError> {
-    match drain_stream(connector, publisher, starting_cursor).await {
+    match drain_stream(connector, publisher, starting_cursor, settling).await {
         Err(HostError::Sync(SyncError::ResyncRequired)) => {
-            drain_stream(connector, publisher, None).await
+            drain_stream(connector, publisher, None, settling).await
         }
         other => other,
     }
@@ -93,33 +442,94 @@ async fn drain_stream<C: Connector + ?Sized>(
     connector: &C,
     publisher: &impl BusPublisher,
     cursor: Option<SyncToken>,
+    settling: &Settling,
 ) -> Result<(), HostError> {
     let mut stream = connector.sync(cursor);
     while let Some(next) = stream.next().await {
-        publisher.publish(next?).await?;
+        let batch = next?;
+        match connector.on_commit(&batch) {
+            Some(hook) => {
+                let settling = Arc::clone(settling);
+                let on_ack = Box::new(move || {
+                    hook(settling.lock().unwrap().expect("the consumer settles a batch before acking it"))
+                });
+                publisher.publish_with_ack_hook(batch, on_ack).await?
+            }
+            None => publisher.publish(batch).await?,
+        }
     }
     Ok(())
 }
 
+/// How the consumer settled the batch it is about to ack. The ack runs
+/// that batch's commit hook synchronously, so the hook reads its own.
+pub(crate) type Settling = Arc<Mutex<Option<Settled>>>;
+
 /// Pull deliveries, apply each batch in its own vault transaction, and ack
 /// strictly after commit.
 async fn consume<S: BusSubscriber>(
     mut subscriber: S,
     vault: Arc<Mutex<Vault>>,
+    metrics: Option<Metrics>,
+    processors: Processors,
+    settling: Settling,
 ) -> Result<PipelineStats, HostError> {
     let mut stats = PipelineStats::default();
     while let Some(delivery) = subscriber.next().await {
         let (batch, ack) = delivery.into_parts();
         let delta_count = batch.deltas.len() as u64;
-        let v = Arc::clone(&vault);
-        // apply_batch is blocking (sqlite); keep it off the async threads.
-        tokio::task::spawn_blocking(move || v.lock().unwrap().apply_batch(&batch))
-            .await
-            .map_err(|e| HostError::Join(e.to_string()))??;
-        // The transaction is committed — and only now is it safe to ack.
-        ack.ack();
-        stats.batches_applied += 1;
-        stats.deltas_applied += delta_count;
+        let (applied, derived) = apply(&vault, batch, metrics.clone(), Arc::clone(&processors)).await?;
+        // The transaction is committed (or the batch quarantined, cursor
+        // and all) — and only now is it safe to ack. Ack hooks do file
+        // I/O (shredding, spool removal), so off the async workers.
+        *settling.lock().unwrap() = Some(applied.settled());
+        tokio::task::spawn_blocking(move || ack.ack()).await.map_err(|e| HostError::Join(e.to_string()))?;
+        match applied {
+            Applied::Committed(_) => {
+                stats.batches_applied += 1;
+                stats.deltas_applied += delta_count;
+                stats.deltas_derived += derived;
+            }
+            Applied::Quarantined => stats.batches_quarantined += 1,
+        }
     }
     Ok(stats)
 }
+
+/// One batch through the processors (see [`derive`]), then validation and
+/// one vault transaction holding the batch and what was derived from it
+/// (see [`validate`]), timed into `metrics` when present. Also returns the
+/// number of derived deltas committed.
+async fn apply(
+    vault: &Arc<Mutex<Vault>>,
+    batch: DeltaBatch,
+    metrics: Option<Metrics>,
+    processors: Processors,
+) -> Result<(Applied, u64), HostError> {
+    let v = Arc::clone(vault);
+    // apply_batch is blocking (sqlite); keep it off the async threads.
+    tokio::task::spawn_blocking(move || {
+        let derived = derive(&processors, &batch);
+        let mut v = v.lock().unwrap();
+        let started = Instant::now();
+        let applied = validate_and_apply(&mut v, &batch, &derived)?;
+        if let Some(m) = &metrics {
+            match &applied {
+                Applied::Committed(derived) => {
+                    m.observe_batch_apply(&batch.connector_id, started.elapsed());
+                    for processor_id in &derived.quarantined {
+                        m.batch_quarantined(processor_id);
+                    }
+                }
+                Applied::Quarantined => m.batch_quarantined(&batch.connector_id),
+            }
+        }
+        let derived = match &applied {
+            Applied::Committed(derived) => derived.deltas,
+            Applied::Quarantined => 0,
+        };
+        Ok((applied, derived))
+    })
+    .await
+    .map_err(|e| HostError::Join(e.to_string()))?
+}
@@ -0,0 +1,223 @@
+//! Long-lived host mode: one fair bus, one consumer, many connectors.
+//!
+//! [`run_pipeline_once`](crate::run_pipeline_once) builds a bus and a
+//! consumer per pass, so nothing sees backpressure or fairness across
+//! sources. A [`SharedHost`] owns a single `wkyt_broker::fair` bus and a
+//! single consumer task for its whole life; each [`SharedHost::run_once`]
+//! pumps one connector into it, and passes for different connectors may
+//! run concurrently. The fair bus serves connectors round-robin, so a
+//! calendar backfill and a file import interleave batch by batch.
+//!
+//! Completion is tracked per batch: every batch is published with an ack
+//! hook holding a oneshot sender, so a pass knows exactly which of *its*
+//! batches committed — and how (applied or quarantined, and what was
+//! derived) — even though the consumer is shared. A batch whose delivery
+//! is dropped un-acked closes its oneshot instead.
+//!
+//! Failure isolation: when a batch fails to apply, its connector is
+//! poisoned — the consumer drops that connector's remaining queued batches
+//! un-acked (their cursors must not commit past the failed one) and keeps
+//! serving everyone else. The failing pass collects the error once all its
+//! receipts resolve, which also clears the poison for the next pass. The
+//! per-connector lease rule is unchanged: one pass per connector at a time.
+
+use crate::derive::retire_legacy_claims_async;
+use crate::{apply, pump, record_pass, refuse_if_spooled, Applied, HostError, PipelineStats, Processors, Settling};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use wkyt_broker::{
+    fair, fair_with_metrics, AckHook, BusError, BusPublisher, BusSubscriber, FairPublisher, FairSubscriber,
+};
+use wkyt_core::{Connector, DeltaBatch};
+use wkyt_metrics::Metrics;
+use wkyt_vault::{Spool, Vault};
+
+pub struct SharedHost {
+    vault: Arc<Mutex<Vault>>,
+    metrics: Option<Metrics>,
+    spool: Option<Arc<Spool>>,
+    processors: Processors,
+    publisher: FairPublisher,
+    consumer: JoinHandle<()>,
+    state: Arc<ConsumerState>,
+}
+
+#[derive(Default)]
+struct ConsumerState {
+    /// Cumulative committed work per connector since the host started.
+    stats: Mutex<BTreeMap<String, PipelineStats>>,
+    /// Connectors whose last batch failed to apply, with the failure.
+    poisoned: Mutex<BTreeMap<String, HostError>>,
+    /// The outcome of the batch being acked, handed to its ack hook: the
+    /// consumer sets it immediately before `ack()`, which runs the hook
+    /// synchronously.
+    acking: Mutex<PipelineStats>,
+    /// The same, for connectors' commit hooks (see [`pump`]).
+    settling: Settling,
+}
+
+impl SharedHost {
+    pub(crate) fn start(
+        vault: Arc<Mutex<Vault>>,
+        metrics: Option<Metrics>,
+        processors: Processors,
+        spool: Option<Arc<Spool>>,
+        per_connector_capacity: usize,
+    ) -> Self {
+        let (publisher, subscriber) = match &metrics {
+            Some(m) => fair_with_metrics(per_connector_capacity, m.clone()),
+            None => fair(per_connector_capacity),
+        };
+        let state = Arc::new(ConsumerState::default());
+        let consumer = tokio::spawn(consume_shared(
+            subscriber,
+            Arc::clone(&vault),
+            metrics.clone(),
+            Arc::clone(&processors),
+            Arc::clone(&state),
+        ));
+        Self { vault, metrics, spool, processors, publisher, consumer, state }
+    }
+
+    /// One pass for `connector` through the shared bus: resume from the
+    /// committed cursor, publish its batches, and return once every one of
+    /// them has committed — or with the error of the first that did not.
+    pub async fn run_once<C: Connector + ?Sized>(
+        &self,
+        connector: &C,
+    ) -> Result<PipelineStats, HostError> {
+        let result = self.run_once_inner(connector).await;
+        record_pass(&self.vault, self.metrics.as_ref(), connector.id(), &result).await;
+        result
+    }
+
+    async fn run_once_inner<C: Connector + ?Sized>(
+        &self,
+        connector: &C,
+    ) -> Result<PipelineStats, HostError> {
+        refuse_if_spooled(self.spool.as_ref(), connector.id()).await?;
+        retire_legacy_claims_async(&self.vault, &self.processors).await?;
+        connector.init().await?;
+        let starting_cursor = self.vault.lock().unwrap().cursor(connector.id())?;
+
+        let publisher = ReceiptPublisher {
+            inner: &self.publisher,
+            state: &self.state,
+            receipts: Mutex::new(Vec::new()),
+        };
+        let pump_result = pump(connector, &publisher, starting_cursor, &self.state.settling).await;
+
+        // Wait for every batch this pass published, in publish order.
+        let mut stats = PipelineStats::default();
+        let mut uncommitted = false;
+        for receipt in publisher.receipts.into_inner().unwrap() {
+            match receipt.await {
+                Ok(outcome) => stats.absorb(&outcome),
+                Err(_) => uncommitted = true,
+            }
+        }
+
+        // Every receipt has resolved, so none of this pass's batches is
+        // still queued: clearing the poison cannot let a straggler through.
+        let apply_error = self.state.poisoned.lock().unwrap().remove(connector.id());
+        if let Some(e) = apply_error {
+            return Err(e);
+        }
+        if uncommitted {
+            return Err(HostError::Join("shared consumer stopped before committing".into()));
+        }
+        // As in the per-pass mode: committed progress is durable either
+        // way, and a pump failure is still the pass's failure.
+        pump_result?;
+        Ok(stats)
+    }
+
+    /// Cumulative committed batches and deltas per connector since start.
+    pub fn stats(&self) -> BTreeMap<String, PipelineStats> {
+        self.state.stats.lock().unwrap().clone()
+    }
+
+    /// Close the bus, let the consumer drain what is queued, and return the
+    /// final per-connector stats. Callers should let in-flight passes
+    /// finish first.
+    pub async fn shutdown(self) -> Result<BTreeMap<String, PipelineStats>, HostError> {
+        drop(self.publisher);
+        self.consumer.await.map_err(|e| HostError::Join(e.to_string()))?;
+        Ok(self.state.stats.lock().unwrap().clone())
+    }
+}
+
+/// Publishes into the shared bus with a commit receipt per batch.
+struct ReceiptPublisher<'a> {
+    inner: &'a FairPublisher,
+    state: &'a Arc<ConsumerState>,
+    receipts: Mutex<Vec<oneshot::Receiver<PipelineStats>>>,
+}
+
+#[async_trait::async_trait]
+impl BusPublisher for ReceiptPublisher<'_> {
+    async fn publish(&self, batch: DeltaBatch) -> Result<(), BusError> {
+        self.publish_with_ack_hook(batch, Box::new(|| {})).await
+    }
+
+    /// The connector's hook runs before the receipt resolves, so a pass
+    /// returns only once its commit work is done.
+    async fn publish_with_ack_hook(&self, batch: DeltaBatch, on_ack: AckHook) -> Result<(), BusError> {
+        let (tx, rx) = oneshot::channel();
+        let state = Arc::clone(self.state);
+        self.inner
+            .publish_with_ack_hook(
+                batch,
+                Box::new(move || {
+                    on_ack();
+                    let _ = tx.send(std::mem::take(&mut *state.acking.lock().unwrap()));
+                }),
+            )
+            .await?;
+        self.receipts.lock().unwrap().push(rx);
+        Ok(())
+    }
+}
+
+async fn consume_shared(
+    mut subscriber: FairSubscriber,
+    vault: Arc<Mutex<Vault>>,
+    metrics: Option<Metrics>,
+    processors: Processors,
+    state: Arc<ConsumerState>,
+) {
+    while let Some(delivery) = subscriber.next().await {
+        let (batch, ack) = delivery.into_parts();
+        let connector_id = batch.connector_id.clone();
+        if state.poisoned.lock().unwrap().contains_key(&connector_id) {
+            // Dropped un-acked: its pass sees the receipt close.
+            continue;
+        }
+        let delta_count = batch.deltas.len() as u64;
+        match apply(&vault, batch, metrics.clone(), Arc::clone(&processors)).await {
+            Ok((applied, derived)) => {
+                let outcome = match applied {
+                    Applied::Committed(_) => PipelineStats {
+                        batches_applied: 1,
+                        deltas_applied: delta_count,
+                        deltas_derived: derived,
+                        ..Default::default()
+                    },
+                    Applied::Quarantined => PipelineStats { batches_quarantined: 1, ..Default::default() },
+                };
+                state.stats.lock().unwrap().entry(connector_id.clone()).or_default().absorb(&outcome);
+                *state.acking.lock().unwrap() = outcome;
+                *state.settling.lock().unwrap() = Some(applied.settled());
+                // Ack hooks do file I/O; see `consume`.
+                if let Err(e) = tokio::task::spawn_blocking(move || ack.ack()).await {
+                    state.poisoned.lock().unwrap().insert(connector_id, HostError::Join(e.to_string()));
+                }
+            }
+            Err(e) => {
+                state.poisoned.lock().unwrap().insert(connector_id, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+//! Batch validation ahead of apply, and the dead-letter policy around it.
+//!
+//! `Vault::apply_batch` trusts its input. The checks here enforce the
+//! contract every connector is supposed to honour (D13):
+//!
+//! - the batch's connector id is non-empty and free of U+001F, the
+//!   separator `Item::deterministic_id` joins on;
+//! - every upsert's `connector_id` is the batch's;
+//! - every upsert's `id` is `deterministic_id(connector_id, source_id)`;
+//! - no source id is empty;
+//! - every chunk is at most `MAX_CHUNK_BYTES` and hashes to its address
+//!   (the vault keeps the first copy of a hash forever, so a wrong one
+//!   would poison every item sharing it).
+//!
+//! A batch with any rejection is quarantined whole, at once, with one
+//! reason per rejection: it is deterministic, so retrying cannot help, and
+//! applying the valid remainder would split a batch its connector built
+//! as a unit. A batch that passes validation but fails to apply is counted
+//! and quarantined after [`MAX_APPLY_ATTEMPTS`] — the first failures may
+//! be transient. Either way the quarantine commits with the cursor advance,
+//! so the connector moves on (see `wkyt_vault`'s dead-letter docs).
+
+use crate::derive::Derived;
+use crate::HostError;
+use sha2::{Digest, Sha256};
+use wkyt_core::{Delta, DeltaBatch, Item, Settled, MAX_CHUNK_BYTES};
+use wkyt_vault::{DerivedBatch, FailureOutcome, Vault};
+
+/// Failed applies of one batch before it is quarantined.
+pub const MAX_APPLY_ATTEMPTS: u32 = 3;
+
+/// Why a batch failed validation. Deltas are identified by their position
+/// in the batch and their source id.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Rejection {
+    #[error("batch connector id is empty")]
+    EmptyConnectorId,
+    #[error("batch connector id {0:?} contains U+001F, the deterministic-id separator")]
+    SeparatorInConnectorId(String),
+    #[error("delta {index}: source id is empty")]
+    EmptySourceId { index: usize },
+    #[error("delta {index} ({source_id:?}): item connector id {item:?} is not the batch's {batch:?}")]
+    ConnectorMismatch {
+        index: usize,
+        source_id: String,
+        item: String,
+        batch: String,
+    },
+    #[error("delta {index} ({source_id:?}): id {actual} is not the deterministic id {expected}")]
+    IdMismatch {
+        index: usize,
+        source_id: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("delta {index}: chunk of {size} bytes exceeds {MAX_CHUNK_BYTES}")]
+    ChunkTooLarge { index: usize, size: usize },
+    #[error("delta {index}: chunk data does not hash to {sha256}")]
+    ChunkHashMismatch { index: usize, sha256: String },
+}
+
+/// Every rejection in `batch`; empty means it may be applied.
+pub fn validate_batch(batch: &DeltaBatch) -> Vec<Rejection> {
+    let mut rejections = Vec::new();
+    if batch.connector_id.is_empty() {
+        rejections.push(Rejection::EmptyConnectorId);
+    }
+    if batch.connector_id.contains('\u{1F}') {
+        rejections.push(Rejection::SeparatorInConnectorId(batch.connector_id.clone()));
+    }
+    for (index, delta) in batch.deltas.iter().enumerate() {
+        match delta {
+            Delta::Upsert(item) => {
+                if item.source_id.is_empty() {
+                    rejections.push(Rejection::EmptySourceId { index });
+                }
+                if item.connector_id != batch.connector_id {
+                    rejections.push(Rejection::ConnectorMismatch {
+                        index,
+                        source_id: item.source_id.clone(),
+                        item: item.connector_id.clone(),
+                        batch: batch.connector_id.clone(),
+                    });
+                }
+                let expected = Item::deterministic_id(&batch.connector_id, &item.source_id).to_string();
+                if item.id != expected {
+                    rejections.push(Rejection::IdMismatch {
+                        index,
+                        source_id: item.source_id.clone(),
+                        expected,
+                        actual: item.id.clone(),
+                    });
+                }
+            }
+            Delta::Tombstone { source_id } => {
+                if source_id.is_empty() {
+                    rejections.push(Rejection::EmptySourceId { index });
+                }
+            }
+            Delta::Chunk { sha256, data } => {
+                if data.len() > MAX_CHUNK_BYTES {
+                    rejections.push(Rejection::ChunkTooLarge { index, size: data.len() });
+                } else if format!("{:x}", Sha256::digest(data)) != *sha256 {
+                    rejections.push(Rejection::ChunkHashMismatch { index, sha256: sha256.clone() });
+                }
+            }
+        }
+    }
+    rejections
+}
+
+/// What happened to a delivered batch.
+pub(crate) enum Applied {
+    /// Committed, with what was derived from it.
+    Committed(Derived),
+    /// Quarantined (with its cursor advanced); the delivery is handled.
+    /// Nothing is derived from a quarantined batch.
+    Quarantined,
+}
+
+impl Applied {
+    /// How a commit hook hears of it.
+    pub(crate) fn settled(&self) -> Settled {
+        match self {
+            Applied::Committed(_) => Settled::Committed,
+            Applied::Quarantined => Settled::Quarantined,
+        }
+    }
+}
+
+/// Validate, apply with what the processors derived from `batch` (see
+/// [`derive`](crate::derive)), and apply the dead-letter policy. Blocking
+/// (sqlite).
+pub(crate) fn validate_and_apply(
+    vault: &mut Vault,
+    batch: &DeltaBatch,
+    derived: &[DerivedBatch],
+) -> Result<Applied, HostError> {
+    let rejections = validate_batch(batch);
+    if !rejections.is_empty() {
+        vault.quarantine_batch(batch, &reasons(&rejections))?;
+        return Ok(Applied::Quarantined);
+    }
+    match vault.apply_batch_with_derived(batch, derived) {
+        Ok(quarantined) => {
+            vault.clear_apply_failures(batch)?;
+            Ok(Applied::Committed(Derived::committed(derived, &quarantined)))
+        }
+        // If even recording the failure fails, the apply error is the
+        // more useful one to surface.
+        Err(e) => match vault.record_apply_failure(batch, &e.to_string(), MAX_APPLY_ATTEMPTS) {
+            Ok(FailureOutcome::Quarantined { .. }) => Ok(Applied::Quarantined),
+            _ => Err(e.into()),
+        },
+    }
+}
+
+/// Retry a quarantined batch: re-validate, then apply its deltas (never
+/// its cursor) and drop it from quarantine in one transaction. A failed
+/// retry leaves it quarantined with the new reasons recorded.
+pub fn retry_dead_letter(vault: &mut Vault, id: i64) -> Result<(), HostError> {
+    let letter = vault
+        .dead_letter(id)?
+        .ok_or(wkyt_vault::VaultError::DeadLetterNotFound(id))?;
+    let rejections = validate_batch(&letter.batch);
+    if !rejections.is_empty() {
+        vault.note_dead_letter_failure(id, &reasons(&rejections))?;
+        return Err(HostError::Rejected(rejections));
+    }
+    if let Err(e) = vault.replay_dead_letter(id) {
+        vault.note_dead_letter_failure(id, &[e.to_string()])?;
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+pub(crate) fn reasons(rejections: &[Rejection]) -> Vec<String> {
+    rejections.iter().map(ToString::to_string).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use serde_json::json;
+    use wkyt_core::ItemKind;
+
+    fn batch(connector_id: &str, deltas: Vec<Delta>) -> DeltaBatch {
+        DeltaBatch { connector_id: connector_id.into(), deltas, cursor: None }
+    }
+
+    fn item(connector_id: &str, source_id: &str) -> Item {
+        Item::new(source_id, connector_id, ItemKind::File, Utc::now(), json!({}))
+    }
+
+    #[test]
+    fn well_formed_batches_pass() {
+        let b = batch(
+            "file-import",
+            vec![Delta::Upsert(item("file-import", "a.json")), Delta::Tombstone { source_id: "b.json".into() }],
+        );
+        assert!(validate_batch(&b).is_empty());
+    }
+
+    #[test]
+    fn each_contract_breach_is_named_precisely() {
+        let mut forged = item("file-import", "forged.json");
+        forged.id = Item::deterministic_id("other", "forged.json").to_string();
+        let b = batch(
+            "file-import",
+            vec![
+                Delta::Upsert(item("google-calendar", "x")),
+                Delta::Upsert(forged),
+                Delta::Upsert(item("file-import", "")),
+                Delta::Tombstone { source_id: String::new() },
+            ],
+        );
+        let rejections = validate_batch(&b);
+        assert!(matches!(&rejections[0], Rejection::ConnectorMismatch { index: 0, item, .. } if item == "google-calendar"));
+        // The mismatched connector also yields an id that is wrong for the batch.
+        assert!(matches!(&rejections[1], Rejection::IdMismatch { index: 0, .. }));
+        assert!(matches!(&rejections[2], Rejection::IdMismatch { index: 1, source_id, .. } if source_id == "forged.json"));
+        assert!(matches!(&rejections[3], Rejection::EmptySourceId { index: 2 }));
+        assert!(matches!(&rejections[4], Rejection::EmptySourceId { index: 3 }));
+        assert_eq!(rejections.len(), 5);
+    }
+
+    #[test]
+    fn chunks_must_hash_to_their_address_and_fit() {
+        let good = Delta::Chunk { sha256: format!("{:x}", Sha256::digest(b"abc")), data: b"abc".to_vec() };
+        let forged = Delta::Chunk { sha256: format!("{:x}", Sha256::digest(b"abc")), data: b"abd".to_vec() };
+        let huge = Delta::Chunk { sha256: String::new(), data: vec![0; MAX_CHUNK_BYTES + 1] };
+        assert_eq!(
+            validate_batch(&batch("file-import", vec![good, forged, huge])),
+            [
+                Rejection::ChunkHashMismatch { index: 1, sha256: format!("{:x}", Sha256::digest(b"abc")) },
+                Rejection::ChunkTooLarge { index: 2, size: MAX_CHUNK_BYTES + 1 },
+            ]
+        );
+    }
+
+    #[test]
+    fn separator_in_connector_id_is_rejected() {
+        let b = batch("evil\u{1F}id", vec![]);
+        assert_eq!(validate_batch(&b), [Rejection::SeparatorInConnectorId("evil\u{1F}id".into())]);
+        assert_eq!(validate_batch(&batch("", vec![])), [Rejection::EmptyConnectorId]);
+    }
+}
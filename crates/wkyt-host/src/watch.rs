@@ -0,0 +1,181 @@
+//! Push-mode passes: run a connector when it reports a change (see
+//! [`ChangeNotifier`]) instead of on a timer.
+//!
+//! One pass at startup catches up on whatever changed while nothing was
+//! watching. After that, a change notification opens a debounce window
+//! that closes once the source has been quiet for the debounce period, or
+//! after ten periods of continuous change, so a burst of writes costs one
+//! pass and a tree that never settles still gets synced. A full rescan
+//! runs whenever the rescan interval passes without a pass, catching
+//! changes the notifier dropped. Each pass is an ordinary
+//! [`Pipeline::run_once`] from the stored cursor.
+
+use crate::{HostError, Pipeline, PipelineStats};
+use futures_util::StreamExt;
+use std::time::Duration;
+use tokio::time::{sleep, timeout, Instant};
+use wkyt_core::ChangeNotifier;
+
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+const DEFAULT_RESCAN: Duration = Duration::from_secs(10 * 60);
+/// The debounce window never stays open longer than this many periods.
+const MAX_DEBOUNCE_PERIODS: u32 = 10;
+
+/// Why a watched pass ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wake {
+    /// The catch-up pass when watching starts.
+    Startup,
+    /// The source reported changes (debounced).
+    Changed,
+    /// The rescan interval passed without a pass.
+    Rescan,
+}
+
+/// Timing for [`Pipeline::run_watched`].
+#[derive(Debug, Clone)]
+pub struct Watch {
+    debounce: Duration,
+    rescan: Duration,
+}
+
+impl Default for Watch {
+    fn default() -> Self {
+        Self { debounce: DEFAULT_DEBOUNCE, rescan: DEFAULT_RESCAN }
+    }
+}
+
+impl Watch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Quiet period that closes a debounce window (default 500 ms).
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Longest stretch without a pass before a fallback full rescan
+    /// (default 10 minutes).
+    pub fn with_rescan(mut self, rescan: Duration) -> Self {
+        self.rescan = rescan;
+        self
+    }
+}
+
+impl Pipeline {
+    /// Run `connector` on change until its notifications end, reporting
+    /// every pass to `on_pass`. A failed pass is reported, not returned:
+    /// the next wake-up retries from the committed cursor, as polling
+    /// would. Returns `Ok` when the change stream ends (fall back to
+    /// polling), or the error that stopped the watch from starting.
+    pub async fn run_watched<C: ChangeNotifier + ?Sized>(
+        &self,
+        connector: &C,
+        watch: &Watch,
+        mut on_pass: impl FnMut(Wake, Result<PipelineStats, HostError>),
+    ) -> Result<(), HostError> {
+        // Subscribe before the catch-up pass, so a change made during it
+        // still wakes the loop (init first: there may be nothing to watch
+        // until it has run).
+        connector.init().await?;
+        // Fused: the debounce loop may already have seen the end.
+        let mut changes = connector.changes()?.fuse();
+        on_pass(Wake::Startup, self.run_once(connector).await);
+
+        loop {
+            let wake = tokio::select! {
+                change = changes.next() => match change {
+                    Some(()) => Wake::Changed,
+                    None => return Ok(()),
+                },
+                () = sleep(watch.rescan) => Wake::Rescan,
+            };
+            if wake == Wake::Changed {
+                let deadline = Instant::now() + watch.debounce * MAX_DEBOUNCE_PERIODS;
+                while Instant::now() < deadline {
+                    match timeout(watch.debounce, changes.next()).await {
+                        Ok(Some(())) => continue,
+                        // Ended: run this pass; the next select returns.
+                        Ok(None) | Err(_) => break,
+                    }
+                }
+            }
+            on_pass(wake, self.run_once(connector).await);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::stream;
+    use std::sync::{Arc, Mutex};
+    use wkyt_core::{ChangeStream, Connector, DeltaStream, SyncError, SyncToken};
+    use wkyt_vault::{KeyService, MemoryKekStore, Vault};
+
+    /// Counts its syncs; wake-ups come from a test-held channel.
+    struct Manual {
+        syncs: Mutex<u32>,
+        changes: Mutex<Option<ChangeStream>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Connector for Manual {
+        fn id(&self) -> &str {
+            "manual"
+        }
+        async fn init(&self) -> Result<(), SyncError> {
+            Ok(())
+        }
+        fn sync(&self, _cursor: Option<SyncToken>) -> DeltaStream<'_> {
+            *self.syncs.lock().unwrap() += 1;
+            Box::pin(stream::empty())
+        }
+    }
+
+    impl ChangeNotifier for Manual {
+        fn changes(&self) -> Result<ChangeStream, SyncError> {
+            Ok(self.changes.lock().unwrap().take().expect("watched once"))
+        }
+    }
+
+    #[tokio::test]
+    async fn bursts_coalesce_rescans_fill_gaps_and_an_ended_stream_returns() {
+        let dir = tempfile::tempdir().unwrap();
+        let (dek, _) = KeyService::new(MemoryKekStore::default(), dir.path()).provision().unwrap();
+        let pipeline = Pipeline::new(Arc::new(Mutex::new(Vault::open(&dir.path().join("v.db"), &dek).unwrap())));
+
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel::<()>();
+        let changes: ChangeStream = Box::pin(stream::unfold(rx, |mut rx| async { rx.recv().await.map(|()| ((), rx)) }));
+        let connector = Manual { syncs: Mutex::new(0), changes: Mutex::new(Some(changes)) };
+        let watch = Watch::new().with_debounce(Duration::from_millis(40)).with_rescan(Duration::from_millis(300));
+
+        let wakes = Mutex::new(Vec::new());
+        let driver = async {
+            sleep(Duration::from_millis(50)).await;
+            for _ in 0..5 {
+                tx.send(()).unwrap();
+                sleep(Duration::from_millis(5)).await;
+            }
+            // Quiet for longer than the rescan interval.
+            sleep(Duration::from_millis(450)).await;
+            drop(tx);
+        };
+        let (watched, ()) = tokio::join!(
+            pipeline.run_watched(&connector, &watch, |wake, result| {
+                result.unwrap();
+                wakes.lock().unwrap().push(wake);
+            }),
+            driver,
+        );
+        watched.unwrap();
+
+        let wakes = wakes.into_inner().unwrap();
+        assert_eq!(wakes[..2], [Wake::Startup, Wake::Changed], "five notifications, one pass");
+        assert!(wakes[2..].contains(&Wake::Rescan), "{wakes:?}");
+        assert!(!wakes[2..].contains(&Wake::Changed));
+        assert_eq!(*connector.syncs.lock().unwrap() as usize, wakes.len());
+    }
+}
@@ -0,0 +1,134 @@
+//! Poison batches: validation rejects malformed ones into quarantine at
+//! once, repeated apply failures are quarantined after
+//! `MAX_APPLY_ATTEMPTS`, and either way the connector's cursor moves on.
+
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+use wkyt_host::{retry_dead_letter, run_pipeline_once, HostError, Rejection, MAX_APPLY_ATTEMPTS};
+use wkyt_vault::{KeyService, MemoryKekStore, Vault, VaultError};
+
+/// Replays a fixed script of single-event batches with cursors
+/// `after-1` … `after-n`, resuming after the committed one. `forge_at`
+/// gives that batch's item an id that is not its deterministic id.
+struct Scripted {
+    batches: u32,
+    forge_at: Option<u32>,
+}
+
+fn ts() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339("2024-07-04T12:00:00Z").unwrap().with_timezone(&Utc)
+}
+
+#[async_trait::async_trait]
+impl Connector for Scripted {
+    fn id(&self) -> &str {
+        "scripted"
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        let resume_after = cursor
+            .and_then(|c| c.0.strip_prefix("after-").and_then(|n| n.parse().ok()))
+            .unwrap_or(0);
+        let batches = (resume_after + 1..=self.batches).map(|n| {
+            let mut item = Item::new(format!("evt-{n}"), "scripted", ItemKind::Event, ts(), json!({ "n": n }));
+            if Some(n) == self.forge_at {
+                item.id = Item::deterministic_id("forged", &item.source_id).to_string();
+            }
+            Ok(DeltaBatch {
+                connector_id: "scripted".into(),
+                deltas: vec![Delta::Upsert(item)],
+                cursor: Some(SyncToken(format!("after-{n}"))),
+            })
+        });
+        Box::pin(stream::iter(batches.collect::<Vec<_>>()))
+    }
+}
+
+fn vault() -> (tempfile::TempDir, Arc<Mutex<Vault>>) {
+    let dir = tempfile::tempdir().unwrap();
+    let svc = KeyService::new(MemoryKekStore::default(), dir.path());
+    let (dek, _recovery) = svc.provision().unwrap();
+    let vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+    (dir, Arc::new(Mutex::new(vault)))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn malformed_batch_is_quarantined_at_once_and_the_pass_continues() {
+    let (_dir, vault) = vault();
+    let connector = Scripted { batches: 4, forge_at: Some(2) };
+
+    let stats = run_pipeline_once(&connector, Arc::clone(&vault)).await.unwrap();
+    assert_eq!(stats.batches_applied, 3);
+    assert_eq!(stats.batches_quarantined, 1);
+
+    let mut v = vault.lock().unwrap();
+    assert_eq!(v.cursor("scripted").unwrap(), Some(SyncToken("after-4".into())));
+    assert_eq!(v.items("scripted").unwrap().len(), 3);
+    let letters = v.dead_letters().unwrap();
+    assert_eq!(letters.len(), 1);
+    let letter = &letters[0];
+    assert_eq!(letter.connector_id, "scripted");
+    assert_eq!(letter.batch.cursor, Some(SyncToken("after-2".into())));
+    assert_eq!(letter.reasons.len(), 1);
+    assert!(letter.reasons[0].contains("is not the deterministic id"), "{:?}", letter.reasons);
+
+    // Retrying a deterministic rejection fails the same way and keeps it.
+    let err = retry_dead_letter(&mut v, letter.id).unwrap_err();
+    assert!(matches!(&err, HostError::Rejected(r) if matches!(r[..], [Rejection::IdMismatch { index: 0, .. }])));
+    assert_eq!(v.dead_letter(letter.id).unwrap().unwrap().attempts, 2);
+
+    v.discard_dead_letter(letter.id).unwrap();
+    assert!(v.dead_letters().unwrap().is_empty());
+    assert!(matches!(
+        retry_dead_letter(&mut v, letter.id),
+        Err(HostError::Vault(VaultError::DeadLetterNotFound(_)))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn repeated_apply_failure_is_quarantined_after_max_attempts() {
+    let (_dir, vault) = vault();
+    // A row for ("scripted", "evt-2") under a foreign id: the well-formed
+    // batch 2 passes validation but fails on the UNIQUE constraint.
+    let mut squatter = Item::new("evt-2", "scripted", ItemKind::Event, ts(), json!({}));
+    squatter.id = Item::deterministic_id("elsewhere", "evt-2").to_string();
+    vault
+        .lock()
+        .unwrap()
+        .apply_batch(&DeltaBatch { connector_id: "scripted".into(), deltas: vec![Delta::Upsert(squatter)], cursor: None })
+        .unwrap();
+    let connector = Scripted { batches: 3, forge_at: None };
+
+    for attempt in 1..MAX_APPLY_ATTEMPTS {
+        let result = run_pipeline_once(&connector, Arc::clone(&vault)).await;
+        assert!(matches!(result, Err(HostError::Vault(_))), "attempt {attempt}: {result:?}");
+        let v = vault.lock().unwrap();
+        assert_eq!(v.cursor("scripted").unwrap(), Some(SyncToken("after-1".into())));
+        assert!(v.dead_letters().unwrap().is_empty(), "not yet quarantined");
+    }
+
+    let stats = run_pipeline_once(&connector, Arc::clone(&vault)).await.unwrap();
+    assert_eq!(stats.batches_quarantined, 1);
+    assert_eq!(stats.batches_applied, 1, "batch 3 follows the quarantined one");
+
+    let mut v = vault.lock().unwrap();
+    assert_eq!(v.cursor("scripted").unwrap(), Some(SyncToken("after-3".into())));
+    let letters = v.dead_letters().unwrap();
+    assert_eq!(letters.len(), 1);
+    assert_eq!(letters[0].attempts, MAX_APPLY_ATTEMPTS);
+    assert!(letters[0].reasons[0].contains("UNIQUE"), "{:?}", letters[0].reasons);
+
+    // The conflict is still there: a retry fails, records why, and never
+    // moves the cursor.
+    let id = letters[0].id;
+    assert!(matches!(retry_dead_letter(&mut v, id), Err(HostError::Vault(_))));
+    assert_eq!(v.dead_letter(id).unwrap().unwrap().attempts, MAX_APPLY_ATTEMPTS + 1);
+    assert_eq!(v.cursor("scripted").unwrap(), Some(SyncToken("after-3".into())));
+}
@@ -0,0 +1,80 @@
+//! Dry-run passes report what a live pass would change and write nothing.
+
+use serde_json::json;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wkyt_connector_file::FileImporter;
+use wkyt_core::SyncToken;
+use wkyt_host::{EvidenceClaims, Pipeline};
+use wkyt_vault::{KeyService, MemoryKekStore, Vault};
+
+fn without_scan_time(cursor: &Option<SyncToken>) -> serde_json::Value {
+    let mut value: serde_json::Value = serde_json::from_str(&cursor.as_ref().unwrap().0).unwrap();
+    value.as_object_mut().unwrap().remove("scanned_at_ms");
+    value
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_previews_new_changed_and_deleted_without_writing() {
+    let vault_dir = tempfile::tempdir().unwrap();
+    let watch = tempfile::tempdir().unwrap();
+    let svc = KeyService::new(MemoryKekStore::default(), vault_dir.path());
+    let (dek, _recovery) = svc.provision().unwrap();
+    let vault = Arc::new(Mutex::new(Vault::open(&vault_dir.path().join("vault.db"), &dek).unwrap()));
+    let connector = FileImporter::new("file-import", watch.path().to_path_buf());
+    let pipeline = Pipeline::new(Arc::clone(&vault)).with_processor(EvidenceClaims);
+
+    fs::write(watch.path().join("a.json"), r#"{"k": 1}"#).unwrap();
+    fs::write(watch.path().join("b.json"), r#"{"k": 1}"#).unwrap();
+
+    // 1. Fresh directory: everything is new, what the processors derive
+    // included, and nothing is written.
+    let report = pipeline.run_dry(&connector).await.unwrap();
+    assert_eq!(report.batches, 1);
+    assert_eq!(report.new_items.len(), 6, "2 files, 2 claims, 2 evidence links");
+    assert!(report.changed_items.is_empty() && report.deleted_items.is_empty());
+    assert!(report.resulting_cursor.is_some());
+    {
+        let v = vault.lock().unwrap();
+        assert_eq!(v.item_count().unwrap(), 0);
+        assert_eq!(v.cursor("file-import").unwrap(), None);
+    }
+
+    // The live pass lands exactly where the preview said it would (bar
+    // the scan time the file cursor records).
+    pipeline.run_once(&connector).await.unwrap();
+    let committed = vault.lock().unwrap().cursor("file-import").unwrap();
+    assert_eq!(without_scan_time(&committed), without_scan_time(&report.resulting_cursor));
+
+    // 2. Edit one file, delete the other.
+    std::thread::sleep(Duration::from_millis(20)); // a distinct mtime
+    fs::write(watch.path().join("a.json"), r#"{"k": 2}"#).unwrap();
+    fs::remove_file(watch.path().join("b.json")).unwrap();
+    let committed = vault.lock().unwrap().cursor("file-import").unwrap();
+
+    let report = pipeline.run_dry(&connector).await.unwrap();
+    assert!(report.new_items.is_empty());
+    // The deleted file's claim and evidence link go with it (D19).
+    let deleted: Vec<_> = report.deleted_items.iter().map(|i| i.connector_id.as_str()).collect();
+    assert_eq!(deleted, [EvidenceClaims::ID, EvidenceClaims::ID, "file-import"]);
+    assert_eq!(report.deleted_items[2].source_id, "b.json");
+    let file = report
+        .changed_items
+        .iter()
+        .find(|c| c.after.source_id == "a.json")
+        .expect("edited file is reported as changed");
+    let content = file.property_diffs.iter().find(|d| d.path == "/content/k").unwrap();
+    assert_eq!((content.before.clone(), content.after.clone()), (Some(json!(1)), Some(json!(2))));
+    assert!(file.property_diffs.iter().any(|d| d.path == "/modified_ms"));
+    assert_ne!(report.resulting_cursor, committed);
+
+    // Still nothing written.
+    {
+        let v = vault.lock().unwrap();
+        assert_eq!(v.cursor("file-import").unwrap(), committed);
+        assert_eq!(v.live_item("file-import", "a.json").unwrap().unwrap().properties["content"]["k"], 1);
+        assert!(v.live_item("file-import", "b.json").unwrap().is_some());
+        assert_eq!(v.items(EvidenceClaims::ID).unwrap().len(), 4);
+    }
+}
@@ -6,7 +6,7 @@ use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 use wkyt_connector_file::FileImporter;
 use wkyt_core::{Item, SyncToken};
-use wkyt_host::run_pipeline_once;
+use wkyt_host::{run_pipeline_once, EvidenceClaims, Pipeline};
 use wkyt_vault::{KeyService, MemoryKekStore, Vault};
 
 struct Rig {
@@ -34,17 +34,26 @@ fn rig() -> Rig {
 #[tokio::test(flavor = "multi_thread")]
 async fn drop_modify_delete_lands_in_encrypted_vault() {
     let r = rig();
+    let pipeline = Pipeline::new(Arc::clone(&r.vault)).with_processor(EvidenceClaims);
 
-    // 1. Drop two files → both land.
+    // 1. Drop two files → both land, and the calendar's event with them.
     fs::write(r.watch_dir.path().join("notes.json"), r#"{"note": "hello"}"#).unwrap();
-    fs::write(r.watch_dir.path().join("cal.ics"), "BEGIN:VCALENDAR\nEND:VCALENDAR").unwrap();
+    fs::write(
+        r.watch_dir.path().join("cal.ics"),
+        "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:e1\nSUMMARY:standup\nDTSTART:20240902T090000Z\nEND:VEVENT\nEND:VCALENDAR",
+    )
+    .unwrap();
 
-    let stats = run_pipeline_once(&r.connector, Arc::clone(&r.vault)).await.unwrap();
+    let stats = pipeline.run_once(&r.connector).await.unwrap();
     assert_eq!(stats.batches_applied, 1);
-    assert_eq!(stats.deltas_applied, 6); // 2 files + 2 claims + 2 rels
+    assert_eq!(stats.deltas_applied, 3);
+    assert_eq!(stats.deltas_derived, 6); // a claim + its evidence link per file and event
     {
         let v = r.vault.lock().unwrap();
-        assert_eq!(v.item_count().unwrap(), 6);
+        assert_eq!(v.item_count().unwrap(), 9);
+        assert_eq!(v.items(EvidenceClaims::ID).unwrap().len(), 6);
+        let event = v.live_item("file-import", "cal.ics#e1").unwrap().unwrap();
+        assert_eq!(event.properties["summary"], "standup");
         let items = v.items("file-import").unwrap();
         let notes = items.iter().find(|i| i.source_id == "notes.json").unwrap();
         assert_eq!(notes.properties["content"]["note"], "hello");
@@ -56,29 +65,32 @@ async fn drop_modify_delete_lands_in_encrypted_vault() {
     }
 
     // 2. Idle pass: nothing changed, nothing applied.
-    let stats = run_pipeline_once(&r.connector, Arc::clone(&r.vault)).await.unwrap();
+    let stats = pipeline.run_once(&r.connector).await.unwrap();
     assert_eq!(stats, wkyt_host::PipelineStats::default());
 
     // 3. Modify → in-place update, no duplicate row.
     fs::write(r.watch_dir.path().join("notes.json"), r#"{"note": "edited"}"#).unwrap();
-    let stats = run_pipeline_once(&r.connector, Arc::clone(&r.vault)).await.unwrap();
-    assert_eq!(stats.deltas_applied, 3); // file + claim + rel updated
+    let stats = pipeline.run_once(&r.connector).await.unwrap();
+    assert_eq!((stats.deltas_applied, stats.deltas_derived), (1, 2)); // file, then claim + rel
     {
         let v = r.vault.lock().unwrap();
-        assert_eq!(v.item_count().unwrap(), 6, "modification must not duplicate");
+        assert_eq!(v.item_count().unwrap(), 9, "modification must not duplicate");
         let items = v.items("file-import").unwrap();
         let notes = items.iter().find(|i| i.source_id == "notes.json").unwrap();
         assert_eq!(notes.properties["content"]["note"], "edited");
     }
 
-    // 4. Delete → tombstone; the row leaves the live set. (no tombstones generated for claim and rel here, so just the file gets tombstoned). Wait, does file connector delete the claim/rel? The file connector `tombstones` just does it for the source_id (the file itself). So the claim and rel remain.
-    // Actually, it deletes the source_id `notes.json` or `cal.ics` (tombstone).
+    // 4. Delete → tombstone; the row and its event leave the live set, and
+    // so do the claims and links derived from them.
     fs::remove_file(r.watch_dir.path().join("cal.ics")).unwrap();
-    run_pipeline_once(&r.connector, Arc::clone(&r.vault)).await.unwrap();
+    pipeline.run_once(&r.connector).await.unwrap();
     {
         let v = r.vault.lock().unwrap();
-        assert_eq!(v.item_count().unwrap(), 5); // 1 file deleted. 6 - 1 = 5.
-        assert!(v.items("file-import").unwrap().iter().all(|i| i.source_id != "cal.ics"));
+        assert_eq!(v.item_count().unwrap(), 3);
+        assert!(v.items("file-import").unwrap().iter().all(|i| !i.source_id.starts_with("cal.ics")));
+        let claims = v.temporal_claims_with_evidence().unwrap();
+        assert_eq!(claims.len(), 1, "the deleted file's claim is retracted with it");
+        assert_eq!(claims[0].1[0].source_id, "notes.json");
     }
 }
 
@@ -101,9 +113,9 @@ async fn corrupted_cursor_triggers_one_full_resync_without_duplicates() {
     }
 
     let stats = run_pipeline_once(&r.connector, Arc::clone(&r.vault)).await.unwrap();
-    assert_eq!(stats.deltas_applied, 3, "full resync re-delivers the file");
+    assert_eq!(stats.deltas_applied, 1, "full resync re-delivers the file");
     let v = r.vault.lock().unwrap();
-    assert_eq!(v.item_count().unwrap(), 3, "resync over existing data must not duplicate");
+    assert_eq!(v.item_count().unwrap(), 1, "resync over existing data must not duplicate");
 }
 
 #[tokio::test(flavor = "multi_thread")]
@@ -114,10 +126,84 @@ async fn many_files_flow_through_bounded_batches_with_per_batch_commits() {
     }
 
     let stats = run_pipeline_once(&r.connector, Arc::clone(&r.vault)).await.unwrap();
-    assert_eq!(stats.deltas_applied, 450); // 150 * 3
+    assert_eq!(stats.deltas_applied, 150);
     assert!(
         stats.batches_applied >= 3,
         "150 files at batch size 64 must arrive as multiple bounded batches"
     );
-    assert_eq!(r.vault.lock().unwrap().item_count().unwrap(), 450);
+    assert_eq!(r.vault.lock().unwrap().item_count().unwrap(), 150);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn metrics_pipeline_records_bus_apply_and_vault_series() {
+    let r = rig();
+    for i in 0..70 {
+        fs::write(r.watch_dir.path().join(format!("m{i:02}.json")), "{}").unwrap();
+    }
+    let metrics = wkyt_metrics::Metrics::new();
+    let pipeline = Pipeline::new(Arc::clone(&r.vault))
+        .with_metrics(metrics.clone())
+        .with_processor(EvidenceClaims);
+
+    let stats = pipeline.run_once(&r.connector).await.unwrap();
+    assert_eq!(stats.batches_applied, 2);
+
+    let text = metrics.render();
+    assert!(text.contains("wkyt_bus_published_total{connector=\"file-import\"} 2\n"));
+    assert!(text.contains("wkyt_bus_acked_total{connector=\"file-import\"} 2\n"));
+    assert!(text.contains("wkyt_bus_queue_depth 0\n"));
+    assert!(text.contains("wkyt_batch_apply_seconds_count{connector=\"file-import\"} 2\n"));
+    assert!(text.contains("wkyt_vault_items{kind=\"file\"} 70\n"));
+    assert!(text.contains("wkyt_vault_items{kind=\"claim\"} 70\n"));
+    assert!(!text.contains("wkyt_vault_database_bytes 0\n"));
+
+    // A failing pass is counted against its connector and error class.
+    // (Its watch dir would live under a regular file: init cannot create it.)
+    let broken = FileImporter::new("broken", r.watch_dir.path().join("m00.json").join("sub"));
+    assert!(pipeline.run_once(&broken).await.is_err());
+    assert!(metrics
+        .render()
+        .contains("wkyt_connector_errors_total{connector=\"broken\",kind=\"retryable\"} 1\n"));
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn binary_content_reassembles_from_vault_chunks() {
+    let r = rig();
+    let connector = FileImporter::new("file-import", r.watch_dir.path().to_path_buf()).with_include("*.bin");
+    let pipeline = Pipeline::new(Arc::clone(&r.vault));
+    // 1.5 MiB of bytes that are not UTF-8: two chunks.
+    let data: Vec<u8> = (0..3 * 512 * 1024u32).map(|i| (i % 251) as u8 | 0x80).collect();
+    fs::write(r.watch_dir.path().join("blob.bin"), &data).unwrap();
+
+    let stats = pipeline.run_once(&connector).await.unwrap();
+    assert_eq!(stats.batches_applied, 3, "one batch per chunk, then the item");
+    let v = r.vault.lock().unwrap();
+    let item = v.live_item("file-import", "blob.bin").unwrap().unwrap();
+    assert_eq!(item.properties["mime_type"], "application/octet-stream");
+    let mut out = Vec::new();
+    assert_eq!(v.write_content(&item, &mut out).unwrap(), data.len() as u64);
+    assert_eq!(out, data);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn shredded_drops_stay_in_the_vault_and_leave_the_folder() {
+    let r = rig();
+    let connector = FileImporter::new("file-import", r.watch_dir.path().to_path_buf()).with_shred_after_import();
+    let pipeline = Pipeline::new(Arc::clone(&r.vault)).with_processor(EvidenceClaims);
+    let dropped = r.watch_dir.path().join("secret.json");
+    fs::write(&dropped, r#"{"pin": "1234"}"#).unwrap();
+
+    let stats = pipeline.run_once(&connector).await.unwrap();
+    assert_eq!((stats.deltas_applied, stats.deltas_derived), (1, 2));
+    assert!(!dropped.exists(), "shredded once its batch committed");
+    assert_eq!(r.vault.lock().unwrap().temporal_claims_with_evidence().unwrap().len(), 1);
+
+    // The next pass finds it gone: no tombstone, the vault copy stays
+    // live, and the claim derived from the file is retracted.
+    pipeline.run_once(&connector).await.unwrap();
+    let v = r.vault.lock().unwrap();
+    let item = v.live_item("file-import", "secret.json").unwrap().unwrap();
+    assert_eq!(item.properties["content"]["pin"], "1234");
+    assert!(v.temporal_claims_with_evidence().unwrap().is_empty(), "the shredded file's claim is gone");
+    assert!(v.items(EvidenceClaims::ID).unwrap().is_empty(), "and so is its evidence link");
 }
@@ -0,0 +1,130 @@
+//! Shared long-lived host: many connectors, one fair bus, one consumer,
+//! per-connector stats and failure isolation.
+
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde_json::json;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use wkyt_connector_file::FileImporter;
+use wkyt_core::{Connector, Delta, DeltaBatch, DeltaStream, Item, ItemKind, SyncError, SyncToken};
+use wkyt_host::{EvidenceClaims, HostError, Pipeline, PipelineStats};
+use wkyt_vault::{KeyService, MemoryKekStore, Vault};
+
+/// Streams `batches` single-event batches, `evt-1` … `evt-n`.
+struct Backfill {
+    id: &'static str,
+    batches: u32,
+}
+
+fn ts() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339("2024-07-04T12:00:00Z").unwrap().with_timezone(&Utc)
+}
+
+#[async_trait::async_trait]
+impl Connector for Backfill {
+    fn id(&self) -> &str {
+        self.id
+    }
+
+    async fn init(&self) -> Result<(), SyncError> {
+        Ok(())
+    }
+
+    fn sync(&self, cursor: Option<SyncToken>) -> DeltaStream<'_> {
+        if cursor.is_some() {
+            return Box::pin(stream::iter(vec![]));
+        }
+        let batches = (1..=self.batches).map(|n| {
+            let properties = json!({ "n": n, "start": { "dateTime": "2024-07-03T09:00:00Z" } });
+            let item = Item::new(format!("evt-{n}"), self.id, ItemKind::Event, ts(), properties);
+            Ok(DeltaBatch {
+                connector_id: self.id.into(),
+                deltas: vec![Delta::Upsert(item)],
+                cursor: Some(SyncToken(format!("after-{n}"))),
+            })
+        });
+        Box::pin(stream::iter(batches.collect::<Vec<_>>()))
+    }
+}
+
+fn vault() -> (tempfile::TempDir, Arc<Mutex<Vault>>) {
+    let dir = tempfile::tempdir().unwrap();
+    let svc = KeyService::new(MemoryKekStore::default(), dir.path());
+    let (dek, _recovery) = svc.provision().unwrap();
+    let vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+    (dir, Arc::new(Mutex::new(vault)))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn concurrent_connectors_share_one_consumer_with_per_connector_stats() {
+    let (_dir, vault) = vault();
+    let watch = tempfile::tempdir().unwrap();
+    for i in 0..3 {
+        fs::write(watch.path().join(format!("n{i}.json")), "{}").unwrap();
+    }
+    let files = FileImporter::new("file-import", watch.path().to_path_buf());
+    let google = Backfill { id: "google-calendar", batches: 40 };
+
+    let host = Pipeline::new(Arc::clone(&vault)).with_processor(EvidenceClaims).into_shared(2);
+    let (g, f) = tokio::join!(host.run_once(&google), host.run_once(&files));
+    assert_eq!(
+        g.unwrap(),
+        PipelineStats { batches_applied: 40, deltas_applied: 40, deltas_derived: 80, ..Default::default() }
+    );
+    assert_eq!(
+        f.unwrap(),
+        PipelineStats { batches_applied: 1, deltas_applied: 3, deltas_derived: 6, ..Default::default() }
+    );
+
+    {
+        let v = vault.lock().unwrap();
+        // Per-connector ordering held: the last cursor is the last batch's.
+        assert_eq!(v.cursor("google-calendar").unwrap(), Some(SyncToken("after-40".into())));
+        assert!(v.cursor("file-import").unwrap().is_some());
+    }
+
+    // A second pass reuses the same bus and consumer.
+    assert_eq!(host.run_once(&google).await.unwrap(), PipelineStats::default());
+
+    let stats = host.shutdown().await.unwrap();
+    assert_eq!(stats["google-calendar"].batches_applied, 40);
+    assert_eq!(stats["file-import"].deltas_applied, 3);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_failing_connector_is_isolated_and_its_cursor_stops_at_the_last_commit() {
+    let (_dir, vault) = vault();
+    // A row for ("bad", "evt-3") stored under a foreign id (as a buggy
+    // older build could have): the well-formed batch 3 then fails to apply
+    // on the UNIQUE constraint.
+    let mut squatter = Item::new("evt-3", "bad", ItemKind::Event, ts(), json!({}));
+    squatter.id = Item::deterministic_id("elsewhere", "evt-3").to_string();
+    vault
+        .lock()
+        .unwrap()
+        .apply_batch(&DeltaBatch { connector_id: "bad".into(), deltas: vec![Delta::Upsert(squatter)], cursor: None })
+        .unwrap();
+    let bad = Backfill { id: "bad", batches: 6 };
+    let good = Backfill { id: "good", batches: 10 };
+
+    let host = Pipeline::new(Arc::clone(&vault)).into_shared(1);
+    let (b, g) = tokio::join!(host.run_once(&bad), host.run_once(&good));
+    assert!(matches!(b, Err(HostError::Vault(_))), "the failed apply surfaces: {b:?}");
+    assert_eq!(g.unwrap().batches_applied, 10, "other connectors keep flowing");
+
+    {
+        let v = vault.lock().unwrap();
+        assert_eq!(
+            v.cursor("bad").unwrap(),
+            Some(SyncToken("after-2".into())),
+            "batches queued behind the failure must not commit past it"
+        );
+        assert_eq!(v.items("bad").unwrap().len(), 3); // evt-1, evt-2, squatter
+    }
+    assert_eq!(host.stats()["bad"].batches_applied, 2);
+
+    // The poison clears with the failed pass: the next pass is served again
+    // and resumes from the committed cursor.
+    assert_eq!(host.run_once(&bad).await.unwrap(), PipelineStats::default());
+}
@@ -0,0 +1,71 @@
+//! Locked-vault operation (D16): connector passes spool encrypted batches
+//! while the vault is closed; unlocking drains them through the normal
+//! ack-after-commit path with the cursor landing where a live run would
+//! have left it.
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+use wkyt_connector_file::FileImporter;
+use wkyt_host::{drain_spool, run_pipeline_spooled, HostError, Pipeline, PipelineStats};
+use wkyt_vault::{KeyService, MemoryKekStore, Spool, Vault};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn spool_while_locked_then_drain_on_unlock() {
+    let vault_dir = tempfile::tempdir().unwrap();
+    let watch_dir = tempfile::tempdir().unwrap();
+    let spool_dir = vault_dir.path().join("spool");
+    let svc = KeyService::new(MemoryKekStore::default(), vault_dir.path());
+    let (dek, _recovery) = svc.provision().unwrap();
+    let db_path = vault_dir.path().join("vault.db");
+    drop(Vault::open(&db_path, &dek).unwrap()); // created, then "locked"
+    drop(dek);
+
+    let connector = FileImporter::new("file-import", watch_dir.path().to_path_buf());
+    let spool = Arc::new(Spool::open(&spool_dir, svc.spool_key().unwrap()).unwrap());
+
+    // 1. Locked: a full pass goes to the spool, sealed.
+    fs::write(watch_dir.path().join("diary.json"), r#"{"entry": "confidential"}"#).unwrap();
+    let stats = run_pipeline_spooled(&connector, Arc::clone(&spool), None).await.unwrap();
+    assert_eq!(stats.batches_spooled, 1);
+    assert_eq!(stats.deltas_spooled, 1);
+    for entry in fs::read_dir(&spool_dir).unwrap() {
+        let bytes = fs::read(entry.unwrap().path()).unwrap();
+        let needle = b"confidential";
+        assert!(!bytes.windows(needle.len()).any(|w| w == needle), "plaintext at rest");
+    }
+
+    // 2. Still locked: the next pass resumes from the spooled cursor, so an
+    // unchanged tree spools nothing and a new file spools only itself.
+    let stats = run_pipeline_spooled(&connector, Arc::clone(&spool), None).await.unwrap();
+    assert_eq!(stats.batches_spooled, 0);
+    fs::write(watch_dir.path().join("later.json"), r#"{"entry": "two"}"#).unwrap();
+    let stats = run_pipeline_spooled(&connector, Arc::clone(&spool), None).await.unwrap();
+    assert_eq!(stats.deltas_spooled, 1);
+    let newest_cursor = spool.last_cursor("file-import").unwrap();
+    assert_eq!(spool.pending().unwrap().len(), 2);
+
+    // 3. Unlock → drain, oldest first, through the vault's commit path.
+    let dek = svc.unlock().unwrap();
+    let vault = Arc::new(Mutex::new(Vault::open(&db_path, &dek).unwrap()));
+    // A live pass first would commit a cursor the drain then rewinds.
+    let pipeline = Pipeline::new(Arc::clone(&vault)).with_spool(Arc::clone(&spool));
+    let err = pipeline.run_once(&connector).await.unwrap_err();
+    assert!(matches!(err, HostError::SpoolPending(ref id) if id == "file-import"), "{err}");
+    let stats = drain_spool(Arc::clone(&spool), Arc::clone(&vault)).await.unwrap();
+    assert_eq!(stats, PipelineStats { batches_applied: 2, deltas_applied: 2, ..Default::default() });
+    assert!(spool.is_empty().unwrap(), "committed batches leave the spool");
+    {
+        let v = vault.lock().unwrap();
+        assert_eq!(v.item_count().unwrap(), 2);
+        assert_eq!(v.cursor("file-import").unwrap(), newest_cursor);
+    }
+
+    // 4. The live pass after draining picks up exactly where the spool left
+    // off: nothing to do.
+    let stats = pipeline.run_once(&connector).await.unwrap();
+    assert_eq!(stats, PipelineStats::default());
+
+    // Draining an empty spool is a no-op.
+    let stats = drain_spool(spool, vault).await.unwrap();
+    assert_eq!(stats, PipelineStats::default());
+}
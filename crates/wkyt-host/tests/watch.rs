@@ -0,0 +1,44 @@
+//! Push mode end to end: a file dropped into a watched tree reaches the
+//! vault on the notification, with no polling interval in between.
+
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use wkyt_connector_file::FileImporter;
+use wkyt_host::{Pipeline, PipelineStats, Wake, Watch};
+use wkyt_vault::{KeyService, MemoryKekStore, Vault};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_dropped_file_is_ingested_on_notification() {
+    let vault_dir = tempfile::tempdir().unwrap();
+    let watch_dir = tempfile::tempdir().unwrap();
+    let (dek, _) = KeyService::new(MemoryKekStore::default(), vault_dir.path()).provision().unwrap();
+    let vault = Arc::new(Mutex::new(Vault::open(&vault_dir.path().join("vault.db"), &dek).unwrap()));
+    let pipeline = Pipeline::new(Arc::clone(&vault));
+    let connector = FileImporter::new("file-import", watch_dir.path().join("inbox"));
+    fs::create_dir_all(watch_dir.path().join("inbox/2024")).unwrap();
+
+    // Rescan far beyond the test's life: only notifications can wake it.
+    let watch = Watch::new().with_debounce(Duration::from_millis(50)).with_rescan(Duration::from_secs(3600));
+    let (tx, mut passes) = tokio::sync::mpsc::unbounded_channel::<(Wake, PipelineStats)>();
+    let watched = pipeline.run_watched(&connector, &watch, |wake, result| {
+        tx.send((wake, result.unwrap())).unwrap();
+    });
+
+    let driver = async {
+        let (wake, stats) = passes.recv().await.unwrap();
+        assert_eq!((wake, stats), (Wake::Startup, PipelineStats::default()));
+
+        fs::write(watch_dir.path().join("inbox/2024/a.json"), r#"{"k": 1}"#).unwrap();
+        let (wake, stats) = tokio::time::timeout(Duration::from_secs(10), passes.recv()).await.unwrap().unwrap();
+        assert_eq!(wake, Wake::Changed);
+        assert_eq!(stats.deltas_applied, 1);
+    };
+
+    tokio::select! {
+        result = watched => panic!("watch ended early: {result:?}"),
+        () = driver => {}
+    }
+    let v = vault.lock().unwrap();
+    assert!(v.live_item("file-import", "2024/a.json").unwrap().is_some());
+}
@@ -0,0 +1,391 @@
+//! Ingestion and vault telemetry (optional).
+//!
+//! A [`Metrics`] handle is a cheap clone of one shared registry. The
+//! broker and host record into it when given one; nothing records when
+//! they are not, so the metrics are opt-in at runtime rather than a build
+//! flavour. [`Metrics::render`] produces the Prometheus text exposition
+//! format (0.0.4), and [`MetricsServer`] serves it over plain HTTP on a
+//! **loopback address only** — the values (connector ids, item counts by
+//! kind, database size) describe the user's data and are not for the LAN.
+//!
+//! Series:
+//!
+//! | name | type | labels |
+//! |---|---|---|
+//! | `wkyt_bus_published_total` | counter | `connector` |
+//! | `wkyt_bus_acked_total` | counter | `connector` |
+//! | `wkyt_bus_queue_depth` | gauge | — (summed over live buses) |
+//! | `wkyt_batch_apply_seconds` | histogram | `connector` |
+//! | `wkyt_connector_errors_total` | counter | `connector`, `kind` |
+//! | `wkyt_batches_quarantined_total` | counter | `connector` |
+//! | `wkyt_vault_items` | gauge | `kind` |
+//! | `wkyt_vault_database_bytes` | gauge | — |
+//!
+//! The registry is a mutex over small ordered maps: updates happen once
+//! per batch, not per delta, so contention is not a concern and sorted
+//! output keeps scrapes diffable.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Upper bounds (seconds) of the apply-latency histogram buckets. A batch
+/// is at most a few hundred deltas in one sqlite transaction: the
+/// interesting range is a millisecond to a few seconds.
+const APPLY_BUCKETS: &[f64] = &[0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Largest request head the endpoint reads before answering 400.
+const MAX_REQUEST_BYTES: usize = 8 * 1024;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MetricsError {
+    #[error("metrics endpoint must bind a loopback address, got {0}")]
+    NotLoopback(SocketAddr),
+    #[error("metrics endpoint io error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Clone, Default)]
+pub struct Metrics {
+    registry: Arc<Mutex<Registry>>,
+}
+
+#[derive(Default)]
+struct Registry {
+    published: BTreeMap<String, u64>,
+    acked: BTreeMap<String, u64>,
+    /// Depth per live bus, by the id [`Metrics::register_bus`] handed out:
+    /// several buses may share one registry, and the gauge is their sum.
+    queue_depth: BTreeMap<u64, u64>,
+    next_bus: u64,
+    apply_seconds: BTreeMap<String, Histogram>,
+    errors: BTreeMap<(String, String), u64>,
+    quarantined: BTreeMap<String, u64>,
+    vault_items: BTreeMap<String, u64>,
+    vault_bytes: u64,
+}
+
+struct Histogram {
+    /// Non-cumulative per-bucket counts; the last slot is `+Inf`.
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self { buckets: vec![0; APPLY_BUCKETS.len() + 1], sum: 0.0, count: 0 }
+    }
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        let slot = APPLY_BUCKETS.iter().position(|le| value <= *le).unwrap_or(APPLY_BUCKETS.len());
+        self.buckets[slot] += 1;
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A batch from `connector` was accepted by the bus.
+    pub fn bus_published(&self, connector: &str) {
+        *self.lock().published.entry(connector.to_string()).or_default() += 1;
+    }
+
+    /// A delivery from `connector` was acked (its transaction committed).
+    pub fn bus_acked(&self, connector: &str) {
+        *self.lock().acked.entry(connector.to_string()).or_default() += 1;
+    }
+
+    /// An id for one bus's queue depth; see [`Metrics::set_bus_queue_depth`].
+    pub fn register_bus(&self) -> u64 {
+        let mut r = self.lock();
+        r.next_bus += 1;
+        let bus = r.next_bus;
+        r.queue_depth.insert(bus, 0);
+        bus
+    }
+
+    /// Batches currently in flight on bus `bus`.
+    pub fn set_bus_queue_depth(&self, bus: u64, depth: u64) {
+        self.lock().queue_depth.insert(bus, depth);
+    }
+
+    /// Bus `bus` is gone; its depth no longer counts.
+    pub fn unregister_bus(&self, bus: u64) {
+        self.lock().queue_depth.remove(&bus);
+    }
+
+    /// Wall time of one `apply_batch` transaction for `connector`.
+    pub fn observe_batch_apply(&self, connector: &str, elapsed: Duration) {
+        self.lock().apply_seconds.entry(connector.to_string()).or_default().observe(elapsed.as_secs_f64());
+    }
+
+    /// A pipeline pass for `connector` failed; `kind` is a short,
+    /// bounded-cardinality class such as `retryable` or `vault`.
+    pub fn connector_error(&self, connector: &str, kind: &str) {
+        *self.lock().errors.entry((connector.to_string(), kind.to_string())).or_default() += 1;
+    }
+
+    /// A batch from `connector` was moved to the dead-letter table.
+    pub fn batch_quarantined(&self, connector: &str) {
+        *self.lock().quarantined.entry(connector.to_string()).or_default() += 1;
+    }
+
+    /// Replace the live-item-count snapshot. Kinds absent from `counts`
+    /// are dropped rather than left at a stale value.
+    pub fn set_vault_items(&self, counts: impl IntoIterator<Item = (String, u64)>) {
+        self.lock().vault_items = counts.into_iter().collect();
+    }
+
+    pub fn set_vault_database_bytes(&self, bytes: u64) {
+        self.lock().vault_bytes = bytes;
+    }
+
+    /// Everything recorded so far, in Prometheus text format.
+    pub fn render(&self) -> String {
+        let r = self.lock();
+        let mut out = String::new();
+
+        header(&mut out, "wkyt_bus_published_total", "counter", "Batches accepted by the bus.");
+        for (connector, n) in &r.published {
+            sample(&mut out, "wkyt_bus_published_total", &[("connector", connector)], *n as f64);
+        }
+        header(
+            &mut out,
+            "wkyt_bus_acked_total",
+            "counter",
+            "Deliveries acked after their vault transaction committed.",
+        );
+        for (connector, n) in &r.acked {
+            sample(&mut out, "wkyt_bus_acked_total", &[("connector", connector)], *n as f64);
+        }
+        header(&mut out, "wkyt_bus_queue_depth", "gauge", "Batches in flight on the bus.");
+        let depth: u64 = r.queue_depth.values().sum();
+        sample(&mut out, "wkyt_bus_queue_depth", &[], depth as f64);
+
+        header(&mut out, "wkyt_batch_apply_seconds", "histogram", "Vault apply_batch transaction latency.");
+        for (connector, h) in &r.apply_seconds {
+            let mut cumulative = 0;
+            for (le, n) in APPLY_BUCKETS.iter().zip(&h.buckets) {
+                cumulative += n;
+                let le = le.to_string();
+                sample(
+                    &mut out,
+                    "wkyt_batch_apply_seconds_bucket",
+                    &[("connector", connector), ("le", &le)],
+                    cumulative as f64,
+                );
+            }
+            sample(
+                &mut out,
+                "wkyt_batch_apply_seconds_bucket",
+                &[("connector", connector), ("le", "+Inf")],
+                h.count as f64,
+            );
+            sample(&mut out, "wkyt_batch_apply_seconds_sum", &[("connector", connector)], h.sum);
+            sample(&mut out, "wkyt_batch_apply_seconds_count", &[("connector", connector)], h.count as f64);
+        }
+
+        header(
+            &mut out,
+            "wkyt_connector_errors_total",
+            "counter",
+            "Failed pipeline passes by connector and error class.",
+        );
+        for ((connector, kind), n) in &r.errors {
+            sample(&mut out, "wkyt_connector_errors_total", &[("connector", connector), ("kind", kind)], *n as f64);
+        }
+        header(&mut out, "wkyt_batches_quarantined_total", "counter", "Batches moved to the dead-letter table.");
+        for (connector, n) in &r.quarantined {
+            sample(&mut out, "wkyt_batches_quarantined_total", &[("connector", connector)], *n as f64);
+        }
+
+        header(&mut out, "wkyt_vault_items", "gauge", "Live vault items by kind.");
+        for (kind, n) in &r.vault_items {
+            sample(&mut out, "wkyt_vault_items", &[("kind", kind)], *n as f64);
+        }
+        header(&mut out, "wkyt_vault_database_bytes", "gauge", "Size of the encrypted vault database.");
+        sample(&mut out, "wkyt_vault_database_bytes", &[], r.vault_bytes as f64);
+        out
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Registry> {
+        // A panic mid-update leaves at worst one torn counter; telemetry
+        // must not take the pipeline down with it.
+        self.registry.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+fn sample(out: &mut String, name: &str, labels: &[(&str, &str)], value: f64) {
+    out.push_str(name);
+    if !labels.is_empty() {
+        out.push('{');
+        for (i, (k, v)) in labels.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{k}=\"{}\"", escape_label(v));
+        }
+        out.push('}');
+    }
+    let _ = writeln!(out, " {value}");
+}
+
+/// Label values escape backslash, double quote and newline (exposition
+/// format spec); connector ids are config, not trusted to be tame.
+fn escape_label(v: &str) -> String {
+    v.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// Minimal HTTP/1.1 scrape endpoint: `GET /metrics` and nothing else.
+pub struct MetricsServer {
+    listener: TcpListener,
+}
+
+impl MetricsServer {
+    /// Bind `addr`, which must be a loopback address (`127.0.0.0/8` or
+    /// `::1`). Port 0 picks a free port; see [`MetricsServer::local_addr`].
+    pub async fn bind(addr: SocketAddr) -> Result<Self, MetricsError> {
+        if !addr.ip().is_loopback() {
+            return Err(MetricsError::NotLoopback(addr));
+        }
+        Ok(Self { listener: TcpListener::bind(addr).await? })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, MetricsError> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Answer scrapes until the task is dropped. Per-connection failures
+    /// are the client's problem and never end the loop.
+    pub async fn serve(self, metrics: Metrics) -> Result<(), MetricsError> {
+        loop {
+            let (stream, _peer) = self.listener.accept().await?;
+            let metrics = metrics.clone();
+            tokio::spawn(async move {
+                let _ = respond(stream, &metrics).await;
+            });
+        }
+    }
+}
+
+async fn respond(mut stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    let mut head = Vec::with_capacity(512);
+    let mut buf = [0u8; 512];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+        if head.len() > MAX_REQUEST_BYTES {
+            return write_response(&mut stream, "400 Bad Request", "text/plain", "request too large\n").await;
+        }
+    }
+
+    let request_line = String::from_utf8_lossy(&head);
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => {
+            let body = metrics.render();
+            write_response(&mut stream, "200 OK", "text/plain; version=0.0.4; charset=utf-8", &body).await
+        }
+        (Some("GET"), Some(_)) => write_response(&mut stream, "404 Not Found", "text/plain", "not found\n").await,
+        _ => write_response(&mut stream, "405 Method Not Allowed", "text/plain", "method not allowed\n").await,
+    }
+}
+
+async fn write_response(stream: &mut TcpStream, status: &str, content_type: &str, body: &str) -> std::io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(body.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_emits_labelled_counters_and_cumulative_histogram() {
+        let m = Metrics::new();
+        m.bus_published("file-import");
+        m.bus_published("file-import");
+        m.bus_acked("file-import");
+        m.observe_batch_apply("file-import", Duration::from_millis(3));
+        m.observe_batch_apply("file-import", Duration::from_secs(20));
+        m.connector_error("google-calendar", "auth_required");
+        m.batch_quarantined("file-import");
+        m.set_vault_items([("file".to_string(), 2), ("claim".to_string(), 2)]);
+        m.set_vault_database_bytes(4096);
+
+        let text = m.render();
+        assert!(text.contains("wkyt_bus_published_total{connector=\"file-import\"} 2\n"));
+        assert!(text.contains("wkyt_bus_acked_total{connector=\"file-import\"} 1\n"));
+        assert!(text.contains("wkyt_batch_apply_seconds_bucket{connector=\"file-import\",le=\"0.001\"} 0\n"));
+        assert!(text.contains("wkyt_batch_apply_seconds_bucket{connector=\"file-import\",le=\"0.005\"} 1\n"));
+        assert!(text.contains("wkyt_batch_apply_seconds_bucket{connector=\"file-import\",le=\"10\"} 1\n"));
+        assert!(text.contains("wkyt_batch_apply_seconds_bucket{connector=\"file-import\",le=\"+Inf\"} 2\n"));
+        assert!(text.contains("wkyt_batch_apply_seconds_count{connector=\"file-import\"} 2\n"));
+        assert!(text.contains("wkyt_connector_errors_total{connector=\"google-calendar\",kind=\"auth_required\"} 1\n"));
+        assert!(text.contains("wkyt_batches_quarantined_total{connector=\"file-import\"} 1\n"));
+        assert!(text.contains("wkyt_vault_items{kind=\"claim\"} 2\n"));
+        assert!(text.contains("wkyt_vault_database_bytes 4096\n"));
+        assert!(text.contains("# TYPE wkyt_batch_apply_seconds histogram\n"));
+    }
+
+    #[test]
+    fn label_values_are_escaped() {
+        let m = Metrics::new();
+        m.bus_published("we\"ird\\id\n");
+        assert!(m.render().contains(r#"{connector="we\"ird\\id\n"} 1"#));
+    }
+
+    #[tokio::test]
+    async fn refuses_non_loopback_bind() {
+        let addr: SocketAddr = "0.0.0.0:0".parse().unwrap();
+        assert!(matches!(MetricsServer::bind(addr).await, Err(MetricsError::NotLoopback(_))));
+    }
+
+    #[tokio::test]
+    async fn serves_metrics_over_loopback_http() {
+        let server = MetricsServer::bind("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let metrics = Metrics::new();
+        metrics.bus_published("file-import");
+        let task = tokio::spawn(server.serve(metrics));
+
+        let scrape = |path: &'static str| async move {
+            let mut s = TcpStream::connect(addr).await.unwrap();
+            s.write_all(format!("GET {path} HTTP/1.1\r\nHost: x\r\n\r\n").as_bytes()).await.unwrap();
+            let mut response = String::new();
+            s.read_to_string(&mut response).await.unwrap();
+            response
+        };
+
+        let ok = scrape("/metrics").await;
+        assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(ok.contains("text/plain; version=0.0.4"));
+        assert!(ok.contains("wkyt_bus_published_total{connector=\"file-import\"} 1"));
+        assert!(scrape("/").await.starts_with("HTTP/1.1 404"));
+        task.abort();
+    }
+}
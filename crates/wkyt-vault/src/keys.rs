@@ -41,6 +41,7 @@ const XNONCE_LEN: usize = 24;
 const KEYCHAIN_BLOB: &str = "dek.keychain.json";
 const RECOVERY_BLOB: &str = "dek.recovery.json";
 const KEYRING_USER: &str = "vault-kek";
+const SPOOL_KEY_INFO: &[u8] = b"wkyt-spool-key:v1";
 
 /// The data encryption key: what sqlcipher receives. Exists unwrapped only
 /// in process memory; zeroized on drop. Bytes are deliberately reachable
@@ -95,6 +96,31 @@ impl std::fmt::Debug for RecoveryKey {
     }
 }
 
+/// Key for the encrypted batch spool (D16), derived from the keychain KEK
+/// with HKDF-SHA256 under its own purpose label. Independent of the DEK,
+/// so batches can be spooled while the vault itself stays locked.
+pub struct SpoolKey(Zeroizing<[u8; KEY_LEN]>);
+
+impl SpoolKey {
+    fn derive(kek: &[u8; KEY_LEN]) -> Self {
+        let mut key = Zeroizing::new([0u8; KEY_LEN]);
+        hkdf::Hkdf::<sha2::Sha256>::new(None, kek)
+            .expand(SPOOL_KEY_INFO, &mut *key)
+            .expect("32 bytes is a valid HKDF-SHA256 output length");
+        SpoolKey(key)
+    }
+
+    pub(crate) fn bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for SpoolKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SpoolKey(<redacted>)")
+    }
+}
+
 /// No key bytes in any variant, ever: errors get logged and shown in UI.
 #[derive(Debug, thiserror::Error)]
 pub enum KeyError {
@@ -572,6 +598,15 @@ impl<S: KekStore> KeyService<S> {
         unwrap(&blob, &kek, "keychain")
     }
 
+    /// Derive the spool key (D16) from the keychain KEK. Needs only the
+    /// KEK, not the DEK: this is what lets connectors spool while the
+    /// vault is locked. Changes whenever the KEK does (`recover`), which
+    /// orphans anything still spooled under the old one.
+    pub fn spool_key(&self) -> Result<SpoolKey, KeyError> {
+        let kek = self.store.get()?.ok_or(KeyError::KekMissing)?;
+        Ok(SpoolKey::derive(&kek))
+    }
+
     /// D8 ceremony verification: proves the user actually saved the key by
     /// requiring them to re-enter it. Success == the input authenticates
     /// the recovery blob; nothing is mutated.
@@ -849,8 +884,24 @@ mod tests {
     fn debug_output_is_redacted() {
         let dek = Dek::generate();
         let rk = RecoveryKey::generate();
+        let sk = SpoolKey::derive(&[7u8; KEY_LEN]);
         assert_eq!(format!("{dek:?}"), "Dek(<redacted>)");
         assert_eq!(format!("{rk:?}"), "RecoveryKey(<redacted>)");
+        assert_eq!(format!("{sk:?}"), "SpoolKey(<redacted>)");
+    }
+
+    #[test]
+    fn spool_key_is_stable_per_kek_and_distinct_from_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let svc = svc(dir.path());
+        assert!(matches!(svc.spool_key(), Err(KeyError::KekMissing)));
+        svc.provision().unwrap();
+
+        let a = svc.spool_key().unwrap();
+        let b = svc.spool_key().unwrap();
+        assert_eq!(a.bytes(), b.bytes(), "same KEK must derive the same spool key");
+        let kek = svc.store.get().unwrap().unwrap();
+        assert_ne!(a.bytes(), &*kek, "the spool must never hold the KEK itself");
     }
 
     #[cfg(unix)]
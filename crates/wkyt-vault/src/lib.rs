@@ -12,6 +12,15 @@
 //!   `PRAGMA key`, fails closed on wrong key or plaintext files, and
 //!   applies the D9 hardening (0600 permissions, in-memory temp store,
 //!   sqlcipher memory security).
+//! - Dead-letter quarantine ([`Vault::quarantine_batch`] and friends) for
+//!   batches the host cannot apply, so one poison batch cannot pin its
+//!   connector's cursor forever.
+//! - Content chunks ([`Vault::chunk`], [`Vault::write_content`]): large
+//!   and binary content delivered as `Delta::Chunk`, stored once per
+//!   SHA-256 inside the encrypted database.
+//! - [`spool::Spool`] — encrypted holding area for batches produced while
+//!   the vault is locked (D16), sealed under a key derived from the
+//!   keychain KEK and drained through the normal commit path on unlock.
 //!
 //! Memory-handling rules (D12): key material lives only in
 //! `Zeroizing` buffers, is never formatted into errors or `Debug` output,
@@ -21,7 +30,12 @@
 
 mod hexfmt;
 pub mod keys;
+pub mod spool;
 pub mod vault;
 
-pub use keys::{Dek, KeyError, KeyService, KeyState, KeyringStore, KekStore, MemoryKekStore, RecoveryKey, DynamicKekStore, PassphraseKekStore};
-pub use vault::{rotate_dek, unlock_vault, Vault, VaultError};
+pub use keys::{Dek, KeyError, KeyService, KeyState, KeyringStore, KekStore, MemoryKekStore, RecoveryKey, DynamicKekStore, PassphraseKekStore, SpoolKey};
+pub use spool::{Spool, SpoolError};
+pub use vault::{
+    batch_fingerprint, rotate_dek, unlock_vault, DeadLetter, DerivedBatch, FailureOutcome, LegacyClaims, PreviewChange,
+    Vault, VaultError,
+};
@@ -0,0 +1,332 @@
+//! Encrypted on-disk spool for batches produced while the vault is locked
+//! (D16).
+//!
+//! The bus is a dumb pipe (D11) and durability normally lives in
+//! [`Vault::apply_batch`](crate::Vault::apply_batch), so without a spool a
+//! connector can only run while the vault is open. The spool is the
+//! exception: a directory of sealed batch files, one per batch, that the
+//! host drains through the ordinary ack-after-commit path once the vault
+//! unlocks.
+//!
+//! File format (`<seq>.spool`, 20-digit zero-padded sequence number):
+//!
+//! ```text
+//! [version: u8][nonce: 24 bytes][XChaCha20-Poly1305(DeltaBatch protobuf)]
+//! ```
+//!
+//! The AEAD key is the [`SpoolKey`] (HKDF of the keychain KEK), never the
+//! DEK, and the associated data binds each file to its format version and
+//! sequence number: renaming a file to reorder the queue fails
+//! authentication instead of silently replaying batches out of order.
+//!
+//! Ordering: sequence numbers are global and monotonic, and draining is
+//! strictly ascending, so per-connector cursor order is preserved. Removal
+//! after commit goes through [`Spool::remove_through`], which deletes
+//! oldest-first and stops at the first failure — leftovers are therefore
+//! always a contiguous suffix of what was committed, and replaying them is
+//! idempotent (D13) rather than a cursor regression.
+//!
+//! The spool is a cache, not a source of truth: if it is lost (or the KEK
+//! changes under it), the connector re-syncs from the vault's committed
+//! cursor.
+
+use crate::keys::SpoolKey;
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use wkyt_core::{CodecError, DeltaBatch, SyncToken};
+
+const SPOOL_VERSION: u8 = 1;
+const SPOOL_EXT: &str = "spool";
+const XNONCE_LEN: usize = 24;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SpoolError {
+    #[error("spool io error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Wrong key (the KEK changed since spooling), tampering, or a file
+    /// renamed out of sequence. Deliberately one variant, like the key
+    /// blobs: no oracle distinguishing the cases.
+    #[error("spooled batch {0} failed authentication")]
+    Integrity(u64),
+    #[error("spooled batch {0} has unsupported format version {1}")]
+    UnsupportedVersion(u64, u8),
+    #[error("spooled batch {seq} does not decode: {source}")]
+    Codec {
+        seq: u64,
+        #[source]
+        source: CodecError,
+    },
+}
+
+pub struct Spool {
+    dir: PathBuf,
+    key: SpoolKey,
+    /// Next sequence number to assign; guards appends against each other.
+    next_seq: Mutex<u64>,
+}
+
+impl Spool {
+    /// Open (creating if absent) the spool directory. Sequence numbering
+    /// resumes after the highest file already present.
+    pub fn open(dir: &Path, key: SpoolKey) -> Result<Self, SpoolError> {
+        fs::create_dir_all(dir)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700))?;
+        }
+        let spool = Self { dir: dir.to_path_buf(), key, next_seq: Mutex::new(0) };
+        let next = spool.pending()?.last().map_or(1, |last| last + 1);
+        *spool.next_seq.lock().unwrap() = next;
+        Ok(spool)
+    }
+
+    /// Seal and persist one batch; returns its sequence number. Durable
+    /// (fsync + atomic rename) before returning.
+    pub fn append(&self, batch: &DeltaBatch) -> Result<u64, SpoolError> {
+        let mut next = self.next_seq.lock().unwrap();
+        let seq = *next;
+        let cipher = XChaCha20Poly1305::new(self.key.bytes().into());
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ct = cipher
+            .encrypt(&nonce, Payload { msg: &batch.encode_to_vec(), aad: &aad_for(seq) })
+            .expect("XChaCha20-Poly1305 encryption is infallible for in-memory input");
+
+        let mut sealed = Vec::with_capacity(1 + XNONCE_LEN + ct.len());
+        sealed.push(SPOOL_VERSION);
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ct);
+        write_atomic(&self.path_for(seq), &sealed)?;
+        *next = seq + 1;
+        Ok(seq)
+    }
+
+    /// Sequence numbers of every spooled batch, oldest first.
+    pub fn pending(&self) -> Result<Vec<u64>, SpoolError> {
+        let mut seqs = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SPOOL_EXT) {
+                continue; // temp files, strays
+            }
+            if let Some(seq) = path.file_stem().and_then(|s| s.to_str()?.parse().ok()) {
+                seqs.push(seq);
+            }
+        }
+        seqs.sort_unstable();
+        Ok(seqs)
+    }
+
+    pub fn is_empty(&self) -> Result<bool, SpoolError> {
+        Ok(self.pending()?.is_empty())
+    }
+
+    /// Open and decode one spooled batch.
+    pub fn read(&self, seq: u64) -> Result<DeltaBatch, SpoolError> {
+        let sealed = fs::read(self.path_for(seq))?;
+        if sealed.len() < 1 + XNONCE_LEN {
+            return Err(SpoolError::Integrity(seq));
+        }
+        if sealed[0] != SPOOL_VERSION {
+            return Err(SpoolError::UnsupportedVersion(seq, sealed[0]));
+        }
+        let (nonce, ct) = sealed[1..].split_at(XNONCE_LEN);
+        let cipher = XChaCha20Poly1305::new(self.key.bytes().into());
+        let plain = cipher
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ct, aad: &aad_for(seq) })
+            .map_err(|_| SpoolError::Integrity(seq))?;
+        DeltaBatch::decode(&plain).map_err(|source| SpoolError::Codec { seq, source })
+    }
+
+    /// Delete every spooled batch up to and including `seq`, oldest first,
+    /// stopping at the first failure (see module docs for why order
+    /// matters). Called once `seq`'s batch has committed.
+    pub fn remove_through(&self, seq: u64) -> Result<(), SpoolError> {
+        for pending in self.pending()?.into_iter().take_while(|p| *p <= seq) {
+            match fs::remove_file(self.path_for(pending)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether any spooled batch belongs to `connector_id`: if so, that
+    /// connector's live passes wait until the spool is drained.
+    pub fn holds(&self, connector_id: &str) -> Result<bool, SpoolError> {
+        for seq in self.pending()? {
+            if self.read(seq)?.connector_id == connector_id {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// The newest cursor spooled for `connector_id`: where that connector
+    /// resumes while the vault (and its committed cursor) is unreadable.
+    pub fn last_cursor(&self, connector_id: &str) -> Result<Option<SyncToken>, SpoolError> {
+        for seq in self.pending()?.into_iter().rev() {
+            let batch = self.read(seq)?;
+            if batch.connector_id == connector_id && batch.cursor.is_some() {
+                return Ok(batch.cursor);
+            }
+        }
+        Ok(None)
+    }
+
+    fn path_for(&self, seq: u64) -> PathBuf {
+        self.dir.join(format!("{seq:020}.{SPOOL_EXT}"))
+    }
+}
+
+fn aad_for(seq: u64) -> Vec<u8> {
+    format!("wkyt-spool:v{SPOOL_VERSION}:{seq}").into_bytes()
+}
+
+/// Write-to-temp + rename with 0600 from creation (D9), as for key blobs.
+fn write_atomic(path: &Path, bytes: &[u8]) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(0o600);
+        }
+        let mut f = opts.open(&tmp)?;
+        use std::io::Write;
+        f.write_all(bytes)?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{KeyService, MemoryKekStore};
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+    use wkyt_core::{Delta, Item, ItemKind};
+
+    fn key(dir: &Path) -> SpoolKey {
+        let svc = KeyService::new(MemoryKekStore::default(), dir);
+        svc.provision().unwrap();
+        svc.spool_key().unwrap()
+    }
+
+    fn batch(connector_id: &str, source_id: &str, cursor: Option<&str>) -> DeltaBatch {
+        let ts = DateTime::parse_from_rfc3339("2024-07-04T12:00:00Z").unwrap().with_timezone(&Utc);
+        let mut item = Item::new(source_id, connector_id, ItemKind::File, ts, json!({"secret": "diary"}));
+        item.ingested_at = ts;
+        DeltaBatch {
+            connector_id: connector_id.into(),
+            deltas: vec![Delta::Upsert(item)],
+            cursor: cursor.map(|c| SyncToken(c.into())),
+        }
+    }
+
+    #[test]
+    fn append_read_round_trip_in_sequence_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(&dir.path().join("spool"), key(dir.path())).unwrap();
+        let a = batch("file-import", "a.json", Some("c1"));
+        let b = batch("file-import", "b.json", Some("c2"));
+
+        assert_eq!(spool.append(&a).unwrap(), 1);
+        assert_eq!(spool.append(&b).unwrap(), 2);
+        assert_eq!(spool.pending().unwrap(), vec![1, 2]);
+        assert_eq!(spool.read(1).unwrap(), a);
+        assert_eq!(spool.read(2).unwrap(), b);
+    }
+
+    #[test]
+    fn spooled_files_are_not_plaintext() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(&dir.path().join("spool"), key(dir.path())).unwrap();
+        let seq = spool.append(&batch("file-import", "diary.json", None)).unwrap();
+
+        let bytes = fs::read(spool.path_for(seq)).unwrap();
+        for needle in [&b"diary"[..], b"file-import", b"secret"] {
+            assert!(
+                !bytes.windows(needle.len()).any(|w| w == needle),
+                "spool file leaks {:?}",
+                String::from_utf8_lossy(needle)
+            );
+        }
+    }
+
+    #[test]
+    fn reordering_by_rename_and_wrong_key_fail_closed() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool_dir = dir.path().join("spool");
+        let spool = Spool::open(&spool_dir, key(dir.path())).unwrap();
+        spool.append(&batch("c", "a", None)).unwrap();
+        spool.append(&batch("c", "b", None)).unwrap();
+
+        // Swap the two files: AAD binds the sequence number.
+        let (one, two, tmp) = (spool.path_for(1), spool.path_for(2), spool_dir.join("x"));
+        fs::rename(&one, &tmp).unwrap();
+        fs::rename(&two, &one).unwrap();
+        fs::rename(&tmp, &two).unwrap();
+        assert!(matches!(spool.read(1), Err(SpoolError::Integrity(1))));
+
+        // A different KEK (keychain recovered) cannot read the old spool.
+        let other = tempfile::tempdir().unwrap();
+        let reopened = Spool::open(&spool_dir, key(other.path())).unwrap();
+        assert!(matches!(reopened.read(2), Err(SpoolError::Integrity(2))));
+    }
+
+    #[test]
+    fn reopen_continues_numbering_and_remove_through_is_prefix_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool_dir = dir.path().join("spool");
+        let svc = KeyService::new(MemoryKekStore::default(), dir.path());
+        svc.provision().unwrap();
+
+        let spool = Spool::open(&spool_dir, svc.spool_key().unwrap()).unwrap();
+        for n in 0..3 {
+            spool.append(&batch("c", &format!("s{n}"), None)).unwrap();
+        }
+        drop(spool);
+
+        let spool = Spool::open(&spool_dir, svc.spool_key().unwrap()).unwrap();
+        assert_eq!(spool.append(&batch("c", "s3", None)).unwrap(), 4);
+        spool.remove_through(2).unwrap();
+        assert_eq!(spool.pending().unwrap(), vec![3, 4]);
+        spool.remove_through(4).unwrap();
+        assert!(spool.is_empty().unwrap());
+    }
+
+    #[test]
+    fn last_cursor_is_newest_per_connector() {
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(&dir.path().join("spool"), key(dir.path())).unwrap();
+        spool.append(&batch("file-import", "a", Some("f1"))).unwrap();
+        spool.append(&batch("google-calendar", "e", Some("g1"))).unwrap();
+        spool.append(&batch("file-import", "b", Some("f2"))).unwrap();
+        spool.append(&batch("file-import", "c", None)).unwrap();
+
+        assert_eq!(spool.last_cursor("file-import").unwrap(), Some(SyncToken("f2".into())));
+        assert_eq!(spool.last_cursor("google-calendar").unwrap(), Some(SyncToken("g1".into())));
+        assert_eq!(spool.last_cursor("imap").unwrap(), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn spool_files_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let spool = Spool::open(&dir.path().join("spool"), key(dir.path())).unwrap();
+        let seq = spool.append(&batch("c", "a", None)).unwrap();
+        let mode = fs::metadata(spool.path_for(seq)).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+}
@@ -33,11 +33,15 @@
 use crate::hexfmt;
 use crate::keys::{Dek, KekStore, KeyError, KeyService};
 use chrono::{DateTime, Utc};
-use rusqlite::{Connection, OptionalExtension};
+use rusqlite::{Connection, OptionalExtension, Transaction};
+use std::collections::BTreeMap;
 use std::path::Path;
 use wkyt_core::{Delta, DeltaBatch, Item, ItemKind, SyncToken};
 use zeroize::Zeroizing;
 
+mod dead_letter;
+pub use dead_letter::{batch_fingerprint, DeadLetter, FailureOutcome};
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ItemRevision {
     pub revision_id: i64,
@@ -46,6 +50,33 @@ pub struct ItemRevision {
     pub replaced_at: DateTime<Utc>,
 }
 
+/// A batch a processor derived from the batch being applied (see
+/// [`Vault::apply_batch_with_derived`]). Non-empty `rejections` mean it
+/// failed validation: it is quarantined with those reasons, not applied.
+#[derive(Debug, Clone)]
+pub struct DerivedBatch {
+    pub batch: DeltaBatch,
+    pub rejections: Vec<String>,
+}
+
+/// One row a [`Vault::preview`] touched. `None` means no live row: not
+/// there, or tombstoned.
+#[derive(Debug, Clone)]
+pub struct PreviewChange {
+    pub before: Option<Item>,
+    pub after: Option<Item>,
+}
+
+/// The claims and `has_evidence` links connectors wrote themselves before
+/// processors derived them (D18), found by [`Vault::legacy_claims`].
+#[derive(Debug, Clone, Default)]
+pub struct LegacyClaims {
+    /// The live legacy claims and links, under their connectors' ids.
+    pub items: Vec<Item>,
+    /// The live records those links cite as evidence.
+    pub evidence: Vec<Item>,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum VaultError {
     /// Wrong DEK, tampered file, or a plaintext database where the vault
@@ -62,6 +93,11 @@ pub enum VaultError {
     /// from a future schema). Surfaced, never silently skipped.
     #[error("corrupt row for item {id}: {reason}")]
     CorruptRow { id: String, reason: String },
+    #[error("no dead letter with id {0}")]
+    DeadLetterNotFound(i64),
+    /// An item lists a content chunk the vault does not hold.
+    #[error("item {id} references missing chunk {sha256}")]
+    MissingChunk { id: String, sha256: String },
 }
 
 const SCHEMA: &str = "
@@ -90,6 +126,14 @@ const SCHEMA: &str = "
     CREATE INDEX IF NOT EXISTS idx_items_timestamp ON items (timestamp_ms);
     CREATE INDEX IF NOT EXISTS idx_items_connector ON items (connector_id);
 
+    -- Live relationships by endpoint: the D19 cascade looks links up by
+    -- source and target once per deleted item and per level, and would
+    -- otherwise scan every row's JSON each time.
+    CREATE INDEX IF NOT EXISTS idx_relationships_source ON items (json_extract(properties, '$.source'))
+        WHERE kind = '\"relationship\"' AND deleted_at_ms IS NULL;
+    CREATE INDEX IF NOT EXISTS idx_relationships_target ON items (json_extract(properties, '$.target'))
+        WHERE kind = '\"relationship\"' AND deleted_at_ms IS NULL;
+
     -- D15/M2: Item revision history. We store historical states of items.
     -- Handled via a trigger on update.
     CREATE TABLE IF NOT EXISTS item_revisions (
@@ -128,6 +172,30 @@ const SCHEMA: &str = "
         updated_at_ms INTEGER NOT NULL
     );
 
+    -- Content chunks (Delta::Chunk), keyed by the SHA-256 of their data
+    -- and shared by every item listing that hash under `chunks`. Never
+    -- rewritten: the same hash is the same bytes.
+    CREATE TABLE IF NOT EXISTS chunks (
+        sha256 TEXT PRIMARY KEY,
+        data   BLOB NOT NULL
+    );
+
+    -- Batches the host could not apply (see vault/dead_letter.rs). The
+    -- payload is the batch's wkyt.delta.v1 encoding; it lives inside the
+    -- encrypted database like everything else. quarantined_at_ms NULL
+    -- means failures are still being counted.
+    CREATE TABLE IF NOT EXISTS dead_letters (
+        id                INTEGER PRIMARY KEY AUTOINCREMENT,
+        connector_id      TEXT NOT NULL,
+        fingerprint       TEXT NOT NULL UNIQUE,
+        payload           BLOB NOT NULL,
+        reasons           TEXT NOT NULL,
+        attempts          INTEGER NOT NULL,
+        first_failed_at_ms INTEGER NOT NULL,
+        last_failed_at_ms INTEGER NOT NULL,
+        quarantined_at_ms INTEGER
+    );
+
     INSERT OR IGNORE INTO vault_meta (key, value) VALUES ('schema_version', '1');
 ";
 
@@ -178,69 +246,100 @@ impl Vault {
     /// together, or none of it does.
     pub fn apply_batch(&mut self, batch: &DeltaBatch) -> Result<(), VaultError> {
         let tx = self.conn.transaction()?;
-        for delta in &batch.deltas {
-            match delta {
-                Delta::Upsert(item) => {
-                    // Conflict target is the id (deterministic, D13);
-                    // connector_id/source_id are immutable under a given id
-                    // by construction. A *different* id colliding on
-                    // (connector_id, source_id) violates the UNIQUE
-                    // constraint and fails the whole batch loudly — that
-                    // means id derivation broke, and silently absorbing it
-                    // would corrupt identity.
-                    tx.execute(
-                        "INSERT INTO items (id, connector_id, source_id, kind,
-                                            timestamp_ms, ingested_at_ms,
-                                            properties, raw_payload, deleted_at_ms, valid_to_ms)
-                         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, NULL, ?9)
-                         ON CONFLICT (id) DO UPDATE SET
-                             kind           = excluded.kind,
-                             timestamp_ms   = excluded.timestamp_ms,
-                             ingested_at_ms = excluded.ingested_at_ms,
-                             properties     = excluded.properties,
-                             raw_payload    = excluded.raw_payload,
-                             deleted_at_ms  = NULL,
-                             valid_to_ms    = excluded.valid_to_ms",
-                        (
-                            &item.id,
-                            &item.connector_id,
-                            &item.source_id,
-                            serde_json::to_string(&item.kind)
-                                .expect("ItemKind serialization is infallible"),
-                            item.timestamp.timestamp_millis(),
-                            item.ingested_at.timestamp_millis(),
-                            item.properties.to_string(),
-                            item.raw_payload.as_ref().map(|v| v.to_string()),
-                            item.valid_to.as_ref().map(|v| v.timestamp_millis()),
-                        ),
-                    )?;
-                }
-                Delta::Tombstone { source_id } => {
-                    // Soft delete; unknown source_id is a no-op (tombstone
-                    // for something we never ingested — at-least-once
-                    // delivery makes that normal).
-                    tx.execute(
-                        "UPDATE items SET deleted_at_ms = ?1
-                         WHERE connector_id = ?2 AND source_id = ?3",
-                        (now_ms(), &batch.connector_id, source_id),
-                    )?;
-                }
-            }
-        }
+        apply_deltas(&tx, &batch.connector_id, &batch.deltas)?;
         if let Some(cursor) = &batch.cursor {
-            tx.execute(
-                "INSERT INTO cursors (connector_id, cursor, updated_at_ms)
-                 VALUES (?1, ?2, ?3)
-                 ON CONFLICT (connector_id) DO UPDATE SET
-                     cursor = excluded.cursor,
-                     updated_at_ms = excluded.updated_at_ms",
-                (&batch.connector_id, &cursor.0, now_ms()),
-            )?;
+            write_cursor(&tx, &batch.connector_id, cursor)?;
         }
         tx.commit()?;
         Ok(())
     }
 
+    /// [`Vault::apply_batch`], plus what processors derived from `batch`, in
+    /// the same transaction: a crash can lose neither half without the
+    /// other. Each derived batch applies under its own savepoint; one that
+    /// carries rejections or fails to apply is quarantined instead, in the
+    /// same transaction, without undoing `batch`. Returns the indices of
+    /// the derived batches quarantined.
+    pub fn apply_batch_with_derived(
+        &mut self,
+        batch: &DeltaBatch,
+        derived: &[DerivedBatch],
+    ) -> Result<Vec<usize>, VaultError> {
+        let mut tx = self.conn.transaction()?;
+        let quarantined = apply_with_derived(&mut tx, batch, derived)?;
+        tx.commit()?;
+        Ok(quarantined)
+    }
+
+    /// What applying `batches`, each with what was derived from it, would
+    /// do — derived rows and tombstone cascades included — without doing
+    /// it: everything runs in one transaction that is rolled back. Returns
+    /// every row touched, before and after, by connector and source id. A
+    /// batch that would fail to apply fails the preview.
+    pub fn preview(
+        &mut self,
+        batches: &[(DeltaBatch, Vec<DerivedBatch>)],
+    ) -> Result<Vec<PreviewChange>, VaultError> {
+        let mut tx = self.conn.transaction()?;
+        // Temp objects are transactional too: the rollback drops them.
+        tx.execute_batch(
+            "CREATE TEMP TABLE preview_before (
+                 id TEXT PRIMARY KEY, existed INTEGER NOT NULL,
+                 connector_id TEXT, source_id TEXT, kind TEXT, timestamp_ms INTEGER,
+                 ingested_at_ms INTEGER, properties TEXT, raw_payload TEXT,
+                 deleted_at_ms INTEGER, valid_to_ms INTEGER
+             );
+             CREATE TEMP TRIGGER preview_insert AFTER INSERT ON main.items BEGIN
+                 INSERT OR IGNORE INTO preview_before (id, existed) VALUES (new.id, 0);
+             END;
+             CREATE TEMP TRIGGER preview_update AFTER UPDATE ON main.items BEGIN
+                 INSERT OR IGNORE INTO preview_before VALUES (
+                     old.id, 1, old.connector_id, old.source_id, old.kind, old.timestamp_ms,
+                     old.ingested_at_ms, old.properties, old.raw_payload, old.deleted_at_ms, old.valid_to_ms
+                 );
+             END;",
+        )?;
+        for (batch, derived) in batches {
+            apply_with_derived(&mut tx, batch, derived)?;
+        }
+        let select = |sql: &str| -> Result<BTreeMap<String, Item>, VaultError> {
+            let mut stmt = tx.prepare(sql)?;
+            let rows = stmt.query_map([], row_to_item)?;
+            let mut items = BTreeMap::new();
+            for row in rows {
+                let item = row??;
+                items.insert(item.id.clone(), item);
+            }
+            Ok(items)
+        };
+        let mut before = select(
+            "SELECT id, connector_id, source_id, kind, timestamp_ms,
+                    ingested_at_ms, properties, raw_payload, valid_to_ms
+             FROM temp.preview_before
+             WHERE existed AND deleted_at_ms IS NULL",
+        )?;
+        let mut after = select(
+            "SELECT id, connector_id, source_id, kind, timestamp_ms,
+                    ingested_at_ms, properties, raw_payload, valid_to_ms
+             FROM main.items
+             WHERE id IN (SELECT id FROM temp.preview_before) AND deleted_at_ms IS NULL",
+        )?;
+        let touched = {
+            let mut stmt = tx.prepare(
+                "SELECT i.id FROM temp.preview_before b JOIN main.items i ON i.id = b.id
+                 ORDER BY i.connector_id, i.source_id",
+            )?;
+            let ids = stmt.query_map([], |r| r.get::<_, String>(0))?;
+            ids.collect::<Result<Vec<_>, _>>()?
+        };
+        tx.rollback()?;
+        Ok(touched
+            .into_iter()
+            .map(|id| PreviewChange { before: before.remove(&id), after: after.remove(&id) })
+            .filter(|change| change.before.is_some() || change.after.is_some())
+            .collect())
+    }
+
     /// The committed resume position for a connector, if any.
     pub fn cursor(&self, connector_id: &str) -> Result<Option<SyncToken>, VaultError> {
         Ok(self
@@ -271,6 +370,47 @@ impl Vault {
         Ok(items)
     }
 
+    /// The live row for one source record, if any (tombstoned rows are not
+    /// live).
+    pub fn live_item(&self, connector_id: &str, source_id: &str) -> Result<Option<Item>, VaultError> {
+        self.conn
+            .query_row(
+                "SELECT id, connector_id, source_id, kind, timestamp_ms,
+                        ingested_at_ms, properties, raw_payload, valid_to_ms
+                 FROM items
+                 WHERE connector_id = ?1 AND source_id = ?2 AND deleted_at_ms IS NULL",
+                (connector_id, source_id),
+                row_to_item,
+            )
+            .optional()?
+            .transpose()
+    }
+
+    /// One content chunk by hash.
+    pub fn chunk(&self, sha256: &str) -> Result<Option<Vec<u8>>, VaultError> {
+        Ok(self
+            .conn
+            .query_row("SELECT data FROM chunks WHERE sha256 = ?1", (sha256,), |r| r.get(0))
+            .optional()?)
+    }
+
+    /// Write `item`'s chunked content (its `chunks` property, in order) to
+    /// `out`, one chunk in memory at a time. Returns the bytes written;
+    /// an item without chunks writes nothing.
+    pub fn write_content(&self, item: &Item, out: &mut impl std::io::Write) -> Result<u64, VaultError> {
+        let mut written = 0;
+        let hashes = item.properties["chunks"].as_array().map(Vec::as_slice).unwrap_or_default();
+        for sha256 in hashes.iter().filter_map(|h| h.as_str()) {
+            let data = self.chunk(sha256)?.ok_or_else(|| VaultError::MissingChunk {
+                id: item.id.clone(),
+                sha256: sha256.to_string(),
+            })?;
+            out.write_all(&data)?;
+            written += data.len() as u64;
+        }
+        Ok(written)
+    }
+
     /// Live items across all connectors, newest event first — the viewer's
     /// query (Spec DoD #7).
     pub fn recent_items(&self, limit: u32) -> Result<Vec<Item>, VaultError> {
@@ -411,6 +551,33 @@ impl Vault {
         )?)
     }
 
+    /// Live items grouped by kind, keyed by the kind's snake_case name.
+    /// Custom kinds are folded into `other` so the key set stays bounded
+    /// (this feeds telemetry labels).
+    pub fn item_counts_by_kind(&self) -> Result<BTreeMap<String, u64>, VaultError> {
+        let mut stmt = self.conn.prepare(
+            "SELECT kind, count(*) FROM items WHERE deleted_at_ms IS NULL GROUP BY kind",
+        )?;
+        let rows = stmt.query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?)))?;
+        let mut counts = BTreeMap::new();
+        for row in rows {
+            let (kind, n) = row?;
+            let name = match serde_json::from_str::<serde_json::Value>(&kind) {
+                Ok(serde_json::Value::String(name)) => name,
+                _ => "other".to_string(),
+            };
+            *counts.entry(name).or_insert(0) += n as u64;
+        }
+        Ok(counts)
+    }
+
+    /// Size of the (encrypted) database in bytes: page count × page size.
+    pub fn database_size_bytes(&self) -> Result<u64, VaultError> {
+        let pages: i64 = self.conn.query_row("PRAGMA page_count", [], |r| r.get(0))?;
+        let page_size: i64 = self.conn.query_row("PRAGMA page_size", [], |r| r.get(0))?;
+        Ok((pages * page_size) as u64)
+    }
+
     /// Retrieve human context items (Phase 5).
     pub fn human_context_items(&self) -> Result<Vec<Item>, VaultError> {
         let mut stmt = self.conn.prepare(
@@ -428,6 +595,42 @@ impl Vault {
         Ok(items)
     }
 
+    /// Live claims connectors emitted before D18 — `{record}-claim` with a
+    /// free-form `source` — and their `{record}-rel` links, which carry
+    /// `type` rather than `relation`, with the live records the links cite.
+    pub fn legacy_claims(&self) -> Result<LegacyClaims, VaultError> {
+        const LEGACY_LINK: &str = "kind = '\"relationship\"' AND deleted_at_ms IS NULL AND source_id LIKE '%-rel'
+            AND json_extract(properties, '$.type') = 'has_evidence'
+            AND json_extract(properties, '$.relation') IS NULL";
+        let select = |sql: &str| -> Result<Vec<Item>, VaultError> {
+            let mut stmt = self.conn.prepare(sql)?;
+            let rows = stmt.query_map([], row_to_item)?;
+            let mut items = Vec::new();
+            for row in rows {
+                items.push(row??);
+            }
+            Ok(items)
+        };
+        let items = select(&format!(
+            "SELECT id, connector_id, source_id, kind, timestamp_ms,
+                    ingested_at_ms, properties, raw_payload, valid_to_ms
+             FROM items
+             WHERE ({LEGACY_LINK})
+                OR (kind = '\"claim\"' AND deleted_at_ms IS NULL AND source_id LIKE '%-claim'
+                    AND json_extract(properties, '$.source') IN ('file_importer', 'google_calendar'))
+             ORDER BY connector_id, source_id"
+        ))?;
+        let evidence = select(&format!(
+            "SELECT id, connector_id, source_id, kind, timestamp_ms,
+                    ingested_at_ms, properties, raw_payload, valid_to_ms
+             FROM items
+             WHERE deleted_at_ms IS NULL
+               AND id IN (SELECT json_extract(properties, '$.target') FROM items WHERE {LEGACY_LINK})
+             ORDER BY connector_id, source_id"
+        ))?;
+        Ok(LegacyClaims { items, evidence })
+    }
+
     /// Small KV surface for vault bookkeeping (schema version, ceremony
     /// acknowledgement flag, …).
     pub fn put_meta(&self, key: &str, value: &str) -> Result<(), VaultError> {
@@ -495,6 +698,175 @@ pub fn unlock_vault<S: KekStore>(
     }
 }
 
+/// [`Vault::apply_batch_with_derived`] inside the caller's transaction.
+fn apply_with_derived(
+    tx: &mut Transaction<'_>,
+    batch: &DeltaBatch,
+    derived: &[DerivedBatch],
+) -> Result<Vec<usize>, VaultError> {
+    apply_deltas(tx, &batch.connector_id, &batch.deltas)?;
+    let mut quarantined = Vec::new();
+    for (index, DerivedBatch { batch: derived, rejections }) in derived.iter().enumerate() {
+        let reasons = match rejections.is_empty() {
+            false => rejections.clone(),
+            true => {
+                let savepoint = tx.savepoint()?;
+                match apply_deltas(&savepoint, &derived.connector_id, &derived.deltas) {
+                    Ok(()) => {
+                        savepoint.commit()?;
+                        continue;
+                    }
+                    // Dropping the savepoint rolls back just this batch.
+                    Err(e) => vec![e.to_string()],
+                }
+            }
+        };
+        dead_letter::upsert_failure(tx, derived, &reasons, true)?;
+        quarantined.push(index);
+    }
+    if let Some(cursor) = &batch.cursor {
+        write_cursor(tx, &batch.connector_id, cursor)?;
+    }
+    Ok(quarantined)
+}
+
+/// The delta half of [`Vault::apply_batch`], inside the caller's
+/// transaction (also used to replay dead letters without their cursor).
+fn apply_deltas(tx: &Connection, connector_id: &str, deltas: &[Delta]) -> Result<(), VaultError> {
+    for delta in deltas {
+        match delta {
+            Delta::Upsert(item) => {
+                // Conflict target is the id (deterministic, D13);
+                // connector_id/source_id are immutable under a given id
+                // by construction. A *different* id colliding on
+                // (connector_id, source_id) violates the UNIQUE
+                // constraint and fails the whole batch loudly — that
+                // means id derivation broke, and silently absorbing it
+                // would corrupt identity.
+                tx.execute(
+                    "INSERT INTO items (id, connector_id, source_id, kind,
+                                        timestamp_ms, ingested_at_ms,
+                                        properties, raw_payload, deleted_at_ms, valid_to_ms)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, NULL, ?9)
+                     ON CONFLICT (id) DO UPDATE SET
+                         kind           = excluded.kind,
+                         timestamp_ms   = excluded.timestamp_ms,
+                         ingested_at_ms = excluded.ingested_at_ms,
+                         properties     = excluded.properties,
+                         raw_payload    = excluded.raw_payload,
+                         deleted_at_ms  = NULL,
+                         valid_to_ms    = excluded.valid_to_ms",
+                    (
+                        &item.id,
+                        &item.connector_id,
+                        &item.source_id,
+                        serde_json::to_string(&item.kind)
+                            .expect("ItemKind serialization is infallible"),
+                        item.timestamp.timestamp_millis(),
+                        item.ingested_at.timestamp_millis(),
+                        item.properties.to_string(),
+                        item.raw_payload.as_ref().map(|v| v.to_string()),
+                        item.valid_to.as_ref().map(|v| v.timestamp_millis()),
+                    ),
+                )?;
+            }
+            Delta::Tombstone { source_id } => {
+                // Soft delete; unknown source_id is a no-op (tombstone
+                // for something we never ingested — at-least-once
+                // delivery makes that normal), and so is a repeat: the
+                // first deletion time stands.
+                let deleted_at = now_ms();
+                let deleted = tx
+                    .query_row(
+                        "UPDATE items SET deleted_at_ms = ?1
+                         WHERE connector_id = ?2 AND source_id = ?3 AND deleted_at_ms IS NULL
+                         RETURNING id",
+                        (deleted_at, connector_id, source_id),
+                        |r| r.get::<_, String>(0),
+                    )
+                    .optional()?;
+                if let Some(id) = deleted {
+                    cascade_tombstone(tx, id, deleted_at)?;
+                }
+            }
+            Delta::Chunk { sha256, data } => {
+                // Content-addressed: a hash already stored is already
+                // these bytes.
+                tx.execute("INSERT OR IGNORE INTO chunks (sha256, data) VALUES (?1, ?2)", (sha256, data))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Provenance-aware deletion (D19), inside the tombstone's transaction.
+/// The `has_evidence` links into and out of a deleted item go with it;
+/// a claim left with no live evidence is deleted in turn, and so on
+/// through claims that cite claims. A claim that still has other live
+/// evidence stays. Links written before D18 carry `type` rather than
+/// `relation` and are matched too.
+fn cascade_tombstone(tx: &Connection, root: String, deleted_at: i64) -> Result<(), VaultError> {
+    let mut unlink = tx.prepare(&unlink_sql())?;
+    let mut remaining = tx.prepare(&remaining_evidence_sql())?;
+    let mut retract = tx.prepare(
+        "UPDATE items SET deleted_at_ms = ?1
+         WHERE id = ?2 AND kind = '\"claim\"' AND deleted_at_ms IS NULL
+         RETURNING id",
+    )?;
+
+    let mut work = vec![root];
+    while let Some(id) = work.pop() {
+        let claims = unlink
+            .query_map((deleted_at, &id), |r| r.get::<_, Option<String>>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for claim in claims.into_iter().flatten().filter(|c| *c != id) {
+            if remaining.query_row((&claim,), |r| r.get::<_, i64>(0))? == 0 {
+                if let Some(retracted) = retract.query_row((deleted_at, &claim), |r| r.get(0)).optional()? {
+                    work.push(retracted);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Live `has_evidence` links; the kind and liveness terms let the
+/// endpoint indexes (`idx_relationships_*`) serve the lookups below.
+const IS_EVIDENCE_LINK: &str = "kind = '\"relationship\"' AND deleted_at_ms IS NULL
+    AND COALESCE(json_extract(properties, '$.relation'), json_extract(properties, '$.type')) = 'has_evidence'";
+
+/// Delete the evidence links into and out of `?2` at `?1`, returning the
+/// claims they supported. One lookup per endpoint: SQLite will not search
+/// two expression indexes for an `OR`.
+fn unlink_sql() -> String {
+    format!(
+        "UPDATE items SET deleted_at_ms = ?1
+         WHERE id IN (
+             SELECT id FROM items WHERE {IS_EVIDENCE_LINK} AND json_extract(properties, '$.target') = ?2
+             UNION
+             SELECT id FROM items WHERE {IS_EVIDENCE_LINK} AND json_extract(properties, '$.source') = ?2
+         )
+         RETURNING json_extract(properties, '$.source')"
+    )
+}
+
+/// Count the live evidence left for claim `?1`.
+fn remaining_evidence_sql() -> String {
+    format!("SELECT count(*) FROM items WHERE {IS_EVIDENCE_LINK} AND json_extract(properties, '$.source') = ?1")
+}
+
+fn write_cursor(tx: &Connection, connector_id: &str, cursor: &SyncToken) -> Result<(), VaultError> {
+    tx.execute(
+        "INSERT INTO cursors (connector_id, cursor, updated_at_ms)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT (connector_id) DO UPDATE SET
+             cursor = excluded.cursor,
+             updated_at_ms = excluded.updated_at_ms",
+        (connector_id, &cursor.0, now_ms()),
+    )?;
+    Ok(())
+}
+
 fn apply_key_pragma(conn: &Connection, pragma: &str, dek: &Dek) -> Result<(), VaultError> {
     debug_assert!(pragma == "key" || pragma == "rekey");
     // Raw-key form. The hex and the composed SQL both hold key material:
@@ -591,7 +963,7 @@ mod tests {
     use serde_json::json;
     use std::io::Read;
 
-    fn provision(dir: &Path) -> Dek {
+    pub(super) fn provision(dir: &Path) -> Dek {
         let svc = KeyService::new(MemoryKekStore::default(), dir);
         let (dek, _recovery) = svc.provision().unwrap();
         dek
@@ -601,7 +973,7 @@ mod tests {
         DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
     }
 
-    fn event(source_id: &str, version: u32) -> Item {
+    pub(super) fn event(source_id: &str, version: u32) -> Item {
         Item::new(
             source_id,
             "google-calendar",
@@ -611,7 +983,7 @@ mod tests {
         )
     }
 
-    fn batch(deltas: Vec<Delta>, cursor: Option<&str>) -> DeltaBatch {
+    pub(super) fn batch(deltas: Vec<Delta>, cursor: Option<&str>) -> DeltaBatch {
         DeltaBatch {
             connector_id: "google-calendar".into(),
             deltas,
@@ -750,12 +1122,15 @@ mod tests {
             .unwrap();
         assert_eq!(vault.items("google-calendar").unwrap().len(), 0);
         assert_eq!(vault.item_count().unwrap(), 0);
+        assert!(vault.live_item("google-calendar", "evt-1").unwrap().is_none());
 
         // Source re-creates the record (e.g. meeting un-cancelled).
         vault.apply_batch(&batch(vec![Delta::Upsert(event("evt-1", 3))], None)).unwrap();
         let items = vault.items("google-calendar").unwrap();
         assert_eq!(items.len(), 1);
         assert_eq!(items[0].properties["version"], json!(3));
+        let live = vault.live_item("google-calendar", "evt-1").unwrap().unwrap();
+        assert_eq!(live.id, items[0].id);
 
         // Tombstone for something never ingested: harmless no-op.
         vault
@@ -763,6 +1138,265 @@ mod tests {
             .unwrap();
     }
 
+    fn claim(source_id: &str) -> Item {
+        let mut c = event(source_id, 1);
+        c.connector_id = "evidence-claims".into();
+        c.id = Item::deterministic_id("evidence-claims", source_id).to_string();
+        c.kind = ItemKind::Claim;
+        c
+    }
+
+    fn link(source_id: &str, claim: &Item, evidence: &Item, relation_key: &str) -> Item {
+        let mut l = claim.clone();
+        l.source_id = source_id.into();
+        l.id = Item::deterministic_id("evidence-claims", source_id).to_string();
+        l.kind = ItemKind::Relationship;
+        l.properties = json!({ "source": claim.id, "target": evidence.id, relation_key: "has_evidence" });
+        l
+    }
+
+    fn derived(deltas: Vec<Delta>) -> DeltaBatch {
+        DeltaBatch { connector_id: "evidence-claims".into(), deltas, cursor: None }
+    }
+
+    fn live(vault: &Vault, connector_id: &str, source_id: &str) -> bool {
+        vault.live_item(connector_id, source_id).unwrap().is_some()
+    }
+
+    #[test]
+    fn tombstones_cascade_to_claims_left_without_evidence() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+
+        let (a, b) = (event("evt-a", 1), event("evt-b", 1));
+        let only_a = claim("only-a");
+        let both = claim("both");
+        let meta = claim("meta"); // cites only-a as its evidence
+        let mut same_as = link("same-as", &only_a, &a, "relation");
+        same_as.properties["relation"] = json!("same_as");
+        vault.apply_batch(&batch(vec![Delta::Upsert(a), Delta::Upsert(b)], None)).unwrap();
+        vault
+            .apply_batch(&derived(vec![
+                Delta::Upsert(link("only-a/ev", &only_a, &event("evt-a", 1), "relation")),
+                Delta::Upsert(link("both/ev-a", &both, &event("evt-a", 1), "relation")),
+                // A pre-D18 link: `type` rather than `relation`.
+                Delta::Upsert(link("both/ev-b", &both, &event("evt-b", 1), "type")),
+                Delta::Upsert(link("meta/ev", &meta, &only_a, "relation")),
+                Delta::Upsert(same_as),
+                Delta::Upsert(only_a),
+                Delta::Upsert(both),
+                Delta::Upsert(meta),
+            ]))
+            .unwrap();
+
+        vault.apply_batch(&batch(vec![Delta::Tombstone { source_id: "evt-a".into() }], None)).unwrap();
+        assert!(!live(&vault, "evidence-claims", "only-a"), "its only evidence is gone");
+        assert!(!live(&vault, "evidence-claims", "only-a/ev"));
+        assert!(!live(&vault, "evidence-claims", "meta"), "cascades through claims citing claims");
+        assert!(!live(&vault, "evidence-claims", "meta/ev"));
+        assert!(!live(&vault, "evidence-claims", "both/ev-a"));
+        assert!(live(&vault, "evidence-claims", "both"), "still has evt-b");
+        assert!(live(&vault, "evidence-claims", "same-as"), "only evidence links cascade");
+
+        vault.apply_batch(&batch(vec![Delta::Tombstone { source_id: "evt-b".into() }], None)).unwrap();
+        assert!(!live(&vault, "evidence-claims", "both"));
+        assert!(!live(&vault, "evidence-claims", "both/ev-b"));
+    }
+
+    #[test]
+    fn derived_batches_commit_with_their_input_or_are_quarantined_in_its_transaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+        // A row under the claim's (connector, source) with another id: the
+        // derived upsert of "taken" violates the UNIQUE constraint.
+        let mut squatter = claim("taken");
+        squatter.id = Item::deterministic_id("elsewhere", "taken").to_string();
+        vault.apply_batch(&derived(vec![Delta::Upsert(squatter)])).unwrap();
+
+        let good = DerivedBatch { batch: derived(vec![Delta::Upsert(claim("fine"))]), rejections: vec![] };
+        let failing = DerivedBatch { batch: derived(vec![Delta::Upsert(claim("taken"))]), rejections: vec![] };
+        let rejected = DerivedBatch { batch: derived(vec![]), rejections: vec!["malformed".into()] };
+        let input = batch(vec![Delta::Upsert(event("evt-1", 1))], Some("c1"));
+        let quarantined = vault.apply_batch_with_derived(&input, &[good, failing, rejected]).unwrap();
+        assert_eq!(quarantined, [1, 2]);
+        assert!(live(&vault, "google-calendar", "evt-1"), "the input commits");
+        assert!(live(&vault, "evidence-claims", "fine"));
+        assert_eq!(vault.cursor("google-calendar").unwrap(), Some(SyncToken("c1".into())));
+        let letters = vault.dead_letters().unwrap();
+        assert_eq!(letters.len(), 2);
+        assert_eq!(letters[1].reasons, ["malformed"]);
+
+        // An input that fails takes its derivations down with it.
+        let mut clash = event("evt-2", 1);
+        clash.id = Item::deterministic_id("elsewhere", "evt-1").to_string();
+        clash.source_id = "evt-1".into();
+        let good = DerivedBatch { batch: derived(vec![Delta::Upsert(claim("orphan"))]), rejections: vec![] };
+        assert!(vault.apply_batch_with_derived(&batch(vec![Delta::Upsert(clash)], Some("c2")), &[good]).is_err());
+        assert!(!live(&vault, "evidence-claims", "orphan"));
+        assert_eq!(vault.cursor("google-calendar").unwrap(), Some(SyncToken("c1".into())));
+    }
+
+    #[test]
+    fn previews_report_cascades_and_derivations_and_write_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+        let evt = event("evt-1", 1);
+        let only = claim("only");
+        let only_ev = link("only/ev", &only, &evt, "relation");
+        vault.apply_batch(&batch(vec![Delta::Upsert(evt.clone())], Some("c1"))).unwrap();
+        vault.apply_batch(&derived(vec![Delta::Upsert(only), Delta::Upsert(only_ev)])).unwrap();
+
+        let deletion = batch(vec![Delta::Tombstone { source_id: "evt-1".into() }], Some("c2"));
+        let addition = batch(vec![Delta::Upsert(event("evt-2", 1))], Some("c3"));
+        let derivation = DerivedBatch { batch: derived(vec![Delta::Upsert(claim("new"))]), rejections: vec![] };
+        let batches = [(deletion, vec![]), (addition, vec![derivation])];
+        for _ in 0..2 {
+            let changes = vault.preview(&batches).unwrap();
+            let summary: Vec<(&str, bool, bool)> = changes
+                .iter()
+                .map(|c| {
+                    let item = c.before.as_ref().or(c.after.as_ref()).unwrap();
+                    (item.source_id.as_str(), c.before.is_some(), c.after.is_some())
+                })
+                .collect();
+            assert_eq!(
+                summary,
+                [
+                    ("new", false, true),
+                    ("only", true, false),
+                    ("only/ev", true, false),
+                    ("evt-1", true, false),
+                    ("evt-2", false, true),
+                ]
+            );
+        }
+        assert!(live(&vault, "google-calendar", "evt-1"), "rolled back");
+        assert!(live(&vault, "evidence-claims", "only"));
+        assert!(!live(&vault, "google-calendar", "evt-2"));
+        assert_eq!(vault.cursor("google-calendar").unwrap(), Some(SyncToken("c1".into())));
+    }
+
+    #[test]
+    fn legacy_claims_are_found_with_the_records_they_cite() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+        let evt = event("evt-1", 1);
+        let mut old_claim = event("evt-1-claim", 1);
+        old_claim.kind = ItemKind::Claim;
+        old_claim.properties = json!({ "assertion": "took place", "source": "google_calendar" });
+        let mut old_link = event("evt-1-rel", 1);
+        old_link.kind = ItemKind::Relationship;
+        old_link.properties = json!({ "source": old_claim.id, "target": evt.id, "type": "has_evidence" });
+        let new_claim = claim("evt-1/claim");
+        let new_link = link("evt-1/evidence", &new_claim, &evt, "relation");
+        let legacy = vec![Delta::Upsert(evt.clone()), Delta::Upsert(old_claim), Delta::Upsert(old_link)];
+        vault.apply_batch(&batch(legacy, None)).unwrap();
+        vault.apply_batch(&derived(vec![Delta::Upsert(new_claim), Delta::Upsert(new_link)])).unwrap();
+
+        let legacy = vault.legacy_claims().unwrap();
+        let found: Vec<&str> = legacy.items.iter().map(|i| i.source_id.as_str()).collect();
+        assert_eq!(found, ["evt-1-claim", "evt-1-rel"]);
+        assert_eq!(legacy.evidence.iter().map(|i| &i.id).collect::<Vec<_>>(), [&evt.id]);
+    }
+
+    #[test]
+    fn cascade_lookups_use_the_relationship_endpoint_indexes() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+        for (sql, params) in [(unlink_sql(), 2), (remaining_evidence_sql(), 1)] {
+            let mut stmt = vault.conn.prepare(&format!("EXPLAIN QUERY PLAN {sql}")).unwrap();
+            let plan: Vec<String> = stmt
+                .query_map(rusqlite::params_from_iter(vec!["x"; params]), |r| r.get::<_, String>(3))
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+            let searched = plan.iter().any(|step| step.starts_with("SEARCH items USING INDEX idx_relationships_"));
+            assert!(searched, "{sql}: {plan:?}");
+            assert!(!plan.iter().any(|step| step.starts_with("SCAN items")), "{sql}: {plan:?}");
+        }
+    }
+
+    #[test]
+    fn cascade_rolls_back_with_its_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+        let c = claim("c");
+        vault.apply_batch(&batch(vec![Delta::Upsert(event("evt-a", 1))], None)).unwrap();
+        vault
+            .apply_batch(&derived(vec![
+                Delta::Upsert(link("c/ev", &c, &event("evt-a", 1), "relation")),
+                Delta::Upsert(c),
+            ]))
+            .unwrap();
+
+        // The tombstone and its cascade run, then a later delta in the same
+        // batch violates UNIQUE: nothing of it may stick.
+        let mut squatter = event("evt-a", 2);
+        squatter.id = Item::deterministic_id("elsewhere", "evt-a").to_string();
+        let poison = batch(vec![Delta::Tombstone { source_id: "evt-a".into() }, Delta::Upsert(squatter)], None);
+        assert!(vault.apply_batch(&poison).is_err());
+        assert!(live(&vault, "google-calendar", "evt-a"));
+        assert!(live(&vault, "evidence-claims", "c"));
+        assert!(live(&vault, "evidence-claims", "c/ev"));
+    }
+
+    #[test]
+    fn counts_by_kind_skip_tombstones_and_fold_custom_kinds() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+
+        let mut custom = event("evt-x", 1);
+        custom.kind = ItemKind::Other("widget".into());
+        vault
+            .apply_batch(&batch(
+                vec![
+                    Delta::Upsert(event("evt-1", 1)),
+                    Delta::Upsert(event("evt-2", 1)),
+                    Delta::Upsert(custom),
+                    Delta::Tombstone { source_id: "evt-2".into() },
+                ],
+                None,
+            ))
+            .unwrap();
+
+        let counts = vault.item_counts_by_kind().unwrap();
+        assert_eq!(counts.get("event"), Some(&1));
+        assert_eq!(counts.get("other"), Some(&1));
+        assert!(vault.database_size_bytes().unwrap() > 0);
+    }
+
+    #[test]
+    fn chunks_are_stored_once_and_reassemble_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let dek = provision(dir.path());
+        let mut vault = Vault::open(&dir.path().join("vault.db"), &dek).unwrap();
+
+        let chunk = |sha256: &str, data: &[u8]| Delta::Chunk { sha256: sha256.into(), data: data.to_vec() };
+        let mut item = event("evt-c", 1);
+        item.properties = serde_json::json!({ "chunks": ["h1", "h2", "h1"] });
+        vault.apply_batch(&batch(vec![chunk("h1", b"ab"), chunk("h2", &[0, 255])], None)).unwrap();
+        // Re-delivered: the first copy stands.
+        vault.apply_batch(&batch(vec![chunk("h1", b"ab"), Delta::Upsert(item.clone())], None)).unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(vault.write_content(&item, &mut out).unwrap(), 6);
+        assert_eq!(out, [b'a', b'b', 0, 255, b'a', b'b']);
+        assert_eq!(vault.chunk("h2").unwrap(), Some(vec![0, 255]));
+
+        item.properties = serde_json::json!({ "chunks": ["h1", "nope"] });
+        assert!(matches!(
+            vault.write_content(&item, &mut Vec::new()),
+            Err(VaultError::MissingChunk { sha256, .. }) if sha256 == "nope"
+        ));
+    }
+
     #[test]
     fn stored_items_round_trip_through_domain_types() {
         let dir = tempfile::tempdir().unwrap();
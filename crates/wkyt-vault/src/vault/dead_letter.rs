@@ -0,0 +1,362 @@
+//! Dead-letter quarantine for batches the host cannot apply.
+//!
+//! Without it, a poison batch blocks its connector forever: the batch fails,
+//! the cursor never advances, and every pass replays it. The host instead
+//! either quarantines a batch at once (it failed validation — retrying
+//! cannot help) or counts apply failures against the batch's
+//! [`batch_fingerprint`] and quarantines it after a threshold. Quarantine
+//! and the cursor advance past the batch commit in **one transaction**, so
+//! the connector moves on without the batch ever being half-recorded.
+//!
+//! A quarantined batch keeps its full protobuf payload (inside the
+//! encrypted database) so it can be inspected, replayed, or discarded.
+//! Replay applies its deltas but never its cursor: by then the connector
+//! has committed newer positions, and rewinding to the quarantined one
+//! would re-ingest everything after it.
+
+use super::{apply_deltas, ms_to_dt, now_ms, write_cursor, Vault, VaultError};
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+use sha2::{Digest, Sha256};
+use wkyt_core::{Delta, DeltaBatch};
+
+/// A quarantined batch and why it was quarantined.
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub id: i64,
+    pub connector_id: String,
+    pub fingerprint: String,
+    pub batch: DeltaBatch,
+    /// Most recent failure reasons (one per rejected delta for validation
+    /// failures, the apply error otherwise).
+    pub reasons: Vec<String>,
+    pub attempts: u32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+    pub quarantined_at: DateTime<Utc>,
+}
+
+/// What [`Vault::record_apply_failure`] did with the failure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FailureOutcome {
+    /// Counted; the batch stays on the normal path and will be retried.
+    Pending { attempts: u32 },
+    /// Threshold reached: quarantined and the cursor moved past it.
+    Quarantined { id: i64 },
+}
+
+/// Stable identity of a batch across replays: connector, cursor, and the
+/// ids/source ids/chunk hashes it touches — not its payload bytes, which change on
+/// every re-sync (`ingested_at`). Length-prefixed fields, hex SHA-256.
+pub fn batch_fingerprint(batch: &DeltaBatch) -> String {
+    let mut h = Sha256::new();
+    let mut field = |tag: u8, bytes: &[u8]| {
+        h.update([tag]);
+        h.update((bytes.len() as u64).to_le_bytes());
+        h.update(bytes);
+    };
+    field(b'c', batch.connector_id.as_bytes());
+    field(b'k', batch.cursor.as_ref().map_or(&[][..], |c| c.0.as_bytes()));
+    for delta in &batch.deltas {
+        match delta {
+            Delta::Upsert(item) => field(b'u', item.id.as_bytes()),
+            Delta::Tombstone { source_id } => field(b't', source_id.as_bytes()),
+            Delta::Chunk { sha256, .. } => field(b'b', sha256.as_bytes()),
+        }
+    }
+    crate::hexfmt::encode(&h.finalize())
+}
+
+impl Vault {
+    /// Quarantine `batch` immediately and advance its connector's cursor
+    /// past it, atomically. Returns the dead letter's id.
+    pub fn quarantine_batch(
+        &mut self,
+        batch: &DeltaBatch,
+        reasons: &[String],
+    ) -> Result<i64, VaultError> {
+        let tx = self.conn.transaction()?;
+        let id = upsert_failure(&tx, batch, reasons, true)?.0;
+        if let Some(cursor) = &batch.cursor {
+            write_cursor(&tx, &batch.connector_id, cursor)?;
+        }
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// Count one failed apply of `batch`. On the `max_attempts`-th failure
+    /// the batch is quarantined and the cursor advanced past it, in the
+    /// same transaction.
+    pub fn record_apply_failure(
+        &mut self,
+        batch: &DeltaBatch,
+        reason: &str,
+        max_attempts: u32,
+    ) -> Result<FailureOutcome, VaultError> {
+        let tx = self.conn.transaction()?;
+        let (id, attempts) = upsert_failure(&tx, batch, &[reason.to_string()], false)?;
+        let outcome = if attempts >= max_attempts {
+            tx.execute(
+                "UPDATE dead_letters SET quarantined_at_ms = ?1
+                 WHERE id = ?2 AND quarantined_at_ms IS NULL",
+                (now_ms(), id),
+            )?;
+            if let Some(cursor) = &batch.cursor {
+                write_cursor(&tx, &batch.connector_id, cursor)?;
+            }
+            FailureOutcome::Quarantined { id }
+        } else {
+            FailureOutcome::Pending { attempts }
+        };
+        tx.commit()?;
+        Ok(outcome)
+    }
+
+    /// Forget failures counted for `batch` once it has applied after all.
+    /// Quarantined entries are left alone.
+    pub fn clear_apply_failures(&self, batch: &DeltaBatch) -> Result<(), VaultError> {
+        self.conn.execute(
+            "DELETE FROM dead_letters WHERE fingerprint = ?1 AND quarantined_at_ms IS NULL",
+            (batch_fingerprint(batch),),
+        )?;
+        Ok(())
+    }
+
+    /// Every quarantined batch, oldest quarantine first.
+    pub fn dead_letters(&self) -> Result<Vec<DeadLetter>, VaultError> {
+        let mut stmt = self.conn.prepare(&format!(
+            "{SELECT_DEAD_LETTER} WHERE quarantined_at_ms IS NOT NULL ORDER BY quarantined_at_ms, id"
+        ))?;
+        let rows = stmt.query_map([], read_row)?;
+        let mut letters = Vec::new();
+        for row in rows {
+            letters.push(row?.into_dead_letter()?);
+        }
+        Ok(letters)
+    }
+
+    pub fn dead_letter(&self, id: i64) -> Result<Option<DeadLetter>, VaultError> {
+        self.conn
+            .query_row(
+                &format!("{SELECT_DEAD_LETTER} WHERE id = ?1 AND quarantined_at_ms IS NOT NULL"),
+                (id,),
+                read_row,
+            )
+            .optional()?
+            .map(RawDeadLetter::into_dead_letter)
+            .transpose()
+    }
+
+    /// Apply a quarantined batch's deltas (never its cursor — see module
+    /// docs) and remove it from quarantine, atomically. On failure nothing
+    /// changes; record why with [`Vault::note_dead_letter_failure`].
+    pub fn replay_dead_letter(&mut self, id: i64) -> Result<(), VaultError> {
+        let letter = self.dead_letter(id)?.ok_or(VaultError::DeadLetterNotFound(id))?;
+        let tx = self.conn.transaction()?;
+        apply_deltas(&tx, &letter.batch.connector_id, &letter.batch.deltas)?;
+        tx.execute("DELETE FROM dead_letters WHERE id = ?1", (id,))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Record a failed retry: bump attempts and replace the reasons.
+    pub fn note_dead_letter_failure(&self, id: i64, reasons: &[String]) -> Result<(), VaultError> {
+        let updated = self.conn.execute(
+            "UPDATE dead_letters
+             SET attempts = attempts + 1, reasons = ?1, last_failed_at_ms = ?2
+             WHERE id = ?3 AND quarantined_at_ms IS NOT NULL",
+            (reasons_json(reasons), now_ms(), id),
+        )?;
+        if updated == 0 {
+            return Err(VaultError::DeadLetterNotFound(id));
+        }
+        Ok(())
+    }
+
+    /// Drop a quarantined batch for good.
+    pub fn discard_dead_letter(&self, id: i64) -> Result<(), VaultError> {
+        let deleted = self.conn.execute(
+            "DELETE FROM dead_letters WHERE id = ?1 AND quarantined_at_ms IS NOT NULL",
+            (id,),
+        )?;
+        if deleted == 0 {
+            return Err(VaultError::DeadLetterNotFound(id));
+        }
+        Ok(())
+    }
+}
+
+const SELECT_DEAD_LETTER: &str = "
+    SELECT id, connector_id, fingerprint, payload, reasons, attempts,
+           first_failed_at_ms, last_failed_at_ms, quarantined_at_ms
+    FROM dead_letters";
+
+/// Insert or bump the failure row for `batch`; returns (id, attempts).
+pub(super) fn upsert_failure(
+    tx: &rusqlite::Connection,
+    batch: &DeltaBatch,
+    reasons: &[String],
+    quarantine: bool,
+) -> Result<(i64, u32), VaultError> {
+    let now = now_ms();
+    Ok(tx.query_row(
+        "INSERT INTO dead_letters (connector_id, fingerprint, payload, reasons, attempts,
+                                   first_failed_at_ms, last_failed_at_ms, quarantined_at_ms)
+         VALUES (?1, ?2, ?3, ?4, 1, ?5, ?5, CASE WHEN ?6 THEN ?5 END)
+         ON CONFLICT (fingerprint) DO UPDATE SET
+             payload           = excluded.payload,
+             reasons           = excluded.reasons,
+             attempts          = attempts + 1,
+             last_failed_at_ms = excluded.last_failed_at_ms,
+             quarantined_at_ms = coalesce(quarantined_at_ms, excluded.quarantined_at_ms)
+         RETURNING id, attempts",
+        (
+            &batch.connector_id,
+            batch_fingerprint(batch),
+            batch.encode_to_vec(),
+            reasons_json(reasons),
+            now,
+            quarantine,
+        ),
+        |r| Ok((r.get(0)?, r.get(1)?)),
+    )?)
+}
+
+fn reasons_json(reasons: &[String]) -> String {
+    serde_json::to_string(reasons).expect("string list serialization is infallible")
+}
+
+struct RawDeadLetter {
+    id: i64,
+    connector_id: String,
+    fingerprint: String,
+    payload: Vec<u8>,
+    reasons: String,
+    attempts: u32,
+    first_failed_at_ms: i64,
+    last_failed_at_ms: i64,
+    quarantined_at_ms: i64,
+}
+
+fn read_row(r: &rusqlite::Row<'_>) -> rusqlite::Result<RawDeadLetter> {
+    Ok(RawDeadLetter {
+        id: r.get(0)?,
+        connector_id: r.get(1)?,
+        fingerprint: r.get(2)?,
+        payload: r.get(3)?,
+        reasons: r.get(4)?,
+        attempts: r.get(5)?,
+        first_failed_at_ms: r.get(6)?,
+        last_failed_at_ms: r.get(7)?,
+        quarantined_at_ms: r.get(8)?,
+    })
+}
+
+impl RawDeadLetter {
+    fn into_dead_letter(self) -> Result<DeadLetter, VaultError> {
+        let corrupt = |reason: String| VaultError::CorruptRow {
+            id: format!("dead_letter:{}", self.id),
+            reason,
+        };
+        let batch = DeltaBatch::decode(&self.payload).map_err(|e| corrupt(e.to_string()))?;
+        let reasons = serde_json::from_str(&self.reasons).map_err(|e| corrupt(e.to_string()))?;
+        let ts = |ms| ms_to_dt(ms).ok_or_else(|| corrupt(format!("bad timestamp {ms}")));
+        Ok(DeadLetter {
+            id: self.id,
+            connector_id: self.connector_id.clone(),
+            fingerprint: self.fingerprint.clone(),
+            batch,
+            reasons,
+            attempts: self.attempts,
+            first_failed_at: ts(self.first_failed_at_ms)?,
+            last_failed_at: ts(self.last_failed_at_ms)?,
+            quarantined_at: ts(self.quarantined_at_ms)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{batch, event, provision};
+    use super::*;
+    use wkyt_core::SyncToken;
+
+    fn open(dir: &std::path::Path) -> Vault {
+        Vault::open(&dir.join("vault.db"), &provision(dir)).unwrap()
+    }
+
+    #[test]
+    fn fingerprint_ignores_ingestion_time_but_not_identity() {
+        let a = batch(vec![Delta::Upsert(event("evt-1", 1))], Some("c1"));
+        let mut replay = a.clone();
+        if let Delta::Upsert(item) = &mut replay.deltas[0] {
+            item.ingested_at = Utc::now();
+            item.properties = serde_json::json!({ "changed": true });
+        }
+        assert_eq!(batch_fingerprint(&a), batch_fingerprint(&replay));
+
+        let other = batch(vec![Delta::Upsert(event("evt-2", 1))], Some("c1"));
+        assert_ne!(batch_fingerprint(&a), batch_fingerprint(&other));
+        let moved = batch(vec![Delta::Upsert(event("evt-1", 1))], Some("c2"));
+        assert_ne!(batch_fingerprint(&a), batch_fingerprint(&moved));
+    }
+
+    #[test]
+    fn repeated_failures_quarantine_and_advance_the_cursor_atomically() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut vault = open(dir.path());
+        let poison = batch(vec![Delta::Upsert(event("evt-1", 1))], Some("after-poison"));
+
+        for expected in 1..=2 {
+            let outcome = vault.record_apply_failure(&poison, "disk on fire", 3).unwrap();
+            assert_eq!(outcome, FailureOutcome::Pending { attempts: expected });
+            assert_eq!(vault.cursor("google-calendar").unwrap(), None);
+        }
+        assert!(vault.dead_letters().unwrap().is_empty(), "pending is not quarantined");
+
+        let FailureOutcome::Quarantined { id } = vault.record_apply_failure(&poison, "still", 3).unwrap()
+        else {
+            panic!("third failure must quarantine");
+        };
+        assert_eq!(vault.cursor("google-calendar").unwrap(), Some(SyncToken("after-poison".into())));
+
+        let letter = vault.dead_letter(id).unwrap().unwrap();
+        assert_eq!(letter.attempts, 3);
+        assert_eq!(letter.reasons, ["still"]);
+        assert_eq!(letter.batch.deltas.len(), 1);
+        assert_eq!(vault.item_count().unwrap(), 0, "quarantine applies nothing");
+    }
+
+    #[test]
+    fn success_after_failure_clears_the_pending_count() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut vault = open(dir.path());
+        let b = batch(vec![Delta::Upsert(event("evt-1", 1))], Some("c1"));
+        vault.record_apply_failure(&b, "busy", 3).unwrap();
+        vault.apply_batch(&b).unwrap();
+        vault.clear_apply_failures(&b).unwrap();
+        // Counting starts over.
+        assert_eq!(vault.record_apply_failure(&b, "busy", 3).unwrap(), FailureOutcome::Pending { attempts: 1 });
+    }
+
+    #[test]
+    fn replay_applies_deltas_without_rewinding_the_cursor_and_discard_drops() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut vault = open(dir.path());
+        let quarantined = batch(vec![Delta::Upsert(event("evt-1", 1))], Some("old"));
+        let id = vault.quarantine_batch(&quarantined, &["bad".into()]).unwrap();
+        vault.apply_batch(&batch(vec![Delta::Upsert(event("evt-2", 1))], Some("new"))).unwrap();
+
+        vault.replay_dead_letter(id).unwrap();
+        assert_eq!(vault.item_count().unwrap(), 2);
+        assert_eq!(vault.cursor("google-calendar").unwrap(), Some(SyncToken("new".into())));
+        assert!(vault.dead_letters().unwrap().is_empty());
+        assert!(matches!(vault.replay_dead_letter(id), Err(VaultError::DeadLetterNotFound(_))));
+
+        let id = vault.quarantine_batch(&quarantined, &["bad".into()]).unwrap();
+        vault.note_dead_letter_failure(id, &["worse".into()]).unwrap();
+        assert_eq!(vault.dead_letter(id).unwrap().unwrap().attempts, 2);
+        vault.discard_dead_letter(id).unwrap();
+        assert!(vault.dead_letter(id).unwrap().is_none());
+    }
+}
@@ -56,6 +56,9 @@ pub fn run() {
             vault_commands::get_stats,
             vault_commands::query_claims,
             vault_commands::query_claim_revisions,
+            vault_commands::list_dead_letters,
+            vault_commands::retry_dead_letter,
+            vault_commands::discard_dead_letter,
             vault_commands::list_capabilities,
             vault_commands::invoke_capability,
             vault_commands::set_passphrase,
@@ -27,6 +27,8 @@ use std::sync::{Arc, Mutex};
 use wkyt_connector_file::FileImporter;
 use wkyt_connector_google::GoogleCalendarConnector;
 use wkyt_core::{CapabilityInvocation, CapabilityManifest, CapabilityResult};
+use wkyt_host::{EvidenceClaims, Pipeline, PipelineStats, Watch};
+use wkyt_metrics::{Metrics, MetricsServer};
 use wkyt_vault::{unlock_vault, KeyError, KeyService, KeyState, DynamicKekStore, Vault};
 
 const KEYRING_SERVICE: &str = "wkyt";
@@ -121,6 +123,19 @@ pub struct ClaimView {
     pub evidence: Vec<EvidenceView>,
 }
 
+/// A quarantined batch, summarised: the deltas themselves stay in the
+/// vault, only their count crosses to the webview.
+#[derive(Serialize)]
+pub struct DeadLetterView {
+    pub id: i64,
+    pub connector_id: String,
+    pub delta_count: usize,
+    pub reasons: Vec<String>,
+    pub attempts: u32,
+    pub first_failed_at: String,
+    pub quarantined_at: String,
+}
+
 #[derive(Serialize)]
 pub struct RevisionView {
     pub revision_id: i64,
@@ -146,19 +161,33 @@ fn start_pipeline(app: &tauri::AppHandle, state: &AppState) {
     let connector = FileImporter::new("file-import", state.import_dir.clone());
     println!("[wkyt] watching {:?} — drop .json/.ics files there", state.import_dir);
     let _app = app.clone(); // reserved for emitting ingest events to the UI later
+    let metrics = start_metrics_endpoint();
 
-    // File importer loop (existing)
-    let vault_file = Arc::clone(&vault);
+    // File importer: woken by filesystem notifications, with a fallback
+    // rescan; plain polling if the watch cannot start or dies.
+    let file_pipeline = pipeline_for(Arc::clone(&vault), metrics.clone());
     tauri::async_runtime::spawn(async move {
+        let report = |stats: &PipelineStats| {
+            if stats.batches_applied > 0 {
+                println!(
+                    "[wkyt] file: ingested {} deltas in {} batches",
+                    stats.deltas_applied, stats.batches_applied
+                );
+            }
+        };
+        let watched = file_pipeline
+            .run_watched(&connector, &Watch::new(), |wake, result| match result {
+                Ok(stats) => report(&stats),
+                Err(e) => eprintln!("[wkyt] file pipeline pass ({wake:?}) failed: {e}"),
+            })
+            .await;
+        match watched {
+            Ok(()) => eprintln!("[wkyt] file watch ended; polling instead"),
+            Err(e) => eprintln!("[wkyt] file watch unavailable ({e}); polling instead"),
+        }
         loop {
-            match wkyt_host::run_pipeline_once(&connector, Arc::clone(&vault_file)).await {
-                Ok(stats) if stats.batches_applied > 0 => {
-                    println!(
-                        "[wkyt] file: ingested {} deltas in {} batches",
-                        stats.deltas_applied, stats.batches_applied
-                    );
-                }
-                Ok(_) => {}
+            match file_pipeline.run_once(&connector).await {
+                Ok(stats) => report(&stats),
                 Err(e) => eprintln!("[wkyt] file pipeline pass failed: {e}"),
             }
             tokio::time::sleep(std::time::Duration::from_secs(10)).await;
@@ -174,13 +203,13 @@ fn start_pipeline(app: &tauri::AppHandle, state: &AppState) {
         .or_else(|| std::env::var("WKYT_GOOGLE_CLIENT_SECRET").ok());
 
     if let Some(client_id) = client_id {
-        let vault_google = Arc::clone(&vault);
+        let google_pipeline = pipeline_for(Arc::clone(&vault), metrics);
         let google = GoogleCalendarConnector::new(client_id, client_secret);
         tauri::async_runtime::spawn(async move {
             // Initial delay: let the file pipeline settle first.
             tokio::time::sleep(std::time::Duration::from_secs(5)).await;
             loop {
-                match wkyt_host::run_pipeline_once(&google, Arc::clone(&vault_google)).await {
+                match google_pipeline.run_once(&google).await {
                     Ok(stats) if stats.batches_applied > 0 => {
                         println!(
                             "[wkyt] google: ingested {} deltas in {} batches",
@@ -205,6 +234,41 @@ fn start_pipeline(app: &tauri::AppHandle, state: &AppState) {
     }
 }
 
+/// Opt-in telemetry for always-on machines: `WKYT_METRICS_ADDR=127.0.0.1:9464`
+/// serves Prometheus text on that (loopback-only) address.
+fn start_metrics_endpoint() -> Option<Metrics> {
+    let addr = std::env::var("WKYT_METRICS_ADDR").ok()?;
+    let addr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("[wkyt] ignoring WKYT_METRICS_ADDR {addr:?}: {e}");
+            return None;
+        }
+    };
+    let metrics = Metrics::new();
+    let served = metrics.clone();
+    tauri::async_runtime::spawn(async move {
+        match MetricsServer::bind(addr).await {
+            Ok(server) => {
+                println!("[wkyt] metrics on http://{addr}/metrics");
+                if let Err(e) = server.serve(served).await {
+                    eprintln!("[wkyt] metrics endpoint stopped: {e}");
+                }
+            }
+            Err(e) => eprintln!("[wkyt] metrics endpoint not started: {e}"),
+        }
+    });
+    Some(metrics)
+}
+
+fn pipeline_for(vault: Arc<Mutex<Vault>>, metrics: Option<Metrics>) -> Pipeline {
+    let pipeline = Pipeline::new(vault).with_processor(EvidenceClaims);
+    match metrics {
+        Some(m) => pipeline.with_metrics(m),
+        None => pipeline,
+    }
+}
+
 #[tauri::command]
 pub async fn vault_status(
     app: tauri::AppHandle,
@@ -394,11 +458,13 @@ pub async fn query_claims(state: tauri::State<'_, Arc<AppState>>) -> Result<Vec<
         Ok(results
             .into_iter()
             .map(|(claim, evidence)| {
+                // Derived claims name their input's connector; agent
+                // claims are their own topic.
                 let topic = claim
                     .properties
-                    .get("source")
+                    .pointer("/provenance/input_connector_id")
                     .and_then(|s| s.as_str())
-                    .unwrap_or("Unknown")
+                    .unwrap_or(&claim.connector_id)
                     .to_string();
 
                 let assertion = claim
@@ -864,3 +930,52 @@ pub async fn get_human_context(
     .await
     .map_err(|e| e.to_string())?
 }
+
+#[tauri::command]
+pub async fn list_dead_letters(
+    state: tauri::State<'_, Arc<AppState>>,
+) -> Result<Vec<DeadLetterView>, String> {
+    let s = Arc::clone(&state);
+    tauri::async_runtime::spawn_blocking(move || {
+        let vault = s.cached_vault().ok_or("vault is not unlocked")?;
+        let letters = vault.lock().unwrap().dead_letters().map_err(|e| e.to_string())?;
+        Ok(letters
+            .into_iter()
+            .map(|d| DeadLetterView {
+                id: d.id,
+                connector_id: d.connector_id,
+                delta_count: d.batch.deltas.len(),
+                reasons: d.reasons,
+                attempts: d.attempts,
+                first_failed_at: d.first_failed_at.to_rfc3339(),
+                quarantined_at: d.quarantined_at.to_rfc3339(),
+            })
+            .collect())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn retry_dead_letter(state: tauri::State<'_, Arc<AppState>>, id: i64) -> Result<(), String> {
+    let s = Arc::clone(&state);
+    tauri::async_runtime::spawn_blocking(move || {
+        let vault = s.cached_vault().ok_or("vault is not unlocked")?;
+        let mut vault = vault.lock().unwrap();
+        wkyt_host::retry_dead_letter(&mut vault, id).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+pub async fn discard_dead_letter(state: tauri::State<'_, Arc<AppState>>, id: i64) -> Result<(), String> {
+    let s = Arc::clone(&state);
+    tauri::async_runtime::spawn_blocking(move || {
+        let vault = s.cached_vault().ok_or("vault is not unlocked")?;
+        let result = vault.lock().unwrap().discard_dead_letter(id);
+        result.map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}